@@ -0,0 +1,39 @@
+// Converts the OpenAPI document at `MCP_OPENAPI_SPEC`, if set, into the
+// operations `openapi::generated()` serves as tools. A bad document fails the
+// build rather than the first request.
+
+use std::path::Path;
+use std::{env, fs};
+
+#[path = "src/openapi/spec.rs"]
+#[allow(dead_code)]
+mod spec;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/openapi/spec.rs");
+    println!("cargo:rerun-if-env-changed=MCP_OPENAPI_SPEC");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("openapi.json");
+    let operations = match env::var("MCP_OPENAPI_SPEC") {
+        Ok(path) if !path.is_empty() => generate(&path),
+        _ => "null".to_string(),
+    };
+    fs::write(out, operations).unwrap();
+}
+
+/// Paths are relative to the crate root. Documents ending in `.yaml` or
+/// `.yml` are read as YAML, anything else as JSON.
+fn generate(path: &str) -> String {
+    println!("cargo:rerun-if-changed={}", path);
+    let text = fs::read_to_string(path).unwrap_or_else(|e| panic!("MCP_OPENAPI_SPEC: cannot read {}: {}", path, e));
+    let document: serde_json::Value = match path.ends_with(".yaml") || path.ends_with(".yml") {
+        true => serde_yaml::from_str(&text).unwrap_or_else(|e| panic!("MCP_OPENAPI_SPEC: {} is not YAML: {}", path, e)),
+        false => serde_json::from_str(&text).unwrap_or_else(|e| panic!("MCP_OPENAPI_SPEC: {} is not JSON: {}", path, e)),
+    };
+    let api = spec::parse_spec(&document).unwrap_or_else(|e| panic!("MCP_OPENAPI_SPEC: {}", e));
+    for skipped in &api.skipped {
+        println!("cargo:warning=Skipped OpenAPI operation {}", skipped);
+    }
+    serde_json::to_string(&api).unwrap()
+}
@@ -15,9 +15,9 @@ curl -X POST "$SERVER_URL/mcp" \
     "jsonrpc": "2.0",
     "method": "initialize",
     "params": {
-      "protocol_version": "2024-11-05",
+      "protocolVersion": "2024-11-05",
       "capabilities": {},
-      "client_info": {
+      "clientInfo": {
         "name": "test-client",
         "version": "1.0.0"
       }
@@ -37,7 +37,7 @@ curl -X POST "$SERVER_URL/mcp" \
   }'
 echo -e "\n"
 
-echo "3. Testing tools/call with add:"
+echo "3. Testing tools/call with evaluate:"
 curl -X POST "$SERVER_URL/mcp" \
   -H "Content-Type: application/json" \
   -H "Accept: application/json" \
@@ -45,10 +45,9 @@ curl -X POST "$SERVER_URL/mcp" \
     "jsonrpc": "2.0",
     "method": "tools/call",
     "params": {
-      "name": "add",
+      "name": "evaluate",
       "arguments": {
-        "a": 5,
-        "b": 3
+        "expression": "5 + 3"
       }
     },
     "id": 3
@@ -82,9 +81,9 @@ curl -X POST "$SERVER_URL/mcp" \
     "jsonrpc": "2.0",
     "method": "initialize",
     "params": {
-      "protocol_version": "2024-11-05",
+      "protocolVersion": "2024-11-05",
       "capabilities": {},
-      "client_info": {
+      "clientInfo": {
         "name": "sse-client",
         "version": "1.0.0"
       }
@@ -116,18 +115,12 @@ echo ""
 echo "=== Testing Legacy HTTP Endpoints ==="
 echo ""
 
-echo "1. Testing /mcp/add endpoint:"
-curl -X POST "$SERVER_URL/mcp/add" \
-  -H "Content-Type: application/json" \
-  -d '{"a": 10, "b": 20}'
-echo -e "\n"
-
-echo "2. Testing /mcp/calculate endpoint:"
+echo "1. Testing /mcp/calculate endpoint:"
 curl -X POST "$SERVER_URL/mcp/calculate" \
   -H "Content-Type: application/json" \
   -d '{"operation": "divide", "a": 100, "b": 25}'
 echo -e "\n"
 
-echo "3. Testing GET /mcp endpoint:"
+echo "2. Testing GET /mcp endpoint:"
 curl -X GET "$SERVER_URL/mcp"
 echo -e "\n"
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! Runs the MCP conformance suite against a server URL.
+//!
+//! ```text
+//! mcp-conformance http://localhost:8787/mcp \
+//!     [--transport streamable-http|sse] \
+//!     [--header "Authorization: Bearer ..."] \
+//!     [--call evaluate '{"expression": "2 + 3"}'] \
+//!     [--skip ping]
+//! ```
+
+use std::process::ExitCode;
+
+use mcp_client::conformance::{self, SuiteConfig};
+use mcp_client::transport::TransportKind;
+
+const USAGE: &str = "Usage: mcp-conformance <URL> [--transport streamable-http|sse] \
+[--header 'Name: value']... [--call TOOL JSON_ARGS] [--skip CHECK_ID]... [--list]";
+
+fn parse_args(args: Vec<String>) -> Result<SuiteConfig, String> {
+    let mut args = args.into_iter();
+    let mut url = None;
+    let mut transport = TransportKind::StreamableHttp;
+    let mut headers = Vec::new();
+    let mut tool_call = None;
+    let mut skip = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--transport" => transport = value("--transport")?.parse()?,
+            "--header" => {
+                let header = value("--header")?;
+                let (name, val) = header
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid header {:?}, expected 'Name: value'", header))?;
+                headers.push((name.trim().to_string(), val.trim().to_string()));
+            },
+            "--call" => {
+                let name = value("--call")?;
+                let arguments = value("--call")?;
+                let arguments = serde_json::from_str(&arguments)
+                    .map_err(|e| format!("Invalid --call arguments: {}", e))?;
+                tool_call = Some((name, arguments));
+            },
+            "--skip" => skip.push(value("--skip")?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ if url.is_none() => url = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    let url = url.ok_or("Missing server URL")?;
+    let mut config = SuiteConfig::new(&url, transport);
+    config.headers = headers;
+    config.tool_call = tool_call;
+    config.skip = skip;
+    Ok(config)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if args.iter().any(|arg| arg == "--list") {
+        for id in conformance::check_ids() {
+            println!("{}", id);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let config = match parse_args(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    println!("MCP conformance: {} ({:?})\n", config.url, config.transport);
+    let report = conformance::run(&config);
+    println!("{}", report);
+
+    if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,609 @@
+//! Scripted conformance suite run by the `mcp-conformance` binary. Each check
+//! maps to one requirement from the MCP specification and reports pass, fail
+//! or skip independently, so one broken feature does not hide the rest.
+
+use std::fmt;
+
+use serde_json::json;
+
+use crate::transport::{HttpResponse, TransportKind};
+use crate::types::*;
+use crate::{ClientError, McpClient, SUPPORTED_PROTOCOL_VERSIONS};
+
+#[derive(Debug, Clone)]
+pub struct SuiteConfig {
+    pub url: String,
+    pub transport: TransportKind,
+    pub headers: Vec<(String, String)>,
+    /// Tool name and arguments used for the successful `tools/call` check.
+    pub tool_call: Option<(String, serde_json::Value)>,
+    /// Check ids to report as skipped without running.
+    pub skip: Vec<String>,
+}
+
+impl SuiteConfig {
+    pub fn new(url: &str, transport: TransportKind) -> Self {
+        Self {
+            url: url.to_string(),
+            transport,
+            headers: Vec::new(),
+            tool_call: None,
+            skip: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub id: &'static str,
+    pub requirement: &'static str,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    pub fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|r| predicate(&r.outcome)).count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|o| *o == Outcome::Pass)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Fail(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Skip(_)))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let (label, detail) = match &result.outcome {
+                Outcome::Pass => ("PASS", None),
+                Outcome::Fail(reason) => ("FAIL", Some(reason)),
+                Outcome::Skip(reason) => ("SKIP", Some(reason)),
+            };
+            writeln!(f, "{}  {:<32} {}", label, result.id, result.requirement)?;
+            if let Some(detail) = detail {
+                writeln!(f, "      -> {}", detail)?;
+            }
+        }
+        write!(
+            f,
+            "\n{} passed, {} failed, {} skipped",
+            self.passed(),
+            self.failed(),
+            self.skipped()
+        )
+    }
+}
+
+struct Check {
+    id: &'static str,
+    requirement: &'static str,
+    run: fn(&mut Context) -> Outcome,
+}
+
+struct Context<'a> {
+    config: &'a SuiteConfig,
+    client: Option<McpClient>,
+    initialize_result: Option<serde_json::Value>,
+    assigned_session: Option<String>,
+}
+
+impl Context<'_> {
+    fn client(&mut self) -> Result<&mut McpClient, Outcome> {
+        match (&self.initialize_result, self.client.as_mut()) {
+            (Some(_), Some(client)) => Ok(client),
+            _ => Err(Outcome::Skip("Handshake did not complete".to_string())),
+        }
+    }
+
+    fn raw(&mut self, body: &str) -> Result<HttpResponse, Outcome> {
+        self.client()?.send_raw(body).map_err(fail)
+    }
+}
+
+/// Runs every check against the configured server.
+pub fn run(config: &SuiteConfig) -> Report {
+    let mut context = Context {
+        config,
+        client: None,
+        initialize_result: None,
+        assigned_session: None,
+    };
+
+    let results = CHECKS
+        .iter()
+        .map(|check| {
+            let outcome = if config.skip.iter().any(|id| id == check.id) {
+                Outcome::Skip("Skipped by --skip".to_string())
+            } else {
+                (check.run)(&mut context)
+            };
+            CheckResult {
+                id: check.id,
+                requirement: check.requirement,
+                outcome,
+            }
+        })
+        .collect();
+
+    Report { results }
+}
+
+/// Ids of all checks, in execution order.
+pub fn check_ids() -> Vec<&'static str> {
+    CHECKS.iter().map(|check| check.id).collect()
+}
+
+const CHECKS: &[Check] = &[
+    // Handshake
+    Check {
+        id: "lifecycle.initialize",
+        requirement: "Server answers initialize with protocolVersion, capabilities and serverInfo",
+        run: check_initialize,
+    },
+    Check {
+        id: "lifecycle.protocol-version",
+        requirement: "Negotiated protocolVersion is one the client supports",
+        run: check_protocol_version,
+    },
+    Check {
+        id: "lifecycle.initialized",
+        requirement: "notifications/initialized is accepted (202 Accepted on Streamable HTTP)",
+        run: check_initialized_notification,
+    },
+    Check {
+        id: "ping",
+        requirement: "ping is answered with an empty result",
+        run: check_ping,
+    },
+    // Listing
+    Check {
+        id: "tools.list",
+        requirement: "tools/list returns tools with a name and an object inputSchema",
+        run: check_tools_list,
+    },
+    Check {
+        id: "tools.list.unique-names",
+        requirement: "Tool names are unique",
+        run: check_tool_names_unique,
+    },
+    // Calling
+    Check {
+        id: "tools.call",
+        requirement: "tools/call returns a content array for a valid call",
+        run: check_tools_call,
+    },
+    Check {
+        id: "tools.call.unknown-tool",
+        requirement: "tools/call for an unknown tool reports an error",
+        run: check_unknown_tool,
+    },
+    // Errors
+    Check {
+        id: "errors.method-not-found",
+        requirement: "Unknown methods fail with -32601",
+        run: check_method_not_found,
+    },
+    Check {
+        id: "errors.parse-error",
+        requirement: "Malformed JSON fails with -32700 and a null id",
+        run: check_parse_error,
+    },
+    Check {
+        id: "errors.invalid-request",
+        requirement: "A request without a method fails with -32600",
+        run: check_invalid_request,
+    },
+    Check {
+        id: "errors.id-echo",
+        requirement: "Responses echo the request id, including string ids",
+        run: check_id_echo,
+    },
+    Check {
+        id: "transport.content-type",
+        requirement: "Responses use application/json or text/event-stream",
+        run: check_content_type,
+    },
+    // Batches
+    Check {
+        id: "batch.requests",
+        requirement: "A batch of requests gets one response per request",
+        run: check_batch_requests,
+    },
+    Check {
+        id: "batch.mixed",
+        requirement: "Notifications inside a batch get no response",
+        run: check_batch_mixed,
+    },
+    Check {
+        id: "batch.notifications-only",
+        requirement: "A batch of only notifications is accepted with no body",
+        run: check_batch_notifications_only,
+    },
+    // Sessions
+    Check {
+        id: "session.assigned",
+        requirement: "Server may assign Mcp-Session-Id on initialize",
+        run: check_session_assigned,
+    },
+    Check {
+        id: "session.id-format",
+        requirement: "Session ids contain only visible ASCII characters",
+        run: check_session_id_format,
+    },
+    Check {
+        id: "session.unknown",
+        requirement: "Requests with an unknown session id get 404 Not Found",
+        run: check_unknown_session,
+    },
+    Check {
+        id: "session.delete",
+        requirement: "DELETE ends the session (or is refused with 405)",
+        run: check_session_delete,
+    },
+];
+
+fn fail(e: impl fmt::Display) -> Outcome {
+    Outcome::Fail(e.to_string())
+}
+
+fn single_response(response: &HttpResponse) -> Result<JsonRpcResponse, Outcome> {
+    let messages = response.messages().map_err(fail)?;
+    messages
+        .into_iter()
+        .find_map(|message| match message {
+            JsonRpcMessage::Response(response) => Some(response),
+            JsonRpcMessage::Request(_) => None,
+        })
+        .ok_or_else(|| fail(format!("HTTP {} with no JSON-RPC response", response.status)))
+}
+
+fn expect_error_code(response: &JsonRpcResponse, code: i32) -> Outcome {
+    match &response.error {
+        Some(error) if error.code == code => Outcome::Pass,
+        Some(error) => fail(format!("Expected error {}, got {}: {}", code, error.code, error.message)),
+        None => fail(format!("Expected error {}, got a result", code)),
+    }
+}
+
+fn check_initialize(context: &mut Context) -> Outcome {
+    let config = context.config;
+    let mut client = match McpClient::connect(&config.url, config.transport, config.headers.clone()) {
+        Ok(client) => client,
+        Err(e) => return fail(e),
+    };
+
+    let params = json!({
+        "protocolVersion": LATEST_PROTOCOL_VERSION,
+        "capabilities": {},
+        "clientInfo": { "name": "mcp-conformance", "version": env!("CARGO_PKG_VERSION") },
+    });
+    let result = client.request("initialize", Some(params));
+    let assigned_session = client.session_id().map(str::to_string);
+    context.client = Some(client);
+
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return fail(e),
+    };
+    if let Err(e) = serde_json::from_value::<InitializeResult>(result.clone()) {
+        return fail(format!("Invalid InitializeResult: {}", e));
+    }
+    if result["serverInfo"]["name"].as_str().unwrap_or_default().is_empty() {
+        return fail("serverInfo.name is empty");
+    }
+
+    context.initialize_result = Some(result);
+    context.assigned_session = assigned_session;
+    Outcome::Pass
+}
+
+fn check_protocol_version(context: &mut Context) -> Outcome {
+    let Some(result) = &context.initialize_result else {
+        return Outcome::Skip("Handshake did not complete".to_string());
+    };
+    let version = result["protocolVersion"].as_str().unwrap_or_default();
+    if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+        Outcome::Pass
+    } else {
+        fail(format!("Unsupported protocolVersion {:?}", version))
+    }
+}
+
+fn check_initialized_notification(context: &mut Context) -> Outcome {
+    let body = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string();
+    let response = match context.raw(&body) {
+        Ok(response) => response,
+        Err(outcome) => return outcome,
+    };
+
+    match context.config.transport {
+        TransportKind::StreamableHttp if response.status != 202 => {
+            fail(format!("Expected 202 Accepted, got {}", response.status))
+        },
+        _ if !(200..300).contains(&response.status) => fail(format!("HTTP {}", response.status)),
+        _ => Outcome::Pass,
+    }
+}
+
+fn check_ping(context: &mut Context) -> Outcome {
+    match context.client().map(|client| client.request("ping", None)) {
+        Ok(Ok(result)) if result == json!({}) => Outcome::Pass,
+        Ok(Ok(result)) => fail(format!("Expected empty result, got {}", result)),
+        Ok(Err(e)) => fail(e),
+        Err(outcome) => outcome,
+    }
+}
+
+fn list_tools(context: &mut Context) -> Result<Vec<Tool>, Outcome> {
+    let has_tools = context
+        .initialize_result
+        .as_ref()
+        .is_some_and(|result| result["capabilities"].get("tools").is_some());
+    let client = context.client()?;
+    if !has_tools {
+        return Err(Outcome::Skip("Server does not declare the tools capability".to_string()));
+    }
+    client.list_tools().map_err(fail)
+}
+
+fn check_tools_list(context: &mut Context) -> Outcome {
+    let tools = match list_tools(context) {
+        Ok(tools) => tools,
+        Err(outcome) => return outcome,
+    };
+    for tool in &tools {
+        if tool.name.is_empty() {
+            return fail("Tool with empty name");
+        }
+        if tool.input_schema.get("type").and_then(|t| t.as_str()) != Some("object") {
+            return fail(format!("Tool {:?} inputSchema is not of type object", tool.name));
+        }
+    }
+    Outcome::Pass
+}
+
+fn check_tool_names_unique(context: &mut Context) -> Outcome {
+    let tools = match list_tools(context) {
+        Ok(tools) => tools,
+        Err(outcome) => return outcome,
+    };
+    let mut names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    names.sort_unstable();
+    match names.windows(2).find(|pair| pair[0] == pair[1]) {
+        Some(pair) => fail(format!("Duplicate tool name {:?}", pair[0])),
+        None => Outcome::Pass,
+    }
+}
+
+fn check_tools_call(context: &mut Context) -> Outcome {
+    let Some((name, arguments)) = context.config.tool_call.clone() else {
+        return Outcome::Skip("No --call configured".to_string());
+    };
+    let client = match context.client() {
+        Ok(client) => client,
+        Err(outcome) => return outcome,
+    };
+    match client.call_tool(&name, Some(arguments)) {
+        Ok(result) if result.is_error == Some(true) => fail(format!("Tool reported an error: {}", result.text())),
+        Ok(result) if result.content.is_empty() => fail("Result has no content"),
+        Ok(_) => Outcome::Pass,
+        Err(e) => fail(e),
+    }
+}
+
+fn check_unknown_tool(context: &mut Context) -> Outcome {
+    let client = match context.client() {
+        Ok(client) => client,
+        Err(outcome) => return outcome,
+    };
+    match client.call_tool("__conformance_missing_tool__", Some(json!({}))) {
+        Err(ClientError::Rpc(_)) => Outcome::Pass,
+        Ok(result) if result.is_error == Some(true) => Outcome::Pass,
+        Ok(_) => fail("Unknown tool call succeeded"),
+        Err(e) => fail(e),
+    }
+}
+
+fn check_method_not_found(context: &mut Context) -> Outcome {
+    match context.client().map(|client| client.request_raw("conformance/does-not-exist", None)) {
+        Ok(Ok(response)) => expect_error_code(&response, -32601),
+        Ok(Err(e)) => fail(e),
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_parse_error(context: &mut Context) -> Outcome {
+    let response = match context.raw("{\"jsonrpc\": \"2.0\", \"method\": ") {
+        Ok(response) => response,
+        Err(outcome) => return outcome,
+    };
+    let response = match single_response(&response) {
+        Ok(response) => response,
+        Err(outcome) => return outcome,
+    };
+    if !response.id.is_null() {
+        return fail(format!("Expected null id, got {}", response.id));
+    }
+    expect_error_code(&response, -32700)
+}
+
+fn check_invalid_request(context: &mut Context) -> Outcome {
+    let body = json!({ "jsonrpc": "2.0", "id": "conformance-invalid" }).to_string();
+    match context.raw(&body).and_then(|response| single_response(&response)) {
+        Ok(response) => expect_error_code(&response, -32600),
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_id_echo(context: &mut Context) -> Outcome {
+    let body = json!({ "jsonrpc": "2.0", "id": "conformance-echo", "method": "tools/list" }).to_string();
+    match context.raw(&body).and_then(|response| single_response(&response)) {
+        Ok(response) if response.id == json!("conformance-echo") => Outcome::Pass,
+        Ok(response) => fail(format!("Expected id \"conformance-echo\", got {}", response.id)),
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_content_type(context: &mut Context) -> Outcome {
+    let body = json!({ "jsonrpc": "2.0", "id": "conformance-ct", "method": "tools/list" }).to_string();
+    let response = match context.raw(&body) {
+        Ok(response) => response,
+        Err(outcome) => return outcome,
+    };
+    match response.content_type.as_deref() {
+        Some(ct) if ct.starts_with("application/json") || ct.starts_with("text/event-stream") => Outcome::Pass,
+        other => fail(format!("Unexpected Content-Type {:?}", other)),
+    }
+}
+
+fn batch_responses(context: &mut Context, batch: serde_json::Value) -> Result<(HttpResponse, Vec<JsonRpcResponse>), Outcome> {
+    let response = context.raw(&batch.to_string())?;
+    let responses = response
+        .messages()
+        .map_err(fail)?
+        .into_iter()
+        .filter_map(|message| match message {
+            JsonRpcMessage::Response(response) => Some(response),
+            JsonRpcMessage::Request(_) => None,
+        })
+        .collect();
+    Ok((response, responses))
+}
+
+fn check_batch_requests(context: &mut Context) -> Outcome {
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": "batch-1", "method": "tools/list" },
+        { "jsonrpc": "2.0", "id": "batch-2", "method": "conformance/does-not-exist" },
+    ]);
+    let responses = match batch_responses(context, batch) {
+        Ok((_, responses)) => responses,
+        Err(outcome) => return outcome,
+    };
+    let ids: Vec<&serde_json::Value> = responses.iter().map(|r| &r.id).collect();
+    if responses.len() == 2 && ids.contains(&&json!("batch-1")) && ids.contains(&&json!("batch-2")) {
+        Outcome::Pass
+    } else {
+        fail(format!("Expected responses for batch-1 and batch-2, got ids {:?}", ids))
+    }
+}
+
+fn check_batch_mixed(context: &mut Context) -> Outcome {
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": "batch-3", "method": "tools/list" },
+        { "jsonrpc": "2.0", "method": "notifications/conformance" },
+    ]);
+    match batch_responses(context, batch) {
+        Ok((_, responses)) if responses.len() == 1 && responses[0].id == json!("batch-3") => Outcome::Pass,
+        Ok((_, responses)) => fail(format!("Expected one response for batch-3, got {}", responses.len())),
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_batch_notifications_only(context: &mut Context) -> Outcome {
+    let batch = json!([
+        { "jsonrpc": "2.0", "method": "notifications/conformance" },
+        { "jsonrpc": "2.0", "method": "notifications/conformance" },
+    ]);
+    let streamable = context.config.transport == TransportKind::StreamableHttp;
+    match batch_responses(context, batch) {
+        Ok((response, _)) if streamable && response.status != 202 => {
+            fail(format!("Expected 202 Accepted, got {}", response.status))
+        },
+        Ok((_, responses)) if !responses.is_empty() => fail("Notifications were answered"),
+        Ok(_) => Outcome::Pass,
+        Err(outcome) => outcome,
+    }
+}
+
+fn session_id(context: &mut Context) -> Result<String, Outcome> {
+    context.client()?;
+    if context.config.transport != TransportKind::StreamableHttp {
+        return Err(Outcome::Skip("Sessions are a Streamable HTTP feature".to_string()));
+    }
+    context
+        .assigned_session
+        .clone()
+        .ok_or_else(|| Outcome::Skip("Server does not assign sessions".to_string()))
+}
+
+fn check_session_assigned(context: &mut Context) -> Outcome {
+    match session_id(context) {
+        Ok(_) => Outcome::Pass,
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_session_id_format(context: &mut Context) -> Outcome {
+    match session_id(context) {
+        Ok(id) if !id.is_empty() && id.bytes().all(|b| (0x21..=0x7e).contains(&b)) => Outcome::Pass,
+        Ok(id) => fail(format!("Session id {:?} is not visible ASCII", id)),
+        Err(outcome) => outcome,
+    }
+}
+
+fn check_unknown_session(context: &mut Context) -> Outcome {
+    let original = match session_id(context) {
+        Ok(id) => id,
+        Err(outcome) => return outcome,
+    };
+    let body = json!({ "jsonrpc": "2.0", "id": "conformance-session", "method": "tools/list" }).to_string();
+
+    let client = context.client.as_mut().expect("checked by session_id");
+    client.set_session_id(Some("conformance-unknown-session".to_string()));
+    let response = client.transport().post(&body, client.session_id());
+    client.set_session_id(Some(original));
+
+    match response {
+        Ok(response) if response.status == 404 => Outcome::Pass,
+        Ok(response) => fail(format!("Expected 404 Not Found, got {}", response.status)),
+        Err(e) => fail(e),
+    }
+}
+
+fn check_session_delete(context: &mut Context) -> Outcome {
+    let id = match session_id(context) {
+        Ok(id) => id,
+        Err(outcome) => return outcome,
+    };
+    let client = context.client.as_mut().expect("checked by session_id");
+    let status = match client.transport().delete(&id) {
+        Ok(status) => status,
+        Err(e) => return fail(e),
+    };
+    if status == 405 {
+        return Outcome::Pass;
+    }
+    if !(200..300).contains(&status) {
+        return fail(format!("DELETE returned {}", status));
+    }
+
+    let body = json!({ "jsonrpc": "2.0", "id": "conformance-deleted", "method": "tools/list" }).to_string();
+    match client.transport().post(&body, Some(&id)) {
+        Ok(response) if response.status == 404 => Outcome::Pass,
+        Ok(response) => fail(format!("Deleted session still answered with {}", response.status)),
+        Err(e) => fail(e),
+    }
+}
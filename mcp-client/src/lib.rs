@@ -0,0 +1,243 @@
+//! Typed client for MCP servers speaking Streamable HTTP or the legacy
+//! HTTP+SSE transport, plus the conformance suite behind `mcp-conformance`.
+
+use std::fmt;
+
+pub mod conformance;
+pub mod transport;
+pub mod types;
+
+use transport::{HttpResponse, Transport, TransportKind};
+use types::*;
+
+/// Protocol revisions this client knows how to speak.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request never produced an HTTP response.
+    Http(String),
+    /// The server answered with a non-success HTTP status.
+    Status(u16, String),
+    /// The server's reply did not follow JSON-RPC or MCP framing.
+    Protocol(String),
+    /// The server answered with a JSON-RPC error object.
+    Rpc(JsonRpcError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "HTTP error: {}", e),
+            ClientError::Status(status, body) => write!(f, "HTTP {}: {}", status, body),
+            ClientError::Protocol(e) => write!(f, "Protocol error: {}", e),
+            ClientError::Rpc(e) => write!(f, "JSON-RPC error {}: {}", e.code, e.message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<ureq::Error> for ClientError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(status, response) => {
+                ClientError::Status(status, response.into_string().unwrap_or_default())
+            },
+            ureq::Error::Transport(e) => ClientError::Http(e.to_string()),
+        }
+    }
+}
+
+pub struct McpClient {
+    transport: Transport,
+    session_id: Option<String>,
+    next_id: u64,
+    notifications: Vec<JsonRpcRequest>,
+    server: Option<InitializeResult>,
+}
+
+impl McpClient {
+    /// Creates a client for a Streamable HTTP endpoint such as `http://host/mcp`.
+    pub fn streamable_http(url: &str) -> Self {
+        Self::with_transport(Transport::streamable_http(url, Vec::new()))
+    }
+
+    /// Opens a legacy SSE stream such as `http://host/sse`.
+    pub fn sse(url: &str) -> Result<Self, ClientError> {
+        Transport::sse(url, Vec::new()).map(Self::with_transport)
+    }
+
+    /// Connects with the given transport, sending `headers` on every request.
+    pub fn connect(url: &str, kind: TransportKind, headers: Vec<(String, String)>) -> Result<Self, ClientError> {
+        let transport = match kind {
+            TransportKind::StreamableHttp => Transport::streamable_http(url, headers),
+            TransportKind::Sse => Transport::sse(url, headers)?,
+        };
+        Ok(Self::with_transport(transport))
+    }
+
+    pub fn with_transport(transport: Transport) -> Self {
+        Self {
+            transport,
+            session_id: None,
+            next_id: 1,
+            notifications: Vec::new(),
+            server: None,
+        }
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Overrides the session id sent with subsequent requests.
+    pub fn set_session_id(&mut self, session_id: Option<String>) {
+        self.session_id = session_id;
+    }
+
+    /// Result of the last successful `initialize`.
+    pub fn server(&self) -> Option<&InitializeResult> {
+        self.server.as_ref()
+    }
+
+    /// Drains server-initiated requests and notifications received so far.
+    pub fn take_notifications(&mut self) -> Vec<JsonRpcRequest> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    pub fn next_request_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Performs the `initialize` handshake followed by `notifications/initialized`.
+    pub fn initialize(&mut self, client_info: Implementation) -> Result<InitializeResult, ClientError> {
+        let params = InitializeParams {
+            protocol_version: LATEST_PROTOCOL_VERSION.to_string(),
+            capabilities: ClientCapabilities::default(),
+            client_info,
+        };
+        let result = self.request("initialize", Some(to_value(params)?))?;
+        let result: InitializeResult = serde_json::from_value(result)
+            .map_err(|e| ClientError::Protocol(format!("Invalid initialize result: {}", e)))?;
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&result.protocol_version.as_str()) {
+            return Err(ClientError::Protocol(format!(
+                "Unsupported protocol version: {}",
+                result.protocol_version
+            )));
+        }
+
+        self.notify("notifications/initialized", None)?;
+        self.server = Some(result.clone());
+        Ok(result)
+    }
+
+    /// Sends a request and returns its `result`, mapping JSON-RPC errors to
+    /// [`ClientError::Rpc`].
+    pub fn request(&mut self, method: &str, params: Option<serde_json::Value>) -> Result<serde_json::Value, ClientError> {
+        let response = self.request_raw(method, params)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(ClientError::Rpc(error)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(ClientError::Protocol("Response has neither result nor error".to_string())),
+        }
+    }
+
+    /// Sends a request and returns the full response envelope.
+    pub fn request_raw(&mut self, method: &str, params: Option<serde_json::Value>) -> Result<JsonRpcResponse, ClientError> {
+        let id = self.next_request_id();
+        let request = JsonRpcRequest::new(id, method, params);
+        let mut responses = self.send(&to_value(&request)?)?;
+        let index = responses
+            .iter()
+            .position(|response| response.id == serde_json::json!(id))
+            .ok_or_else(|| ClientError::Protocol(format!("No response for request id {}", id)))?;
+        Ok(responses.swap_remove(index))
+    }
+
+    pub fn notify(&mut self, method: &str, params: Option<serde_json::Value>) -> Result<(), ClientError> {
+        let notification = JsonRpcRequest::notification(method, params);
+        self.send(&to_value(&notification)?).map(|_| ())
+    }
+
+    /// Sends several requests in one JSON-RPC batch. Responses are returned in
+    /// the order the server sent them.
+    pub fn batch(&mut self, requests: Vec<JsonRpcRequest>) -> Result<Vec<JsonRpcResponse>, ClientError> {
+        self.send(&to_value(&requests)?)
+    }
+
+    /// Sends an arbitrary body and returns the raw HTTP exchange, recording
+    /// any session id the server assigns.
+    pub fn send_raw(&mut self, body: &str) -> Result<HttpResponse, ClientError> {
+        let response = self.transport.post(body, self.session_id.as_deref())?;
+        if let Some(session_id) = &response.session_id {
+            self.session_id = Some(session_id.clone());
+        }
+        Ok(response)
+    }
+
+    /// Lists every tool, following `nextCursor` pagination.
+    pub fn list_tools(&mut self) -> Result<Vec<Tool>, ClientError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.as_ref().map(|c| serde_json::json!({ "cursor": c }));
+            let page: ListToolsResult = serde_json::from_value(self.request("tools/list", params)?)
+                .map_err(|e| ClientError::Protocol(format!("Invalid tools/list result: {}", e)))?;
+            tools.extend(page.tools);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    pub fn call_tool(&mut self, name: &str, arguments: Option<serde_json::Value>) -> Result<CallToolResult, ClientError> {
+        let params = serde_json::json!({ "name": name, "arguments": arguments });
+        serde_json::from_value(self.request("tools/call", Some(params))?)
+            .map_err(|e| ClientError::Protocol(format!("Invalid tools/call result: {}", e)))
+    }
+
+    pub fn ping(&mut self) -> Result<(), ClientError> {
+        self.request("ping", None).map(|_| ())
+    }
+
+    /// Terminates the session with `DELETE`, if the server assigned one.
+    pub fn close(&mut self) -> Result<(), ClientError> {
+        if let Some(session_id) = self.session_id.take() {
+            let status = self.transport.delete(&session_id)?;
+            if !(200..300).contains(&status) && status != 405 {
+                return Err(ClientError::Status(status, "Session termination failed".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: &serde_json::Value) -> Result<Vec<JsonRpcResponse>, ClientError> {
+        let body = serde_json::to_string(message).map_err(|e| ClientError::Protocol(e.to_string()))?;
+        let response = self.send_raw(&body)?;
+        if !(200..300).contains(&response.status) {
+            return Err(ClientError::Status(response.status, response.body));
+        }
+
+        let mut responses = Vec::new();
+        for message in response.messages()? {
+            match message {
+                JsonRpcMessage::Response(response) => responses.push(response),
+                JsonRpcMessage::Request(request) => self.notifications.push(request),
+            }
+        }
+        Ok(responses)
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<serde_json::Value, ClientError> {
+    serde_json::to_value(value).map_err(|e| ClientError::Protocol(e.to_string()))
+}
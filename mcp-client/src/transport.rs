@@ -0,0 +1,383 @@
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::time::Duration;
+
+use crate::types::JsonRpcMessage;
+use crate::ClientError;
+
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    /// Single `/mcp` endpoint; responses come back as JSON or an SSE stream.
+    StreamableHttp,
+    /// Legacy HTTP+SSE: `GET /sse` announces the endpoint to POST messages to.
+    Sse,
+}
+
+impl std::str::FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "streamable-http" | "http" => Ok(TransportKind::StreamableHttp),
+            "sse" => Ok(TransportKind::Sse),
+            other => Err(format!("Unknown transport: {}", other)),
+        }
+    }
+}
+
+/// Raw outcome of one HTTP exchange, kept around so conformance checks can
+/// look at status codes and headers as well as the decoded messages.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub session_id: Option<String>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Decodes the body as either a JSON message (or batch) or an SSE stream
+    /// of messages. An empty body decodes to no messages.
+    pub fn messages(&self) -> Result<Vec<JsonRpcMessage>, ClientError> {
+        decode_messages(self.content_type.as_deref(), &self.body)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+/// Parses a complete `text/event-stream` body into events. Comment lines and
+/// unknown fields are ignored; multi-line `data` is joined with `\n`.
+pub fn parse_sse(body: &str) -> Vec<SseEvent> {
+    let mut parser = SseParser::default();
+    let mut events: Vec<SseEvent> = body.lines().filter_map(|line| parser.feed(line)).collect();
+    events.extend(parser.finish());
+    events
+}
+
+#[derive(Default)]
+struct SseParser {
+    event: Option<String>,
+    id: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, line: &str) -> Option<SseEvent> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            return self.finish();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn finish(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() && self.event.is_none() {
+            return None;
+        }
+        let event = SseEvent {
+            event: self.event.take(),
+            id: self.id.take(),
+            data: self.data.join("\n"),
+        };
+        self.data.clear();
+        Some(event)
+    }
+}
+
+/// Decodes JSON-RPC messages from a response body based on its content type.
+pub fn decode_messages(content_type: Option<&str>, body: &str) -> Result<Vec<JsonRpcMessage>, ClientError> {
+    if body.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let is_sse = content_type.is_some_and(|ct| ct.starts_with("text/event-stream"));
+    let payloads: Vec<String> = if is_sse {
+        parse_sse(body)
+            .into_iter()
+            .filter(|event| event.event.as_deref().unwrap_or("message") == "message")
+            .map(|event| event.data)
+            .collect()
+    } else {
+        vec![body.to_string()]
+    };
+
+    let mut messages = Vec::new();
+    for payload in payloads {
+        let value: serde_json::Value = serde_json::from_str(&payload)
+            .map_err(|e| ClientError::Protocol(format!("Invalid JSON in response: {}", e)))?;
+        let batch = match value {
+            serde_json::Value::Array(items) => items,
+            single => vec![single],
+        };
+        for item in batch {
+            let message = serde_json::from_value(item)
+                .map_err(|e| ClientError::Protocol(format!("Invalid JSON-RPC message: {}", e)))?;
+            messages.push(message);
+        }
+    }
+    Ok(messages)
+}
+
+/// Resolves the endpoint announced by a legacy SSE server against the URL
+/// the stream was opened on.
+pub fn resolve_endpoint(base_url: &str, endpoint: &str) -> String {
+    if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+        return endpoint.to_string();
+    }
+
+    let scheme_end = base_url.find("://").map(|i| i + 3).unwrap_or(0);
+    let path_start = base_url[scheme_end..]
+        .find('/')
+        .map(|i| i + scheme_end)
+        .unwrap_or(base_url.len());
+
+    if endpoint.starts_with('/') {
+        format!("{}{}", &base_url[..path_start], endpoint)
+    } else {
+        let dir_end = base_url[path_start..]
+            .rfind('/')
+            .map(|i| i + path_start + 1)
+            .unwrap_or(base_url.len());
+        let prefix = &base_url[..dir_end];
+        if prefix.ends_with('/') {
+            format!("{}{}", prefix, endpoint)
+        } else {
+            format!("{}/{}", prefix, endpoint)
+        }
+    }
+}
+
+/// The `GET /sse` stream of the legacy transport, kept open because the
+/// server may send its responses there rather than in reply to the POST.
+struct EventStream {
+    lines: Lines<BufReader<Box<dyn Read + Send + Sync>>>,
+    parser: SseParser,
+}
+
+impl EventStream {
+    /// The next event, or `None` once the server closes the stream.
+    fn next_event(&mut self) -> Result<Option<SseEvent>, ClientError> {
+        for line in self.lines.by_ref() {
+            let line = line.map_err(|e| ClientError::Http(e.to_string()))?;
+            if let Some(event) = self.parser.feed(&line) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(self.parser.finish())
+    }
+}
+
+/// Blocking HTTP transport shared by both MCP transport flavours.
+pub struct Transport {
+    agent: ureq::Agent,
+    kind: TransportKind,
+    post_url: String,
+    headers: Vec<(String, String)>,
+    stream: Option<RefCell<EventStream>>,
+}
+
+impl Transport {
+    pub fn streamable_http(url: &str, headers: Vec<(String, String)>) -> Self {
+        Self {
+            agent: build_agent(),
+            kind: TransportKind::StreamableHttp,
+            post_url: url.to_string(),
+            headers,
+            stream: None,
+        }
+    }
+
+    /// Opens the legacy SSE stream and waits for its `endpoint` event. The
+    /// stream stays open for responses the server sends on it.
+    pub fn sse(url: &str, headers: Vec<(String, String)>) -> Result<Self, ClientError> {
+        // The stream outlives any overall timeout; only silence ends it
+        let stream_agent = ureq::AgentBuilder::new()
+            .timeout_connect(TIMEOUT)
+            .timeout_read(TIMEOUT)
+            .build();
+        let mut request = stream_agent.get(url).set("Accept", "text/event-stream");
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+        let response = request.call().map_err(ClientError::from)?;
+
+        let mut stream = EventStream {
+            lines: BufReader::new(response.into_reader()).lines(),
+            parser: SseParser::default(),
+        };
+        let endpoint = loop {
+            match stream.next_event()? {
+                Some(event) if event.event.as_deref() == Some("endpoint") => break event.data,
+                Some(_) => continue,
+                None => return Err(ClientError::Protocol("SSE stream closed without an endpoint event".to_string())),
+            }
+        };
+
+        // Older servers wrap the endpoint in a JSON object
+        let endpoint = serde_json::from_str::<serde_json::Value>(&endpoint)
+            .ok()
+            .and_then(|value| value.get("endpoint").and_then(|e| e.as_str()).map(str::to_string))
+            .unwrap_or(endpoint);
+
+        Ok(Self {
+            agent: build_agent(),
+            kind: TransportKind::Sse,
+            post_url: resolve_endpoint(url, &endpoint),
+            headers,
+            stream: Some(RefCell::new(stream)),
+        })
+    }
+
+    pub fn kind(&self) -> TransportKind {
+        self.kind
+    }
+
+    pub fn post_url(&self) -> &str {
+        &self.post_url
+    }
+
+    /// POSTs a raw body. HTTP error statuses are returned as responses rather
+    /// than errors so callers can inspect them. On the legacy transport,
+    /// responses the server sends on the stream instead, typically after
+    /// `202 Accepted`, are read from there and returned as an event-stream
+    /// body, up to the response to each request in `body`.
+    pub fn post(&self, body: &str, session_id: Option<&str>) -> Result<HttpResponse, ClientError> {
+        let mut request = self
+            .agent
+            .post(&self.post_url)
+            .set("Content-Type", "application/json")
+            .set("Accept", "application/json, text/event-stream");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        if let Some(session_id) = session_id {
+            request = request.set(SESSION_HEADER, session_id);
+        }
+        let response = read_response(request.send_string(body))?;
+        match &self.stream {
+            Some(stream) if (200..300).contains(&response.status) => read_stream_replies(&mut stream.borrow_mut(), body, response),
+            _ => Ok(response),
+        }
+    }
+
+    /// Sends `DELETE` to terminate a session, returning the status code.
+    pub fn delete(&self, session_id: &str) -> Result<u16, ClientError> {
+        let mut request = self.agent.delete(&self.post_url).set(SESSION_HEADER, session_id);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        read_response(request.call()).map(|response| response.status)
+    }
+}
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn build_agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+}
+
+/// The ids of the requests in a message or batch.
+fn request_ids(body: &str) -> Vec<serde_json::Value> {
+    let messages = match serde_json::from_str(body) {
+        Ok(serde_json::Value::Array(batch)) => batch,
+        Ok(message) => vec![message],
+        Err(_) => Vec::new(),
+    };
+    messages
+        .into_iter()
+        .filter(|message| message.get("method").is_some())
+        .filter_map(|message| message.get("id").filter(|id| !id.is_null()).cloned())
+        .collect()
+}
+
+fn as_event(data: &str) -> String {
+    let mut event: String = data.lines().map(|line| format!("data: {}\n", line)).collect();
+    event.push('\n');
+    event
+}
+
+/// Adds the messages read from `stream` to `response` until every request
+/// in `body` has been answered, on the POST or on the stream.
+fn read_stream_replies(stream: &mut EventStream, body: &str, response: HttpResponse) -> Result<HttpResponse, ClientError> {
+    let mut waiting = request_ids(body);
+    for message in response.messages()? {
+        if let JsonRpcMessage::Response(reply) = message {
+            waiting.retain(|id| *id != reply.id);
+        }
+    }
+    if waiting.is_empty() {
+        return Ok(response);
+    }
+
+    let mut events = match response.content_type.as_deref() {
+        Some(content_type) if content_type.starts_with("text/event-stream") => response.body.clone(),
+        _ if response.body.trim().is_empty() => String::new(),
+        _ => as_event(response.body.trim()),
+    };
+    while !waiting.is_empty() {
+        let event = stream
+            .next_event()?
+            .ok_or_else(|| ClientError::Protocol("SSE stream closed before the response arrived".to_string()))?;
+        if event.event.as_deref().unwrap_or("message") != "message" {
+            continue;
+        }
+        for message in decode_messages(Some("text/event-stream"), &as_event(&event.data))? {
+            if let JsonRpcMessage::Response(reply) = message {
+                waiting.retain(|id| *id != reply.id);
+            }
+        }
+        events.push_str(&as_event(&event.data));
+    }
+    Ok(HttpResponse {
+        content_type: Some("text/event-stream".to_string()),
+        body: events,
+        ..response
+    })
+}
+
+fn read_response(result: Result<ureq::Response, ureq::Error>) -> Result<HttpResponse, ClientError> {
+    let response = match result {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => return Err(ClientError::from(e)),
+    };
+
+    let status = response.status();
+    let content_type = response.header("Content-Type").map(str::to_string);
+    let session_id = response.header(SESSION_HEADER).map(str::to_string);
+    let mut body = String::new();
+    response
+        .into_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| ClientError::Http(e.to_string()))?;
+
+    Ok(HttpResponse {
+        status,
+        content_type,
+        session_id,
+        body,
+    })
+}
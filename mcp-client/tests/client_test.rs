@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::thread;
+
+    use mcp_client::types::Implementation;
+    use mcp_client::McpClient;
+    use serde_json::{json, Value};
+
+    /// Reads one request's head and body.
+    fn read_request(stream: &TcpStream) -> (String, Vec<u8>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        (request_line, body)
+    }
+
+    /// A legacy SSE server as the spec describes it: `GET /sse` stays open,
+    /// and each POST gets `202 Accepted` while its response goes out on the
+    /// stream.
+    fn sse_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        let (events, outgoing) = mpsc::channel::<String>();
+
+        thread::spawn(move || {
+            let mut outgoing = Some(outgoing);
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (request_line, body) = read_request(&stream);
+                if request_line.starts_with("GET") {
+                    let outgoing = outgoing.take().unwrap();
+                    thread::spawn(move || {
+                        write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n").unwrap();
+                        write!(stream, "event: endpoint\ndata: /messages?sessionId=abc\n\n").unwrap();
+                        for event in outgoing {
+                            write!(stream, "{}", event).unwrap();
+                        }
+                    });
+                    continue;
+                }
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                if let (_, Some(response)) = reply(&request) {
+                    // Something the client did not ask for comes first
+                    let log = json!({ "jsonrpc": "2.0", "method": "notifications/message", "params": { "level": "info", "data": "working" } });
+                    events.send(format!("event: message\ndata: {}\n\n", log)).unwrap();
+                    events.send(format!("event: message\ndata: {}\n\n", response)).unwrap();
+                }
+                write!(stream, "HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            }
+        });
+
+        url
+    }
+
+    /// Serves `count` HTTP exchanges, answering each JSON-RPC body with `reply`.
+    fn mock_server(count: usize, reply: fn(&Value) -> (u16, Option<Value>)) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let (status, response) = reply(&serde_json::from_slice(&body).unwrap());
+                let body = response.map(|r| r.to_string()).unwrap_or_default();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nMcp-Session-Id: session-1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        url
+    }
+
+    fn reply(request: &Value) -> (u16, Option<Value>) {
+        let result = match request["method"].as_str() {
+            Some("initialize") => json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": "mock", "version": "0.1.0", "title": "Mock Server" },
+                "instructions": "Use add for sums."
+            }),
+            Some("tools/list") => json!({
+                "tools": [{ "name": "add", "inputSchema": { "type": "object" } }]
+            }),
+            Some("tools/call") => json!({
+                "content": [{ "type": "text", "text": "5" }],
+                "isError": false
+            }),
+            Some(_) if request.get("id").is_none() => return (202, None),
+            _ => {
+                return (200, Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32601, "message": "Method not found" }
+                })))
+            },
+        };
+        (200, Some(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })))
+    }
+
+    #[test]
+    fn test_handshake_list_and_call() {
+        let url = mock_server(4, reply);
+        let mut client = McpClient::streamable_http(&url);
+
+        let info = client
+            .initialize(Implementation::new("test", "0.0.0"))
+            .unwrap();
+        assert_eq!(info.server_info.name, "mock");
+        assert_eq!(info.server_info.title.as_deref(), Some("Mock Server"));
+        assert_eq!(info.instructions.as_deref(), Some("Use add for sums."));
+        assert_eq!(client.session_id(), Some("session-1"));
+
+        let tools = client.list_tools().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "add");
+
+        let result = client.call_tool("add", Some(json!({ "a": 2, "b": 3 }))).unwrap();
+        assert_eq!(result.text(), "5");
+    }
+
+    #[test]
+    fn test_rpc_error_is_surfaced() {
+        let url = mock_server(1, reply);
+        let mut client = McpClient::streamable_http(&url);
+
+        match client.request("missing/method", None) {
+            Err(mcp_client::ClientError::Rpc(error)) => assert_eq!(error.code, -32601),
+            other => panic!("Expected RPC error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sse_responses_arrive_on_the_stream() {
+        let url = sse_server();
+        let mut client = McpClient::sse(&url).unwrap();
+        assert!(client.transport().post_url().ends_with("/messages?sessionId=abc"));
+
+        let info = client.initialize(Implementation::new("test", "0.0.0")).unwrap();
+        assert_eq!(info.server_info.name, "mock");
+        let result = client.call_tool("add", Some(json!({ "a": 2, "b": 3 }))).unwrap();
+        assert_eq!(result.text(), "5");
+        assert!(client.take_notifications().iter().any(|n| n.method == "notifications/message"));
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use mcp_client::transport::{decode_messages, parse_sse, resolve_endpoint};
+    use mcp_client::types::JsonRpcMessage;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_sse_events() {
+        let body = "event: endpoint\ndata: /messages\n\n: keep-alive\n\ndata: {\"a\":\ndata: 1}\nid: 7\n\n";
+        let events = parse_sse(body);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("endpoint"));
+        assert_eq!(events[0].data, "/messages");
+        assert_eq!(events[1].event, None);
+        assert_eq!(events[1].data, "{\"a\":\n1}");
+        assert_eq!(events[1].id.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_parse_sse_without_trailing_blank_line() {
+        let events = parse_sse("data: {}\r\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "{}");
+    }
+
+    #[test]
+    fn test_decode_json_response() {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "result": {} }).to_string();
+        let messages = decode_messages(Some("application/json"), &body).unwrap();
+
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(&messages[0], JsonRpcMessage::Response(r) if r.id == json!(1)));
+    }
+
+    #[test]
+    fn test_decode_batch_response() {
+        let body = json!([
+            { "jsonrpc": "2.0", "id": 1, "result": {} },
+            { "jsonrpc": "2.0", "id": 2, "error": { "code": -32601, "message": "Method not found" } },
+        ])
+        .to_string();
+        let messages = decode_messages(Some("application/json"), &body).unwrap();
+
+        assert_eq!(messages.len(), 2);
+        match &messages[1] {
+            JsonRpcMessage::Response(r) => assert_eq!(r.error.as_ref().unwrap().code, -32601),
+            other => panic!("Expected response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_sse_stream_with_notification() {
+        let body = format!(
+            "data: {}\n\ndata: {}\n\n",
+            json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": { "progress": 1 } }),
+            json!({ "jsonrpc": "2.0", "id": "a", "result": { "tools": [] } }),
+        );
+        let messages = decode_messages(Some("text/event-stream"), &body).unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(&messages[0], JsonRpcMessage::Request(r) if r.method == "notifications/progress"));
+        assert!(matches!(&messages[1], JsonRpcMessage::Response(r) if r.id == json!("a")));
+    }
+
+    #[test]
+    fn test_decode_empty_body() {
+        assert!(decode_messages(None, "").unwrap().is_empty());
+        assert!(decode_messages(Some("application/json"), "not json").is_err());
+    }
+
+    #[test]
+    fn test_resolve_endpoint() {
+        assert_eq!(resolve_endpoint("http://localhost:8787/sse", "/messages"), "http://localhost:8787/messages");
+        assert_eq!(resolve_endpoint("http://localhost:8787/mcp/sse", "messages?s=1"), "http://localhost:8787/mcp/messages?s=1");
+        assert_eq!(resolve_endpoint("http://localhost:8787", "/messages"), "http://localhost:8787/messages");
+        assert_eq!(resolve_endpoint("http://a/sse", "https://b/messages"), "https://b/messages");
+    }
+}
@@ -0,0 +1,490 @@
+use std::cell::RefCell;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use worker::*;
+
+/// Clock skew tolerated when checking `exp` and `nbf`, in seconds.
+const CLOCK_SKEW_SECS: u64 = 60;
+/// How long a fetched JWKS is reused before being fetched again, in seconds.
+const JWKS_CACHE_TTL_SECS: u64 = 600;
+
+/// The authenticated caller, handed to tool handlers through `ToolContext`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub subject: String,
+    pub scopes: Vec<String>,
+    pub roles: Vec<String>,
+    /// Verified JWT claims, or an empty object for API keys.
+    pub claims: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// No bearer token was presented.
+    MissingToken,
+    /// The authenticator does not handle tokens of this shape.
+    Unsupported,
+    /// The token was understood but rejected.
+    InvalidToken(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "Missing bearer token"),
+            AuthError::Unsupported => write!(f, "Unsupported token"),
+            AuthError::InvalidToken(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Validates a bearer token. Implement this to plug in another scheme.
+pub trait Authenticator {
+    fn authenticate(&self, token: &str, now: u64) -> std::result::Result<Principal, AuthError>;
+}
+
+/// Static API keys, typically loaded from a Worker secret.
+pub struct ApiKeyAuthenticator {
+    keys: Vec<ApiKey>,
+}
+
+struct ApiKey {
+    name: String,
+    digest: [u8; 32],
+    scopes: Vec<String>,
+}
+
+impl ApiKeyAuthenticator {
+    /// Parses a comma-separated list of `name:key[:scopes]` entries, where
+    /// scopes are space-separated. Entries without a name, including
+    /// `:key`, are labelled by position.
+    pub fn from_spec(spec: &str) -> Self {
+        let keys = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .enumerate()
+            .map(|(index, entry)| {
+                let position = || format!("key-{}", index + 1);
+                let mut parts = entry.splitn(3, ':').map(str::trim);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(name), Some(key), scopes) => ApiKey {
+                        name: match name {
+                            "" => position(),
+                            name => name.to_string(),
+                        },
+                        digest: digest(key),
+                        scopes: scopes.unwrap_or_default().split_whitespace().map(str::to_string).collect(),
+                    },
+                    _ => ApiKey {
+                        name: position(),
+                        digest: digest(entry),
+                        scopes: Vec::new(),
+                    },
+                }
+            })
+            .collect();
+        Self { keys }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The principal a key's `subject` names, with the scopes it has now;
+    /// for re-checking grants made with the key earlier.
+    pub fn principal(&self, subject: &str) -> Option<Principal> {
+        let name = subject.strip_prefix("api-key:")?;
+        // The last matching entry wins, as in `authenticate`
+        self.keys.iter().rev().find(|key| key.name == name).map(ApiKey::principal)
+    }
+}
+
+impl ApiKey {
+    fn principal(&self) -> Principal {
+        Principal {
+            subject: format!("api-key:{}", self.name),
+            scopes: self.scopes.clone(),
+            roles: Vec::new(),
+            claims: serde_json::json!({}),
+        }
+    }
+}
+
+impl Authenticator for ApiKeyAuthenticator {
+    fn authenticate(&self, token: &str, _now: u64) -> std::result::Result<Principal, AuthError> {
+        // Compare digests so the comparison time does not depend on the key
+        let presented = digest(token);
+        let mut matched = None;
+        for key in &self.keys {
+            if constant_time_eq(&key.digest, &presented) {
+                matched = Some(key);
+            }
+        }
+
+        matched
+            .map(ApiKey::principal)
+            .ok_or_else(|| AuthError::InvalidToken("Unknown API key".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub alg: Option<String>,
+    #[serde(rename = "use", default)]
+    pub key_use: Option<String>,
+    // RSA
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+    // EC
+    #[serde(default)]
+    pub crv: Option<String>,
+    #[serde(default)]
+    pub x: Option<String>,
+    #[serde(default)]
+    pub y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// JWT bearer tokens signed with RS256 or ES256 and verified against a JWKS.
+pub struct JwtAuthenticator {
+    jwks: Jwks,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtAuthenticator {
+    pub fn new(jwks: Jwks, issuer: Option<String>, audience: Option<String>) -> Self {
+        Self { jwks, issuer, audience }
+    }
+
+    fn find_key(&self, header: &JwtHeader) -> std::result::Result<&Jwk, AuthError> {
+        let kty = if header.alg == "RS256" { "RSA" } else { "EC" };
+        let mut candidates = self.jwks.keys.iter().filter(|key| {
+            key.kty == kty
+                && key.key_use.as_deref().unwrap_or("sig") == "sig"
+                && key.alg.as_deref().is_none_or(|alg| alg == header.alg)
+        });
+
+        match &header.kid {
+            Some(kid) => candidates.find(|key| key.kid.as_deref() == Some(kid.as_str())),
+            None => candidates.next(),
+        }
+        .ok_or_else(|| AuthError::InvalidToken("No matching signing key".to_string()))
+    }
+
+    fn check_claims(&self, claims: &serde_json::Value, now: u64) -> std::result::Result<(), AuthError> {
+        let invalid = |reason: &str| Err(AuthError::InvalidToken(reason.to_string()));
+
+        match claims.get("exp").and_then(|exp| exp.as_u64()) {
+            Some(exp) if exp + CLOCK_SKEW_SECS < now => return invalid("Token expired"),
+            Some(_) => {},
+            None => return invalid("Token has no exp claim"),
+        }
+        if let Some(nbf) = claims.get("nbf").and_then(|nbf| nbf.as_u64()) {
+            if nbf > now + CLOCK_SKEW_SECS {
+                return invalid("Token not yet valid");
+            }
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(|iss| iss.as_str()) != Some(issuer.as_str()) {
+                return invalid("Unexpected issuer");
+            }
+        }
+        if let Some(audience) = &self.audience {
+            let matches = match claims.get("aud") {
+                Some(serde_json::Value::String(aud)) => aud == audience,
+                Some(serde_json::Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false,
+            };
+            if !matches {
+                return invalid("Unexpected audience");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, token: &str, now: u64) -> std::result::Result<Principal, AuthError> {
+        let mut parts = token.split('.');
+        let (Some(header_b64), Some(payload_b64), Some(signature_b64), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(AuthError::Unsupported);
+        };
+
+        let header: JwtHeader = decode_json(header_b64)?;
+        if header.alg != "RS256" && header.alg != "ES256" {
+            return Err(AuthError::InvalidToken(format!("Unsupported algorithm {}", header.alg)));
+        }
+        let key = self.find_key(&header)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature_b64)
+            .map_err(|_| AuthError::InvalidToken("Malformed signature".to_string()))?;
+        let signing_input = &token[..header_b64.len() + 1 + payload_b64.len()];
+
+        verify_signature(&header.alg, key, signing_input.as_bytes(), &signature)?;
+
+        let claims: serde_json::Value = decode_json(payload_b64)?;
+        self.check_claims(&claims, now)?;
+
+        Ok(Principal {
+            subject: claims
+                .get("sub")
+                .and_then(|sub| sub.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            scopes: scopes_from_claims(&claims),
+            roles: string_list(claims.get("roles")),
+            claims,
+        })
+    }
+}
+
+/// Reads scopes from an OAuth `scope` string or an `scp` array.
+fn scopes_from_claims(claims: &serde_json::Value) -> Vec<String> {
+    match claims.get("scope") {
+        Some(serde_json::Value::String(scope)) => scope.split_whitespace().map(str::to_string).collect(),
+        _ => string_list(claims.get("scp")),
+    }
+}
+
+fn string_list(value: Option<&serde_json::Value>) -> Vec<String> {
+    match value {
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(serde_json::Value::String(item)) => vec![item.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn verify_signature(alg: &str, key: &Jwk, message: &[u8], signature: &[u8]) -> std::result::Result<(), AuthError> {
+    let bad_key = || AuthError::InvalidToken("Malformed signing key".to_string());
+    let bad_signature = || AuthError::InvalidToken("Invalid signature".to_string());
+    let field = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|v| URL_SAFE_NO_PAD.decode(v).ok())
+            .ok_or_else(bad_key)
+    };
+
+    match alg {
+        "RS256" => {
+            use rsa::signature::Verifier;
+
+            let n = rsa::BigUint::from_bytes_be(&field(&key.n)?);
+            let e = rsa::BigUint::from_bytes_be(&field(&key.e)?);
+            let public_key = rsa::RsaPublicKey::new(n, e).map_err(|_| bad_key())?;
+            let verifying_key = rsa::pkcs1v15::VerifyingKey::<Sha256>::new(public_key);
+            let signature = rsa::pkcs1v15::Signature::try_from(signature).map_err(|_| bad_signature())?;
+            verifying_key.verify(message, &signature).map_err(|_| bad_signature())
+        },
+        "ES256" => {
+            use p256::ecdsa::signature::Verifier;
+
+            if key.crv.as_deref() != Some("P-256") {
+                return Err(bad_key());
+            }
+            let x = field(&key.x)?;
+            let y = field(&key.y)?;
+            if x.len() != 32 || y.len() != 32 {
+                return Err(bad_key());
+            }
+            let mut point = Vec::with_capacity(65);
+            point.push(0x04);
+            point.extend_from_slice(&x);
+            point.extend_from_slice(&y);
+            let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&point).map_err(|_| bad_key())?;
+            let signature = p256::ecdsa::Signature::from_slice(signature).map_err(|_| bad_signature())?;
+            verifying_key.verify(message, &signature).map_err(|_| bad_signature())
+        },
+        _ => Err(AuthError::InvalidToken(format!("Unsupported algorithm {}", alg))),
+    }
+}
+
+fn decode_json<T: serde::de::DeserializeOwned>(segment: &str) -> std::result::Result<T, AuthError> {
+    URL_SAFE_NO_PAD
+        .decode(segment)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| AuthError::InvalidToken("Malformed token".to_string()))
+}
+
+fn digest(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Ordered set of authenticators; the first to accept a token wins.
+#[derive(Default)]
+pub struct AuthChain {
+    authenticators: Vec<Box<dyn Authenticator>>,
+}
+
+impl AuthChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Box::new(authenticator));
+        self
+    }
+
+    /// Auth is disabled when no authenticator is configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.authenticators.is_empty()
+    }
+
+    pub fn authenticate(&self, token: Option<&str>, now: u64) -> std::result::Result<Principal, AuthError> {
+        let token = token.filter(|t| !t.is_empty()).ok_or(AuthError::MissingToken)?;
+        let mut error = AuthError::Unsupported;
+        for authenticator in &self.authenticators {
+            match authenticator.authenticate(token, now) {
+                Ok(principal) => return Ok(principal),
+                Err(AuthError::Unsupported) => {},
+                Err(e) => error = e,
+            }
+        }
+        Err(match error {
+            AuthError::Unsupported => AuthError::InvalidToken("Unrecognized token".to_string()),
+            e => e,
+        })
+    }
+
+    /// Builds the chain from Worker bindings:
+    ///
+    /// - `MCP_API_KEYS` (secret): comma-separated `name:key` entries
+    /// - `MCP_JWKS_URL` or `MCP_JWKS` (inline JSON): keys for JWT bearer tokens
+    /// - `MCP_JWT_ISSUER`, `MCP_JWT_AUDIENCE`: optional expected `iss` / `aud`
+    pub async fn from_env(env: &Env) -> Result<Self> {
+        let mut chain = AuthChain::new();
+
+        if let Some(spec) = env_string(env, "MCP_API_KEYS") {
+            let api_keys = ApiKeyAuthenticator::from_spec(&spec);
+            if !api_keys.is_empty() {
+                chain = chain.with(api_keys);
+            }
+        }
+
+        let jwks = match (env_string(env, "MCP_JWKS"), env_string(env, "MCP_JWKS_URL")) {
+            (Some(inline), _) => Some(
+                serde_json::from_str::<Jwks>(&inline)
+                    .map_err(|e| Error::RustError(format!("Invalid MCP_JWKS: {}", e)))?,
+            ),
+            (None, Some(url)) => Some(fetch_jwks(&url).await?),
+            (None, None) => None,
+        };
+        if let Some(jwks) = jwks {
+            chain = chain.with(JwtAuthenticator::new(
+                jwks,
+                env_string(env, "MCP_JWT_ISSUER"),
+                env_string(env, "MCP_JWT_AUDIENCE"),
+            ));
+        }
+
+        Ok(chain)
+    }
+}
+
+/// Reads a binding as a secret first, then as a plain var.
+pub(crate) fn env_string(env: &Env, name: &str) -> Option<String> {
+    env.secret(name)
+        .map(|s| s.to_string())
+        .or_else(|_| env.var(name).map(|v| v.to_string()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+thread_local! {
+    static JWKS_CACHE: RefCell<Option<(String, u64, Jwks)>> = const { RefCell::new(None) };
+}
+
+async fn fetch_jwks(url: &str) -> Result<Jwks> {
+    let now = now_secs();
+    let cached = JWKS_CACHE.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .filter(|(cached_url, fetched_at, _)| cached_url == url && now < fetched_at + JWKS_CACHE_TTL_SECS)
+            .map(|(_, _, jwks)| jwks.clone())
+    });
+    if let Some(jwks) = cached {
+        return Ok(jwks);
+    }
+
+    let mut response = Fetch::Url(Url::parse(url)?).send().await?;
+    if response.status_code() != 200 {
+        return Err(Error::RustError(format!("JWKS fetch failed with status {}", response.status_code())));
+    }
+    let jwks: Jwks = response.json().await?;
+    JWKS_CACHE.with(|cache| *cache.borrow_mut() = Some((url.to_string(), now, jwks.clone())));
+    Ok(jwks)
+}
+
+/// 32 random bytes, base64url encoded: for ids that must not be guessable.
+pub(crate) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("random source available");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(crate) fn now_secs() -> u64 {
+    Date::now().as_millis() / 1000
+}
+
+/// Extracts the token from an `Authorization: Bearer ...` header.
+pub fn bearer_token(req: &Request) -> Option<String> {
+    let header = req.headers().get("Authorization").ok().flatten()?;
+    let (scheme, token) = header.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string())
+}
+
+/// Builds the 401 response with an RFC 6750 `WWW-Authenticate` challenge,
+/// pointing at the RFC 9728 resource metadata when given.
+pub fn unauthorized(error: &AuthError, resource_metadata: Option<&str>, headers: Headers) -> Result<Response> {
+    let mut challenge = "Bearer realm=\"mcp\"".to_string();
+    if let Some(url) = resource_metadata {
+        challenge.push_str(&format!(", resource_metadata=\"{}\"", url));
+    }
+    if *error != AuthError::MissingToken {
+        challenge.push_str(&format!(
+            ", error=\"invalid_token\", error_description=\"{}\"",
+            error.to_string().replace('"', "'")
+        ));
+    }
+    headers.append("WWW-Authenticate", &challenge)?;
+    headers.append("Content-Type", "application/json")?;
+
+    let body = serde_json::json!({
+        "error": if *error == AuthError::MissingToken { "unauthorized" } else { "invalid_token" },
+        "error_description": error.to_string(),
+    });
+    Ok(Response::ok(body.to_string())?.with_status(401).with_headers(headers))
+}
@@ -0,0 +1,163 @@
+//! Cancellation of in-flight requests by `notifications/cancelled`.
+//!
+//! In-flight requests live in a per-isolate table, so a cancellation only
+//! works when its POST reaches the isolate running the request. Workers
+//! routes each request independently, so on a normal deployment that is
+//! luck; only deployments where one isolate serves a whole session, as
+//! declared by `MCP_SINGLE_ISOLATE`, can rely on it. Elsewhere the
+//! notification is ignored and the request runs to completion.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: bool,
+    reason: Option<String>,
+    wakers: Vec<Waker>,
+}
+
+/// Trips when the client sends `notifications/cancelled` for the request.
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Rc<RefCell<TokenState>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled
+    }
+
+    /// The reason the client gave, if any.
+    pub fn reason(&self) -> Option<String> {
+        self.state.borrow().reason.clone()
+    }
+
+    /// Cancels the token and wakes everything waiting on it. Later calls
+    /// keep the first reason.
+    pub fn cancel(&self, reason: Option<String>) {
+        let wakers = {
+            let mut state = self.state.borrow_mut();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            state.reason = reason;
+            std::mem::take(&mut state.wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Resolves once the token is cancelled.
+    pub async fn cancelled(&self) {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.cancelled {
+                Poll::Ready(())
+            } else {
+                state.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Runs `future` to completion, or gives up with `None` as soon as the
+    /// token is cancelled.
+    pub async fn run_until_cancelled<F: Future>(&self, future: F) -> Option<F::Output> {
+        let mut future = pin!(future);
+        let mut cancelled = pin!(self.cancelled());
+        poll_fn(|cx| {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            future.as_mut().poll(cx).map(Some)
+        })
+        .await
+    }
+}
+
+thread_local! {
+    static IN_FLIGHT: RefCell<HashMap<(String, String), CancellationToken>> = RefCell::new(HashMap::new());
+}
+
+/// Requests without an `Mcp-Session-Id` share one namespace.
+fn in_flight_key(session_id: Option<&str>, request_id: &serde_json::Value) -> (String, String) {
+    (session_id.unwrap_or_default().to_string(), request_id.to_string())
+}
+
+/// Registers a request as in flight until the guard is dropped.
+pub fn track(session_id: Option<&str>, request_id: &serde_json::Value) -> InFlight {
+    let key = in_flight_key(session_id, request_id);
+    let token = CancellationToken::new();
+    IN_FLIGHT.with(|in_flight| in_flight.borrow_mut().insert(key.clone(), token.clone()));
+    InFlight { key, token }
+}
+
+/// Cancels an in-flight request of the same session. Returns `false` if the
+/// request is unknown or already finished, which the spec says to ignore.
+/// Without a session nothing is cancelled, since requests from different
+/// clients could share an id.
+pub fn cancel(session_id: Option<&str>, request_id: &serde_json::Value, reason: Option<String>) -> bool {
+    if session_id.is_none() {
+        return false;
+    }
+    let key = in_flight_key(session_id, request_id);
+    let token = IN_FLIGHT.with(|in_flight| in_flight.borrow().get(&key).cloned());
+    match token {
+        Some(token) => {
+            token.cancel(reason);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Cancels every in-flight request of a session, e.g. when its stream goes dead.
+pub fn cancel_session(session_id: &str, reason: Option<String>) {
+    let tokens: Vec<CancellationToken> = IN_FLIGHT.with(|in_flight| {
+        in_flight
+            .borrow()
+            .iter()
+            .filter(|((session, _), _)| session == session_id)
+            .map(|(_, token)| token.clone())
+            .collect()
+    });
+    for token in tokens {
+        token.cancel(reason.clone());
+    }
+}
+
+/// Keeps a request cancellable; dropping it forgets the request.
+#[derive(Debug)]
+pub struct InFlight {
+    key: (String, String),
+    token: CancellationToken,
+}
+
+impl InFlight {
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.with(|in_flight| {
+            let mut in_flight = in_flight.borrow_mut();
+            // A reused request id may have replaced this entry
+            if in_flight.get(&self.key).is_some_and(|token| Rc::ptr_eq(&token.state, &self.token.state)) {
+                in_flight.remove(&self.key);
+            }
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tools::ToolRegistry;
+
+/// At most this many values are returned per `completion/complete`.
+pub const MAX_VALUES: usize = 100;
+
+/// What is being completed: a prompt, a resource template or, as an
+/// extension, a tool whose arguments the client is filling in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CompletionRef {
+    #[serde(rename = "ref/prompt")]
+    Prompt { name: String },
+    /// `uri` is the resource template, e.g. `file:///{path}`.
+    #[serde(rename = "ref/resource")]
+    Resource { uri: String },
+    /// Not part of the MCP spec, so only clients written for this server
+    /// send it.
+    #[serde(rename = "ref/tool")]
+    Tool { name: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub values: Vec<String>,
+    /// All matches, including those cut off by `MAX_VALUES`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    pub has_more: bool,
+}
+
+impl Completion {
+    /// Keeps the first `MAX_VALUES` of `values`, which may not be every
+    /// match, so `total` is left out. Pass up to one more than `MAX_VALUES`
+    /// to set `has_more`.
+    pub fn from_first(mut values: Vec<String>) -> Self {
+        let has_more = values.len() > MAX_VALUES;
+        values.truncate(MAX_VALUES);
+        Self {
+            values,
+            total: None,
+            has_more,
+        }
+    }
+
+    /// Keeps the first `MAX_VALUES` of `ranked`, best match first.
+    pub fn from_ranked(mut ranked: Vec<String>) -> Self {
+        let total = ranked.len();
+        ranked.truncate(MAX_VALUES);
+        Self {
+            has_more: total > ranked.len(),
+            total: Some(total),
+            values: ranked,
+        }
+    }
+}
+
+/// Suggests values for one argument. `arguments` holds values the client
+/// has already chosen for other arguments of the same prompt or template.
+pub trait Completer {
+    /// Matching values, best first.
+    fn complete(&self, value: &str, arguments: &HashMap<String, String>) -> Vec<String>;
+}
+
+impl<F> Completer for F
+where
+    F: Fn(&str, &HashMap<String, String>) -> Vec<String>,
+{
+    fn complete(&self, value: &str, arguments: &HashMap<String, String>) -> Vec<String> {
+        self(value, arguments)
+    }
+}
+
+/// Completes from a fixed list: prefix matches first, then values that
+/// contain the input elsewhere, ignoring case and keeping list order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticCompleter {
+    values: Vec<String>,
+}
+
+impl StaticCompleter {
+    pub fn new(values: &[&str]) -> Self {
+        Self {
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+impl Completer for StaticCompleter {
+    fn complete(&self, value: &str, _arguments: &HashMap<String, String>) -> Vec<String> {
+        let input = value.to_lowercase();
+        let (prefixed, rest): (Vec<&String>, Vec<&String>) = self
+            .values
+            .iter()
+            .filter(|candidate| candidate.to_lowercase().contains(&input))
+            .partition(|candidate| candidate.to_lowercase().starts_with(&input));
+        prefixed.into_iter().chain(rest).cloned().collect()
+    }
+}
+
+/// Completers keyed by reference and argument name; used for
+/// `completion/complete`.
+#[derive(Default)]
+pub struct CompletionRegistry {
+    completers: HashMap<(CompletionRef, String), Box<dyn Completer>>,
+}
+
+impl fmt::Debug for CompletionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionRegistry")
+            .field("completers", &self.completers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl CompletionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, reference: CompletionRef, argument: &str, completer: impl Completer + 'static) {
+        self.completers.insert((reference, argument.to_string()), Box::new(completer));
+    }
+
+    /// Registers a `StaticCompleter` for every string `enum` among the
+    /// tool's input properties.
+    pub fn register_tool_enums(&mut self, tools: &ToolRegistry) {
+        for tool in tools.get_tools() {
+            let Some(properties) = tool.input_schema.get("properties").and_then(|p| p.as_object()) else {
+                continue;
+            };
+            for (argument, schema) in properties {
+                let Some(values) = schema.get("enum").and_then(|e| e.as_array()) else {
+                    continue;
+                };
+                let values: Vec<&str> = values.iter().filter_map(|v| v.as_str()).collect();
+                self.register(
+                    CompletionRef::Tool { name: tool.name.clone() },
+                    argument,
+                    StaticCompleter::new(&values),
+                );
+            }
+        }
+    }
+
+    pub fn handles(&self, reference: &CompletionRef, argument: &str) -> bool {
+        self.completers.contains_key(&(reference.clone(), argument.to_string()))
+    }
+
+    /// Nothing registered for the argument completes to no values.
+    pub fn complete(
+        &self,
+        reference: &CompletionRef,
+        argument: &str,
+        value: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Completion {
+        match self.completers.get(&(reference.clone(), argument.to_string())) {
+            Some(completer) => Completion::from_ranked(completer.complete(value, arguments)),
+            None => Completion::default(),
+        }
+    }
+}
+
+/// Template variables of the resource providers, such as the KV and R2
+/// `key` or the D1 `table`, need no completer here: `completion/complete`
+/// asks the provider that lists the template, through
+/// `ResourceProvider::complete`, whenever nothing is registered for it.
+pub fn register_default_completers(registry: &mut CompletionRegistry, tools: &ToolRegistry) {
+    // Covers the calculator's `operation`
+    registry.register_tool_enums(tools);
+}
@@ -0,0 +1,577 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::auth::env_string;
+use crate::cors::{CorsPolicy, OriginPattern};
+use crate::keepalive::PingConfig;
+use crate::http::WorkersFetch;
+use crate::openapi::{self, OpenApi, OpenApiConfig};
+use crate::plugin::{self, PluginConfig, PluginSource};
+use crate::rate_limit::{KeyStrategy, RateLimit, RateLimitConfig};
+use crate::resources::kv::KvResourceConfig;
+use crate::resources::r2::R2ResourceConfig;
+use crate::sql::D1Config;
+use crate::tools::{self, register_default_tools, ToolAccess, ToolRegistry};
+use crate::upstream::{self, Upstream, UpstreamConfig};
+
+/// An image clients can show for the server, as in `serverInfo.icons`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Icon {
+    /// An `https:` or `data:` URI.
+    pub src: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Sizes such as `48x48`, or `any` for scalable formats.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sizes: Vec<String>,
+}
+
+impl Icon {
+    pub fn new(src: &str) -> Self {
+        Self {
+            src: src.to_string(),
+            mime_type: None,
+            sizes: Vec::new(),
+        }
+    }
+}
+
+/// A variable that is set but cannot be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub var: &'static str,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(var: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            var,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.var, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Deployment settings read from Worker vars and secrets. Anything unset
+/// falls back to the package metadata in `Cargo.toml`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub name: String,
+    pub version: String,
+    /// Human-readable name for display; `name` stays the identifier.
+    pub title: Option<String>,
+    pub website_url: Option<String>,
+    pub icons: Vec<Icon>,
+    /// How agents should use this server, sent in `InitializeResult`.
+    pub instructions: Option<String>,
+    /// When set, only these tools are registered.
+    pub enabled_tools: Option<Vec<String>>,
+    pub rate_limits: RateLimitConfig,
+    pub cors: CorsPolicy,
+    pub ping: PingConfig,
+    /// A KV namespace served as `kv://` resources, if any.
+    pub kv_resources: Option<KvResourceConfig>,
+    /// An R2 bucket served as `r2://` resources, if any.
+    pub r2_resources: Option<R2ResourceConfig>,
+    /// A D1 database behind `sql_query` and the schema resources, if any.
+    pub d1: Option<D1Config>,
+    /// How tools generated from the build-time OpenAPI document call the API.
+    pub openapi: OpenApiConfig,
+    /// WebAssembly tool plugins, loaded by [`ServerConfig::load_plugins`].
+    pub plugins: PluginConfig,
+    /// MCP servers whose tools are proxied, namespaced by their names.
+    pub upstreams: Vec<UpstreamConfig>,
+    /// Declares that every request of a session reaches the same isolate,
+    /// e.g. in `wrangler dev` or behind a Durable Object. Tools may only
+    /// send server-to-client requests (sampling, roots, elicitation) then,
+    /// since the client's reply arrives as a separate POST that must find
+    /// the isolate waiting for it.
+    pub single_isolate: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            title: None,
+            website_url: None,
+            icons: Vec::new(),
+            instructions: None,
+            enabled_tools: None,
+            rate_limits: RateLimitConfig::default(),
+            cors: CorsPolicy::default(),
+            ping: PingConfig::default(),
+            kv_resources: None,
+            r2_resources: None,
+            d1: None,
+            openapi: OpenApiConfig::default(),
+            plugins: PluginConfig::default(),
+            upstreams: Vec::new(),
+            single_isolate: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env(env: &Env) -> std::result::Result<Self, ConfigError> {
+        Self::from_vars(|name| env_string(env, name))
+    }
+
+    /// Builds the config from any variable lookup, such as a map in tests.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> std::result::Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        if let Some(name) = var("MCP_SERVER_NAME") {
+            config.name = name;
+        }
+        if let Some(version) = var("MCP_SERVER_VERSION") {
+            config.version = version;
+        }
+        config.title = var("MCP_SERVER_TITLE");
+        if let Some(url) = var("MCP_WEBSITE_URL") {
+            check_url(&url, &["https", "http"]).map_err(|e| ConfigError::new("MCP_WEBSITE_URL", e))?;
+            config.website_url = Some(url);
+        }
+        if let Some(icons) = var("MCP_ICONS") {
+            config.icons = parse_icons(&icons).map_err(|e| ConfigError::new("MCP_ICONS", e))?;
+        }
+        config.instructions = var("MCP_INSTRUCTIONS");
+        if let Some(tools) = var("MCP_ENABLED_TOOLS") {
+            config.enabled_tools = Some(split_list(&tools).collect());
+        }
+
+        if let Some(global) = var("MCP_RATE_LIMIT") {
+            config.rate_limits.global =
+                Some(RateLimit::parse(&global).map_err(|e| ConfigError::new("MCP_RATE_LIMIT", e))?);
+        }
+        if let Some(tools) = var("MCP_TOOL_RATE_LIMITS") {
+            config.rate_limits = config
+                .rate_limits
+                .with_tool_limits(&tools)
+                .map_err(|e| ConfigError::new("MCP_TOOL_RATE_LIMITS", e))?;
+        }
+        if let Some(key) = var("MCP_RATE_LIMIT_KEY") {
+            config.rate_limits.key = key.parse().map_err(|e| ConfigError::new("MCP_RATE_LIMIT_KEY", e))?;
+            // Unsigned session ids are whatever the client sends
+            if config.rate_limits.key == KeyStrategy::Session && var("MCP_SESSION_SECRET").is_none() {
+                return Err(ConfigError::new("MCP_RATE_LIMIT_KEY", "session keys need MCP_SESSION_SECRET to be set"));
+            }
+        }
+
+        if let Some(origins) = var("MCP_CORS_ORIGINS") {
+            config.cors = config.cors.with_origins(&origins);
+            if config.cors.allowed_origins.is_empty() {
+                return Err(ConfigError::new("MCP_CORS_ORIGINS", "no origins listed"));
+            }
+        }
+        if let Some(hosts) = var("MCP_CORS_ALLOWED_HOSTS") {
+            config.cors = config.cors.with_hosts(&hosts);
+            if config.cors.allowed_hosts.is_empty() {
+                return Err(ConfigError::new("MCP_CORS_ALLOWED_HOSTS", "no hosts listed"));
+            }
+        }
+        if let Some(credentials) = var("MCP_CORS_ALLOW_CREDENTIALS") {
+            config.cors.allow_credentials = parse_bool(&credentials)
+                .ok_or_else(|| ConfigError::new("MCP_CORS_ALLOW_CREDENTIALS", "expected true or false"))?;
+        }
+        if config.cors.allow_credentials && config.cors.allowed_origins.contains(&OriginPattern::Any) {
+            return Err(ConfigError::new(
+                "MCP_CORS_ALLOW_CREDENTIALS",
+                "cannot be used with the * origin; list origins in MCP_CORS_ORIGINS",
+            ));
+        }
+        if let Some(headers) = var("MCP_CORS_ALLOW_HEADERS") {
+            config.cors.allow_headers.extend(split_list(&headers));
+        }
+        if let Some(headers) = var("MCP_CORS_EXPOSE_HEADERS") {
+            config.cors.expose_headers.extend(split_list(&headers));
+        }
+        if let Some(max_age) = var("MCP_CORS_MAX_AGE") {
+            config.cors.max_age_secs = max_age
+                .trim()
+                .parse()
+                .map_err(|_| ConfigError::new("MCP_CORS_MAX_AGE", format!("{:?} is not a number of seconds", max_age)))?;
+        }
+
+        if let Some(interval) = var("MCP_PING_INTERVAL") {
+            config.ping.interval_secs = interval
+                .trim()
+                .parse()
+                .map_err(|_| ConfigError::new("MCP_PING_INTERVAL", format!("{:?} is not a number of seconds", interval)))?;
+        }
+        if let Some(max_missed) = var("MCP_PING_MAX_MISSED") {
+            config.ping.max_missed = match max_missed.trim().parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(ConfigError::new("MCP_PING_MAX_MISSED", format!("{:?} is not a positive number", max_missed))),
+            };
+        }
+
+        if let Some(single_isolate) = var("MCP_SINGLE_ISOLATE") {
+            config.single_isolate = parse_bool(&single_isolate)
+                .ok_or_else(|| ConfigError::new("MCP_SINGLE_ISOLATE", "expected true or false"))?;
+        }
+
+        if let Some(binding) = var("MCP_KV_RESOURCES") {
+            let mut kv = KvResourceConfig::new(binding.trim());
+            if let Some(namespace) = var("MCP_KV_RESOURCE_NAMESPACE") {
+                kv.namespace = check_host(&namespace).map_err(|e| ConfigError::new("MCP_KV_RESOURCE_NAMESPACE", e))?;
+            }
+            kv.prefix = var("MCP_KV_RESOURCE_PREFIX").unwrap_or_default();
+            if let Some(writable) = var("MCP_KV_RESOURCE_WRITABLE") {
+                kv.writable = parse_bool(&writable)
+                    .ok_or_else(|| ConfigError::new("MCP_KV_RESOURCE_WRITABLE", "expected true or false"))?;
+            }
+            if let Some(access) = var("MCP_KV_RESOURCE_ACCESS") {
+                kv.access = parse_access(&access).map_err(|e| ConfigError::new("MCP_KV_RESOURCE_ACCESS", e))?;
+            }
+            config.kv_resources = Some(kv);
+        } else {
+            require_unset(
+                &var,
+                "MCP_KV_RESOURCES",
+                &["MCP_KV_RESOURCE_NAMESPACE", "MCP_KV_RESOURCE_PREFIX", "MCP_KV_RESOURCE_WRITABLE", "MCP_KV_RESOURCE_ACCESS"],
+            )?;
+        }
+
+        if let Some(binding) = var("MCP_R2_RESOURCES") {
+            let mut r2 = R2ResourceConfig::new(binding.trim());
+            if let Some(bucket) = var("MCP_R2_RESOURCE_BUCKET") {
+                r2.bucket = check_host(&bucket).map_err(|e| ConfigError::new("MCP_R2_RESOURCE_BUCKET", e))?;
+            }
+            if let Some(prefixes) = var("MCP_R2_RESOURCE_PREFIXES") {
+                r2.prefixes = split_list(&prefixes).collect();
+            }
+            if let Some(writable) = var("MCP_R2_RESOURCE_WRITABLE") {
+                r2.writable = parse_bool(&writable)
+                    .ok_or_else(|| ConfigError::new("MCP_R2_RESOURCE_WRITABLE", "expected true or false"))?;
+            }
+            if let Some(max_read) = var("MCP_R2_MAX_READ_BYTES") {
+                r2.max_read_bytes = match max_read.trim().parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(ConfigError::new("MCP_R2_MAX_READ_BYTES", format!("{:?} is not a positive number", max_read))),
+                };
+            }
+            if let Some(access) = var("MCP_R2_RESOURCE_ACCESS") {
+                r2.access = parse_access(&access).map_err(|e| ConfigError::new("MCP_R2_RESOURCE_ACCESS", e))?;
+            }
+            config.r2_resources = Some(r2);
+        } else {
+            require_unset(
+                &var,
+                "MCP_R2_RESOURCES",
+                &[
+                    "MCP_R2_RESOURCE_BUCKET",
+                    "MCP_R2_RESOURCE_PREFIXES",
+                    "MCP_R2_RESOURCE_WRITABLE",
+                    "MCP_R2_MAX_READ_BYTES",
+                    "MCP_R2_RESOURCE_ACCESS",
+                ],
+            )?;
+        }
+
+        if let Some(binding) = var("MCP_D1_DATABASE") {
+            let mut d1 = D1Config::new(binding.trim());
+            if let Some(name) = var("MCP_D1_NAME") {
+                d1.name = check_host(&name).map_err(|e| ConfigError::new("MCP_D1_NAME", e))?;
+            }
+            if let Some(read_only) = var("MCP_D1_READ_ONLY") {
+                d1.read_only = parse_bool(&read_only)
+                    .ok_or_else(|| ConfigError::new("MCP_D1_READ_ONLY", "expected true or false"))?;
+            }
+            if let Some(max_rows) = var("MCP_D1_MAX_ROWS") {
+                d1.max_rows = match max_rows.trim().parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(ConfigError::new("MCP_D1_MAX_ROWS", format!("{:?} is not a positive number", max_rows))),
+                };
+            }
+            if let Some(access) = var("MCP_D1_ACCESS") {
+                d1.access = parse_access(&access).map_err(|e| ConfigError::new("MCP_D1_ACCESS", e))?;
+            }
+            config.d1 = Some(d1);
+        } else {
+            require_unset(&var, "MCP_D1_DATABASE", &["MCP_D1_NAME", "MCP_D1_READ_ONLY", "MCP_D1_MAX_ROWS", "MCP_D1_ACCESS"])?;
+        }
+
+        if let Some(url) = var("MCP_OPENAPI_BASE_URL") {
+            check_url(&url, &["https", "http"]).map_err(|e| ConfigError::new("MCP_OPENAPI_BASE_URL", e))?;
+            config.openapi.base_url = Some(url);
+        }
+        if let Some(token) = var("MCP_OPENAPI_BEARER_TOKEN") {
+            config.openapi.headers.push(("Authorization".to_string(), format!("Bearer {}", token.trim())));
+        }
+        if let Some(headers) = var("MCP_OPENAPI_HEADERS") {
+            let headers: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&headers)
+                .map_err(|e| ConfigError::new("MCP_OPENAPI_HEADERS", format!("expected a JSON object: {}", e)))?;
+            for (name, value) in headers {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| ConfigError::new("MCP_OPENAPI_HEADERS", format!("{} is not a string", name)))?;
+                config.openapi.headers.push((name, value.to_string()));
+            }
+        }
+
+        if let Some(sources) = var("MCP_PLUGINS") {
+            config.plugins.sources = split_list(&sources)
+                .map(|source| PluginSource::parse(&source))
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|e| ConfigError::new("MCP_PLUGINS", e))?;
+            if config.plugins.sources.is_empty() {
+                return Err(ConfigError::new("MCP_PLUGINS", "no plugins listed"));
+            }
+            if let Some(fuel) = var("MCP_PLUGIN_FUEL") {
+                config.plugins.limits.fuel = match fuel.trim().parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(ConfigError::new("MCP_PLUGIN_FUEL", format!("{:?} is not a positive number", fuel))),
+                };
+            }
+            if let Some(memory) = var("MCP_PLUGIN_MEMORY_MB") {
+                config.plugins.limits.memory_bytes = match memory.trim().parse::<usize>() {
+                    Ok(n) if n > 0 => n << 20,
+                    _ => return Err(ConfigError::new("MCP_PLUGIN_MEMORY_MB", format!("{:?} is not a positive number", memory))),
+                };
+            }
+        } else {
+            require_unset(&var, "MCP_PLUGINS", &["MCP_PLUGIN_FUEL", "MCP_PLUGIN_MEMORY_MB"])?;
+        }
+
+        if let Some(upstreams) = var("MCP_UPSTREAMS") {
+            let timeout = match var("MCP_UPSTREAM_TIMEOUT") {
+                Some(timeout) => match timeout.trim().parse() {
+                    Ok(secs) if secs > 0 => Duration::from_secs(secs),
+                    _ => return Err(ConfigError::new("MCP_UPSTREAM_TIMEOUT", format!("{:?} is not a positive number of seconds", timeout))),
+                },
+                None => upstream::DEFAULT_TIMEOUT,
+            };
+            let deadline = match var("MCP_UPSTREAM_DEADLINE") {
+                Some(deadline) => match deadline.trim().parse() {
+                    Ok(secs) if secs > 0 => Duration::from_secs(secs),
+                    _ => return Err(ConfigError::new("MCP_UPSTREAM_DEADLINE", format!("{:?} is not a positive number of seconds", deadline))),
+                },
+                None => upstream::DEFAULT_DEADLINE,
+            };
+            config.upstreams = parse_upstreams(&upstreams, timeout, deadline).map_err(|e| ConfigError::new("MCP_UPSTREAMS", e))?;
+        } else {
+            require_unset(&var, "MCP_UPSTREAMS", &["MCP_UPSTREAM_TIMEOUT", "MCP_UPSTREAM_DEADLINE"])?;
+        }
+
+        Ok(config)
+    }
+
+    /// Fetches and compiles the plugins in `MCP_PLUGINS`, or reuses those this
+    /// isolate loaded recently, for [`tool_registry`](Self::tool_registry).
+    /// A plugin that fails is logged and left out.
+    pub async fn load_plugins(&self, env: &Env) {
+        for source in &self.plugins.sources {
+            if let Err(e) = plugin::load(env, source, self.plugins.limits).await {
+                plugin::log_skipped(source, &e);
+            }
+        }
+    }
+
+    /// The default tools, plus the KV, R2 and SQL tools when enabled, the
+    /// tools generated from an OpenAPI document, the plugins this isolate has
+    /// loaded and the upstreams, narrowed to `enabled_tools`. Naming a tool
+    /// that does not exist is an error rather than silently serving nothing,
+    /// unless a plugin that might have it is missing.
+    pub fn tool_registry(&self) -> std::result::Result<ToolRegistry, ConfigError> {
+        let mut registry = ToolRegistry::new();
+        register_default_tools(&mut registry);
+        if self.kv_resources.as_ref().is_some_and(|kv| kv.writable) {
+            tools::kv::register_kv_tools(&mut registry);
+        }
+        if let Some(r2) = &self.r2_resources {
+            tools::r2::register_r2_tools(&mut registry, r2.writable);
+        }
+        if let Some(d1) = &self.d1 {
+            tools::sql::register_sql_tools(&mut registry, d1.read_only);
+        }
+        if let Some(api) = openapi::generated() {
+            let api = OpenApi::new(api, WorkersFetch).configure(&self.openapi);
+            if !api.has_base_url() {
+                return Err(ConfigError::new("MCP_OPENAPI_BASE_URL", "the OpenAPI document has no absolute server URL"));
+            }
+            registry.register_openapi(api).map_err(|e| ConfigError::new("MCP_OPENAPI_SPEC", e))?;
+        }
+        let mut missing_plugins = false;
+        for source in &self.plugins.sources {
+            let registered = match plugin::cached(source) {
+                Some(plugin) => registry.register_plugin(plugin).inspect_err(|e| plugin::log_skipped(source, e)),
+                None => Err("not loaded".to_string()),
+            };
+            missing_plugins |= registered.is_err();
+        }
+        for upstream in &self.upstreams {
+            registry
+                .register_upstream(Upstream::new(upstream.clone(), WorkersFetch))
+                .map_err(|e| ConfigError::new("MCP_UPSTREAMS", e))?;
+        }
+        if let Some(enabled) = &self.enabled_tools {
+            let enabled: Vec<String> = match missing_plugins {
+                true => enabled.iter().filter(|name| registry.has_tool(name)).cloned().collect(),
+                false => enabled.clone(),
+            };
+            registry
+                .retain_tools(&enabled)
+                .map_err(|e| ConfigError::new("MCP_ENABLED_TOOLS", e))?;
+        }
+        Ok(registry)
+    }
+}
+
+/// Accepts a JSON array of icon objects or a comma-separated list of URLs.
+fn parse_icons(spec: &str) -> std::result::Result<Vec<Icon>, String> {
+    let icons: Vec<Icon> = if spec.trim_start().starts_with('[') {
+        serde_json::from_str(spec).map_err(|e| format!("expected a JSON array of icons: {}", e))?
+    } else {
+        split_list(spec).map(|src| Icon::new(&src)).collect()
+    };
+    for icon in &icons {
+        check_url(&icon.src, &["https", "data"])?;
+    }
+    Ok(icons)
+}
+
+fn check_url(value: &str, schemes: &[&str]) -> std::result::Result<(), String> {
+    let url = url::Url::parse(value).map_err(|e| format!("{:?} is not a URL: {}", value, e))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("{:?} must use {}", value, schemes.join(" or ")));
+    }
+    Ok(())
+}
+
+/// An upstream in the JSON form of `MCP_UPSTREAMS`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct UpstreamSpec {
+    name: String,
+    url: String,
+    timeout_secs: Option<u64>,
+    deadline_secs: Option<u64>,
+    #[serde(default)]
+    headers: std::collections::BTreeMap<String, String>,
+    /// Required of callers to see and use its tools.
+    #[serde(default)]
+    scopes: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Accepts a JSON array of upstream objects, for timeouts and headers, or a
+/// comma-separated list of `name=url`.
+fn parse_upstreams(spec: &str, timeout: Duration, deadline: Duration) -> std::result::Result<Vec<UpstreamConfig>, String> {
+    let upstreams: Vec<UpstreamConfig> = if spec.trim_start().starts_with('[') {
+        let specs: Vec<UpstreamSpec> =
+            serde_json::from_str(spec).map_err(|e| format!("expected a JSON array of upstreams: {}", e))?;
+        specs
+            .into_iter()
+            .map(|spec| UpstreamConfig {
+                timeout: spec.timeout_secs.map(Duration::from_secs).unwrap_or(timeout),
+                deadline: spec.deadline_secs.map(Duration::from_secs).unwrap_or(deadline),
+                headers: spec.headers.into_iter().collect(),
+                access: ToolAccess {
+                    scopes: spec.scopes,
+                    roles: spec.roles,
+                },
+                ..UpstreamConfig::new(&spec.name, &spec.url)
+            })
+            .collect()
+    } else {
+        split_list(spec)
+            .map(|entry| match entry.split_once('=') {
+                Some((name, url)) => Ok(UpstreamConfig {
+                    timeout,
+                    deadline,
+                    ..UpstreamConfig::new(name.trim(), url.trim())
+                }),
+                None => Err(format!("{:?} is not name=url", entry)),
+            })
+            .collect::<std::result::Result<_, _>>()?
+    };
+    if upstreams.is_empty() {
+        return Err("no upstreams listed".to_string());
+    }
+    for (i, upstream) in upstreams.iter().enumerate() {
+        let valid = !upstream.name.is_empty()
+            && upstream.name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if !valid {
+            return Err(format!("{:?} is not a valid upstream name", upstream.name));
+        }
+        if upstreams[..i].iter().any(|other| other.name == upstream.name) {
+            return Err(format!("upstream {} is listed twice", upstream.name));
+        }
+        if upstream.timeout.is_zero() {
+            return Err(format!("the timeout of upstream {} must be positive", upstream.name));
+        }
+        if upstream.deadline.is_zero() {
+            return Err(format!("the deadline of upstream {} must be positive", upstream.name));
+        }
+        check_url(&upstream.url, &["https", "http"])?;
+    }
+    Ok(upstreams)
+}
+
+/// Settings that only make sense alongside `enabling` are errors without it.
+fn require_unset(
+    var: &impl Fn(&str) -> Option<String>,
+    enabling: &str,
+    names: &[&'static str],
+) -> std::result::Result<(), ConfigError> {
+    match names.iter().find(|name| var(name).is_some()) {
+        Some(name) => Err(ConfigError::new(name, format!("{} is not set", enabling))),
+        None => Ok(()),
+    }
+}
+
+/// Scopes and roles in the JSON form of `MCP_KV_RESOURCE_ACCESS` and friends.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessSpec {
+    #[serde(default)]
+    scopes: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Accepts a JSON object such as `{"scopes": ["docs:read"], "roles": ["staff"]}`.
+fn parse_access(value: &str) -> std::result::Result<ToolAccess, String> {
+    let spec: AccessSpec =
+        serde_json::from_str(value).map_err(|e| format!("expected a JSON object of scopes and roles: {}", e))?;
+    Ok(ToolAccess {
+        scopes: spec.scopes,
+        roles: spec.roles,
+    })
+}
+
+/// Names used as the host of resource URIs, such as `kv://<name>/`.
+fn check_host(value: &str) -> std::result::Result<String, String> {
+    let valid = !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    match valid {
+        true => Ok(value.to_string()),
+        false => Err(format!("{:?} is not a valid URI host", value)),
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string)
+}
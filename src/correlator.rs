@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use crate::auth::random_token;
+use crate::cancellation::CancellationToken;
+use crate::notifications::Outbox;
+
+/// Why a server-to-client request got no usable reply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientRequestError {
+    /// The client did not declare this capability in `initialize`.
+    Unsupported(&'static str),
+    /// Server-to-client requests are off because the deployment has not
+    /// been declared single-isolate; see `ServerConfig::single_isolate`.
+    Disabled,
+    /// The response is plain JSON, so nothing reaches the client until the
+    /// tool has finished. Clients must accept `text/event-stream`.
+    NoStream,
+    Timeout,
+    Cancelled,
+    /// The client answered with a JSON-RPC error.
+    Rejected { code: i64, message: String },
+    /// The server's own request was malformed, e.g. an unsupported schema.
+    InvalidRequest(String),
+    /// The client's result did not have the expected shape.
+    InvalidResult(String),
+}
+
+impl fmt::Display for ClientRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientRequestError::Unsupported(capability) => write!(f, "Client does not support {}", capability),
+            ClientRequestError::Disabled => write!(f, "Server-to-client requests are disabled; set MCP_SINGLE_ISOLATE to enable them"),
+            ClientRequestError::NoStream => write!(f, "Client did not accept an event stream"),
+            ClientRequestError::Timeout => write!(f, "Client did not reply in time"),
+            ClientRequestError::Cancelled => write!(f, "Request cancelled"),
+            ClientRequestError::Rejected { code, message } => write!(f, "Client error {}: {}", code, message),
+            ClientRequestError::InvalidRequest(e) => write!(f, "Invalid request to client: {}", e),
+            ClientRequestError::InvalidResult(e) => write!(f, "Invalid result from client: {}", e),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    reply: Option<Result<serde_json::Value, ClientRequestError>>,
+    waker: Option<Waker>,
+}
+
+/// A request is only answered from the session it was sent to; requests
+/// sent outside a session use an empty session id.
+type PendingKey = (String, String);
+
+thread_local! {
+    static PENDING: RefCell<HashMap<PendingKey, Pending>> = RefCell::new(HashMap::new());
+}
+
+/// Hands a client's JSON-RPC response to the request waiting for it.
+/// Returns `false` if nothing in this isolate is waiting for `id` from
+/// `session_id`.
+pub fn resolve(session_id: Option<&str>, id: &serde_json::Value, response: &serde_json::Value) -> bool {
+    let Some(id) = id.as_str() else {
+        return false;
+    };
+    let reply = match response.get("error") {
+        Some(error) => Err(ClientRequestError::Rejected {
+            code: error.get("code").and_then(|c| c.as_i64()).unwrap_or_default(),
+            message: error.get("message").and_then(|m| m.as_str()).unwrap_or_default().to_string(),
+        }),
+        None => Ok(response.get("result").cloned().unwrap_or_default()),
+    };
+    let waiting = PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        let slot = pending.get_mut(&(session_id.unwrap_or_default().to_string(), id.to_string()))?;
+        slot.reply = Some(reply);
+        Some(slot.waker.take())
+    });
+    match waiting {
+        Some(waker) => {
+            waker.into_iter().for_each(Waker::wake);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Forgets the request when its caller stops waiting.
+struct Registration(PendingKey);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        PENDING.with(|pending| pending.borrow_mut().remove(&self.0));
+    }
+}
+
+/// Sends `method` to the client through `outbox` and waits for the reply,
+/// at most `timeout`, giving up early if `cancellation` trips. The reply
+/// must reach this isolate, from the same session; Workers does not route
+/// a client's later POST to the isolate that is waiting, which is why
+/// [`ToolContext::send_request`](crate::tools::ToolContext::send_request)
+/// only calls this on deployments declared single-isolate. Ids are random
+/// so other clients cannot guess them.
+pub async fn request(
+    outbox: &Outbox,
+    session_id: Option<&str>,
+    method: &str,
+    params: serde_json::Value,
+    timeout: Duration,
+    cancellation: &CancellationToken,
+) -> Result<serde_json::Value, ClientRequestError> {
+    let id = format!("server-{}", random_token());
+    let key = (session_id.unwrap_or_default().to_string(), id.clone());
+    PENDING.with(|pending| pending.borrow_mut().insert(key.clone(), Pending::default()));
+    let _registration = Registration(key.clone());
+    outbox.request(&id, method, params);
+
+    let reply = poll_fn(|cx| {
+        PENDING.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            let slot = pending.entry(key.clone()).or_default();
+            match slot.reply.take() {
+                Some(reply) => Poll::Ready(reply),
+                None => {
+                    slot.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                },
+            }
+        })
+    });
+    let timed_out = async {
+        sleep(timeout).await;
+        Err(ClientRequestError::Timeout)
+    };
+    cancellation
+        .run_until_cancelled(first(reply, timed_out))
+        .await
+        .unwrap_or(Err(ClientRequestError::Cancelled))
+}
+
+/// Whichever future finishes first.
+pub(crate) async fn first<T>(a: impl Future<Output = T>, b: impl Future<Output = T>) -> T {
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+    poll_fn(|cx| {
+        if let Poll::Ready(value) = a.as_mut().poll(cx) {
+            return Poll::Ready(value);
+        }
+        b.as_mut().poll(cx)
+    })
+    .await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    worker::Delay::from(duration).await
+}
+
+/// Outside Workers, e.g. in tests, a thread stands in for `setTimeout`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let mut started = false;
+    poll_fn(|cx| {
+        if done.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        if !started {
+            started = true;
+            let (done, waker) = (done.clone(), cx.waker().clone());
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                done.store(true, Ordering::SeqCst);
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    })
+    .await
+}
@@ -0,0 +1,219 @@
+use worker::*;
+
+const DEFAULT_ALLOW_METHODS: &[&str] = &["GET", "POST", "DELETE", "OPTIONS"];
+const DEFAULT_ALLOW_HEADERS: &[&str] = &["Content-Type", "Authorization", "Mcp-Session-Id", "Mcp-Protocol-Version"];
+const DEFAULT_EXPOSE_HEADERS: &[&str] = &["Mcp-Session-Id", "WWW-Authenticate"];
+const DEFAULT_MAX_AGE_SECS: u32 = 86400;
+
+/// An allowed origin: `*`, an exact origin such as `https://app.example.com`,
+/// or a pattern where `*` stands for one or more characters other than `/`,
+/// such as `https://*.example.com` or `http://localhost:*`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OriginPattern {
+    Any,
+    Exact(String),
+    Glob(String),
+}
+
+impl OriginPattern {
+    pub fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim().trim_end_matches('/').to_ascii_lowercase();
+        match pattern.as_str() {
+            "*" => OriginPattern::Any,
+            _ if pattern.contains('*') => OriginPattern::Glob(pattern),
+            _ => OriginPattern::Exact(pattern),
+        }
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Exact(exact) => *exact == origin,
+            OriginPattern::Glob(glob) => glob_matches(glob.as_bytes(), origin.as_bytes()),
+        }
+    }
+}
+
+fn glob_matches(pattern: &[u8], value: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some((b'*', rest)) => (1..=value.len())
+            .take_while(|&n| value[n - 1] != b'/')
+            .any(|n| glob_matches(rest, &value[n..])),
+        Some((c, rest)) => value.first() == Some(c) && glob_matches(rest, &value[1..]),
+    }
+}
+
+/// `host[:port]` of a URL, the port only when it is not the scheme's
+/// default, as in a `Host` header.
+fn authority(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Cross-origin policy applied to every route by [`CorsPolicy::handle`].
+///
+/// Browsers always send `Origin` on cross-origin and non-GET requests, so a
+/// request whose `Origin` is not allowed is rejected outright rather than
+/// just missing CORS headers. Without `allowed_origins`, a page is only
+/// allowed on its own host and only when that host is in `allowed_hosts`.
+/// Comparing the origin with the `Host` header alone would not stop DNS
+/// rebinding, where a hostile page reaches a local server under the
+/// attacker's host name and both name that host; so by default, with
+/// neither list set, every request carrying an `Origin` is rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsPolicy {
+    /// Empty falls back to same-host pages on `allowed_hosts`.
+    pub allowed_origins: Vec<OriginPattern>,
+    /// `Host` values, as `host[:port]`, whose own pages are allowed when
+    /// `allowed_origins` is empty.
+    pub allowed_hosts: Vec<String>,
+    pub allow_credentials: bool,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub max_age_secs: u32,
+}
+
+impl Default for CorsPolicy {
+    /// No browser origins, no credentials.
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_hosts: Vec::new(),
+            allow_credentials: false,
+            allow_methods: DEFAULT_ALLOW_METHODS.iter().map(|m| m.to_string()).collect(),
+            allow_headers: DEFAULT_ALLOW_HEADERS.iter().map(|h| h.to_string()).collect(),
+            expose_headers: DEFAULT_EXPOSE_HEADERS.iter().map(|h| h.to_string()).collect(),
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+        }
+    }
+}
+
+impl CorsPolicy {
+    /// Allows only the given comma-separated origins or patterns.
+    pub fn with_origins(mut self, spec: &str) -> Self {
+        self.allowed_origins = spec
+            .split(',')
+            .filter(|origin| !origin.trim().is_empty())
+            .map(OriginPattern::parse)
+            .collect();
+        self
+    }
+
+    /// Allows pages served from the given comma-separated `host[:port]`
+    /// values to call the server on that same host.
+    pub fn with_hosts(mut self, spec: &str) -> Self {
+        self.allowed_hosts = spec
+            .split(',')
+            .map(|host| host.trim().to_ascii_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        self
+    }
+
+    /// Requests without an `Origin` header come from non-browser clients and
+    /// are always allowed. `host` is the request's own `host[:port]`.
+    pub fn allows_origin(&self, origin: Option<&str>, host: &str) -> bool {
+        match origin {
+            None => true,
+            Some(origin) if self.allowed_origins.is_empty() => {
+                self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))
+                    && Url::parse(origin)
+                        .ok()
+                        .and_then(|url| authority(&url))
+                        .is_some_and(|origin| origin.eq_ignore_ascii_case(host))
+            },
+            Some(origin) => self.allowed_origins.iter().any(|pattern| pattern.matches(origin)),
+        }
+    }
+
+    /// Headers for an actual (non-preflight) response. Credentials are never
+    /// allowed alongside `*`.
+    pub fn response_headers(&self, origin: Option<&str>, host: &str) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        let wildcard = self.allowed_origins.contains(&OriginPattern::Any);
+        match origin {
+            _ if wildcard => headers.push(("Access-Control-Allow-Origin", "*".to_string())),
+            Some(origin) if self.allows_origin(Some(origin), host) => {
+                headers.push(("Access-Control-Allow-Origin", origin.to_string()));
+                headers.push(("Vary", "Origin".to_string()));
+                if self.allow_credentials {
+                    headers.push(("Access-Control-Allow-Credentials", "true".to_string()));
+                }
+            },
+            _ => {
+                headers.push(("Vary", "Origin".to_string()));
+                return headers;
+            },
+        }
+        if !self.expose_headers.is_empty() {
+            headers.push(("Access-Control-Expose-Headers", self.expose_headers.join(", ")));
+        }
+        headers
+    }
+
+    /// Headers answering an `OPTIONS` preflight.
+    pub fn preflight_headers(&self, origin: Option<&str>, host: &str) -> Vec<(&'static str, String)> {
+        let mut headers = self.response_headers(origin, host);
+        if headers.iter().any(|(name, _)| *name == "Access-Control-Allow-Origin") {
+            headers.push(("Access-Control-Allow-Methods", self.allow_methods.join(", ")));
+            headers.push(("Access-Control-Allow-Headers", self.allow_headers.join(", ")));
+            headers.push(("Access-Control-Max-Age", self.max_age_secs.to_string()));
+        }
+        headers
+    }
+
+    /// `existing` response headers with the CORS headers for `origin` added,
+    /// replacing any of the same name.
+    pub fn merge_headers(
+        &self,
+        existing: impl IntoIterator<Item = (String, String)>,
+        origin: Option<&str>,
+        host: &str,
+    ) -> Vec<(String, String)> {
+        let cors = self.response_headers(origin, host);
+        let mut headers: Vec<(String, String)> = existing
+            .into_iter()
+            .filter(|(name, _)| !cors.iter().any(|(cors_name, _)| cors_name.eq_ignore_ascii_case(name)))
+            .collect();
+        headers.extend(cors.into_iter().map(|(name, value)| (name.to_string(), value)));
+        headers
+    }
+
+    /// Rejects disallowed origins, answers preflights and adds CORS headers
+    /// to whatever `route` returns. Responses such as `Response::redirect`
+    /// have immutable headers, so the headers are copied into new ones
+    /// rather than changed in place.
+    pub async fn handle<F>(&self, req: Request, route: impl FnOnce(Request) -> F) -> Result<Response>
+    where
+        F: std::future::Future<Output = Result<Response>>,
+    {
+        let origin = req.headers().get("Origin")?;
+        let origin = origin.as_deref();
+        let host = req.url().ok().and_then(|url| authority(&url)).unwrap_or_default();
+
+        if !self.allows_origin(origin, &host) {
+            return Response::error("Forbidden: origin not allowed", 403);
+        }
+
+        if req.method() == Method::Options {
+            let headers = Headers::new();
+            for (name, value) in self.preflight_headers(origin, &host) {
+                headers.set(name, &value)?;
+            }
+            return Ok(Response::empty()?.with_status(204).with_headers(headers));
+        }
+
+        let response = route(req).await?;
+        let headers = Headers::new();
+        for (name, value) in self.merge_headers(response.headers().entries(), origin, &host) {
+            headers.append(&name, &value)?;
+        }
+        Ok(response.with_headers(headers))
+    }
+}
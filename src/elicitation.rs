@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Users may take a while to fill in a form.
+pub const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The user's answer to `elicitation/create`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElicitResult {
+    /// The submitted form, checked against the requested schema.
+    Accept(serde_json::Map<String, serde_json::Value>),
+    /// The user explicitly said no.
+    Decline,
+    /// The user dismissed the request without choosing.
+    Cancel,
+}
+
+impl ElicitResult {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, ElicitResult::Accept(_))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Accept,
+    Decline,
+    Cancel,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawResult {
+    action: Action,
+    #[serde(default)]
+    content: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// A schema for a single yes/no question, used by `ToolContext::confirm`.
+pub fn confirmation_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "confirm": { "type": "boolean", "title": "Confirm" }
+        },
+        "required": ["confirm"]
+    })
+}
+
+/// Checks that `schema` is what clients accept: a flat object whose
+/// properties are strings, numbers, integers, booleans or string enums.
+pub fn check_schema(schema: &serde_json::Value) -> Result<(), String> {
+    if schema.get("type").and_then(|t| t.as_str()) != Some("object") {
+        return Err("schema must have type \"object\"".to_string());
+    }
+    let properties = schema
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .ok_or("schema must list properties")?;
+    for (name, property) in properties {
+        match property.get("type").and_then(|t| t.as_str()) {
+            Some("string" | "number" | "integer" | "boolean") => {},
+            _ => return Err(format!("property '{}' must be a string, number, integer or boolean", name)),
+        }
+    }
+    Ok(())
+}
+
+/// Parses the client's reply, validating accepted content against `schema`.
+pub fn parse_result(result: serde_json::Value, schema: &serde_json::Value) -> Result<ElicitResult, String> {
+    let raw: RawResult = serde_json::from_value(result).map_err(|e| e.to_string())?;
+    match raw.action {
+        Action::Decline => Ok(ElicitResult::Decline),
+        Action::Cancel => Ok(ElicitResult::Cancel),
+        Action::Accept => {
+            let content = raw.content.unwrap_or_default();
+            validate(&content, schema)?;
+            Ok(ElicitResult::Accept(content))
+        },
+    }
+}
+
+fn validate(content: &serde_json::Map<String, serde_json::Value>, schema: &serde_json::Value) -> Result<(), String> {
+    let empty = serde_json::Map::new();
+    let properties = schema.get("properties").and_then(|p| p.as_object()).unwrap_or(&empty);
+    let required = schema.get("required").and_then(|r| r.as_array());
+
+    for name in required.into_iter().flatten().filter_map(|name| name.as_str()) {
+        if !content.contains_key(name) {
+            return Err(format!("missing required field '{}'", name));
+        }
+    }
+    for (name, value) in content {
+        let property = properties.get(name).ok_or_else(|| format!("unexpected field '{}'", name))?;
+        validate_value(name, value, property)?;
+    }
+    Ok(())
+}
+
+fn validate_value(name: &str, value: &serde_json::Value, property: &serde_json::Value) -> Result<(), String> {
+    let bound = |key: &str| property.get(key).and_then(|b| b.as_f64());
+    match property.get("type").and_then(|t| t.as_str()) {
+        Some("string") => {
+            let text = value.as_str().ok_or_else(|| format!("'{}' must be a string", name))?;
+            if let Some(options) = property.get("enum").and_then(|e| e.as_array()) {
+                if !options.iter().any(|option| option.as_str() == Some(text)) {
+                    return Err(format!("'{}' must be one of the offered options", name));
+                }
+            }
+            let length = text.chars().count() as f64;
+            if bound("minLength").is_some_and(|min| length < min) || bound("maxLength").is_some_and(|max| length > max) {
+                return Err(format!("'{}' has the wrong length", name));
+            }
+        },
+        Some(kind @ ("number" | "integer")) => {
+            let number = value.as_f64().ok_or_else(|| format!("'{}' must be a number", name))?;
+            if kind == "integer" && !(value.is_i64() || value.is_u64()) {
+                return Err(format!("'{}' must be an integer", name));
+            }
+            if bound("minimum").is_some_and(|min| number < min) || bound("maximum").is_some_and(|max| number > max) {
+                return Err(format!("'{}' is out of range", name));
+            }
+        },
+        Some("boolean") => {
+            value.as_bool().ok_or_else(|| format!("'{}' must be true or false", name))?;
+        },
+        _ => return Err(format!("'{}' has an unsupported type", name)),
+    }
+    Ok(())
+}
@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Longest expression accepted, in characters.
+pub const MAX_LENGTH: usize = 1024;
+
+/// Deepest nesting of parentheses, calls and unary signs accepted, so
+/// hostile input cannot exhaust the stack.
+pub const MAX_DEPTH: usize = 64;
+
+/// Named constants; variables with the same name take precedence.
+pub const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+];
+
+/// Functions and how many arguments they take; `None` means one or more.
+pub const FUNCTIONS: &[(&str, Option<usize>)] = &[
+    ("abs", Some(1)),
+    ("sqrt", Some(1)),
+    ("cbrt", Some(1)),
+    ("exp", Some(1)),
+    ("ln", Some(1)),
+    ("log10", Some(1)),
+    ("log2", Some(1)),
+    ("sin", Some(1)),
+    ("cos", Some(1)),
+    ("tan", Some(1)),
+    ("asin", Some(1)),
+    ("acos", Some(1)),
+    ("atan", Some(1)),
+    ("floor", Some(1)),
+    ("ceil", Some(1)),
+    ("round", Some(1)),
+    ("trunc", Some(1)),
+    ("pow", Some(2)),
+    ("atan2", Some(2)),
+    ("hypot", Some(2)),
+    ("min", None),
+    ("max", None),
+];
+
+/// What went wrong and where: `column` counts characters from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl ExpressionError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            column: position + 1,
+            message: message.into(),
+        }
+    }
+
+    /// The expression with a caret under the error, for showing to people.
+    pub fn pointer(&self, source: &str) -> String {
+        format!("{}\n{}^", source, " ".repeat(self.column - 1))
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+/// A token, where it starts and its text, in characters.
+#[derive(Debug, Clone)]
+struct Lexeme {
+    token: Token,
+    position: usize,
+    text: String,
+}
+
+fn tokenize(chars: &[char]) -> Result<Vec<Lexeme>, ExpressionError> {
+    let mut lexemes = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            },
+            '0'..='9' | '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                match text.parse::<f64>() {
+                    Ok(value) if value.is_finite() => Token::Number(value),
+                    Ok(_) => return Err(ExpressionError::new(start, format!("Number {} is too large", text))),
+                    Err(_) => return Err(ExpressionError::new(start, format!("Invalid number {}", text))),
+                }
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                Token::Identifier
+            },
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                Token::Caret
+            },
+            _ => {
+                i += 1;
+                match c {
+                    '+' => Token::Plus,
+                    '-' | '−' => Token::Minus,
+                    '*' | '×' => Token::Star,
+                    '/' | '÷' => Token::Slash,
+                    '%' => Token::Percent,
+                    '^' => Token::Caret,
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    ',' => Token::Comma,
+                    _ => return Err(ExpressionError::new(start, format!("Unexpected character '{}'", c))),
+                }
+            },
+        };
+        lexemes.push(Lexeme {
+            token,
+            position: start,
+            text: chars[start..i].iter().collect(),
+        });
+    }
+    Ok(lexemes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Plus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+}
+
+impl BinaryOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Remainder => "%",
+            BinaryOp::Power => "^",
+        }
+    }
+}
+
+/// A parsed expression; `position`s point back into the source for errors
+/// found while evaluating.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Name { name: String, position: usize },
+    Unary { op: UnaryOp, operand: Box<Expression> },
+    Binary { op: BinaryOp, left: Box<Expression>, right: Box<Expression>, position: usize },
+    Call { function: String, arguments: Vec<Expression>, position: usize },
+}
+
+/// Writes the expression fully parenthesized, which parses back to an
+/// expression with the same value.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Number(value) => write!(f, "{:?}", value),
+            Expression::Name { name, .. } => write!(f, "{}", name),
+            Expression::Unary { op: UnaryOp::Negate, operand } => write!(f, "(-{})", operand),
+            Expression::Unary { op: UnaryOp::Plus, operand } => write!(f, "(+{})", operand),
+            Expression::Binary { op, left, right, .. } => write!(f, "({} {} {})", left, op.symbol(), right),
+            Expression::Call { function, arguments, .. } => {
+                write!(f, "{}(", function)?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+/// Precedence climbing over the tokens, loosest first:
+///
+/// ```text
+/// sum     = product (("+" | "-") product)*
+/// product = unary (("*" | "/" | "%") unary)*
+/// unary   = ("-" | "+") unary | power
+/// power   = primary ("^" unary)?
+/// primary = number | name | name "(" sum ("," sum)* ")" | "(" sum ")"
+/// ```
+///
+/// so `-2^2` is `-(2^2)` and `2^3^2` is `2^(3^2)`.
+struct Parser {
+    lexemes: Vec<Lexeme>,
+    next: usize,
+    depth: usize,
+    /// Where the input ends, for errors about missing tokens.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Lexeme> {
+        let lexeme = self.lexemes.get(self.next).cloned();
+        self.next += 1;
+        lexeme
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        match self.peek() {
+            Some(lexeme) if lexeme.token == token => {
+                self.next += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ExpressionError {
+        match self.peek() {
+            Some(lexeme) => ExpressionError::new(lexeme.position, format!("Expected {} but found '{}'", expected, lexeme.text)),
+            None => ExpressionError::new(self.end, format!("Expected {} but the expression ended", expected)),
+        }
+    }
+
+    fn nest(&mut self, position: usize) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        match self.depth > MAX_DEPTH {
+            true => Err(ExpressionError::new(position, format!("Expression is nested more than {} levels deep", MAX_DEPTH))),
+            false => Ok(()),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.product()?;
+        loop {
+            let op = match self.peek().map(|lexeme| lexeme.token) {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Subtract,
+                _ => return Ok(left),
+            };
+            let position = self.advance().unwrap().position;
+            let right = self.product()?;
+            left = Expression::Binary { op, left: Box::new(left), right: Box::new(right), position };
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek().map(|lexeme| lexeme.token) {
+                Some(Token::Star) => BinaryOp::Multiply,
+                Some(Token::Slash) => BinaryOp::Divide,
+                Some(Token::Percent) => BinaryOp::Remainder,
+                _ => return Ok(left),
+            };
+            let position = self.advance().unwrap().position;
+            let right = self.unary()?;
+            left = Expression::Binary { op, left: Box::new(left), right: Box::new(right), position };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        let op = match self.peek().map(|lexeme| lexeme.token) {
+            Some(Token::Minus) => UnaryOp::Negate,
+            Some(Token::Plus) => UnaryOp::Plus,
+            _ => return self.power(),
+        };
+        let position = self.advance().unwrap().position;
+        self.nest(position)?;
+        let operand = self.unary()?;
+        self.depth -= 1;
+        Ok(Expression::Unary { op, operand: Box::new(operand) })
+    }
+
+    fn power(&mut self) -> Result<Expression, ExpressionError> {
+        let base = self.primary()?;
+        let Some(position) = self.peek().filter(|lexeme| lexeme.token == Token::Caret).map(|lexeme| lexeme.position) else {
+            return Ok(base);
+        };
+        self.next += 1;
+        self.nest(position)?;
+        let exponent = self.unary()?;
+        self.depth -= 1;
+        Ok(Expression::Binary { op: BinaryOp::Power, left: Box::new(base), right: Box::new(exponent), position })
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        let Some(lexeme) = self.peek().cloned() else {
+            return Err(self.unexpected("a number, name or '('"));
+        };
+        match lexeme.token {
+            Token::Number(value) => {
+                self.next += 1;
+                Ok(Expression::Number(value))
+            },
+            Token::LeftParen => {
+                self.next += 1;
+                self.nest(lexeme.position)?;
+                let inner = self.sum()?;
+                self.depth -= 1;
+                if !self.eat(Token::RightParen) {
+                    return Err(self.unexpected("')'"));
+                }
+                Ok(inner)
+            },
+            Token::Identifier => {
+                self.next += 1;
+                if !self.eat(Token::LeftParen) {
+                    return Ok(Expression::Name { name: lexeme.text, position: lexeme.position });
+                }
+                let Some(&(_, arity)) = FUNCTIONS.iter().find(|(name, _)| *name == lexeme.text) else {
+                    return Err(ExpressionError::new(lexeme.position, format!("Unknown function {}", lexeme.text)));
+                };
+                self.nest(lexeme.position)?;
+                let mut arguments = vec![self.sum()?];
+                while self.eat(Token::Comma) {
+                    arguments.push(self.sum()?);
+                }
+                self.depth -= 1;
+                if !self.eat(Token::RightParen) {
+                    return Err(self.unexpected("',' or ')'"));
+                }
+                match arity {
+                    Some(arity) if arguments.len() != arity => Err(ExpressionError::new(
+                        lexeme.position,
+                        format!("{} takes {} argument{} but got {}", lexeme.text, arity, if arity == 1 { "" } else { "s" }, arguments.len()),
+                    )),
+                    _ => Ok(Expression::Call { function: lexeme.text, arguments, position: lexeme.position }),
+                }
+            },
+            _ => Err(self.unexpected("a number, name or '('")),
+        }
+    }
+}
+
+/// Parses an arithmetic expression such as `2 * (x + 1) ^ 2 - sqrt(y)`.
+pub fn parse(source: &str) -> Result<Expression, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    if chars.len() > MAX_LENGTH {
+        return Err(ExpressionError::new(MAX_LENGTH, format!("Expression is longer than {} characters", MAX_LENGTH)));
+    }
+    let mut parser = Parser {
+        lexemes: tokenize(&chars)?,
+        next: 0,
+        depth: 0,
+        end: chars.len(),
+    };
+    if parser.lexemes.is_empty() {
+        return Err(ExpressionError::new(0, "Expression is empty"));
+    }
+    let expression = parser.sum()?;
+    match parser.peek() {
+        Some(lexeme) if lexeme.token == Token::RightParen => {
+            Err(ExpressionError::new(lexeme.position, "Unmatched ')'"))
+        },
+        Some(_) => Err(parser.unexpected("an operator")),
+        None => Ok(expression),
+    }
+}
+
+impl Expression {
+    /// Evaluates with `variables` bound. Results that are not finite, such
+    /// as `1 / 0` or `sqrt(-1)`, are errors at the operator or function
+    /// that produced them.
+    pub fn eval(&self, variables: &HashMap<String, f64>) -> Result<f64, ExpressionError> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Name { name, position } => variables
+                .get(name)
+                .copied()
+                .or_else(|| CONSTANTS.iter().find(|(constant, _)| constant == name).map(|(_, value)| *value))
+                .ok_or_else(|| ExpressionError::new(*position, format!("Unknown variable {}", name))),
+            Expression::Unary { op: UnaryOp::Negate, operand } => Ok(-operand.eval(variables)?),
+            Expression::Unary { op: UnaryOp::Plus, operand } => operand.eval(variables),
+            Expression::Binary { op, left, right, position } => {
+                let (left, right) = (left.eval(variables)?, right.eval(variables)?);
+                let value = match op {
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Subtract => left - right,
+                    BinaryOp::Multiply => left * right,
+                    BinaryOp::Divide | BinaryOp::Remainder if right == 0.0 => {
+                        return Err(ExpressionError::new(*position, "Division by zero"));
+                    },
+                    BinaryOp::Divide => left / right,
+                    BinaryOp::Remainder => left % right,
+                    BinaryOp::Power => left.powf(right),
+                };
+                finite(value, *position, op.symbol())
+            },
+            Expression::Call { function, arguments, position } => {
+                let arguments = arguments.iter().map(|argument| argument.eval(variables)).collect::<Result<Vec<f64>, _>>()?;
+                let x = arguments[0];
+                let value = match function.as_str() {
+                    "abs" => x.abs(),
+                    "sqrt" => x.sqrt(),
+                    "cbrt" => x.cbrt(),
+                    "exp" => x.exp(),
+                    "ln" => x.ln(),
+                    "log10" => x.log10(),
+                    "log2" => x.log2(),
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "asin" => x.asin(),
+                    "acos" => x.acos(),
+                    "atan" => x.atan(),
+                    "floor" => x.floor(),
+                    "ceil" => x.ceil(),
+                    "round" => x.round(),
+                    "trunc" => x.trunc(),
+                    "pow" => x.powf(arguments[1]),
+                    "atan2" => x.atan2(arguments[1]),
+                    "hypot" => x.hypot(arguments[1]),
+                    "min" => arguments.iter().copied().fold(f64::INFINITY, f64::min),
+                    "max" => arguments.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    _ => unreachable!("the parser only accepts known functions"),
+                };
+                finite(value, *position, function)
+            },
+        }
+    }
+
+    /// Names the expression reads that are not constants, in order of first
+    /// use.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) => {},
+            Expression::Name { name, .. } => {
+                if !names.contains(&name.as_str()) && !CONSTANTS.iter().any(|(constant, _)| constant == name) {
+                    names.push(name);
+                }
+            },
+            Expression::Unary { operand, .. } => operand.collect_names(names),
+            Expression::Binary { left, right, .. } => {
+                left.collect_names(names);
+                right.collect_names(names);
+            },
+            Expression::Call { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_names(names);
+                }
+            },
+        }
+    }
+}
+
+fn finite(value: f64, position: usize, what: &str) -> Result<f64, ExpressionError> {
+    match value {
+        value if value.is_nan() => Err(ExpressionError::new(position, format!("{} is undefined for these values", what))),
+        value if value.is_infinite() => Err(ExpressionError::new(position, format!("{} overflowed", what))),
+        value => Ok(value),
+    }
+}
+
+/// Parses and evaluates in one step.
+pub fn evaluate(source: &str, variables: &HashMap<String, f64>) -> Result<f64, ExpressionError> {
+    parse(source)?.eval(variables)
+}
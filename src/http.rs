@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use futures_util::stream::{self, LocalBoxStream};
+use worker::*;
+
+/// A body read piece by piece as it arrives.
+pub type BodyStream = LocalBoxStream<'static, Result<Vec<u8>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// The first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The `Content-Type` without parameters.
+    pub fn media_type(&self) -> Option<&str> {
+        self.header("Content-Type").map(|content_type| content_type.split(';').next().unwrap_or_default().trim())
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.media_type().is_some_and(|essence| essence == "application/json" || essence.ends_with("+json"))
+    }
+}
+
+/// Sends the requests that reach other services, such as OpenAPI operations
+/// and upstream MCP servers, so they can run against `Fetch` or, in tests, a
+/// local server.
+#[async_trait(?Send)]
+pub trait HttpClient {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+
+    /// Sends `request` once its headers are back, leaving the body to be
+    /// read as it arrives, for event streams that stay open while the server
+    /// works. The response's own `body` is empty. Clients that cannot stream
+    /// hand over the whole body at once.
+    async fn open(&self, request: HttpRequest) -> Result<(HttpResponse, BodyStream)> {
+        let mut response = self.send(request).await?;
+        let body = std::mem::take(&mut response.body).into_bytes();
+        Ok((response, Box::pin(stream::once(async move { Ok(body) }))))
+    }
+}
+
+pub struct WorkersFetch;
+
+#[async_trait(?Send)]
+impl HttpClient for WorkersFetch {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let mut response = fetch(request).await?;
+        Ok(HttpResponse {
+            status: response.status_code(),
+            headers: response.headers().entries().collect(),
+            body: response.text().await?,
+        })
+    }
+
+    async fn open(&self, request: HttpRequest) -> Result<(HttpResponse, BodyStream)> {
+        let mut response = fetch(request).await?;
+        let head = HttpResponse {
+            status: response.status_code(),
+            headers: response.headers().entries().collect(),
+            body: String::new(),
+        };
+        let body: BodyStream = match response.stream() {
+            Ok(body) => Box::pin(body),
+            // No body at all, as with `202 Accepted`
+            Err(_) => Box::pin(stream::empty()),
+        };
+        Ok((head, body))
+    }
+}
+
+/// Sends `request` with `Fetch`.
+async fn fetch(request: HttpRequest) -> Result<Response> {
+    let headers = Headers::new();
+    for (name, value) in &request.headers {
+        headers.append(name, value)?;
+    }
+    let mut init = RequestInit::new();
+    init.with_method(Method::from(request.method)).with_headers(headers);
+    if let Some(body) = request.body {
+        init.with_body(Some(body.into()));
+    }
+    Fetch::Request(Request::new_with_init(&request.url, &init)?).send().await
+}
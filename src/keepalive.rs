@@ -0,0 +1,127 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use crate::auth::random_token;
+use crate::resources::subscriptions;
+use crate::{cancellation, logging, roots};
+
+/// How often long-lived SSE streams ping the client, and how many pings
+/// may go unanswered before the connection is treated as dead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingConfig {
+    /// Zero disables pings; SSE streams then close after their first event.
+    pub interval_secs: u64,
+    pub max_missed: u32,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 0,
+            max_missed: 3,
+        }
+    }
+}
+
+impl PingConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.interval_secs > 0
+    }
+}
+
+thread_local! {
+    /// Unanswered pings by session, the empty string for none, and id.
+    static OUTSTANDING: RefCell<HashSet<(String, String)>> = RefCell::new(HashSet::new());
+}
+
+/// Records the client's reply to one of our pings. Returns `false` for
+/// responses to anything else, including pings sent to another session.
+pub fn record_pong(session_id: Option<&str>, id: &serde_json::Value) -> bool {
+    id.as_str().is_some_and(|id| {
+        let key = (session_id.unwrap_or_default().to_string(), id.to_string());
+        OUTSTANDING.with(|outstanding| outstanding.borrow_mut().remove(&key))
+    })
+}
+
+/// Forgets everything this isolate holds for a session whose stream went
+/// dead: its log level, cached roots, resource subscriptions and any
+/// requests still running, which are cancelled. This does not end the
+/// session. Other isolates keep their state, and the id stays valid, since
+/// sessions are carried in the id rather than stored; a client that comes
+/// back simply starts again from the defaults here.
+pub fn release_session(session_id: &str) {
+    logging::clear_session_level(session_id);
+    roots::invalidate(session_id);
+    subscriptions::unsubscribe_all(session_id);
+    cancellation::cancel_session(session_id, Some("Session stream closed".to_string()));
+}
+
+/// Pings for one SSE stream. Pongs may arrive on any request for the same
+/// session handled by the same isolate.
+#[derive(Debug)]
+pub struct Heartbeat {
+    config: PingConfig,
+    session_id: Option<String>,
+    pending: Vec<String>,
+}
+
+impl Heartbeat {
+    pub fn new(config: PingConfig, session_id: Option<String>) -> Self {
+        Self {
+            config,
+            session_id,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PingConfig {
+        &self.config
+    }
+
+    /// Pings sent since the client last answered one.
+    pub fn missed(&self) -> usize {
+        self.pending
+            .iter()
+            .filter(|id| OUTSTANDING.with(|outstanding| outstanding.borrow().contains(&self.key(id))))
+            .count()
+    }
+
+    fn key(&self, id: &str) -> (String, String) {
+        (self.session_id.clone().unwrap_or_default(), id.to_string())
+    }
+
+    /// The next `ping` request to send, or `None` once `max_missed` pings in
+    /// a row went unanswered. The stream should then close; its session, if
+    /// any, is released in this isolate.
+    pub fn next_ping(&mut self) -> Option<serde_json::Value> {
+        if self.missed() < self.pending.len() {
+            self.forget_pending();
+        }
+        if self.pending.len() >= self.config.max_missed as usize {
+            if let Some(session_id) = &self.session_id {
+                release_session(session_id);
+            }
+            return None;
+        }
+
+        let id = format!("ping-{}", random_token());
+        OUTSTANDING.with(|outstanding| outstanding.borrow_mut().insert(self.key(&id)));
+        self.pending.push(id.clone());
+        Some(serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": "ping" }))
+    }
+
+    fn forget_pending(&mut self) {
+        OUTSTANDING.with(|outstanding| {
+            let mut outstanding = outstanding.borrow_mut();
+            for id in std::mem::take(&mut self.pending) {
+                outstanding.remove(&self.key(&id));
+            }
+        });
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.forget_pending();
+    }
+}
@@ -1,9 +1,46 @@
+use std::rc::Rc;
+
 use worker::*;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-mod tools;
-use tools::{ToolRegistry, register_default_tools};
+pub mod auth;
+pub mod cancellation;
+pub mod completion;
+pub mod config;
+pub mod correlator;
+pub mod elicitation;
+pub mod expression;
+pub mod http;
+pub mod logging;
+pub mod notifications;
+pub mod cors;
+pub mod keepalive;
+pub mod oauth;
+pub mod openapi;
+pub mod plugin;
+pub mod protocol;
+pub mod rate_limit;
+pub mod resources;
+pub mod roots;
+pub mod sampling;
+pub mod session;
+pub mod sql;
+pub mod tools;
+pub mod upstream;
+use auth::{AuthChain, Principal};
+use cancellation::CancellationToken;
+use completion::{Completion, CompletionRef, CompletionRegistry};
+use config::{ConfigError, Icon, ServerConfig};
+use keepalive::{Heartbeat, PingConfig};
+use logging::{LogLevel, Logger};
+use notifications::{Outbox, SessionStream};
+use protocol::{Feature, ProtocolVersion, LATEST_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+use rate_limit::{RateLimited, RateLimiter};
+use resources::{subscriptions, ResourceError, ResourceRegistry};
+use session::SessionSigner;
+use sql::SqlDatabase;
+use tools::{ToolContext, ToolError, ToolRegistry};
 
 // Core MCP types
 #[derive(Debug, Serialize)]
@@ -11,11 +48,16 @@ struct McpResponse {
     content: Vec<ContentItem>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct ContentItem {
     #[serde(rename = "type")]
     content_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     text: String,
+    /// The fields of other content types, such as an image's `data` and
+    /// `mimeType`, as sent by an upstream server.
+    #[serde(flatten)]
+    data: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,115 +90,297 @@ struct JsonRpcError {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct InitializeResult {
     protocol_version: String,
     capabilities: ServerCapabilities,
     server_info: ServerInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 struct ServerCapabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
     experimental: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     logging: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completions: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     prompts: Option<PromptsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     resources: Option<ResourcesCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<ToolsCapability>,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct PromptsCapability {
     list_changed: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ResourcesCapability {
     subscribe: Option<bool>,
     list_changed: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ToolsCapability {
     list_changed: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ServerInfo {
     name: String,
     version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    website_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    icons: Vec<Icon>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct InitializeParams {
-    protocol_version: String,
-    capabilities: ClientCapabilities,
-    client_info: ClientInfo,
+#[derive(Debug, Serialize)]
+struct ToolsList {
+    tools: Vec<Tool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ClientCapabilities {
-    experimental: Option<serde_json::Value>,
-    sampling: Option<serde_json::Value>,
-    roots: Option<RootsCapability>,
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tool {
+    name: String,
+    description: Option<String>,
+    input_schema: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    annotations: Option<ToolAnnotations>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct RootsCapability {
-    list_changed: Option<bool>,
+impl Tool {
+    pub fn new(name: &str, description: Option<&str>, input_schema: serde_json::Value) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.map(str::to_string),
+            input_schema,
+            annotations: None,
+        }
+    }
+
+    pub fn with_annotations(mut self, annotations: ToolAnnotations) -> Self {
+        self.annotations = Some(annotations);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn annotations(&self) -> Option<&ToolAnnotations> {
+        self.annotations.as_ref()
+    }
+
+    /// Whether the tool may destroy data. As in the spec, a tool without
+    /// annotations is assumed to.
+    pub fn is_destructive(&self) -> bool {
+        let annotations = self.annotations.clone().unwrap_or_default();
+        !annotations.read_only_hint.unwrap_or(false) && annotations.destructive_hint.unwrap_or(true)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ClientInfo {
-    name: String,
-    version: String,
+/// Hints about a tool's behaviour, for clients deciding what to confirm
+/// with the user. Clients must not rely on them for safety.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The tool does not change its environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// The tool may delete or overwrite data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Calling the tool again with the same arguments has no further effect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool reaches systems outside the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
-struct ToolsList {
-    tools: Vec<Tool>,
+impl ToolAnnotations {
+    pub fn read_only() -> Self {
+        Self {
+            read_only_hint: Some(true),
+            ..Self::default()
+        }
+    }
+
+    /// For tools that overwrite or delete; `idempotent` when repeating the
+    /// call changes nothing more.
+    pub fn destructive(idempotent: bool) -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(true),
+            idempotent_hint: Some(idempotent),
+            ..Self::default()
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Tool {
+#[derive(Debug, Deserialize)]
+struct SetLevelParams {
+    level: LogLevel,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelledParams {
+    request_id: serde_json::Value,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListParams {
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResourceParams {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompleteParams {
+    #[serde(rename = "ref")]
+    reference: CompletionRef,
+    argument: CompleteArgument,
+    #[serde(default)]
+    context: CompleteContext,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompleteArgument {
     name: String,
-    description: Option<String>,
-    input_schema: serde_json::Value,
+    value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CompleteContext {
+    #[serde(default)]
+    arguments: std::collections::HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ToolsCallParams {
     name: String,
     arguments: Option<serde_json::Value>,
+    #[serde(rename = "_meta")]
+    meta: Option<RequestMeta>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestMeta {
+    progress_token: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ToolsCallResult {
     content: Vec<ContentItem>,
+    #[serde(default)]
     is_error: Option<bool>,
+    /// The result as JSON, for clients that read it; `content` stays the
+    /// text version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    structured_content: Option<serde_json::Value>,
+}
+
+impl ToolsCallResult {
+    pub fn with_structured_content(mut self, structured_content: serde_json::Value) -> Self {
+        self.structured_content = Some(structured_content);
+        self
+    }
+}
+
+fn tool_context(req: &Request, state: &RequestState) -> ToolContext {
+    let session_id = req.headers().get("Mcp-Session-Id").ok().flatten();
+    let outbox = Outbox::new();
+    ToolContext {
+        principal: state.principal.clone(),
+        logger: Logger::with_outbox(logging::session_level(session_id.as_deref()), outbox.clone()),
+        session_id,
+        outbox,
+        progress_token: None,
+        cancellation: CancellationToken::new(),
+        client_capabilities: Default::default(),
+        protocol_version: ProtocolVersion::assumed(),
+        streaming: false,
+        client_requests: state.config.single_isolate,
+        resources: state.resources.clone(),
+        database: state.database.clone(),
+    }
 }
 
-// Configuration constants - Update these for your MCP server
-const SERVER_NAME: &str = "{{server_name}}";
-const SERVER_VERSION: &str = "1.0.0";
-const PROTOCOL_VERSION: &str = "2024-11-05";
+/// Per-request state shared with route handlers.
+struct RequestState {
+    principal: Option<Principal>,
+    config: ServerConfig,
+    tools: ToolRegistry,
+    completions: CompletionRegistry,
+    resources: Rc<ResourceRegistry>,
+    database: Option<Rc<SqlDatabase>>,
+}
+
+/// JSON-RPC server error returned when the caller may not use a tool.
+const FORBIDDEN: i32 = -32003;
+
+/// JSON-RPC error the spec assigns to reads of unknown resources.
+const RESOURCE_NOT_FOUND: i32 = -32002;
+
+/// JSON-RPC server error returned when a rate limit rejects a tool call.
+const RATE_LIMITED: i32 = -32029;
+
+/// Sent in `_meta` when answering `resources/subscribe`.
+const SUBSCRIPTION_DELIVERY: &str =
+    "best-effort: updates reach the session's GET /mcp stream only when made on the same Worker isolate";
 
-fn handle_json_rpc_request(request: JsonRpcRequest, tool_registry: &ToolRegistry) -> JsonRpcResponse {
+async fn handle_json_rpc_request(request: JsonRpcRequest, state: &RequestState, tool_ctx: &ToolContext) -> JsonRpcResponse {
     match request.method.as_str() {
         "initialize" => {
+            let version = tool_ctx.protocol_version;
             let result = InitializeResult {
-                protocol_version: PROTOCOL_VERSION.to_string(),
+                protocol_version: version.to_string(),
                 capabilities: ServerCapabilities {
                     experimental: None,
-                    logging: None,
+                    logging: Some(json!({})),
+                    // Covers `ref/prompt` and `ref/resource`; `ref/tool` is
+                    // our own extension, which standard clients do not send
+                    completions: version.supports(Feature::Completions).then(|| json!({})),
                     prompts: None,
-                    resources: None,
+                    resources: Some(ResourcesCapability {
+                        subscribe: Some(true),
+                        list_changed: Some(false),
+                    }),
                     tools: Some(ToolsCapability {
                         list_changed: Some(false),
                     }),
                 },
                 server_info: ServerInfo {
-                    name: SERVER_NAME.to_string(),
-                    version: SERVER_VERSION.to_string(),
+                    name: state.config.name.clone(),
+                    version: state.config.version.clone(),
+                    title: state.config.title.clone().filter(|_| version.supports(Feature::Title)),
+                    website_url: state.config.website_url.clone().filter(|_| version.supports(Feature::Icons)),
+                    icons: if version.supports(Feature::Icons) { state.config.icons.clone() } else { Vec::new() },
                 },
+                instructions: state.config.instructions.clone(),
             };
             
             JsonRpcResponse {
@@ -166,9 +390,139 @@ fn handle_json_rpc_request(request: JsonRpcRequest, tool_registry: &ToolRegistry
                 id: request.id,
             }
         },
+        "ping" => {
+            JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                result: Some(json!({})),
+                error: None,
+                id: request.id,
+            }
+        },
+        "logging/setLevel" => {
+            match request.params.and_then(|params| serde_json::from_value::<SetLevelParams>(params).ok()) {
+                Some(params) => {
+                    tool_ctx.logger.set_level(params.level);
+                    logging::set_session_level(tool_ctx.session_id.as_deref(), params.level);
+                    JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        result: Some(json!({})),
+                        error: None,
+                        id: request.id,
+                    }
+                },
+                None => error_response(request.id, -32602, "Invalid params: expected a log level".to_string()),
+            }
+        },
+        "completion/complete" => {
+            match request.params.and_then(|params| serde_json::from_value::<CompleteParams>(params).ok()) {
+                Some(params) => {
+                    // Restricted tools and resources do not leak their values
+                    let principal = tool_ctx.principal.as_ref();
+                    let visible = match &params.reference {
+                        CompletionRef::Tool { name } => state.tools.access(name).allows(principal),
+                        CompletionRef::Resource { uri } => state.resources.allows_template(uri, principal),
+                        CompletionRef::Prompt { .. } => true,
+                    };
+                    let (reference, argument) = (&params.reference, &params.argument);
+                    let completion = match reference {
+                        _ if !visible => Default::default(),
+                        _ if state.completions.handles(reference, &argument.name) => {
+                            state.completions.complete(reference, &argument.name, &argument.value, &params.context.arguments)
+                        },
+                        CompletionRef::Resource { uri } => {
+                            let limit = completion::MAX_VALUES + 1;
+                            match state.resources.complete(uri, &argument.name, &argument.value, limit).await {
+                                Ok(values) => Completion::from_first(values),
+                                Err(e) => return resource_error_response(request.id, e),
+                            }
+                        },
+                        _ => Default::default(),
+                    };
+                    JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        result: Some(json!({ "completion": completion })),
+                        error: None,
+                        id: request.id,
+                    }
+                },
+                None => error_response(request.id, -32602, "Invalid params: expected ref and argument".to_string()),
+            }
+        },
+        "resources/list" => {
+            let params: ListParams = request
+                .params
+                .and_then(|params| serde_json::from_value(params).ok())
+                .unwrap_or_default();
+            match state.resources.list(params.cursor.as_deref(), tool_ctx.principal.as_ref()).await {
+                Ok(page) => JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    result: Some(serde_json::to_value(page).unwrap()),
+                    error: None,
+                    id: request.id,
+                },
+                Err(e) => resource_error_response(request.id, e),
+            }
+        },
+        "resources/templates/list" => {
+            JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                result: Some(json!({ "resourceTemplates": state.resources.templates(tool_ctx.principal.as_ref()) })),
+                error: None,
+                id: request.id,
+            }
+        },
+        "resources/read" => {
+            match request.params.and_then(|params| serde_json::from_value::<ResourceParams>(params).ok()) {
+                Some(params) => match state.resources.read(&params.uri, tool_ctx.principal.as_ref()).await {
+                    Ok(contents) => JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        result: Some(json!({ "contents": contents })),
+                        error: None,
+                        id: request.id,
+                    },
+                    Err(e) => resource_error_response(request.id, e),
+                },
+                None => error_response(request.id, -32602, "Invalid params: expected a uri".to_string()),
+            }
+        },
+        method @ ("resources/subscribe" | "resources/unsubscribe") => {
+            let params = request.params.and_then(|params| serde_json::from_value::<ResourceParams>(params).ok());
+            match (params, &tool_ctx.session_id) {
+                (None, _) => error_response(request.id, -32602, "Invalid params: expected a uri".to_string()),
+                (Some(_), None) => {
+                    error_response(request.id, -32602, "Subscriptions need an Mcp-Session-Id".to_string())
+                },
+                (Some(params), Some(session_id)) => {
+                    if let Err(e) = state.resources.check_access(&params.uri, tool_ctx.principal.as_ref()) {
+                        return resource_error_response(request.id, e);
+                    }
+                    if method == "resources/unsubscribe" {
+                        subscriptions::unsubscribe(session_id, &params.uri);
+                        return JsonRpcResponse {
+                            jsonrpc: "2.0".to_string(),
+                            result: Some(json!({})),
+                            error: None,
+                            id: request.id,
+                        };
+                    }
+                    if let Err(e) = subscriptions::subscribe(session_id, &params.uri) {
+                        return error_response(request.id, -32602, format!("Invalid params: {}", e));
+                    }
+                    // Subscriptions and streams live in this isolate, so an
+                    // update made in another one is never delivered; say so
+                    // rather than imply delivery is guaranteed
+                    JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        result: Some(json!({ "_meta": { "delivery": SUBSCRIPTION_DELIVERY } })),
+                        error: None,
+                        id: request.id,
+                    }
+                },
+            }
+        },
         "tools/list" => {
             let tools = ToolsList {
-                tools: tool_registry.get_tools().clone(),
+                tools: state.tools.list_all_tools(tool_ctx).await,
             };
             
             JsonRpcResponse {
@@ -181,8 +535,17 @@ fn handle_json_rpc_request(request: JsonRpcRequest, tool_registry: &ToolRegistry
         "tools/call" => {
             if let Some(params) = request.params {
                 if let Ok(call_params) = serde_json::from_value::<ToolsCallParams>(params) {
-                    match tool_registry.call_tool(&call_params.name, call_params.arguments) {
-                        Ok(result) => {
+                    let tool_ctx = ToolContext {
+                        logger: tool_ctx.logger.named(&call_params.name),
+                        progress_token: call_params.meta.and_then(|meta| meta.progress_token),
+                        ..tool_ctx.clone()
+                    };
+                    match state.tools.call_tool(&call_params.name, call_params.arguments, &tool_ctx).await {
+                        Ok(mut result) => {
+                            // `content` already carries the text version
+                            if !tool_ctx.protocol_version.supports(Feature::StructuredContent) {
+                                result.structured_content = None;
+                            }
                             JsonRpcResponse {
                                 jsonrpc: "2.0".to_string(),
                                 result: Some(serde_json::to_value(result).unwrap()),
@@ -190,6 +553,26 @@ fn handle_json_rpc_request(request: JsonRpcRequest, tool_registry: &ToolRegistry
                                 id: request.id,
                             }
                         },
+                        Err(ToolError::NotFound(name)) => {
+                            error_response(request.id, -32602, format!("Unknown tool: {}", name))
+                        },
+                        Err(ToolError::Remote { code, message }) => error_response(request.id, code, message),
+                        Err(ToolError::Forbidden(name, access)) => {
+                            let data = json!({
+                                "requiredScopes": access.scopes,
+                                "requiredRoles": access.roles,
+                            });
+                            JsonRpcResponse {
+                                jsonrpc: "2.0".to_string(),
+                                result: None,
+                                error: Some(JsonRpcError {
+                                    code: FORBIDDEN,
+                                    message: ToolError::Forbidden(name, access).to_string(),
+                                    data: Some(data),
+                                }),
+                                id: request.id,
+                            }
+                        },
                         Err(e) => {
                             JsonRpcResponse {
                                 jsonrpc: "2.0".to_string(),
@@ -243,146 +626,464 @@ fn handle_json_rpc_request(request: JsonRpcRequest, tool_registry: &ToolRegistry
     }
 }
 
-fn cors_headers() -> Headers {
-    let mut headers = Headers::new();
-    headers.append("Access-Control-Allow-Origin", "*").unwrap();
-    headers.append("Access-Control-Allow-Methods", "GET, POST, OPTIONS").unwrap();
-    headers.append("Access-Control-Allow-Headers", "Content-Type, Mcp-Session-Id").unwrap();
-    headers.append("Access-Control-Max-Age", "86400").unwrap();
-    headers
+fn error_response(id: Option<serde_json::Value>, code: i32, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+            data: None,
+        }),
+        id,
+    }
+}
+
+fn resource_error_response(id: Option<serde_json::Value>, error: ResourceError) -> JsonRpcResponse {
+    match error {
+        ResourceError::NotFound(ref uri) => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(JsonRpcError {
+                code: RESOURCE_NOT_FOUND,
+                message: error.to_string(),
+                data: Some(json!({ "uri": uri })),
+            }),
+            id,
+        },
+        ResourceError::Forbidden(_, ref access) => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(JsonRpcError {
+                code: FORBIDDEN,
+                message: error.to_string(),
+                data: Some(json!({ "requiredScopes": access.scopes, "requiredRoles": access.roles })),
+            }),
+            id,
+        },
+        ResourceError::InvalidCursor(_) | ResourceError::ReadOnly(_) | ResourceError::TooLarge(..) => {
+            error_response(id, -32602, error.to_string())
+        },
+        ResourceError::Failed(_) => error_response(id, -32603, format!("Internal error: {}", error)),
+    }
 }
 
-async fn handle_mcp_request(mut req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    // Initialize tool registry
-    let mut tool_registry = ToolRegistry::new();
-    register_default_tools(&mut tool_registry);
+fn invalid_request_response() -> JsonRpcResponse {
+    error_response(Some(serde_json::Value::Null), -32600, "Invalid Request".to_string())
+}
 
-    // Handle OPTIONS preflight
-    if req.method() == Method::Options {
-        return Response::empty()
-            .map(|resp| resp.with_headers(cors_headers()));
+/// Handles one JSON-RPC message from a request body. Returns `None` for
+/// notifications, client responses and cancelled requests, which get no
+/// reply.
+async fn handle_json_rpc_message(message: serde_json::Value, state: &RequestState, tool_ctx: &ToolContext) -> Option<JsonRpcResponse> {
+    if message.get("method").is_none() && (message.get("result").is_some() || message.get("error").is_some()) {
+        if let Some(id) = message.get("id") {
+            let session_id = tool_ctx.session_id.as_deref();
+            if !keepalive::record_pong(session_id, id) {
+                correlator::resolve(session_id, id, &message);
+            }
+        }
+        return None;
     }
 
-    // Parse JSON-RPC request
+    let request = match serde_json::from_value::<JsonRpcRequest>(message) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(request) => return Some(error_response(request.id, -32600, "Invalid Request".to_string())),
+        Err(_) => return Some(invalid_request_response()),
+    };
+
+    let Some(id) = request.id.clone() else {
+        handle_json_rpc_notification(request, tool_ctx);
+        return None;
+    };
+
+    let in_flight = cancellation::track(tool_ctx.session_id.as_deref(), &id);
+    let tool_ctx = ToolContext {
+        cancellation: in_flight.token(),
+        ..tool_ctx.clone()
+    };
+    let response = handle_json_rpc_request(request, state, &tool_ctx).await;
+    (!tool_ctx.cancellation.is_cancelled()).then_some(response)
+}
+
+fn handle_json_rpc_notification(notification: JsonRpcRequest, tool_ctx: &ToolContext) {
+    match notification.method.as_str() {
+        "notifications/cancelled" => {
+            if let Some(params) = notification.params.and_then(|params| serde_json::from_value::<CancelledParams>(params).ok()) {
+                cancellation::cancel(tool_ctx.session_id.as_deref(), &params.request_id, params.reason);
+            }
+        },
+        "notifications/roots/list_changed" => {
+            if let Some(session_id) = &tool_ctx.session_id {
+                roots::invalidate(session_id);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn rate_limited_response(id: Option<serde_json::Value>, limited: &RateLimited) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: None,
+        error: Some(JsonRpcError {
+            code: RATE_LIMITED,
+            message: limited.to_string(),
+            data: Some(limited.data()),
+        }),
+        id,
+    }
+}
+
+/// Applies rate limits to `tools/call` before handing the message on.
+async fn dispatch_json_rpc_message(
+    message: serde_json::Value,
+    state: &RequestState,
+    tool_ctx: &ToolContext,
+    rate_limiter: &RateLimiter,
+    client: &str,
+) -> Result<Option<JsonRpcResponse>> {
+    let tool = (message.get("method").and_then(|m| m.as_str()) == Some("tools/call"))
+        .then(|| message.pointer("/params/name").and_then(|name| name.as_str()))
+        .flatten();
+    if let (Some(tool), true) = (tool, rate_limiter.config().is_enabled()) {
+        if let Err(limited) = rate_limiter.check_tool(client, tool, Date::now().as_millis()).await? {
+            let id = message.get("id").cloned();
+            return Ok(id.map(|id| rate_limited_response(Some(id), &limited)));
+        }
+    }
+    Ok(handle_json_rpc_message(message, state, tool_ctx).await)
+}
+
+/// Whether any message in the body is a request, or is malformed, and so
+/// gets a reply.
+fn expects_response(body: &serde_json::Value) -> bool {
+    let needs_reply = |message: &serde_json::Value| match message.get("method") {
+        Some(_) => message.get("id").is_some(),
+        None => message.get("result").is_none() && message.get("error").is_none(),
+    };
+    match body {
+        serde_json::Value::Array(batch) => batch.is_empty() || batch.iter().any(needs_reply),
+        message => needs_reply(message),
+    }
+}
+
+/// Handles a parsed body, either one message or a batch, and returns the
+/// reply to send, if any.
+async fn dispatch_body(
+    body: serde_json::Value,
+    ctx: &RouteContext<RequestState>,
+    tool_ctx: &ToolContext,
+    client: &str,
+) -> Result<Option<serde_json::Value>> {
+    let rate_limiter = RateLimiter::from_env(&ctx.env, ctx.data.config.rate_limits.clone());
+    match body {
+        serde_json::Value::Array(batch) if batch.is_empty() => {
+            Ok(Some(serde_json::to_value(invalid_request_response())?))
+        },
+        serde_json::Value::Array(batch) => {
+            let mut responses = Vec::new();
+            for message in batch {
+                let response = dispatch_json_rpc_message(message, &ctx.data, tool_ctx, &rate_limiter, client).await?;
+                responses.extend(response);
+            }
+            if responses.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(serde_json::to_value(responses)?))
+            }
+        },
+        message => Ok(dispatch_json_rpc_message(message, &ctx.data, tool_ctx, &rate_limiter, client)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?),
+    }
+}
+
+/// Streams queued messages as they appear while `reply` runs, then the
+/// reply itself, so tools can talk to the client mid-call.
+fn sse_events(
+    outbox: Outbox,
+    reply: impl std::future::Future<Output = Result<Option<serde_json::Value>>> + 'static,
+) -> impl futures_util::Stream<Item = Result<Vec<u8>>> {
+    let mut reply = Box::pin(reply);
+    let mut done = false;
+    futures_util::stream::poll_fn(move |cx| {
+        if done {
+            return std::task::Poll::Ready(None);
+        }
+        let poll = reply.as_mut().poll(cx);
+        let mut events = String::new();
+        for message in outbox.take() {
+            events.push_str(&format!("data: {}\n\n", message));
+        }
+        match poll {
+            std::task::Poll::Ready(Ok(reply)) => {
+                done = true;
+                if let Some(reply) = reply {
+                    events.push_str(&format!("data: {}\n\n", reply));
+                }
+                std::task::Poll::Ready(Some(Ok(events.into_bytes())))
+            },
+            std::task::Poll::Ready(Err(e)) => {
+                done = true;
+                std::task::Poll::Ready(Some(Err(e)))
+            },
+            std::task::Poll::Pending if events.is_empty() => std::task::Poll::Pending,
+            std::task::Poll::Pending => std::task::Poll::Ready(Some(Ok(events.into_bytes()))),
+        }
+    })
+}
+
+async fn handle_mcp_request(mut req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    // Session ids are signed, so one this server did not issue is unknown
+    let signer = SessionSigner::from_env(&ctx.env);
+    let session = match req.headers().get("Mcp-Session-Id")? {
+        Some(id) => match signer.verify(&id) {
+            Some(session) => Some(session),
+            None => return Response::error("Session not found", 404),
+        },
+        None => None,
+    };
+
+    let mut tool_ctx = tool_context(&req, &ctx.data);
+    let rate_limiter = RateLimiter::from_env(&ctx.env, ctx.data.config.rate_limits.clone());
+    let client = rate_limiter.config().key.request_key(&req, tool_ctx.principal.as_ref(), session.as_ref());
+    // Without a session, the client names its version on every request
+    let header_version = match &session {
+        Some(_) => None,
+        None => req.headers().get("MCP-Protocol-Version")?,
+    };
+    if let Some(session) = session {
+        tool_ctx.client_capabilities = session.capabilities;
+        tool_ctx.protocol_version = session.protocol_version;
+    }
+    let accept_header = req.headers().get("Accept").unwrap_or(None).unwrap_or_default();
+
+    // Parse JSON-RPC message (single request or batch)
     let body = req.text().await?;
-    let json_rpc_request: JsonRpcRequest = serde_json::from_str(&body)
-        .map_err(|e| worker::Error::RustError(format!("Invalid JSON-RPC request: {}", e)))?;
+    let body = match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            let error = error_response(Some(serde_json::Value::Null), -32700, format!("Parse error: {}", e));
+            return json_rpc_reply(serde_json::to_value(error)?, &accept_header, Headers::new());
+        },
+    };
 
-    // Handle as notification if no ID
-    if json_rpc_request.id.is_none() {
+    // A new session remembers what the client can do and the version agreed
+    let headers = Headers::new();
+    if body.get("method").and_then(|m| m.as_str()) == Some("initialize") {
+        let capabilities = body
+            .pointer("/params/capabilities")
+            .and_then(|capabilities| serde_json::from_value(capabilities.clone()).ok())
+            .unwrap_or_default();
+        let requested = body.pointer("/params/protocolVersion").and_then(|v| v.as_str());
+        let session = signer.issue(capabilities, ProtocolVersion::negotiate(requested));
+        headers.set("Mcp-Session-Id", &session.id)?;
+        tool_ctx.session_id = Some(session.id);
+        tool_ctx.client_capabilities = session.capabilities;
+        tool_ctx.protocol_version = session.protocol_version;
+    } else if let Some(version) = header_version {
+        match ProtocolVersion::parse(&version) {
+            Some(version) => tool_ctx.protocol_version = version,
+            None => return Response::error(format!("Unsupported MCP-Protocol-Version: {}", version), 400),
+        }
+    }
+
+    // Notifications and responses only: accepted with no body
+    if !expects_response(&body) {
+        dispatch_body(body, &ctx, &tool_ctx, &client).await?;
         return Response::empty()
-            .map(|resp| resp.with_headers(cors_headers()));
+            .map(|resp| resp.with_status(202).with_headers(headers));
+    }
+
+    if accept_header.contains("text/event-stream") {
+        // Return SSE stream; log, progress and server-to-client requests are
+        // sent while the call runs, ahead of the response
+        tool_ctx.streaming = true;
+        let outbox = tool_ctx.outbox.clone();
+        let reply = async move { dispatch_body(body, &ctx, &tool_ctx, &client).await };
+        headers.set("Content-Type", "text/event-stream")?;
+        headers.set("Cache-Control", "no-cache")?;
+        return Response::from_stream(sse_events(outbox, reply))
+            .map(|resp| resp.with_headers(headers));
     }
 
-    // Process request
-    let json_rpc_response = handle_json_rpc_request(json_rpc_request, &tool_registry);
+    match dispatch_body(body, &ctx, &tool_ctx, &client).await? {
+        Some(reply) => json_rpc_reply(reply, &accept_header, headers),
+        // Every request in the body was cancelled
+        None => Response::empty().map(|resp| resp.with_status(202).with_headers(headers)),
+    }
+}
 
+/// A complete reply, as JSON or as a single SSE event.
+fn json_rpc_reply(reply: serde_json::Value, accept_header: &str, headers: Headers) -> Result<Response> {
     // Check Accept header for response format
-    let accept_header = req.headers().get("Accept").unwrap_or(None).unwrap_or_default();
-    
     if accept_header.contains("text/event-stream") {
-        // Return SSE stream
-        let sse_data = format!("data: {}\n\n", serde_json::to_string(&json_rpc_response)?);
-        Response::ok(sse_data)
-            .map(|resp| {
-                let mut headers = cors_headers();
-                headers.append("Content-Type", "text/event-stream").unwrap();
-                headers.append("Cache-Control", "no-cache").unwrap();
-                resp.with_headers(headers)
-            })
+        headers.set("Content-Type", "text/event-stream")?;
+        headers.set("Cache-Control", "no-cache")?;
+        Response::ok(format!("data: {}\n\n", serde_json::to_string(&reply)?))
+            .map(|resp| resp.with_headers(headers))
     } else {
-        // Return JSON
-        Response::ok(serde_json::to_string(&json_rpc_response)?)
-            .map(|resp| {
-                let mut headers = cors_headers();
-                headers.append("Content-Type", "application/json").unwrap();
-                resp.with_headers(headers)
-            })
+        headers.set("Content-Type", "application/json")?;
+        Response::ok(serde_json::to_string(&reply)?)
+            .map(|resp| resp.with_headers(headers))
     }
 }
 
-async fn handle_sse_get(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+async fn handle_sse_get(req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
     let sse_response = r#"event: endpoint
 data: {"endpoint": "/messages"}
 
 "#;
     
+    if ctx.data.config.ping.is_enabled() {
+        let session_id = req.headers().get("Mcp-Session-Id").ok().flatten();
+        if session_id.as_deref().is_some_and(|id| SessionSigner::from_env(&ctx.env).verify(id).is_none()) {
+            return Response::error("Session not found", 404);
+        }
+        return event_stream(Some(sse_response.to_string()), ctx.data.config.ping.clone(), session_id);
+    }
+
     Response::ok(sse_response)
         .map(|resp| {
-            let mut headers = cors_headers();
+            let headers = Headers::new();
             headers.append("Content-Type", "text/event-stream").unwrap();
             headers.append("Cache-Control", "no-cache").unwrap();
             resp.with_headers(headers)
         })
 }
 
-async fn handle_messages(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+/// A long-lived SSE stream that sends `first_event`, then anything queued
+/// for the session and, when enabled, pings every `interval_secs` until the
+/// client misses `max_missed` of them in a row. `session_id` must have been
+/// verified.
+fn event_stream(first_event: Option<String>, config: PingConfig, session_id: Option<String>) -> Result<Response> {
+    let mut first_event = first_event;
+    let session_stream = session_id.as_deref().map(SessionStream::open);
+    let interval = std::time::Duration::from_secs(config.interval_secs);
+    let mut heartbeat = config.is_enabled().then(|| Heartbeat::new(config, session_id));
+    let mut delay = None;
+    let events = futures_util::stream::poll_fn(move |cx| {
+        if let Some(event) = first_event.take() {
+            return std::task::Poll::Ready(Some(Ok::<_, Error>(event.into_bytes())));
+        }
+        let mut events = String::new();
+        if let Some(std::task::Poll::Ready(messages)) = session_stream.as_ref().map(|stream| stream.poll_messages(cx)) {
+            for message in messages {
+                events.push_str(&format!("data: {}\n\n", message));
+            }
+        }
+        if let Some(heartbeat) = heartbeat.as_mut() {
+            let tick = delay.get_or_insert_with(|| Box::pin(Delay::from(interval)));
+            if std::future::Future::poll(tick.as_mut(), cx).is_ready() {
+                delay = None;
+                match heartbeat.next_ping() {
+                    Some(ping) => events.push_str(&format!("data: {}\n\n", ping)),
+                    None => return std::task::Poll::Ready(None),
+                }
+                cx.waker().wake_by_ref();
+            }
+        }
+        if events.is_empty() {
+            std::task::Poll::Pending
+        } else {
+            std::task::Poll::Ready(Some(Ok(events.into_bytes())))
+        }
+    });
+
+    Response::from_stream(events)
+        .map(|resp| {
+            let headers = Headers::new();
+            headers.append("Content-Type", "text/event-stream").unwrap();
+            headers.append("Cache-Control", "no-cache").unwrap();
+            resp.with_headers(headers)
+        })
+}
+
+/// `GET /mcp` opens the server-to-client stream when the client accepts SSE
+/// and has a session or pings are enabled; otherwise it describes the
+/// server.
+async fn handle_mcp_get(req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let accept = req.headers().get("Accept").ok().flatten().unwrap_or_default();
+    let session_id = req.headers().get("Mcp-Session-Id").ok().flatten();
+    if !accept.contains("text/event-stream") || (session_id.is_none() && !ctx.data.config.ping.is_enabled()) {
+        return handle_info(req, ctx).await;
+    }
+    if session_id.as_deref().is_some_and(|id| SessionSigner::from_env(&ctx.env).verify(id).is_none()) {
+        return Response::error("Session not found", 404);
+    }
+    event_stream(None, ctx.data.config.ping.clone(), session_id)
+}
+
+async fn handle_messages(req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
     handle_mcp_request(req, ctx).await
 }
 
-// Legacy endpoints - kept for backward compatibility
-async fn handle_legacy_add(mut req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    let body = req.text().await?;
-    let add_request: serde_json::Value = serde_json::from_str(&body)?;
-    
-    let mut tool_registry = ToolRegistry::new();
-    register_default_tools(&mut tool_registry);
-    
-    match tool_registry.call_tool("add", Some(add_request)) {
-        Ok(result) => {
-            let response = McpResponse {
-                content: result.content,
-            };
-            Response::ok(serde_json::to_string(&response)?)
-                .map(|resp| {
-                    let mut headers = cors_headers();
-                    headers.append("Content-Type", "application/json").unwrap();
-                    resp.with_headers(headers)
-                })
+/// Rate limits the legacy endpoints, answering `429` with `Retry-After`.
+async fn check_legacy_rate_limit(req: &Request, ctx: &RouteContext<RequestState>, tool: &str) -> Result<Option<Response>> {
+    let rate_limiter = RateLimiter::from_env(&ctx.env, ctx.data.config.rate_limits.clone());
+    if !rate_limiter.config().is_enabled() {
+        return Ok(None);
+    }
+    let session = req
+        .headers()
+        .get("Mcp-Session-Id")?
+        .and_then(|id| SessionSigner::from_env(&ctx.env).verify(&id));
+    let client = rate_limiter.config().key.request_key(req, ctx.data.principal.as_ref(), session.as_ref());
+    match rate_limiter.check_tool(&client, tool, Date::now().as_millis()).await? {
+        Ok(()) => Ok(None),
+        Err(limited) => {
+            let headers = Headers::new();
+            headers.append("Retry-After", &limited.retry_after_secs().to_string())?;
+            Ok(Some(Response::error(limited.to_string(), 429)?.with_headers(headers)))
         },
-        Err(e) => {
-            Response::error(e, 400)
-        }
     }
 }
 
-async fn handle_legacy_calculate(mut req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+// Legacy endpoints - kept for backward compatibility
+async fn handle_legacy_calculate(mut req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    if let Some(response) = check_legacy_rate_limit(&req, &ctx, "calculate").await? {
+        return Ok(response);
+    }
     let body = req.text().await?;
     let calc_request: serde_json::Value = serde_json::from_str(&body)?;
     
-    let mut tool_registry = ToolRegistry::new();
-    register_default_tools(&mut tool_registry);
-    
-    match tool_registry.call_tool("calculate", Some(calc_request)) {
+    let tool_ctx = tool_context(&req, &ctx.data);
+
+    match ctx.data.tools.call_tool("calculate", Some(calc_request), &tool_ctx).await {
         Ok(result) => {
             let response = McpResponse {
                 content: result.content,
             };
             Response::ok(serde_json::to_string(&response)?)
                 .map(|resp| {
-                    let mut headers = cors_headers();
+                    let headers = Headers::new();
                     headers.append("Content-Type", "application/json").unwrap();
                     resp.with_headers(headers)
                 })
         },
+        Err(e @ ToolError::NotFound(_)) => Response::error(e.to_string(), 404),
+        Err(e @ ToolError::Forbidden(..)) => Response::error(e.to_string(), 403),
         Err(e) => {
-            Response::error(e, 400)
+            Response::error(e.to_string(), 400)
         }
     }
 }
 
-async fn handle_info(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+async fn handle_info(_req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
     let info = json!({
-        "server": SERVER_NAME,
-        "version": SERVER_VERSION,
-        "protocol_version": PROTOCOL_VERSION,
+        "server": ctx.data.config.name,
+        "version": ctx.data.config.version,
+        "protocol_version": LATEST_PROTOCOL_VERSION,
+        "protocol_versions": SUPPORTED_PROTOCOL_VERSIONS,
         "transport": ["streamable_http", "legacy_sse"],
         "endpoints": {
             "mcp": "/mcp",
             "sse": "/sse",
             "messages": "/messages",
+            "health": "/health",
             "legacy": {
-                "add": "/mcp/add",
                 "calculate": "/mcp/calculate"
             }
         }
@@ -390,45 +1091,143 @@ async fn handle_info(_req: Request, _ctx: RouteContext<()>) -> Result<Response>
     
     Response::ok(serde_json::to_string(&info)?)
         .map(|resp| {
-            let mut headers = cors_headers();
+            let headers = Headers::new();
             headers.append("Content-Type", "application/json").unwrap();
             resp.with_headers(headers)
         })
 }
 
+/// Pings every upstream, at most every [`upstream::HEALTH_TTL_SECS`] per
+/// isolate. Answers 503 when any of them is down, so uptime monitors
+/// notice; only authenticated callers see which one and why.
+async fn handle_health(_req: Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let upstreams = upstream::cached_health(ctx.data.tools.upstreams()).await;
+    let healthy = upstreams.iter().all(|upstream| upstream.healthy);
+    let mut health = json!({ "status": if healthy { "ok" } else { "degraded" } });
+    if ctx.data.principal.is_some() {
+        health["upstreams"] = json!(upstreams);
+    }
+    let response = Response::from_json(&health)?;
+    Ok(if healthy { response } else { response.with_status(503) })
+}
+
+/// Routes that can reach a tool or a session's stream, whatever the
+/// method. Preflight requests and the OAuth endpoints stay public.
+fn requires_auth(req: &Request) -> bool {
+    let path = req.path();
+    req.method() != Method::Options && (path == "/mcp" || path.starts_with("/mcp/") || path == "/messages" || path == "/sse")
+}
+
+/// The configured authenticators, plus the built-in authorization server's
+/// when it is enabled.
+async fn auth_chain(env: &Env, origin: &str) -> Result<AuthChain> {
+    let mut auth = AuthChain::from_env(env).await?;
+    if let Some(signer) = oauth::OAuthConfig::from_env(env)?.signer {
+        auth = auth.with(signer.authenticator(origin));
+    }
+    Ok(auth)
+}
+
 #[event(fetch)]
-pub async fn main(req: Request, env: Env, ctx: Context) -> Result<Response> {
+pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
     utils::set_panic_hook();
 
-    let router = Router::new();
+    let config = match ServerConfig::from_env(&env) {
+        Ok(config) => config,
+        Err(e) => return misconfigured(e),
+    };
+
+    config
+        .cors
+        .clone()
+        .handle(req, |req| route(req, env, config))
+        .await
+}
+
+fn misconfigured(error: ConfigError) -> Result<Response> {
+    // The details are for the operator, not the client
+    console_error!("{}", error);
+    Response::error("Server misconfigured", 500)
+}
+
+async fn route(req: Request, env: Env, config: ServerConfig) -> Result<Response> {
+    // Tokens issued by the built-in authorization server are ES256 JWTs
+    let origin = oauth::request_origin(&req)?;
+    let mut principal = None;
+    // `/health` is public, but a token there unlocks the upstream details
+    let health_details = req.path() == "/health" && auth::bearer_token(&req).is_some();
+    if requires_auth(&req) || health_details {
+        let auth = match auth_chain(&env, &origin).await {
+            Ok(auth) => auth,
+            Err(e) => {
+                // E.g. the JWKS could not be fetched; the client may retry
+                console_error!("Authentication unavailable: {}", e);
+                return Response::error("Authentication unavailable", 503);
+            },
+        };
+        if auth.is_enabled() {
+            match auth.authenticate(auth::bearer_token(&req).as_deref(), auth::now_secs()) {
+                Ok(authenticated) => principal = Some(authenticated),
+                Err(e) => {
+                    let resource_metadata = format!("{}{}", origin, oauth::PROTECTED_RESOURCE_PATH);
+                    return auth::unauthorized(&e, Some(&resource_metadata), Headers::new());
+                },
+            }
+        }
+    }
+
+    // Only routes that can reach a tool wait for plugins; elsewhere those
+    // this isolate already loaded are enough
+    if requires_auth(&req) {
+        config.load_plugins(&env).await;
+    }
+    let tools = match config.tool_registry() {
+        Ok(tools) => tools,
+        Err(e) => return misconfigured(e),
+    };
+
+    let mut completions = CompletionRegistry::new();
+    completion::register_default_completers(&mut completions, &tools);
+
+    let database = sql::database_from_env(&env, &config)?;
+    let mut resources = ResourceRegistry::new();
+    resources::register_default_providers(&mut resources, &env, &config, database.as_ref())?;
+    let resources = Rc::new(resources);
+
+    let router = Router::with_data(RequestState { principal, config, tools, completions, resources, database });
     
     router
         // Main MCP endpoints
         .post_async("/mcp", handle_mcp_request)
-        .options("/mcp", |_, _| Response::empty().map(|resp| resp.with_headers(cors_headers())))
         
         // Legacy SSE transport
         .get_async("/sse", handle_sse_get)
         .post_async("/messages", handle_messages)
-        .options("/messages", |_, _| Response::empty().map(|resp| resp.with_headers(cors_headers())))
         
         // Legacy HTTP endpoints (kept for compatibility)
-        .post_async("/mcp/add", handle_legacy_add)
         .post_async("/mcp/calculate", handle_legacy_calculate)
-        .options("/mcp/add", |_, _| Response::empty().map(|resp| resp.with_headers(cors_headers())))
-        .options("/mcp/calculate", |_, _| Response::empty().map(|resp| resp.with_headers(cors_headers())))
         
+        // OAuth metadata (RFC 9728 / RFC 8414) and the optional built-in
+        // authorization server
+        .get_async(oauth::PROTECTED_RESOURCE_PATH, oauth::handle_protected_resource)
+        .get_async("/.well-known/oauth-protected-resource/mcp", oauth::handle_protected_resource)
+        .get_async(oauth::AUTHORIZATION_SERVER_PATH, oauth::handle_authorization_server)
+        .get_async(oauth::JWKS_PATH, oauth::handle_jwks)
+        .post_async("/register", oauth::handle_register)
+        .get_async("/authorize", oauth::handle_authorize_get)
+        .post_async("/authorize", oauth::handle_authorize_post)
+        .post_async("/token", oauth::handle_token)
+
         // Info endpoint
-        .get_async("/mcp", handle_info)
+        .get_async("/mcp", handle_mcp_get)
         .get_async("/", handle_info)
+        .get_async("/health", handle_health)
         
         .run(req, env)
         .await
 }
 
 mod utils {
-    use console_error_panic_hook;
-
     pub fn set_panic_hook() {
         console_error_panic_hook::set_once();
     }
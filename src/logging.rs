@@ -0,0 +1,164 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::notifications::Outbox;
+
+/// RFC 5424 severities, least severe first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self).ok().and_then(|v| v.as_str().map(str::to_string));
+        write!(f, "{}", name.unwrap_or_default())
+    }
+}
+
+/// Session levels an isolate remembers; past this the oldest is forgotten
+/// and that session falls back to the default level.
+pub const MAX_SESSION_LEVELS: usize = 10_000;
+
+thread_local! {
+    /// Each level with the order it was set in, for eviction.
+    static SESSION_LEVELS: RefCell<HashMap<String, (LogLevel, u64)>> = RefCell::new(HashMap::new());
+    static NEXT_SET: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The level a session chose with `logging/setLevel`, or the default.
+pub fn session_level(session_id: Option<&str>) -> LogLevel {
+    session_id
+        .and_then(|id| SESSION_LEVELS.with(|levels| levels.borrow().get(id).map(|(level, _)| *level)))
+        .unwrap_or_default()
+}
+
+/// Remembers a session's level for later requests in this isolate, up to
+/// [`MAX_SESSION_LEVELS`]. Without a session the level only lasts for the
+/// current request.
+pub fn set_session_level(session_id: Option<&str>, level: LogLevel) {
+    let Some(id) = session_id else {
+        return;
+    };
+    let order = NEXT_SET.with(|next| next.replace(next.get() + 1));
+    SESSION_LEVELS.with(|levels| {
+        let mut levels = levels.borrow_mut();
+        if !levels.contains_key(id) && levels.len() >= MAX_SESSION_LEVELS {
+            let oldest = levels.iter().min_by_key(|(_, (_, order))| *order).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                levels.remove(&oldest);
+            }
+        }
+        levels.insert(id.to_string(), (level, order));
+    });
+}
+
+/// Drops a session's level once its stream has gone dead.
+pub fn clear_session_level(session_id: &str) {
+    SESSION_LEVELS.with(|levels| levels.borrow_mut().remove(session_id));
+}
+
+/// Sends `notifications/message` to the client and mirrors each message to
+/// the Worker console. Clones share the level and the outbox.
+#[derive(Debug, Clone, Default)]
+pub struct Logger {
+    level: Rc<Cell<LogLevel>>,
+    name: Option<String>,
+    outbox: Outbox,
+}
+
+impl Logger {
+    pub fn new(level: LogLevel) -> Self {
+        Self::with_outbox(level, Outbox::new())
+    }
+
+    /// A logger that queues its messages in `outbox`, alongside other
+    /// notifications for the same request.
+    pub fn with_outbox(level: LogLevel, outbox: Outbox) -> Self {
+        Self {
+            level: Rc::new(Cell::new(level)),
+            name: None,
+            outbox,
+        }
+    }
+
+    /// A logger that tags messages with `logger`, e.g. the tool name.
+    pub fn named(&self, name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn level(&self) -> LogLevel {
+        self.level.get()
+    }
+
+    pub fn set_level(&self, level: LogLevel) {
+        self.level.set(level);
+    }
+
+    /// Logs `data`, which may be a string or any JSON value, if `level` is at
+    /// or above the client's chosen level.
+    pub fn log(&self, level: LogLevel, data: impl Into<serde_json::Value>) {
+        if level < self.level() {
+            return;
+        }
+        let mut params = serde_json::json!({ "level": level, "data": data.into() });
+        if let Some(name) = &self.name {
+            params["logger"] = serde_json::json!(name);
+        }
+        mirror_to_console(level, &params);
+        self.outbox.notify("notifications/message", params);
+    }
+
+    pub fn debug(&self, data: impl Into<serde_json::Value>) {
+        self.log(LogLevel::Debug, data);
+    }
+
+    pub fn info(&self, data: impl Into<serde_json::Value>) {
+        self.log(LogLevel::Info, data);
+    }
+
+    pub fn notice(&self, data: impl Into<serde_json::Value>) {
+        self.log(LogLevel::Notice, data);
+    }
+
+    pub fn warning(&self, data: impl Into<serde_json::Value>) {
+        self.log(LogLevel::Warning, data);
+    }
+
+    pub fn error(&self, data: impl Into<serde_json::Value>) {
+        self.log(LogLevel::Error, data);
+    }
+
+    /// Drains the outbox, including any other notifications queued in it.
+    pub fn take_messages(&self) -> Vec<serde_json::Value> {
+        self.outbox.take()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn mirror_to_console(level: LogLevel, params: &serde_json::Value) {
+    match level {
+        LogLevel::Debug | LogLevel::Info | LogLevel::Notice => worker::console_log!("{}", params),
+        LogLevel::Warning => worker::console_warn!("{}", params),
+        _ => worker::console_error!("{}", params),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn mirror_to_console(_level: LogLevel, _params: &serde_json::Value) {}
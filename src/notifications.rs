@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use crate::resources::subscriptions;
+
+/// Server-to-client messages queued while a request is handled. On a
+/// `text/event-stream` response they are streamed as they are queued, ahead
+/// of the result. Clones share the same queue.
+#[derive(Debug, Clone, Default)]
+pub struct Outbox {
+    messages: Rc<RefCell<Vec<serde_json::Value>>>,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn notify(&self, method: &str, params: serde_json::Value) {
+        self.messages.borrow_mut().push(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Queues a server-to-client request; see `correlator`.
+    pub fn request(&self, id: &str, method: &str, params: serde_json::Value) {
+        self.messages.borrow_mut().push(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Drains the queued notifications.
+    pub fn take(&self) -> Vec<serde_json::Value> {
+        std::mem::take(&mut self.messages.borrow_mut())
+    }
+}
+
+#[derive(Debug, Default)]
+struct StreamQueue {
+    messages: Vec<serde_json::Value>,
+    waker: Option<Waker>,
+}
+
+thread_local! {
+    static SESSION_STREAMS: RefCell<HashMap<String, Rc<RefCell<StreamQueue>>>> = RefCell::new(HashMap::new());
+}
+
+/// A session's open `GET` stream, which carries notifications that are not
+/// tied to any request, such as resource updates. A newer stream for the
+/// same session replaces it; dropping the current one unregisters it and
+/// drops the session's resource subscriptions in this isolate, which the
+/// client makes again when it reconnects.
+#[derive(Debug)]
+pub struct SessionStream {
+    session_id: String,
+    queue: Rc<RefCell<StreamQueue>>,
+}
+
+impl SessionStream {
+    pub fn open(session_id: &str) -> Self {
+        let queue = Rc::new(RefCell::new(StreamQueue::default()));
+        SESSION_STREAMS.with(|streams| streams.borrow_mut().insert(session_id.to_string(), queue.clone()));
+        Self {
+            session_id: session_id.to_string(),
+            queue,
+        }
+    }
+
+    /// Ready with the queued messages, or registers to be woken by the next
+    /// `send_to_session`.
+    pub fn poll_messages(&self, cx: &mut Context<'_>) -> Poll<Vec<serde_json::Value>> {
+        let mut queue = self.queue.borrow_mut();
+        if queue.messages.is_empty() {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(std::mem::take(&mut queue.messages))
+        }
+    }
+
+    pub fn take(&self) -> Vec<serde_json::Value> {
+        std::mem::take(&mut self.queue.borrow_mut().messages)
+    }
+}
+
+impl Drop for SessionStream {
+    fn drop(&mut self) {
+        let current = SESSION_STREAMS.with(|streams| {
+            let mut streams = streams.borrow_mut();
+            let current = streams.get(&self.session_id).is_some_and(|queue| Rc::ptr_eq(queue, &self.queue));
+            if current {
+                streams.remove(&self.session_id);
+            }
+            current
+        });
+        if current {
+            subscriptions::unsubscribe_all(&self.session_id);
+        }
+    }
+}
+
+/// Queues a notification on the session's open stream. Returns `false` if
+/// the session has no stream in this isolate.
+pub fn send_to_session(session_id: &str, method: &str, params: serde_json::Value) -> bool {
+    let Some(queue) = SESSION_STREAMS.with(|streams| streams.borrow().get(session_id).cloned()) else {
+        return false;
+    };
+    let waker = {
+        let mut queue = queue.borrow_mut();
+        queue.messages.push(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+        queue.waker.take()
+    };
+    waker.into_iter().for_each(Waker::wake);
+    true
+}
@@ -0,0 +1,763 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use p256::ecdsa::signature::Signer;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use worker::*;
+
+use crate::auth::{constant_time_eq, env_string, now_secs, random_token, ApiKeyAuthenticator, Authenticator, Jwks, JwtAuthenticator};
+
+pub const PROTECTED_RESOURCE_PATH: &str = "/.well-known/oauth-protected-resource";
+pub const AUTHORIZATION_SERVER_PATH: &str = "/.well-known/oauth-authorization-server";
+pub const JWKS_PATH: &str = "/.well-known/jwks.json";
+
+const ACCESS_TOKEN_TTL_SECS: u64 = 3600;
+const AUTHORIZATION_CODE_TTL_SECS: u64 = 600;
+const REFRESH_TOKEN_TTL_SECS: u64 = 30 * 24 * 3600;
+const SIGNING_KEY_ID: &str = "mcp-oauth";
+
+/// RFC 9728 metadata describing this server as an OAuth protected resource.
+pub fn protected_resource_metadata(
+    origin: &str,
+    resource_name: &str,
+    authorization_servers: &[String],
+    scopes: &[String],
+) -> serde_json::Value {
+    let mut metadata = json!({
+        "resource": resource_uri(origin),
+        "authorization_servers": authorization_servers,
+        "bearer_methods_supported": ["header"],
+        "resource_name": resource_name,
+    });
+    if !scopes.is_empty() {
+        metadata["scopes_supported"] = json!(scopes);
+    }
+    metadata
+}
+
+/// RFC 8414 metadata for the built-in authorization server.
+pub fn authorization_server_metadata(origin: &str, scopes: &[String]) -> serde_json::Value {
+    let mut metadata = json!({
+        "issuer": origin,
+        "authorization_endpoint": format!("{}/authorize", origin),
+        "token_endpoint": format!("{}/token", origin),
+        "registration_endpoint": format!("{}/register", origin),
+        "jwks_uri": format!("{}{}", origin, JWKS_PATH),
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code", "refresh_token"],
+        "code_challenge_methods_supported": ["S256"],
+        "token_endpoint_auth_methods_supported": ["none", "client_secret_post", "client_secret_basic"],
+    });
+    if !scopes.is_empty() {
+        metadata["scopes_supported"] = json!(scopes);
+    }
+    metadata
+}
+
+/// The canonical URI of the MCP endpoint, used as the token audience.
+pub fn resource_uri(origin: &str) -> String {
+    format!("{}/mcp", origin)
+}
+
+/// Checks a PKCE `code_verifier` against an S256 `code_challenge`.
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    let valid_verifier = (43..=128).contains(&code_verifier.len())
+        && code_verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    valid_verifier && URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+/// An OAuth error response body (RFC 6749 section 5.2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthError {
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl OAuthError {
+    pub fn new(error: &'static str, description: impl Into<String>) -> Self {
+        Self {
+            error,
+            description: description.into(),
+        }
+    }
+
+    fn into_response(self, status: u16) -> Result<Response> {
+        let body = json!({ "error": self.error, "error_description": self.description });
+        Ok(Response::ok(body.to_string())?.with_status(status).with_headers(no_store_headers()))
+    }
+}
+
+/// RFC 7591 client registration request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientRegistrationRequest {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub token_endpoint_auth_method: Option<String>,
+    #[serde(default)]
+    pub grant_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub response_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+/// A registered client as stored in KV.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredClient {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub token_endpoint_auth_method: String,
+    pub grant_types: Vec<String>,
+    pub scope: Option<String>,
+    pub client_id_issued_at: u64,
+    /// SHA-256 of the client secret, for confidential clients.
+    pub client_secret_hash: Option<String>,
+}
+
+impl ClientRegistrationRequest {
+    /// Validates the request and fills in RFC 7591 defaults.
+    pub fn validate(self) -> std::result::Result<ClientRegistrationRequest, OAuthError> {
+        if self.redirect_uris.is_empty() {
+            return Err(OAuthError::new("invalid_redirect_uri", "At least one redirect_uri is required"));
+        }
+        if let Some(uri) = self.redirect_uris.iter().find(|uri| !is_allowed_redirect_uri(uri)) {
+            return Err(OAuthError::new(
+                "invalid_redirect_uri",
+                format!("Redirect URI must use https or a loopback host: {}", uri),
+            ));
+        }
+
+        let method = self
+            .token_endpoint_auth_method
+            .clone()
+            .unwrap_or_else(|| "client_secret_basic".to_string());
+        if !matches!(method.as_str(), "none" | "client_secret_post" | "client_secret_basic") {
+            return Err(OAuthError::new(
+                "invalid_client_metadata",
+                format!("Unsupported token_endpoint_auth_method: {}", method),
+            ));
+        }
+
+        let grant_types = self
+            .grant_types
+            .clone()
+            .unwrap_or_else(|| vec!["authorization_code".to_string()]);
+        if let Some(grant) = grant_types
+            .iter()
+            .find(|grant| !matches!(grant.as_str(), "authorization_code" | "refresh_token"))
+        {
+            return Err(OAuthError::new("invalid_client_metadata", format!("Unsupported grant_type: {}", grant)));
+        }
+        if self.response_types.as_ref().is_some_and(|types| types.iter().any(|t| t != "code")) {
+            return Err(OAuthError::new("invalid_client_metadata", "Only the code response_type is supported"));
+        }
+
+        Ok(ClientRegistrationRequest {
+            token_endpoint_auth_method: Some(method),
+            grant_types: Some(grant_types),
+            ..self
+        })
+    }
+}
+
+fn is_allowed_redirect_uri(uri: &str) -> bool {
+    match Url::parse(uri) {
+        Ok(url) => match url.scheme() {
+            "https" => true,
+            "http" => matches!(url.host_str(), Some("localhost") | Some("127.0.0.1") | Some("[::1]")),
+            // Private-use schemes for native apps (RFC 8252)
+            scheme => scheme.contains('.') && url.fragment().is_none(),
+        },
+        Err(_) => false,
+    }
+}
+
+/// Signs ES256 access tokens and publishes the matching JWKS.
+pub struct TokenSigner {
+    key: p256::ecdsa::SigningKey,
+}
+
+impl TokenSigner {
+    /// Loads a P-256 private scalar given as 32 bytes of base64 or base64url.
+    pub fn from_base64(encoded: &str) -> std::result::Result<Self, String> {
+        let encoded = encoded.trim();
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded.trim_end_matches('='))
+            .or_else(|_| STANDARD.decode(encoded))
+            .map_err(|_| "Signing key is not valid base64".to_string())?;
+        p256::ecdsa::SigningKey::from_slice(&bytes)
+            .map(|key| Self { key })
+            .map_err(|_| "Signing key is not a valid P-256 private key".to_string())
+    }
+
+    pub fn jwks(&self) -> serde_json::Value {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "use": "sig",
+                "alg": "ES256",
+                "kid": SIGNING_KEY_ID,
+                "x": URL_SAFE_NO_PAD.encode(point.x().expect("uncompressed point")),
+                "y": URL_SAFE_NO_PAD.encode(point.y().expect("uncompressed point")),
+            }]
+        })
+    }
+
+    pub fn sign(&self, claims: &serde_json::Value) -> String {
+        let header = json!({ "alg": "ES256", "typ": "at+jwt", "kid": SIGNING_KEY_ID });
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature: p256::ecdsa::Signature = self.key.sign(input.as_bytes());
+        format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+
+    /// Authenticator accepting the access tokens this signer issues.
+    pub fn authenticator(&self, origin: &str) -> JwtAuthenticator {
+        let jwks: Jwks = serde_json::from_value(self.jwks()).expect("generated JWKS is valid");
+        JwtAuthenticator::new(jwks, Some(origin.to_string()), Some(resource_uri(origin)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AuthorizationCode {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    scope: Option<String>,
+    subject: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RefreshToken {
+    client_id: String,
+    scope: Option<String>,
+    subject: String,
+}
+
+/// OAuth settings read from Worker bindings.
+pub struct OAuthConfig {
+    /// External authorization servers advertised in resource metadata.
+    pub authorization_servers: Vec<String>,
+    pub scopes: Vec<String>,
+    /// Present when the built-in authorization server is enabled.
+    pub signer: Option<TokenSigner>,
+}
+
+impl OAuthConfig {
+    /// Reads:
+    ///
+    /// - `MCP_AUTHORIZATION_SERVERS` (var): comma-separated external issuer URLs
+    /// - `MCP_OAUTH_SCOPES` (var): space-separated scopes to advertise
+    /// - `MCP_OAUTH_SIGNING_KEY` (secret): enables the built-in server; it
+    ///   also needs the `OAUTH_KV` namespace binding
+    pub fn from_env(env: &Env) -> Result<Self> {
+        let authorization_servers = env_string(env, "MCP_AUTHORIZATION_SERVERS")
+            .map(|servers| servers.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        let scopes = env_string(env, "MCP_OAUTH_SCOPES")
+            .map(|scopes| scopes.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let signer = env_string(env, "MCP_OAUTH_SIGNING_KEY")
+            .map(|key| TokenSigner::from_base64(&key).map_err(|e| Error::RustError(format!("MCP_OAUTH_SIGNING_KEY: {}", e))))
+            .transpose()?;
+
+        Ok(Self {
+            authorization_servers,
+            scopes,
+            signer,
+        })
+    }
+
+    /// Authorization servers to advertise, including this one when enabled.
+    pub fn advertised_servers(&self, origin: &str) -> Vec<String> {
+        let mut servers = self.authorization_servers.clone();
+        if self.signer.is_some() && !servers.iter().any(|s| s == origin) {
+            servers.insert(0, origin.to_string());
+        }
+        servers
+    }
+}
+
+pub fn request_origin(req: &Request) -> Result<String> {
+    Ok(req.url()?.origin().ascii_serialization())
+}
+
+fn no_store_headers() -> Headers {
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/json").unwrap();
+    headers.set("Cache-Control", "no-store").unwrap();
+    headers
+}
+
+fn json_response(value: &serde_json::Value, status: u16) -> Result<Response> {
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/json").unwrap();
+    Ok(Response::ok(value.to_string())?.with_status(status).with_headers(headers))
+}
+
+fn hash(value: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(value.as_bytes()))
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn form_params(body: &str) -> std::collections::HashMap<String, String> {
+    url::form_urlencoded::parse(body.as_bytes()).into_owned().collect()
+}
+
+fn oauth_kv(env: &Env) -> Result<kv::KvStore> {
+    env.kv("OAUTH_KV")
+}
+
+fn built_in_signer(env: &Env) -> Result<Option<TokenSigner>> {
+    Ok(OAuthConfig::from_env(env)?.signer)
+}
+
+pub(crate) async fn handle_protected_resource(req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    let origin = request_origin(&req)?;
+    let config = OAuthConfig::from_env(&ctx.env)?;
+    json_response(
+        &protected_resource_metadata(
+            &origin,
+            &ctx.data.config.name,
+            &config.advertised_servers(&origin),
+            &config.scopes,
+        ),
+        200,
+    )
+}
+
+pub(crate) async fn handle_authorization_server(req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    let config = OAuthConfig::from_env(&ctx.env)?;
+    if config.signer.is_none() {
+        return Response::error("Not Found", 404);
+    }
+    json_response(&authorization_server_metadata(&request_origin(&req)?, &config.scopes), 200)
+}
+
+pub(crate) async fn handle_jwks(_req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    match built_in_signer(&ctx.env)? {
+        Some(signer) => json_response(&signer.jwks(), 200),
+        None => Response::error("Not Found", 404),
+    }
+}
+
+pub(crate) async fn handle_register(mut req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    if built_in_signer(&ctx.env)?.is_none() {
+        return Response::error("Not Found", 404);
+    }
+
+    let request = match req.json::<ClientRegistrationRequest>().await {
+        Ok(request) => request,
+        Err(e) => return OAuthError::new("invalid_client_metadata", e.to_string()).into_response(400),
+    };
+    let request = match request.validate() {
+        Ok(request) => request,
+        Err(e) => return e.into_response(400),
+    };
+
+    let method = request.token_endpoint_auth_method.clone().unwrap_or_default();
+    let client_secret = (method != "none").then(random_token);
+    let client = RegisteredClient {
+        client_id: random_token(),
+        client_name: request.client_name,
+        redirect_uris: request.redirect_uris,
+        token_endpoint_auth_method: method,
+        grant_types: request.grant_types.unwrap_or_default(),
+        scope: request.scope,
+        client_id_issued_at: now_secs(),
+        client_secret_hash: client_secret.as_deref().map(hash),
+    };
+    oauth_kv(&ctx.env)?
+        .put(&format!("client:{}", client.client_id), serde_json::to_string(&client)?)?
+        .execute()
+        .await?;
+
+    let mut body = json!({
+        "client_id": client.client_id,
+        "client_id_issued_at": client.client_id_issued_at,
+        "client_name": client.client_name,
+        "redirect_uris": client.redirect_uris,
+        "token_endpoint_auth_method": client.token_endpoint_auth_method,
+        "grant_types": client.grant_types,
+        "response_types": ["code"],
+    });
+    if let Some(secret) = client_secret {
+        body["client_secret"] = json!(secret);
+        body["client_secret_expires_at"] = json!(0);
+    }
+    json_response(&body, 201)
+}
+
+async fn load_client(env: &Env, client_id: &str) -> Result<Option<RegisteredClient>> {
+    Ok(oauth_kv(env)?.get(&format!("client:{}", client_id)).json().await?)
+}
+
+/// Parameters of an authorization request, carried through the login form.
+struct AuthorizeParams {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    state: Option<String>,
+    scope: Option<String>,
+}
+
+impl AuthorizeParams {
+    fn parse(params: &std::collections::HashMap<String, String>) -> std::result::Result<Self, OAuthError> {
+        let get = |name: &str| params.get(name).filter(|v| !v.is_empty()).cloned();
+        if get("response_type").as_deref() != Some("code") {
+            return Err(OAuthError::new("unsupported_response_type", "response_type must be code"));
+        }
+        if get("code_challenge_method").as_deref() != Some("S256") {
+            return Err(OAuthError::new("invalid_request", "PKCE with code_challenge_method=S256 is required"));
+        }
+        Ok(Self {
+            client_id: get("client_id").ok_or_else(|| OAuthError::new("invalid_request", "Missing client_id"))?,
+            redirect_uri: get("redirect_uri").ok_or_else(|| OAuthError::new("invalid_request", "Missing redirect_uri"))?,
+            code_challenge: get("code_challenge")
+                .ok_or_else(|| OAuthError::new("invalid_request", "Missing code_challenge"))?,
+            state: get("state"),
+            scope: get("scope"),
+        })
+    }
+
+    fn hidden_fields(&self) -> String {
+        let mut fields = vec![
+            ("response_type", "code"),
+            ("code_challenge_method", "S256"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("code_challenge", self.code_challenge.as_str()),
+        ];
+        if let Some(state) = &self.state {
+            fields.push(("state", state));
+        }
+        if let Some(scope) = &self.scope {
+            fields.push(("scope", scope));
+        }
+        fields
+            .iter()
+            .map(|(name, value)| format!("<input type=\"hidden\" name=\"{}\" value=\"{}\">", name, html_escape(value)))
+            .collect()
+    }
+}
+
+fn login_page(server_name: &str, params: &AuthorizeParams, client: &RegisteredClient, error: Option<&str>) -> Result<Response> {
+    let client_name = client.client_name.as_deref().unwrap_or(&client.client_id);
+    let error = error
+        .map(|e| format!("<p style=\"color:#b00\">{}</p>", html_escape(e)))
+        .unwrap_or_default();
+    let html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>Authorize {client}</title></head>\
+         <body style=\"font-family:sans-serif;max-width:28rem;margin:4rem auto\">\
+         <h1>Authorize {client}</h1>\
+         <p><strong>{client}</strong> is requesting access to {server}{scope}.</p>{error}\
+         <form method=\"post\" action=\"/authorize\">{fields}\
+         <label>Access key <input type=\"password\" name=\"access_key\" autofocus required></label>\
+         <button type=\"submit\">Authorize</button></form></body></html>",
+        client = html_escape(client_name),
+        server = html_escape(server_name),
+        scope = params
+            .scope
+            .as_deref()
+            .map(|s| format!(" with scope <code>{}</code>", html_escape(s)))
+            .unwrap_or_default(),
+        error = error,
+        fields = params.hidden_fields(),
+    );
+
+    let headers = no_store_headers();
+    headers.set("Content-Type", "text/html; charset=utf-8")?;
+    headers.set("X-Frame-Options", "DENY")?;
+    Ok(Response::ok(html)?.with_headers(headers))
+}
+
+/// Validates the client and redirect URI. Errors here are shown to the user
+/// rather than redirected, since the redirect URI cannot be trusted yet.
+async fn authorize_client(
+    env: &Env,
+    params: &std::collections::HashMap<String, String>,
+) -> std::result::Result<(AuthorizeParams, RegisteredClient), Result<Response>> {
+    let params = AuthorizeParams::parse(params).map_err(|e| e.into_response(400))?;
+    let client = match load_client(env, &params.client_id).await {
+        Ok(Some(client)) => client,
+        Ok(None) => return Err(OAuthError::new("invalid_client", "Unknown client_id").into_response(400)),
+        Err(e) => return Err(Err(e)),
+    };
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(OAuthError::new("invalid_request", "redirect_uri is not registered").into_response(400));
+    }
+    Ok((params, client))
+}
+
+pub(crate) async fn handle_authorize_get(req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    if built_in_signer(&ctx.env)?.is_none() {
+        return Response::error("Not Found", 404);
+    }
+    let query = req.url()?.query_pairs().into_owned().collect();
+    match authorize_client(&ctx.env, &query).await {
+        Ok((params, client)) => login_page(&ctx.data.config.name, &params, &client, None),
+        Err(response) => response,
+    }
+}
+
+/// Completes the login form. Users sign in with one of the `MCP_API_KEYS`
+/// access keys; swap this for your identity provider as needed.
+pub(crate) async fn handle_authorize_post(mut req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    if built_in_signer(&ctx.env)?.is_none() {
+        return Response::error("Not Found", 404);
+    }
+    let form = form_params(&req.text().await?);
+    let (params, client) = match authorize_client(&ctx.env, &form).await {
+        Ok(validated) => validated,
+        Err(response) => return response,
+    };
+
+    let access_keys = ApiKeyAuthenticator::from_spec(&env_string(&ctx.env, "MCP_API_KEYS").unwrap_or_default());
+    let access_key = form.get("access_key").map(String::as_str).unwrap_or_default();
+    let principal = match access_keys.authenticate(access_key, now_secs()) {
+        Ok(principal) => principal,
+        Err(_) => return login_page(&ctx.data.config.name, &params, &client, Some("Invalid access key")),
+    };
+
+    let mut redirect = Url::parse(&params.redirect_uri)?;
+    let scope = match granted_scope(params.scope.as_deref(), &principal.scopes, client.scope.as_deref()) {
+        Ok(scope) => scope,
+        Err(e) => {
+            redirect.query_pairs_mut().append_pair("error", e.error).append_pair("error_description", &e.description);
+            if let Some(state) = &params.state {
+                redirect.query_pairs_mut().append_pair("state", state);
+            }
+            return Response::redirect(redirect);
+        },
+    };
+
+    let code = random_token();
+    let stored = AuthorizationCode {
+        client_id: params.client_id.clone(),
+        redirect_uri: params.redirect_uri.clone(),
+        code_challenge: params.code_challenge.clone(),
+        scope,
+        subject: principal.subject,
+    };
+    oauth_kv(&ctx.env)?
+        .put(&format!("code:{}", hash(&code)), serde_json::to_string(&stored)?)?
+        .expiration_ttl(AUTHORIZATION_CODE_TTL_SECS)
+        .execute()
+        .await?;
+
+    redirect.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = &params.state {
+        redirect.query_pairs_mut().append_pair("state", state);
+    }
+    Response::redirect(redirect)
+}
+
+/// The scopes a token may carry: those requested, all of which the signed-in
+/// principal must hold, and which the client must have registered for if it
+/// registered a `scope`. With none requested, everything the principal holds
+/// that the client may use is granted.
+pub fn granted_scope(
+    requested: Option<&str>,
+    held: &[String],
+    client_scope: Option<&str>,
+) -> std::result::Result<Option<String>, OAuthError> {
+    let available: Vec<&str> = held
+        .iter()
+        .map(String::as_str)
+        .filter(|scope| client_scope.is_none_or(|client| client.split_whitespace().any(|allowed| allowed == *scope)))
+        .collect();
+    let granted = match requested.map(str::split_whitespace) {
+        Some(requested) => {
+            let requested: Vec<&str> = requested.collect();
+            if let Some(missing) = requested.iter().find(|scope| !available.contains(scope)) {
+                return Err(OAuthError::new("invalid_scope", format!("Scope {} is not available to this account", missing)));
+            }
+            requested
+        },
+        None => available,
+    };
+    Ok(Some(granted.join(" ")).filter(|scope| !scope.is_empty()))
+}
+
+/// The scopes a refreshed token may carry: what the original grant had and
+/// the access key still holds, narrowed by `requested` if given. A scope
+/// removed from the key since is dropped rather than carried over.
+pub fn refreshed_scope(
+    requested: Option<&str>,
+    original: Option<&str>,
+    held: &[String],
+    client_scope: Option<&str>,
+) -> std::result::Result<Option<String>, OAuthError> {
+    let original: Vec<&str> = original.unwrap_or_default().split_whitespace().collect();
+    let held: Vec<String> = held.iter().filter(|scope| original.contains(&scope.as_str())).cloned().collect();
+    granted_scope(requested, &held, client_scope)
+}
+
+/// Rejects grant types the client did not register for.
+pub fn check_grant_type(client: &RegisteredClient, grant_type: &str) -> std::result::Result<(), OAuthError> {
+    if !matches!(grant_type, "authorization_code" | "refresh_token") {
+        return Err(OAuthError::new("unsupported_grant_type", format!("Unsupported grant_type: {}", grant_type)));
+    }
+    if !client.grant_types.iter().any(|registered| registered == grant_type) {
+        return Err(OAuthError::new(
+            "unauthorized_client",
+            format!("Client is not registered for the {} grant", grant_type),
+        ));
+    }
+    Ok(())
+}
+
+/// The client id and secret of an HTTP Basic `Authorization` header. Both
+/// are form-urlencoded before being joined, per RFC 6749 section 2.3.1.
+pub fn basic_credentials(header: &str) -> Option<(String, String)> {
+    let decoded = STANDARD.decode(header.strip_prefix("Basic ")?.trim()).ok()?;
+    let pair = String::from_utf8(decoded).ok()?;
+    let (id, secret) = pair.split_once(':')?;
+    let decode = |value: &str| url::form_urlencoded::parse(format!("v={}", value).as_bytes()).next().map(|(_, v)| v.into_owned());
+    Some((decode(id)?, decode(secret)?))
+}
+
+/// Authenticates the client at the token endpoint per its registered method.
+fn check_client_secret(
+    client: &RegisteredClient,
+    form: &std::collections::HashMap<String, String>,
+    basic: Option<&(String, String)>,
+) -> std::result::Result<(), OAuthError> {
+    let Some(expected) = &client.client_secret_hash else {
+        return Ok(());
+    };
+    let basic = basic.map(|(_, secret)| secret.clone());
+    let presented = form.get("client_secret").cloned().or(basic);
+
+    match presented {
+        Some(secret) if constant_time_eq(hash(&secret).as_bytes(), expected.as_bytes()) => Ok(()),
+        _ => Err(OAuthError::new("invalid_client", "Client authentication failed")),
+    }
+}
+
+pub(crate) async fn handle_token(mut req: Request, ctx: RouteContext<crate::RequestState>) -> Result<Response> {
+    let Some(signer) = built_in_signer(&ctx.env)? else {
+        return Response::error("Not Found", 404);
+    };
+    let origin = request_origin(&req)?;
+    let form = form_params(&req.text().await?);
+    let get = |name: &str| form.get(name).cloned().unwrap_or_default();
+    let store = oauth_kv(&ctx.env)?;
+
+    let basic = req.headers().get("Authorization")?.and_then(|header| basic_credentials(&header));
+    let client_id = match &basic {
+        Some((id, _)) => id.clone(),
+        None => get("client_id"),
+    };
+    let client = match load_client(&ctx.env, &client_id).await? {
+        Some(client) => client,
+        None => return OAuthError::new("invalid_client", "Unknown client_id").into_response(401),
+    };
+    if let Err(e) = check_client_secret(&client, &form, basic.as_ref()) {
+        return e.into_response(401);
+    }
+    if let Err(e) = check_grant_type(&client, &get("grant_type")) {
+        return e.into_response(400);
+    }
+
+    let (subject, scope) = match get("grant_type").as_str() {
+        "authorization_code" => {
+            let key = format!("code:{}", hash(&get("code")));
+            let Some(code) = store.get(&key).json::<AuthorizationCode>().await? else {
+                return OAuthError::new("invalid_grant", "Unknown or expired code").into_response(400);
+            };
+            // Codes are single use
+            store.delete(&key).await?;
+
+            if code.client_id != client.client_id || code.redirect_uri != get("redirect_uri") {
+                return OAuthError::new("invalid_grant", "Code was issued to another client or redirect_uri")
+                    .into_response(400);
+            }
+            if !verify_pkce(&get("code_verifier"), &code.code_challenge) {
+                return OAuthError::new("invalid_grant", "PKCE verification failed").into_response(400);
+            }
+            (code.subject, code.scope)
+        },
+        _ => {
+            let key = format!("refresh:{}", hash(&get("refresh_token")));
+            let Some(refresh) = store.get(&key).json::<RefreshToken>().await? else {
+                return OAuthError::new("invalid_grant", "Unknown or expired refresh token").into_response(400);
+            };
+            // Refresh tokens rotate on every use
+            store.delete(&key).await?;
+
+            if refresh.client_id != client.client_id {
+                return OAuthError::new("invalid_grant", "Refresh token was issued to another client").into_response(400);
+            }
+            // The key may have lost scopes, or been removed, since the grant
+            let access_keys = ApiKeyAuthenticator::from_spec(&env_string(&ctx.env, "MCP_API_KEYS").unwrap_or_default());
+            let Some(principal) = access_keys.principal(&refresh.subject) else {
+                return OAuthError::new("invalid_grant", "The access key behind this grant no longer exists").into_response(400);
+            };
+            let requested = form.get("scope").map(String::as_str);
+            match refreshed_scope(requested, refresh.scope.as_deref(), &principal.scopes, client.scope.as_deref()) {
+                Ok(scope) => (refresh.subject, scope),
+                Err(e) => return e.into_response(400),
+            }
+        },
+    };
+
+    let now = now_secs();
+    let mut claims = json!({
+        "iss": origin,
+        "aud": resource_uri(&origin),
+        "sub": subject,
+        "client_id": client.client_id,
+        "iat": now,
+        "exp": now + ACCESS_TOKEN_TTL_SECS,
+        "jti": random_token(),
+    });
+    if let Some(scope) = &scope {
+        claims["scope"] = json!(scope);
+    }
+
+    let mut body = json!({
+        "access_token": signer.sign(&claims),
+        "token_type": "Bearer",
+        "expires_in": ACCESS_TOKEN_TTL_SECS,
+    });
+    if let Some(scope) = &scope {
+        body["scope"] = json!(scope);
+    }
+    if client.grant_types.iter().any(|g| g == "refresh_token") {
+        let refresh_token = random_token();
+        let stored = RefreshToken {
+            client_id: client.client_id.clone(),
+            scope,
+            subject,
+        };
+        store
+            .put(&format!("refresh:{}", hash(&refresh_token)), serde_json::to_string(&stored)?)?
+            .expiration_ttl(REFRESH_TOKEN_TTL_SECS)
+            .execute()
+            .await?;
+        body["refresh_token"] = json!(refresh_token);
+    }
+
+    Ok(Response::ok(body.to_string())?.with_headers(no_store_headers()))
+}
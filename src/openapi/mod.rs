@@ -0,0 +1,256 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::http::{HttpClient, HttpRequest};
+use crate::tools::text_result;
+use crate::{Tool, ToolAnnotations, ToolsCallResult};
+
+pub mod spec;
+
+pub use spec::{parse_spec, Api, Location, Operation, SpecError};
+
+/// Operations converted by `build.rs` from the document at `MCP_OPENAPI_SPEC`
+/// when the crate was built, or `null`.
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/openapi.json"));
+
+thread_local! {
+    /// [`GENERATED`], parsed once per isolate.
+    static GENERATED_API: Option<Api> = serde_json::from_str(GENERATED).expect("build.rs writes the operations as JSON");
+}
+
+/// The operations generated at build time, if a spec was given.
+pub fn generated() -> Option<Api> {
+    GENERATED_API.with(Option::clone)
+}
+
+/// Where generated tools send requests and the headers they add, from
+/// `MCP_OPENAPI_BASE_URL` and friends.
+#[derive(Clone, Default, PartialEq)]
+pub struct OpenApiConfig {
+    /// Overrides the spec's server URL.
+    pub base_url: Option<String>,
+    /// Sent with every request, such as `Authorization`.
+    pub headers: Vec<(String, String)>,
+}
+
+/// Header values are usually credentials, so only their names are shown.
+impl fmt::Debug for OpenApiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("OpenApiConfig")
+            .field("base_url", &self.base_url)
+            .field("headers", &headers)
+            .finish()
+    }
+}
+
+/// The operations of an OpenAPI document, served as tools that call the API.
+pub struct OpenApi {
+    api: Api,
+    headers: Vec<(String, String)>,
+    client: Box<dyn HttpClient>,
+}
+
+impl fmt::Debug for OpenApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operations: Vec<&str> = self.api.operations.iter().map(|operation| operation.name.as_str()).collect();
+        f.debug_struct("OpenApi")
+            .field("base_url", &self.api.base_url)
+            .field("operations", &operations)
+            .finish()
+    }
+}
+
+impl OpenApi {
+    pub fn new(api: Api, client: impl HttpClient + 'static) -> Self {
+        Self {
+            api,
+            headers: Vec::new(),
+            client: Box::new(client),
+        }
+    }
+
+    /// Reads an OpenAPI 3 document in JSON.
+    pub fn from_json(spec: &str, client: impl HttpClient + 'static) -> Result<Self, SpecError> {
+        let spec: Value = serde_json::from_str(spec).map_err(|e| SpecError(e.to_string()))?;
+        Ok(Self::new(parse_spec(&spec)?, client))
+    }
+
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.api.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Adds a header to every request, such as an API key. Header
+    /// parameters of the same name, in any case, are dropped from the
+    /// operations, so a caller cannot replace or add to it.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        for operation in &mut self.api.operations {
+            operation
+                .parameters
+                .retain(|parameter| parameter.location != Location::Header || !parameter.name.eq_ignore_ascii_case(name));
+        }
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn configure(mut self, config: &OpenApiConfig) -> Self {
+        if let Some(base_url) = &config.base_url {
+            self = self.base_url(base_url);
+        }
+        for (name, value) in &config.headers {
+            self = self.header(name, value);
+        }
+        self
+    }
+
+    pub fn has_base_url(&self) -> bool {
+        self.api.base_url.is_some()
+    }
+
+    pub fn operation(&self, name: &str) -> Option<&Operation> {
+        self.api.operations.iter().find(|operation| operation.name == name)
+    }
+
+    /// One tool per operation. `GET`, `HEAD` and `OPTIONS` are annotated as
+    /// read-only and everything else as destructive, `PUT` and `DELETE`
+    /// being idempotent.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.api
+            .operations
+            .iter()
+            .map(|operation| {
+                let annotations = match operation.method.as_str() {
+                    _ if operation.is_read_only() => ToolAnnotations::read_only(),
+                    "PUT" | "DELETE" => ToolAnnotations::destructive(true),
+                    _ => ToolAnnotations::destructive(false),
+                };
+                let annotations = ToolAnnotations {
+                    open_world_hint: Some(true),
+                    ..annotations
+                };
+                Tool::new(&operation.name, operation.description.as_deref(), operation.input_schema()).with_annotations(annotations)
+            })
+            .collect()
+    }
+
+    /// Builds the HTTP request for a call, or explains which argument is
+    /// missing or malformed.
+    pub fn request(&self, operation: &Operation, arguments: Option<Value>) -> Result<HttpRequest, String> {
+        let arguments = match arguments {
+            Some(Value::Object(arguments)) => arguments,
+            None | Some(Value::Null) => Map::new(),
+            Some(_) => return Err("Invalid arguments: expected an object".to_string()),
+        };
+        let base_url = self.api.base_url.as_deref().ok_or("Invalid arguments: the API has no base URL")?;
+
+        let mut path = operation.path.clone();
+        let mut query = Vec::new();
+        let mut headers = self.headers.clone();
+        for parameter in &operation.parameters {
+            let value = match arguments.get(&parameter.name) {
+                Some(Value::Null) | None if parameter.required => {
+                    return Err(format!("Missing required argument: {}", parameter.name));
+                },
+                Some(Value::Null) | None => continue,
+                Some(value) => value,
+            };
+            match parameter.location {
+                Location::Path => {
+                    let segment = encode_path(&scalar(value)).ok_or_else(|| format!("Invalid arguments: {} must name a path segment", parameter.name))?;
+                    path = path.replace(&["{", &parameter.name, "}"].concat(), &segment)
+                },
+                Location::Query => match value {
+                    Value::Array(items) => query.extend(items.iter().map(|item| (parameter.name.clone(), scalar(item)))),
+                    value => query.push((parameter.name.clone(), scalar(value))),
+                },
+                Location::Header => headers.push((parameter.name.clone(), scalar(value))),
+            }
+        }
+
+        let mut url = url::Url::parse(&format!("{}{}", base_url, path)).map_err(|e| format!("Invalid arguments: {}", e))?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+
+        let body = match (&operation.body, arguments.get(spec::BODY_ARGUMENT)) {
+            (Some(body), Some(value)) if !value.is_null() => {
+                headers.push(("Content-Type".to_string(), body.content_type.clone()));
+                Some(encode_body(&body.content_type, value)?)
+            },
+            (Some(body), _) if body.required => return Err(format!("Missing required argument: {}", spec::BODY_ARGUMENT)),
+            _ => None,
+        };
+        headers.push(("Accept".to_string(), "application/json".to_string()));
+
+        Ok(HttpRequest {
+            method: operation.method.clone(),
+            url: url.to_string(),
+            headers,
+            body,
+        })
+    }
+
+    /// Calls the API. Error statuses become tool errors with the response
+    /// body, which usually says what was wrong; JSON objects in successful
+    /// responses are also returned as structured content.
+    pub async fn call(&self, operation: &Operation, arguments: Option<Value>) -> Result<ToolsCallResult, String> {
+        let request = match self.request(operation, arguments) {
+            Ok(request) => request,
+            Err(e) => return Ok(text_result(e, true)),
+        };
+        let response = self.client.send(request).await.map_err(|e| e.to_string())?;
+        if !(200..300).contains(&response.status) {
+            return Ok(text_result(format!("HTTP {}: {}", response.status, response.body), true));
+        }
+        let structured = match response.is_json() {
+            true => serde_json::from_str::<Value>(&response.body).ok().filter(Value::is_object),
+            false => None,
+        };
+        let result = text_result(response.body, false);
+        Ok(match structured {
+            Some(structured) => result.with_structured_content(structured),
+            None => result,
+        })
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, so a value stays
+/// within its path segment. `None` for empty, `.` and `..` values, which URL
+/// parsing would collapse into another endpoint even when encoded.
+fn encode_path(value: &str) -> Option<String> {
+    if matches!(value, "" | "." | "..") {
+        return None;
+    }
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    Some(encoded)
+}
+
+fn encode_body(content_type: &str, value: &Value) -> Result<String, String> {
+    match content_type.split(';').next().unwrap_or_default().trim() {
+        "application/x-www-form-urlencoded" => {
+            let fields = value.as_object().ok_or("Invalid arguments: body must be an object of form fields")?;
+            let mut form = url::form_urlencoded::Serializer::new(String::new());
+            for (name, value) in fields {
+                form.append_pair(name, &scalar(value));
+            }
+            Ok(form.finish())
+        },
+        "text/plain" => Ok(scalar(value)),
+        _ => Ok(value.to_string()),
+    }
+}
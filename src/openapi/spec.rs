@@ -0,0 +1,338 @@
+// Turns an OpenAPI 3 document into the operations served as tools. This
+// file only depends on serde so `build.rs` can include it to convert a spec
+// at build time.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+const METHODS: &[&str] = &["get", "put", "post", "delete", "patch", "head", "options"];
+
+/// Tool names longer than this are cut short, as clients reject them.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// The argument that carries an operation's request body.
+pub const BODY_ARGUMENT: &str = "body";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecError(pub String);
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid OpenAPI document: {}", self.0)
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// The operations of an OpenAPI document and where to send them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Api {
+    /// The first absolute server URL, with its variables at their defaults.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    pub operations: Vec<Operation>,
+    /// Operations that were left out, and why.
+    #[serde(default, skip_serializing)]
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    /// The tool name: the `operationId`, or the method and path without one.
+    pub name: String,
+    /// Upper case, as sent.
+    pub method: String,
+    /// The path template, such as `/pets/{petId}`.
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub parameters: Vec<Parameter>,
+    #[serde(default)]
+    pub body: Option<RequestBody>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    Path,
+    Query,
+    Header,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Parameter {
+    pub name: String,
+    pub location: Location,
+    pub required: bool,
+    pub schema: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestBody {
+    pub content_type: String,
+    pub required: bool,
+    pub schema: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl Operation {
+    /// The tool's input schema: one property per parameter, plus `body`.
+    pub fn input_schema(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for parameter in &self.parameters {
+            properties.insert(parameter.name.clone(), described(&parameter.schema, parameter.description.as_deref()));
+            if parameter.required {
+                required.push(json!(parameter.name));
+            }
+        }
+        if let Some(body) = &self.body {
+            properties.insert(BODY_ARGUMENT.to_string(), described(&body.schema, body.description.as_deref()));
+            if body.required {
+                required.push(json!(BODY_ARGUMENT));
+            }
+        }
+        json!({ "type": "object", "properties": properties, "required": required })
+    }
+
+    /// Whether the method only reads, as `GET`, `HEAD` and `OPTIONS` should.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.method.as_str(), "GET" | "HEAD" | "OPTIONS")
+    }
+}
+
+fn described(schema: &Value, description: Option<&str>) -> Value {
+    let mut schema = schema.clone();
+    if let (Some(description), Some(object)) = (description, schema.as_object_mut()) {
+        object.entry("description").or_insert_with(|| json!(description));
+    }
+    schema
+}
+
+/// Reads every operation in `spec`. Operations that cannot be served as a
+/// tool, such as those with cookie parameters or external `$ref`s, are
+/// listed in `skipped` rather than failing the document.
+pub fn parse_spec(spec: &Value) -> Result<Api, SpecError> {
+    let version = spec.get("openapi").and_then(Value::as_str).unwrap_or_default();
+    if !version.starts_with("3.") {
+        return Err(SpecError("only OpenAPI 3 documents are supported".to_string()));
+    }
+    let mut api = Api {
+        base_url: base_url(spec),
+        ..Api::default()
+    };
+    let Some(paths) = spec.get("paths").and_then(Value::as_object) else {
+        return Ok(api);
+    };
+
+    for (path, item) in paths {
+        let item = resolve(spec, item).map_err(|e| SpecError(format!("{}: {}", path, e)))?;
+        for method in METHODS {
+            let Some(operation) = item.get(*method) else {
+                continue;
+            };
+            let label = format!("{} {}", method.to_ascii_uppercase(), path);
+            match parse_operation(spec, path, method, item, operation) {
+                Ok(operation) => {
+                    if api.operations.iter().any(|other| other.name == operation.name) {
+                        return Err(SpecError(format!("{}: tool name {} is used twice", label, operation.name)));
+                    }
+                    api.operations.push(operation);
+                },
+                Err(reason) => api.skipped.push(format!("{}: {}", label, reason)),
+            }
+        }
+    }
+    Ok(api)
+}
+
+fn base_url(spec: &Value) -> Option<String> {
+    let servers = spec.get("servers")?.as_array()?;
+    servers.iter().find_map(|server| {
+        let mut url = server.get("url")?.as_str()?.to_string();
+        if let Some(variables) = server.get("variables").and_then(Value::as_object) {
+            for (name, variable) in variables {
+                let default = variable.get("default").and_then(Value::as_str).unwrap_or_default();
+                url = url.replace(&["{", name, "}"].concat(), default);
+            }
+        }
+        (url.starts_with("https://") || url.starts_with("http://")).then(|| url.trim_end_matches('/').to_string())
+    })
+}
+
+fn parse_operation(spec: &Value, path: &str, method: &str, item: &Value, operation: &Value) -> Result<Operation, String> {
+    let name = match operation.get("operationId").and_then(Value::as_str) {
+        Some(id) => tool_name(id),
+        None => tool_name(&format!("{}_{}", method, path)),
+    };
+    let description = [operation.get("summary"), operation.get("description")]
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+
+    // Operation parameters override path item parameters with the same
+    // name and location
+    let mut parameters: Vec<Parameter> = Vec::new();
+    let declared = [item.get("parameters"), operation.get("parameters")];
+    for parameter in declared.into_iter().flatten().filter_map(Value::as_array).flatten() {
+        let parameter = parse_parameter(spec, parameter)?;
+        parameters.retain(|other| (&other.name, other.location) != (&parameter.name, parameter.location));
+        parameters.push(parameter);
+    }
+    for parameter in &parameters {
+        if parameters.iter().filter(|other| other.name == parameter.name).count() > 1 {
+            return Err(format!("parameter {} appears in more than one location", parameter.name));
+        }
+    }
+    for segment in path.split('{').skip(1) {
+        let name = segment.split('}').next().unwrap_or_default();
+        if !parameters.iter().any(|p| p.location == Location::Path && p.name == name) {
+            return Err(format!("path parameter {} is not declared", name));
+        }
+    }
+
+    let body = match operation.get("requestBody") {
+        Some(body) => Some(parse_body(spec, body)?),
+        None => None,
+    };
+    if body.is_some() && parameters.iter().any(|p| p.name == BODY_ARGUMENT) {
+        return Err(format!("a parameter is named {}, which holds the request body", BODY_ARGUMENT));
+    }
+
+    Ok(Operation {
+        name,
+        method: method.to_ascii_uppercase(),
+        path: path.to_string(),
+        description: (!description.is_empty()).then(|| description.join("\n\n")),
+        parameters,
+        body,
+    })
+}
+
+fn parse_parameter(spec: &Value, parameter: &Value) -> Result<Parameter, String> {
+    let parameter = resolve(spec, parameter)?;
+    let name = parameter.get("name").and_then(Value::as_str).ok_or("a parameter has no name")?;
+    let location = match parameter.get("in").and_then(Value::as_str) {
+        Some("path") => Location::Path,
+        Some("query") => Location::Query,
+        Some("header") => Location::Header,
+        Some(other) => return Err(format!("{} parameters are not supported", other)),
+        None => return Err(format!("parameter {} has no location", name)),
+    };
+    let schema = match parameter.get("schema") {
+        Some(schema) => inline(spec, schema, &mut Vec::new())?,
+        None => json!({ "type": "string" }),
+    };
+    Ok(Parameter {
+        name: name.to_string(),
+        location,
+        required: location == Location::Path || parameter.get("required").and_then(Value::as_bool).unwrap_or(false),
+        schema,
+        description: parameter.get("description").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+/// Takes the first content type in order of preference: JSON, then form
+/// fields, then plain text.
+fn parse_body(spec: &Value, body: &Value) -> Result<RequestBody, String> {
+    let body = resolve(spec, body)?;
+    let content = body.get("content").and_then(Value::as_object).ok_or("the request body has no content")?;
+    let preference = |content_type: &str| {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        match essence {
+            "application/json" => Some(0),
+            _ if essence.ends_with("+json") => Some(1),
+            "application/x-www-form-urlencoded" => Some(2),
+            "text/plain" => Some(3),
+            _ => None,
+        }
+    };
+    let (content_type, media) = content
+        .iter()
+        .filter_map(|(content_type, media)| preference(content_type).map(|rank| (rank, content_type, media)))
+        .min_by_key(|(rank, _, _)| *rank)
+        .map(|(_, content_type, media)| (content_type, media))
+        .ok_or_else(|| format!("unsupported request body types: {}", content.keys().cloned().collect::<Vec<_>>().join(", ")))?;
+    let schema = match media.get("schema") {
+        Some(schema) => inline(spec, schema, &mut Vec::new())?,
+        None => json!({}),
+    };
+    Ok(RequestBody {
+        content_type: content_type.clone(),
+        required: body.get("required").and_then(Value::as_bool).unwrap_or(false),
+        schema,
+        description: body.get("description").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+/// Follows a `$ref` to a component in the same document.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> Result<&'a Value, String> {
+    let mut value = value;
+    for _ in 0..16 {
+        let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+            return Ok(value);
+        };
+        value = lookup(spec, reference)?;
+    }
+    Err("too many nested $refs".to_string())
+}
+
+fn lookup<'a>(spec: &'a Value, reference: &str) -> Result<&'a Value, String> {
+    let pointer = reference
+        .strip_prefix('#')
+        .ok_or_else(|| format!("external $ref {} is not supported", reference))?;
+    spec.pointer(pointer).ok_or_else(|| format!("$ref {} does not exist", reference))
+}
+
+/// Replaces every `$ref` in a schema with what it points to, since tool
+/// schemas must stand alone. A schema that refers back to itself is cut off
+/// at the cycle, where any value is accepted.
+fn inline(spec: &Value, schema: &Value, seen: &mut Vec<String>) -> Result<Value, String> {
+    match schema {
+        Value::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                if seen.iter().any(|other| other == reference) {
+                    return Ok(json!({}));
+                }
+                seen.push(reference.to_string());
+                let inlined = inline(spec, lookup(spec, reference)?, seen);
+                seen.pop();
+                return inlined;
+            }
+            let mut inlined = Map::new();
+            for (key, value) in object {
+                inlined.insert(key.clone(), inline(spec, value, seen)?);
+            }
+            Ok(Value::Object(inlined))
+        },
+        Value::Array(items) => items.iter().map(|item| inline(spec, item, seen)).collect::<Result<_, _>>().map(Value::Array),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Keeps the characters clients accept in tool names and replaces the rest
+/// with `_`.
+pub fn tool_name(raw: &str) -> String {
+    let mut name = String::new();
+    for c in raw.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' };
+        // Collapse runs, as in `get_/pets/{id}`
+        if c != '_' || !name.ends_with('_') {
+            name.push(c);
+        }
+    }
+    let name = name.trim_matches('_');
+    name.chars().take(MAX_NAME_LEN).collect()
+}
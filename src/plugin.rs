@@ -0,0 +1,487 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use wasmi::core::TrapCode;
+use wasmi::{Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+use wasmparser::component_types::{ComponentDefinedType, ComponentEntityType, ComponentValType};
+use wasmparser::{
+    CanonicalFunction, ComponentAlias, ComponentExternalKind, ExternalKind, Parser, Payload, PrimitiveValType, Validator,
+    WasmFeatures,
+};
+use worker::Env;
+
+use crate::tools::text_result;
+use crate::{Tool, ToolsCallResult};
+
+/// Instructions a call may run, roughly; one unit per simple instruction.
+pub const DEFAULT_FUEL: u64 = 50_000_000;
+
+/// The most linear memory a plugin may grow to.
+pub const DEFAULT_MEMORY_BYTES: usize = 16 << 20;
+
+/// The longest string a plugin may return.
+pub const MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+/// The largest module that is compiled; bigger ones are rejected unparsed.
+pub const MAX_MODULE_BYTES: usize = 8 << 20;
+
+/// The most entries a plugin's function table may hold.
+pub const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+/// How long a plugin loaded from R2 or KV is used before the object is
+/// fetched again, so uploads take effect without a deploy.
+pub const REFRESH_SECS: u64 = 300;
+
+/// What each call of a plugin may use. Every call runs in a fresh instance,
+/// so nothing carries over between calls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginLimits {
+    pub fuel: u64,
+    pub memory_bytes: usize,
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            fuel: DEFAULT_FUEL,
+            memory_bytes: DEFAULT_MEMORY_BYTES,
+        }
+    }
+}
+
+/// Plugins to load from R2 or KV, from `MCP_PLUGINS` and friends.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginConfig {
+    pub sources: Vec<PluginSource>,
+    pub limits: PluginLimits,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginError {
+    /// The bytes are not a module or component implementing `wit/plugin.wit`.
+    Invalid(String),
+    OutOfFuel,
+    /// The plugin trapped, for example by growing memory past its limit.
+    Trap(String),
+    /// The plugin broke the calling convention, such as returning a string
+    /// outside its memory.
+    Abi(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Invalid(e) => write!(f, "invalid plugin: {}", e),
+            PluginError::OutOfFuel => write!(f, "ran out of fuel"),
+            PluginError::Trap(e) => write!(f, "trapped: {}", e),
+            PluginError::Abi(e) => write!(f, "broke the calling convention: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+fn run_error(e: wasmi::Error) -> PluginError {
+    match e.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => PluginError::OutOfFuel,
+        _ => PluginError::Trap(e.to_string()),
+    }
+}
+
+/// Tools implemented by a WebAssembly component or core module exporting the
+/// `tools` world of `wit/plugin.wit`. Plugins run in an interpreter, since
+/// Workers cannot compile WebAssembly at runtime, and may not import
+/// anything.
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+    limits: PluginLimits,
+    tools: Vec<Tool>,
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tools: Vec<&str> = self.tools.iter().map(|tool| tool.name.as_str()).collect();
+        f.debug_struct("Plugin")
+            .field("name", &self.name)
+            .field("limits", &self.limits)
+            .field("tools", &tools)
+            .finish()
+    }
+}
+
+/// A running instance; one per call.
+struct Instance {
+    store: Store<StoreLimits>,
+    instance: wasmi::Instance,
+    memory: Memory,
+}
+
+impl Plugin {
+    /// Compiles the component or module and asks it for its tools. `name`
+    /// appears in errors.
+    pub fn new(name: &str, bytes: &[u8], limits: PluginLimits) -> Result<Self, PluginError> {
+        if bytes.len() > MAX_MODULE_BYTES {
+            return Err(PluginError::Invalid(format!("is {} bytes, more than {}", bytes.len(), MAX_MODULE_BYTES)));
+        }
+        // Components share the magic number but have a different version
+        let bytes = if bytes.starts_with(b"\0asm") && bytes.get(4..8).is_some_and(|version| version != [1, 0, 0, 0]) {
+            component_module(bytes)?
+        } else {
+            bytes
+        };
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|e| PluginError::Invalid(e.to_string()))?;
+        if let Some(import) = module.imports().next() {
+            return Err(PluginError::Invalid(format!("imports {}.{}; plugins cannot import anything", import.module(), import.name())));
+        }
+        let mut plugin = Self {
+            name: name.to_string(),
+            engine,
+            module,
+            limits,
+            tools: Vec::new(),
+        };
+
+        let mut instance = plugin.instantiate()?;
+        let list: TypedFunc<(), i32> = instance.func("tools")?;
+        let area = list.call(&mut instance.store, ()).map_err(run_error)?;
+        let json = instance.string_at(area)?;
+        plugin.tools = serde_json::from_str(&json).map_err(|e| PluginError::Invalid(format!("tools are not a JSON array of tools: {}", e)))?;
+        Ok(plugin)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name == name)
+    }
+
+    /// Calls `invoke` with the tool's name and JSON arguments. The inner
+    /// result is the plugin's own: a JSON `CallToolResult` or an error
+    /// message.
+    pub fn invoke(&self, tool: &str, arguments: &Value) -> Result<Result<String, String>, PluginError> {
+        let mut instance = self.instantiate()?;
+        let invoke: TypedFunc<(i32, i32, i32, i32), i32> = instance.func("invoke")?;
+        let (name, name_len) = instance.pass_string(tool)?;
+        let (args, args_len) = instance.pass_string(&arguments.to_string())?;
+        let area = invoke.call(&mut instance.store, (name, name_len, args, args_len)).map_err(run_error)?;
+
+        let mut discriminant = [0u8; 1];
+        instance.read(area, &mut discriminant)?;
+        let string = area.checked_add(4).ok_or_else(|| PluginError::Abi(format!("result area {} is out of range", area)))?;
+        let value = instance.string_at(string)?;
+        match discriminant[0] {
+            0 => Ok(Ok(value)),
+            1 => Ok(Err(value)),
+            other => Err(PluginError::Abi(format!("result discriminant {}", other))),
+        }
+    }
+
+    /// Runs a tool. Errors the plugin returns, and traps such as running out
+    /// of fuel, become tool errors.
+    pub fn call(&self, tool: &str, arguments: Option<Value>) -> Result<ToolsCallResult, String> {
+        let arguments = arguments.unwrap_or_else(|| serde_json::json!({}));
+        match self.invoke(tool, &arguments) {
+            Ok(Ok(result)) => serde_json::from_str(&result).map_err(|e| format!("Plugin {} returned an invalid result: {}", self.name, e)),
+            Ok(Err(message)) => Ok(text_result(message, true)),
+            Err(e) => Ok(text_result(format!("Plugin {} {}", self.name, e), true)),
+        }
+    }
+
+    fn instantiate(&self) -> Result<Instance, PluginError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory_bytes)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .tables(1)
+            .memories(1)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.limits.fuel).map_err(|e| PluginError::Trap(e.to_string()))?;
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(run_error)?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| PluginError::Invalid("no exported memory".to_string()))?;
+        Ok(Instance { store, instance, memory })
+    }
+}
+
+/// The core module inside a component of the `tools` world. The component
+/// may hold nothing that needs a component host: one core module, no
+/// imports, and `tools` and `invoke` lifted with the WIT's types from the
+/// module's exports of the same names. The module then runs like a plain
+/// one.
+fn component_module(bytes: &[u8]) -> Result<&[u8], PluginError> {
+    let invalid = PluginError::Invalid;
+    let types = Validator::new_with_features(WasmFeatures::default())
+        .validate_all(bytes)
+        .map_err(|e| invalid(e.to_string()))?;
+
+    let mut modules = Vec::new();
+    // What each core and component function index refers to, as far as
+    // the check below needs: the core export it aliases, or the core
+    // function it lifts
+    let mut core_funcs: Vec<Option<&str>> = Vec::new();
+    let mut funcs: Vec<Option<u32>> = Vec::new();
+    let mut exports = HashMap::new();
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload.map_err(|e| invalid(e.to_string()))? {
+            Payload::ModuleSection { unchecked_range, .. } => modules.push(unchecked_range),
+            Payload::ComponentSection { .. } => return Err(invalid("nests components".to_string())),
+            Payload::ComponentImportSection(reader) => {
+                if let Some(import) = reader.into_iter().next() {
+                    let import = import.map_err(|e| invalid(e.to_string()))?;
+                    return Err(invalid(format!("imports {}; plugins cannot import anything", import.name.0)));
+                }
+            },
+            Payload::ComponentAliasSection(reader) => {
+                for alias in reader {
+                    match alias.map_err(|e| invalid(e.to_string()))? {
+                        ComponentAlias::CoreInstanceExport { kind: ExternalKind::Func, name, .. } => core_funcs.push(Some(name)),
+                        ComponentAlias::InstanceExport { kind: ComponentExternalKind::Func, .. } => funcs.push(None),
+                        _ => {},
+                    }
+                }
+            },
+            Payload::ComponentCanonicalSection(reader) => {
+                for function in reader {
+                    match function.map_err(|e| invalid(e.to_string()))? {
+                        CanonicalFunction::Lift { core_func_index, .. } => funcs.push(Some(core_func_index)),
+                        // Every other canonical function is a core one
+                        _ => core_funcs.push(None),
+                    }
+                }
+            },
+            Payload::ComponentExportSection(reader) => {
+                for export in reader {
+                    let export = export.map_err(|e| invalid(e.to_string()))?;
+                    if export.kind == ComponentExternalKind::Func {
+                        let func = funcs.get(export.index as usize).copied().flatten();
+                        exports.insert(export.name.0, func);
+                        funcs.push(func);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let string = |ty: &ComponentValType| matches!(ty, ComponentValType::Primitive(PrimitiveValType::String));
+    for name in ["tools", "invoke"] {
+        let Some(ComponentEntityType::Func(id)) = types.component_entity_type_of_export(name) else {
+            return Err(invalid(format!("does not export the function {}", name)));
+        };
+        let ty = &types[id];
+        let typed = match name {
+            "tools" => ty.params.is_empty() && ty.result.as_ref().is_some_and(string),
+            _ => {
+                ty.params.len() == 2
+                    && ty.params.iter().all(|(_, ty)| string(ty))
+                    && matches!(&ty.result, Some(ComponentValType::Type(id)) if matches!(
+                        &types[*id],
+                        ComponentDefinedType::Result { ok: Some(ok), err: Some(err) } if string(ok) && string(err)
+                    ))
+            },
+        };
+        if !typed {
+            return Err(invalid(format!("exports {} with a different type than wit/plugin.wit", name)));
+        }
+        let lifted = exports.get(name).copied().flatten().and_then(|core| core_funcs.get(core as usize).copied().flatten());
+        if lifted != Some(name) {
+            return Err(invalid(format!("does not lift {} from the core export of that name", name)));
+        }
+    }
+    match <[_; 1]>::try_from(modules) {
+        Ok([module]) => Ok(&bytes[module]),
+        Err(modules) => Err(invalid(format!("has {} core modules rather than one", modules.len()))),
+    }
+}
+
+impl Instance {
+    fn func<Params: wasmi::WasmParams, Results: wasmi::WasmResults>(&self, name: &str) -> Result<TypedFunc<Params, Results>, PluginError> {
+        self.instance
+            .get_typed_func(&self.store, name)
+            .map_err(|e| PluginError::Invalid(format!("export {}: {}", name, e)))
+    }
+
+    /// Copies a string into memory the plugin allocated with `cabi_realloc`.
+    fn pass_string(&mut self, value: &str) -> Result<(i32, i32), PluginError> {
+        let realloc: TypedFunc<(i32, i32, i32, i32), i32> = self.func("cabi_realloc")?;
+        let len = i32::try_from(value.len()).map_err(|_| PluginError::Abi("argument too long".to_string()))?;
+        let ptr = realloc.call(&mut self.store, (0, 0, 1, len)).map_err(run_error)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, value.as_bytes())
+            .map_err(|e| PluginError::Abi(e.to_string()))?;
+        Ok((ptr, len))
+    }
+
+    fn read(&self, offset: i32, buffer: &mut [u8]) -> Result<(), PluginError> {
+        self.memory
+            .read(&self.store, offset as u32 as usize, buffer)
+            .map_err(|e| PluginError::Abi(e.to_string()))
+    }
+
+    /// Reads the string whose pointer and length are stored at `area`.
+    fn string_at(&self, area: i32) -> Result<String, PluginError> {
+        let mut words = [0u8; 8];
+        self.read(area, &mut words)?;
+        let ptr = u32::from_le_bytes([words[0], words[1], words[2], words[3]]);
+        let len = u32::from_le_bytes([words[4], words[5], words[6], words[7]]) as usize;
+        if len > MAX_OUTPUT_BYTES {
+            return Err(PluginError::Abi(format!("returned {} bytes, more than {}", len, MAX_OUTPUT_BYTES)));
+        }
+        let mut bytes = vec![0u8; len];
+        self.read(ptr as i32, &mut bytes)?;
+        String::from_utf8(bytes).map_err(|e| PluginError::Abi(e.to_string()))
+    }
+}
+
+/// Where a plugin's module is stored, as written in `MCP_PLUGINS`:
+/// `r2://<binding>/<key>`, `kv://<binding>/<key>` or, for modules deployed
+/// with the Worker as static assets, `assets://<binding>/<path>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginSource {
+    R2 { binding: String, key: String },
+    Kv { binding: String, key: String },
+    Assets { binding: String, path: String },
+}
+
+impl PluginSource {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("{:?} is not r2://<binding>/<key>, kv://<binding>/<key> or assets://<binding>/<path>", value);
+        let (scheme, rest) = value.split_once("://").ok_or_else(invalid)?;
+        let (binding, key) = rest.split_once('/').ok_or_else(invalid)?;
+        if binding.is_empty() || key.is_empty() {
+            return Err(invalid());
+        }
+        let (binding, key) = (binding.to_string(), key.to_string());
+        match scheme {
+            "r2" => Ok(PluginSource::R2 { binding, key }),
+            "kv" => Ok(PluginSource::Kv { binding, key }),
+            "assets" => Ok(PluginSource::Assets { binding, path: key }),
+            _ => Err(invalid()),
+        }
+    }
+
+    async fn fetch(&self, env: &Env) -> worker::Result<Vec<u8>> {
+        let bytes = match self {
+            PluginSource::R2 { binding, key } => match env.bucket(binding)?.get(key).execute().await? {
+                Some(object) => match object.body() {
+                    Some(body) => Some(body.bytes().await?),
+                    None => None,
+                },
+                None => None,
+            },
+            PluginSource::Kv { binding, key } => env.kv(binding)?.get(key).bytes().await?,
+            PluginSource::Assets { binding, path } => {
+                // The host is ignored; only the path selects the asset
+                let mut response = env.assets(binding)?.fetch(format!("https://assets.local/{}", path), None).await?;
+                match response.status_code() {
+                    200 => Some(response.bytes().await?),
+                    _ => None,
+                }
+            },
+        };
+        bytes.ok_or_else(|| worker::Error::RustError(format!("{} does not exist", self)))
+    }
+}
+
+impl fmt::Display for PluginSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginSource::R2 { binding, key } => write!(f, "r2://{}/{}", binding, key),
+            PluginSource::Kv { binding, key } => write!(f, "kv://{}/{}", binding, key),
+            PluginSource::Assets { binding, path } => write!(f, "assets://{}/{}", binding, path),
+        }
+    }
+}
+
+/// Logs a plugin that cannot be served; the server carries on without it.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn log_skipped(source: &PluginSource, error: &str) {
+    worker::console_error!("Skipping plugin {}: {}", source, error);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn log_skipped(_source: &PluginSource, _error: &str) {}
+
+/// A compiled plugin, when its object was fetched, and a hash of the object
+/// so that an unchanged upload is not compiled again.
+struct Cached {
+    plugin: Rc<Plugin>,
+    fetched_at: u64,
+    digest: Vec<u8>,
+}
+
+thread_local! {
+    static PLUGINS: RefCell<HashMap<String, Cached>> = RefCell::new(HashMap::new());
+}
+
+/// The plugin last loaded from `source`, if any.
+pub fn cached(source: &PluginSource) -> Option<Rc<Plugin>> {
+    PLUGINS.with(|plugins| plugins.borrow().get(&source.to_string()).map(|cached| cached.plugin.clone()))
+}
+
+/// Fetches and compiles the plugin unless this isolate did so within
+/// [`REFRESH_SECS`]. If fetching fails after an earlier success, the older
+/// plugin stays in use.
+pub async fn load(env: &Env, source: &PluginSource, limits: PluginLimits) -> Result<Rc<Plugin>, String> {
+    let key = source.to_string();
+    let now = crate::auth::now_secs();
+    let fresh = PLUGINS.with(|plugins| {
+        plugins
+            .borrow()
+            .get(&key)
+            .filter(|cached| now < cached.fetched_at + REFRESH_SECS && cached.plugin.limits == limits)
+            .map(|cached| cached.plugin.clone())
+    });
+    if let Some(plugin) = fresh {
+        return Ok(plugin);
+    }
+
+    let bytes = match source.fetch(env).await {
+        Ok(bytes) => bytes,
+        Err(e) => match cached(source) {
+            Some(plugin) => {
+                worker::console_warn!("Keeping the loaded {}: {}", key, e);
+                return Ok(plugin);
+            },
+            None => return Err(format!("{}: {}", key, e)),
+        },
+    };
+    let digest = Sha256::digest(&bytes).to_vec();
+    let unchanged = PLUGINS.with(|plugins| {
+        plugins
+            .borrow()
+            .get(&key)
+            .filter(|cached| cached.digest == digest && cached.plugin.limits == limits)
+            .map(|cached| cached.plugin.clone())
+    });
+    let plugin = match unchanged {
+        Some(plugin) => plugin,
+        None => Rc::new(Plugin::new(&key, &bytes, limits).map_err(|e| format!("{}: {}", key, e))?),
+    };
+    PLUGINS.with(|plugins| {
+        plugins.borrow_mut().insert(key, Cached { plugin: plugin.clone(), fetched_at: now, digest })
+    });
+    Ok(plugin)
+}
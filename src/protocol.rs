@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// Protocol versions the server speaks, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-11-25", "2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Offered in `initialize` when the client asks for a version we do not
+/// speak, and sent to upstream servers.
+pub const LATEST_PROTOCOL_VERSION: &str = SUPPORTED_PROTOCOL_VERSIONS[0];
+
+/// Assumed for requests with neither a session nor an
+/// `MCP-Protocol-Version` header, as the spec asks.
+pub const DEFAULT_PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Parts of the protocol that older versions lack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// The `completions` capability; `completion/complete` itself is older.
+    Completions,
+    /// `elicitation/create`.
+    Elicitation,
+    /// `structuredContent` in tool results.
+    StructuredContent,
+    /// `serverInfo.title`.
+    Title,
+    /// `serverInfo.icons` and `serverInfo.websiteUrl`.
+    Icons,
+}
+
+impl Feature {
+    fn since(self) -> &'static str {
+        match self {
+            Feature::Completions => "2025-03-26",
+            Feature::Elicitation | Feature::StructuredContent | Feature::Title => "2025-06-18",
+            Feature::Icons => "2025-11-25",
+        }
+    }
+}
+
+/// The protocol version agreed with the client in `initialize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolVersion(&'static str);
+
+impl ProtocolVersion {
+    /// `version` if the server speaks it.
+    pub fn parse(version: &str) -> Option<Self> {
+        SUPPORTED_PROTOCOL_VERSIONS
+            .iter()
+            .find(|supported| **supported == version)
+            .copied()
+            .map(Self)
+    }
+
+    /// [`DEFAULT_PROTOCOL_VERSION`], for clients that never said.
+    pub fn assumed() -> Self {
+        Self(DEFAULT_PROTOCOL_VERSION)
+    }
+
+    /// The client's version when we speak it, otherwise our latest; the
+    /// client disconnects if it cannot speak that.
+    pub fn negotiate(requested: Option<&str>) -> Self {
+        requested.and_then(Self::parse).unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    /// Versions are dates, so they order as strings.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.0 >= feature.since()
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self(LATEST_PROTOCOL_VERSION)
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
@@ -0,0 +1,379 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::auth::Principal;
+use crate::session::Session;
+
+/// KV rejects expirations shorter than a minute.
+const MIN_KV_TTL_SECS: u64 = 60;
+
+/// How often the memory store drops buckets that have refilled.
+const SWEEP_INTERVAL_MS: u64 = 60_000;
+
+/// A token bucket: up to `capacity` calls at once, refilled continuously so
+/// that `capacity` calls are available again after `period_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_secs: u64,
+}
+
+impl RateLimit {
+    pub fn new(capacity: u32, period_secs: u64) -> Self {
+        Self { capacity, period_secs }
+    }
+
+    /// Parses `N/unit`, where unit is `s`, `min`, `h` or `day` (e.g. `60/min`).
+    pub fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let (count, unit) = spec
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("Invalid rate limit {:?}, expected N/unit", spec))?;
+        let capacity = match count.trim().parse() {
+            Ok(0) => return Err(format!("Invalid rate limit {:?}, it must allow at least one call", spec)),
+            Ok(capacity) => capacity,
+            Err(_) => return Err(format!("Invalid rate limit count {:?}", count)),
+        };
+        let period_secs = match unit.trim() {
+            "s" | "sec" | "second" => 1,
+            "m" | "min" | "minute" => 60,
+            "h" | "hour" => 3600,
+            "d" | "day" => 86400,
+            unit => return Err(format!("Invalid rate limit unit {:?}", unit)),
+        };
+        Ok(Self::new(capacity, period_secs))
+    }
+
+    fn refill_per_ms(&self) -> f64 {
+        self.capacity as f64 / (self.period_secs as f64 * 1000.0)
+    }
+
+    /// When `bucket` will be full again, after which forgetting it changes
+    /// nothing.
+    fn full_at_ms(&self, bucket: &Bucket) -> u64 {
+        let missing = self.capacity as f64 - bucket.tokens;
+        bucket.updated_ms + (missing / self.refill_per_ms()).ceil() as u64
+    }
+
+    /// Refills `bucket` up to `now_ms` and takes one token if available.
+    pub fn take(&self, bucket: Option<Bucket>, now_ms: u64) -> (Bucket, Decision) {
+        let capacity = self.capacity as f64;
+        let tokens = match bucket {
+            Some(bucket) => {
+                let elapsed = now_ms.saturating_sub(bucket.updated_ms) as f64;
+                (bucket.tokens + elapsed * self.refill_per_ms()).min(capacity)
+            },
+            None => capacity,
+        };
+
+        if tokens >= 1.0 {
+            let bucket = Bucket { tokens: tokens - 1.0, updated_ms: now_ms };
+            (bucket, Decision::Allowed { remaining: bucket.tokens as u32 })
+        } else {
+            let retry_after_ms = ((1.0 - tokens) / self.refill_per_ms()).ceil() as u64;
+            (Bucket { tokens, updated_ms: now_ms }, Decision::Limited { retry_after_ms })
+        }
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period_secs {
+            1 => write!(f, "{}/s", self.capacity),
+            60 => write!(f, "{}/min", self.capacity),
+            3600 => write!(f, "{}/h", self.capacity),
+            86400 => write!(f, "{}/day", self.capacity),
+            secs => write!(f, "{} per {}s", self.capacity, secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Allowed { remaining: u32 },
+    Limited { retry_after_ms: u64 },
+}
+
+/// Applies [`RateLimit::take`] to each stored bucket. The new buckets are
+/// returned only if every limit allowed the call, so that a call refused by
+/// one limit costs nothing from the others.
+pub fn take_all(limits: &[(String, RateLimit)], stored: &[Option<Bucket>], now_ms: u64) -> (Vec<Decision>, Option<Vec<Bucket>>) {
+    let (buckets, decisions): (Vec<Bucket>, Vec<Decision>) = limits
+        .iter()
+        .zip(stored)
+        .map(|((_, limit), bucket)| limit.take(*bucket, now_ms))
+        .unzip();
+    let allowed = decisions.iter().all(|decision| matches!(decision, Decision::Allowed { .. }));
+    (decisions, allowed.then_some(buckets))
+}
+
+/// Where buckets live. Implementations must apply [`take_all`] to the stored
+/// bucket of each key and persist the buckets it returns, if any.
+#[async_trait(?Send)]
+pub trait RateLimitStore {
+    async fn take(&self, limits: &[(String, RateLimit)], now_ms: u64) -> Result<Vec<Decision>>;
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    /// Each bucket with the time it is full again.
+    entries: HashMap<String, (Bucket, u64)>,
+    swept_ms: u64,
+}
+
+/// Buckets held in memory. [`MemoryRateLimitStore::shared`] is per isolate,
+/// so limits are approximate when a Worker runs in many isolates at once.
+/// Buckets that have refilled are dropped, so idle clients take no memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Rc<RefCell<Buckets>>,
+}
+
+thread_local! {
+    static SHARED_BUCKETS: Rc<RefCell<Buckets>> = Rc::default();
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shared() -> Self {
+        Self {
+            buckets: SHARED_BUCKETS.with(Rc::clone),
+        }
+    }
+
+    /// How many buckets are held.
+    pub fn len(&self) -> usize {
+        self.buckets.borrow().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait(?Send)]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take(&self, limits: &[(String, RateLimit)], now_ms: u64) -> Result<Vec<Decision>> {
+        let mut buckets = self.buckets.borrow_mut();
+        if now_ms >= buckets.swept_ms + SWEEP_INTERVAL_MS {
+            buckets.entries.retain(|_, (_, full_at_ms)| *full_at_ms > now_ms);
+            buckets.swept_ms = now_ms;
+        }
+        let stored: Vec<Option<Bucket>> = limits
+            .iter()
+            .map(|(key, _)| buckets.entries.get(key).map(|(bucket, _)| *bucket))
+            .collect();
+        let (decisions, taken) = take_all(limits, &stored, now_ms);
+        for ((key, limit), bucket) in limits.iter().zip(taken.into_iter().flatten()) {
+            buckets.entries.insert(key.clone(), (bucket, limit.full_at_ms(&bucket)));
+        }
+        Ok(decisions)
+    }
+}
+
+/// Buckets in a KV namespace. KV is eventually consistent, so concurrent
+/// calls from different locations can briefly exceed the limit.
+pub struct KvRateLimitStore {
+    kv: kv::KvStore,
+}
+
+impl KvRateLimitStore {
+    pub fn new(kv: kv::KvStore) -> Self {
+        Self { kv }
+    }
+}
+
+#[async_trait(?Send)]
+impl RateLimitStore for KvRateLimitStore {
+    async fn take(&self, limits: &[(String, RateLimit)], now_ms: u64) -> Result<Vec<Decision>> {
+        let mut stored = Vec::new();
+        for (key, _) in limits {
+            stored.push(self.kv.get(&format!("ratelimit:{}", key)).json::<Bucket>().await?);
+        }
+        let (decisions, taken) = take_all(limits, &stored, now_ms);
+        for ((key, limit), bucket) in limits.iter().zip(taken.into_iter().flatten()) {
+            self.kv
+                .put(&format!("ratelimit:{}", key), serde_json::to_string(&bucket)?)?
+                .expiration_ttl(limit.period_secs.max(MIN_KV_TTL_SECS))
+                .execute()
+                .await?;
+        }
+        Ok(decisions)
+    }
+}
+
+/// What identifies a client for rate limiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyStrategy {
+    /// The authenticated subject, falling back to the client IP.
+    #[default]
+    Principal,
+    Ip,
+    /// The verified `Mcp-Session-Id`, falling back to the client IP. Any
+    /// client can start new sessions, so this spreads limits over a
+    /// client's sessions rather than capping the client; use `Principal` or
+    /// `Ip` for a quota.
+    Session,
+}
+
+impl std::str::FromStr for KeyStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "principal" => Ok(KeyStrategy::Principal),
+            "ip" => Ok(KeyStrategy::Ip),
+            "session" => Ok(KeyStrategy::Session),
+            other => Err(format!("Unknown rate limit key {:?}, expected principal, ip or session", other)),
+        }
+    }
+}
+
+impl KeyStrategy {
+    pub fn client_key(&self, principal: Option<&Principal>, ip: Option<&str>, session_id: Option<&str>) -> String {
+        let preferred = match self {
+            KeyStrategy::Principal => principal.map(|p| format!("principal:{}", p.subject)),
+            KeyStrategy::Session => session_id.map(|id| format!("session:{}", id)),
+            KeyStrategy::Ip => None,
+        };
+        preferred
+            .or_else(|| ip.map(|ip| format!("ip:{}", ip)))
+            .unwrap_or_else(|| "anonymous".to_string())
+    }
+
+    /// Client key for a Worker request, using `CF-Connecting-IP`. Only a
+    /// session that passed [`SessionSigner::verify`](crate::session::SessionSigner::verify)
+    /// counts, which needs `MCP_SESSION_SECRET` to rule out made-up ids; a
+    /// client can still get a new bucket with each `initialize`.
+    pub fn request_key(&self, req: &Request, principal: Option<&Principal>, session: Option<&Session>) -> String {
+        let ip = req.headers().get("CF-Connecting-IP").ok().flatten();
+        self.client_key(principal, ip.as_deref(), session.map(|session| session.id.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitConfig {
+    /// Applies to all tool calls from one client combined.
+    pub global: Option<RateLimit>,
+    /// Applies to calls of one tool from one client.
+    pub tools: HashMap<String, RateLimit>,
+    pub key: KeyStrategy,
+}
+
+impl RateLimitConfig {
+    /// Parses a comma-separated list of `tool=N/unit` entries.
+    pub fn with_tool_limits(mut self, spec: &str) -> std::result::Result<Self, String> {
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (tool, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid tool rate limit {:?}, expected tool=N/unit", entry))?;
+            self.tools.insert(tool.trim().to_string(), RateLimit::parse(limit)?);
+        }
+        Ok(self)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.global.is_some() || !self.tools.is_empty()
+    }
+}
+
+/// A rejected call, with how long the client should wait before retrying.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimited {
+    /// `None` for the global limit, otherwise the tool's name.
+    pub tool: Option<String>,
+    pub limit: RateLimit,
+    pub retry_after_ms: u64,
+}
+
+impl RateLimited {
+    /// Whole seconds, rounded up, for `Retry-After`.
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after_ms.div_ceil(1000)
+    }
+
+    pub fn data(&self) -> serde_json::Value {
+        serde_json::json!({
+            "tool": self.tool,
+            "limit": self.limit.to_string(),
+            "retryAfter": self.retry_after_secs(),
+            "retryAfterMs": self.retry_after_ms,
+        })
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tool {
+            Some(tool) => write!(f, "Rate limit of {} exceeded for tool '{}'", self.limit, tool)?,
+            None => write!(f, "Rate limit of {} exceeded", self.limit)?,
+        }
+        write!(f, "; retry after {}s", self.retry_after_secs())
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, store: impl RateLimitStore + 'static) -> Self {
+        Self {
+            config,
+            store: Box::new(store),
+        }
+    }
+
+    /// Uses the `RATE_LIMIT_KV` namespace when bound, otherwise per-isolate
+    /// memory.
+    pub fn from_env(env: &Env, config: RateLimitConfig) -> Self {
+        match env.kv("RATE_LIMIT_KV") {
+            Ok(kv) => Self::new(config, KvRateLimitStore::new(kv)),
+            Err(_) => Self::new(config, MemoryRateLimitStore::shared()),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Takes a token from the client's global bucket and from its bucket for
+    /// `tool`, or from neither if either is empty.
+    pub async fn check_tool(&self, client: &str, tool: &str, now_ms: u64) -> Result<std::result::Result<(), RateLimited>> {
+        let buckets = [
+            (None, self.config.global, client.to_string()),
+            (Some(tool), self.config.tools.get(tool).copied(), format!("{}:tool:{}", client, tool)),
+        ];
+        let (scopes, limits): (Vec<Option<&str>>, Vec<(String, RateLimit)>) = buckets
+            .into_iter()
+            .filter_map(|(scope, limit, key)| Some((scope, (key, limit?))))
+            .unzip();
+        let decisions = self.store.take(&limits, now_ms).await?;
+        for ((scope, (_, limit)), decision) in scopes.into_iter().zip(limits).zip(decisions) {
+            if let Decision::Limited { retry_after_ms } = decision {
+                return Ok(Err(RateLimited {
+                    tool: scope.map(str::to_string),
+                    limit,
+                    retry_after_ms,
+                }));
+            }
+        }
+        Ok(Ok(()))
+    }
+}
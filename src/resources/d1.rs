@@ -0,0 +1,175 @@
+use std::rc::Rc;
+
+use async_trait::async_trait;
+
+use super::{Resource, ResourceContents, ResourceError, ResourcePage, ResourceProvider, ResourceTemplate};
+use crate::sql::SqlDatabase;
+
+const TABLES: &str = "SELECT name, type, sql FROM sqlite_master \
+    WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' AND name NOT LIKE '\\_cf\\_%' ESCAPE '\\' \
+    ORDER BY name";
+
+const COLUMNS: &str = "SELECT name, type, \"notnull\", pk, dflt_value FROM pragma_table_info(?)";
+
+/// Publishes a database's schema: `d1://<name>/schema` for every table and
+/// view, and `d1://<name>/tables/<table>` for one, with the table name
+/// percent-encoded.
+pub struct SchemaProvider {
+    database: Rc<SqlDatabase>,
+}
+
+impl SchemaProvider {
+    pub fn new(database: Rc<SqlDatabase>) -> Self {
+        Self { database }
+    }
+
+    fn base(&self) -> String {
+        format!("d1://{}/", self.database.name())
+    }
+
+    /// Tables and views with their `CREATE` statements, by name.
+    async fn tables(&self, name: Option<&str>) -> Result<Vec<serde_json::Value>, ResourceError> {
+        let tables = self.database.query_unchecked(TABLES, &[]).await.map_err(failed)?;
+        let mut schema = Vec::new();
+        for row in tables.rows {
+            let table = row[0].as_str().unwrap_or_default();
+            if name.is_some_and(|name| name != table) {
+                continue;
+            }
+            let columns = self
+                .database
+                .query_unchecked(COLUMNS, &[serde_json::json!(table)])
+                .await
+                .map_err(failed)?;
+            let columns: Vec<serde_json::Value> = columns
+                .rows
+                .iter()
+                .map(|column| {
+                    serde_json::json!({
+                        "name": column[0],
+                        "type": column[1],
+                        "notNull": column[2].as_i64() == Some(1),
+                        "primaryKey": column[3].as_i64().is_some_and(|pk| pk > 0),
+                        "default": column[4],
+                    })
+                })
+                .collect();
+            schema.push(serde_json::json!({
+                "name": table,
+                "type": row[1],
+                "sql": row[2],
+                "columns": columns,
+            }));
+        }
+        Ok(schema)
+    }
+}
+
+#[async_trait(?Send)]
+impl ResourceProvider for SchemaProvider {
+    fn handles(&self, uri: &str) -> bool {
+        match uri.strip_prefix(&self.base()) {
+            Some("schema") => true,
+            Some(path) => path.strip_prefix("tables/").and_then(decode_table).is_some_and(|table| !table.is_empty()),
+            None => false,
+        }
+    }
+
+    /// Everything fits on one page.
+    async fn list(&self, _cursor: Option<&str>) -> Result<ResourcePage, ResourceError> {
+        let tables = self.database.query_unchecked(TABLES, &[]).await.map_err(failed)?;
+        let schema = Resource {
+            description: Some("Every table and view, with columns".to_string()),
+            mime_type: Some("application/json".to_string()),
+            ..Resource::new(&format!("{}schema", self.base()), &format!("{} schema", self.database.name()))
+        };
+        let tables = tables.rows.iter().filter_map(|row| row[0].as_str()).map(|table| Resource {
+            mime_type: Some("application/json".to_string()),
+            ..Resource::new(&format!("{}tables/{}", self.base(), encode_table(table)), table)
+        });
+        Ok(ResourcePage {
+            resources: std::iter::once(schema).chain(tables).collect(),
+            next_cursor: None,
+        })
+    }
+
+    async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ResourceError> {
+        let path = uri.strip_prefix(&self.base()).unwrap_or_default();
+        let schema = match path.strip_prefix("tables/") {
+            Some(table) => self
+                .tables(Some(&decode_table(table).ok_or_else(|| ResourceError::NotFound(uri.to_string()))?))
+                .await?
+                .pop()
+                .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?,
+            None if path == "schema" => serde_json::Value::Array(self.tables(None).await?),
+            None => return Err(ResourceError::NotFound(uri.to_string())),
+        };
+        let text = serde_json::to_string_pretty(&schema).unwrap();
+        Ok(vec![ResourceContents::text(uri, Some("application/json"), text)])
+    }
+
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        vec![ResourceTemplate {
+            uri_template: format!("{}tables/{}", self.base(), "{table}"),
+            name: format!("{} tables", self.database.name()),
+            description: Some("A table's columns and CREATE statement".to_string()),
+            mime_type: Some("application/json".to_string()),
+        }]
+    }
+
+    /// Table and view names starting with `value`.
+    async fn complete(
+        &self,
+        _template: &str,
+        argument: &str,
+        value: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, ResourceError> {
+        if argument != "table" {
+            return Ok(Vec::new());
+        }
+        let tables = self.database.query_unchecked(TABLES, &[]).await.map_err(failed)?;
+        Ok(tables
+            .rows
+            .iter()
+            .filter_map(|row| row[0].as_str())
+            .filter(|table| table.starts_with(value))
+            .take(limit)
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, so names with
+/// spaces, slashes or `%` stay one path segment.
+fn encode_table(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The table name in a URI path segment; `None` for malformed escapes.
+fn decode_table(segment: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn failed(e: worker::Error) -> ResourceError {
+    ResourceError::Failed(e.to_string())
+}
@@ -0,0 +1,304 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use worker::*;
+
+use crate::tools::ToolAccess;
+
+use super::{Resource, ResourceContents, ResourceError, ResourcePage, ResourceProvider, ResourceTemplate};
+
+/// Keys per `resources/list` page; KV allows up to 1000.
+pub const PAGE_SIZE: u64 = 100;
+
+/// Which KV namespace to serve as resources, from `MCP_KV_RESOURCES` and
+/// friends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvResourceConfig {
+    /// The KV binding in `wrangler.toml`.
+    pub binding: String,
+    /// The URI authority, as in `kv://<namespace>/<key>`.
+    pub namespace: String,
+    /// Only keys starting with this are exposed.
+    pub prefix: String,
+    /// Whether the `kv_write` and `kv_delete` tools are registered.
+    pub writable: bool,
+    /// Required to see, read and subscribe to the resources.
+    pub access: ToolAccess,
+}
+
+impl KvResourceConfig {
+    /// Serves the whole namespace read-only, named after the binding.
+    pub fn new(binding: &str) -> Self {
+        Self {
+            binding: binding.to_string(),
+            namespace: binding.to_ascii_lowercase().replace('_', "-"),
+            prefix: String::new(),
+            writable: false,
+            access: ToolAccess::default(),
+        }
+    }
+}
+
+/// A key as listed, with the metadata stored alongside its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvKey {
+    pub name: String,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A stored value and its metadata.
+pub type KvEntry = (Vec<u8>, Option<serde_json::Value>);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KvPage {
+    pub keys: Vec<KvKey>,
+    /// Where the next page starts; `None` on the last page.
+    pub cursor: Option<String>,
+}
+
+/// The KV operations the provider needs, so it can run against Workers KV
+/// or, locally and in tests, an in-memory map.
+#[async_trait(?Send)]
+pub trait KvBackend {
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u64) -> Result<KvPage>;
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>>;
+    async fn put(&self, key: &str, value: &[u8], metadata: Option<serde_json::Value>) -> Result<()>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+pub struct WorkersKv {
+    kv: kv::KvStore,
+}
+
+impl WorkersKv {
+    pub fn new(kv: kv::KvStore) -> Self {
+        Self { kv }
+    }
+}
+
+#[async_trait(?Send)]
+impl KvBackend for WorkersKv {
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u64) -> Result<KvPage> {
+        let mut list = self.kv.list().prefix(prefix.to_string()).limit(limit);
+        if let Some(cursor) = cursor {
+            list = list.cursor(cursor.to_string());
+        }
+        let response = list.execute().await?;
+        Ok(KvPage {
+            keys: response
+                .keys
+                .into_iter()
+                .map(|key| KvKey {
+                    name: key.name,
+                    metadata: key.metadata,
+                })
+                .collect(),
+            cursor: response.cursor.filter(|_| !response.list_complete),
+        })
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>> {
+        let (value, metadata) = self.kv.get(key).bytes_with_metadata::<serde_json::Value>().await?;
+        Ok(value.map(|value| (value, metadata)))
+    }
+
+    async fn put(&self, key: &str, value: &[u8], metadata: Option<serde_json::Value>) -> Result<()> {
+        let mut put = self.kv.put_bytes(key, value)?;
+        if let Some(metadata) = metadata {
+            put = put.metadata(metadata)?;
+        }
+        Ok(put.execute().await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        Ok(self.kv.delete(key).await?)
+    }
+}
+
+/// A stand-in for Workers KV, for local runs and tests. Clones share the
+/// same data.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryKv {
+    entries: Rc<RefCell<BTreeMap<String, KvEntry>>>,
+}
+
+impl MemoryKv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait(?Send)]
+impl KvBackend for MemoryKv {
+    /// Keys in order; the cursor is the last key of the previous page.
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u64) -> Result<KvPage> {
+        let entries = self.entries.borrow();
+        let mut keys: Vec<KvKey> = entries
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix) && cursor.is_none_or(|after| name.as_str() > after))
+            .take(limit as usize + 1)
+            .map(|(name, (_, metadata))| KvKey {
+                name: name.clone(),
+                metadata: metadata.clone(),
+            })
+            .collect();
+        let more = keys.len() > limit as usize;
+        keys.truncate(limit as usize);
+        Ok(KvPage {
+            cursor: more.then(|| keys.last().map(|key| key.name.clone())).flatten(),
+            keys,
+        })
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>> {
+        Ok(self.entries.borrow().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: &[u8], metadata: Option<serde_json::Value>) -> Result<()> {
+        self.entries.borrow_mut().insert(key.to_string(), (value.to_vec(), metadata));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.entries.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+/// Exposes the KV keys under `prefix` as `kv://<namespace>/<key>`, with the
+/// prefix left out of the URI. A `mimeType` (or `contentType`) in a key's
+/// metadata is passed on to clients. Read-only unless made `writable`.
+pub struct KvResourceProvider {
+    namespace: String,
+    prefix: String,
+    backend: Box<dyn KvBackend>,
+    writable: bool,
+}
+
+impl KvResourceProvider {
+    pub fn new(namespace: &str, prefix: &str, backend: impl KvBackend + 'static) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            prefix: prefix.to_string(),
+            backend: Box::new(backend),
+            writable: false,
+        }
+    }
+
+    pub fn writable(mut self, writable: bool) -> Self {
+        self.writable = writable;
+        self
+    }
+
+    pub fn uri(&self, key: &str) -> String {
+        format!("kv://{}/{}", self.namespace, key)
+    }
+
+    /// The KV key behind `uri`, if this provider owns it.
+    fn key(&self, uri: &str) -> Option<String> {
+        let key = uri.strip_prefix("kv://")?.strip_prefix(&self.namespace)?.strip_prefix('/')?;
+        (!key.is_empty()).then(|| format!("{}{}", self.prefix, key))
+    }
+
+    fn writable_key(&self, uri: &str) -> std::result::Result<String, ResourceError> {
+        let key = self.key(uri).ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+        match self.writable {
+            true => Ok(key),
+            false => Err(ResourceError::ReadOnly(uri.to_string())),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ResourceProvider for KvResourceProvider {
+    fn handles(&self, uri: &str) -> bool {
+        self.key(uri).is_some()
+    }
+
+    async fn list(&self, cursor: Option<&str>) -> std::result::Result<ResourcePage, ResourceError> {
+        let page = self.backend.list(&self.prefix, cursor, PAGE_SIZE).await.map_err(failed)?;
+        let resources = page
+            .keys
+            .into_iter()
+            .map(|key| {
+                let name = key.name.strip_prefix(&self.prefix).unwrap_or(&key.name).to_string();
+                Resource {
+                    mime_type: key.metadata.as_ref().and_then(mime_type),
+                    ..Resource::new(&self.uri(&name), &name)
+                }
+            })
+            .collect();
+        Ok(ResourcePage {
+            resources,
+            next_cursor: page.cursor,
+        })
+    }
+
+    async fn read(&self, uri: &str) -> std::result::Result<Vec<ResourceContents>, ResourceError> {
+        let key = self.key(uri).ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+        let (value, metadata) = self
+            .backend
+            .get(&key)
+            .await
+            .map_err(failed)?
+            .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+        let mime_type = metadata.as_ref().and_then(mime_type);
+        Ok(vec![ResourceContents::from_bytes(uri, mime_type.as_deref(), value)])
+    }
+
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        vec![ResourceTemplate {
+            uri_template: format!("kv://{}/{}", self.namespace, "{key}"),
+            name: format!("{} KV keys", self.namespace),
+            description: None,
+            mime_type: None,
+        }]
+    }
+
+    /// Keys under the prefix that start with `value`, in key order.
+    async fn complete(
+        &self,
+        _template: &str,
+        argument: &str,
+        value: &str,
+        limit: usize,
+    ) -> std::result::Result<Vec<String>, ResourceError> {
+        if argument != "key" {
+            return Ok(Vec::new());
+        }
+        let prefix = format!("{}{}", self.prefix, value);
+        let page = self.backend.list(&prefix, None, limit as u64).await.map_err(failed)?;
+        Ok(page
+            .keys
+            .into_iter()
+            .map(|key| key.name.strip_prefix(&self.prefix).unwrap_or(&key.name).to_string())
+            .collect())
+    }
+
+    /// Stores `value` under the key for `uri`, recording its MIME type in
+    /// the key's metadata.
+    async fn write(&self, uri: &str, value: &[u8], mime_type: Option<&str>) -> std::result::Result<(), ResourceError> {
+        let key = self.writable_key(uri)?;
+        let metadata = mime_type.map(|mime_type| serde_json::json!({ "mimeType": mime_type }));
+        self.backend.put(&key, value, metadata).await.map_err(failed)
+    }
+
+    async fn delete(&self, uri: &str) -> std::result::Result<(), ResourceError> {
+        let key = self.writable_key(uri)?;
+        self.backend.delete(&key).await.map_err(failed)
+    }
+}
+
+fn mime_type(metadata: &serde_json::Value) -> Option<String> {
+    metadata
+        .get("mimeType")
+        .or_else(|| metadata.get("contentType"))
+        .and_then(|mime_type| mime_type.as_str())
+        .map(str::to_string)
+}
+
+fn failed(e: Error) -> ResourceError {
+    ResourceError::Failed(e.to_string())
+}
@@ -0,0 +1,373 @@
+use std::fmt;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use worker::Env;
+
+use crate::auth::Principal;
+use crate::config::ServerConfig;
+use crate::sql::SqlDatabase;
+use crate::tools::ToolAccess;
+
+pub mod d1;
+pub mod kv;
+pub mod r2;
+pub mod subscriptions;
+
+/// A resource as listed by `resources/list`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Size in bytes, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl Resource {
+    pub fn new(uri: &str, name: &str) -> Self {
+        Self {
+            uri: uri.to_string(),
+            name: name.to_string(),
+            title: None,
+            description: None,
+            mime_type: None,
+            size: None,
+        }
+    }
+}
+
+/// A family of resources, e.g. `kv://cache/{key}`, for `resources/templates/list`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// What `resources/read` returns: text, or base64 for binary data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+impl ResourceContents {
+    pub fn text(uri: &str, mime_type: Option<&str>, text: String) -> Self {
+        Self {
+            uri: uri.to_string(),
+            mime_type: mime_type.map(str::to_string),
+            text: Some(text),
+            blob: None,
+        }
+    }
+
+    pub fn blob(uri: &str, mime_type: Option<&str>, bytes: &[u8]) -> Self {
+        use base64::Engine;
+        Self {
+            uri: uri.to_string(),
+            mime_type: mime_type.map(str::to_string),
+            text: None,
+            blob: Some(base64::engine::general_purpose::STANDARD.encode(bytes)),
+        }
+    }
+
+    /// Text for text, JSON and XML types, and for untyped values that are
+    /// valid UTF-8; a blob otherwise.
+    pub fn from_bytes(uri: &str, mime_type: Option<&str>, bytes: Vec<u8>) -> Self {
+        if mime_type.is_some_and(|mime_type| !is_text(mime_type)) {
+            return Self::blob(uri, mime_type, &bytes);
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::text(uri, mime_type, text),
+            Err(e) => Self::blob(uri, mime_type, e.as_bytes()),
+        }
+    }
+}
+
+fn is_text(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/")
+        || essence.ends_with("json")
+        || essence.ends_with("+xml")
+        || essence == "application/xml"
+        || essence == "application/javascript"
+}
+
+/// One page of `resources/list`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePage {
+    pub resources: Vec<Resource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceError {
+    NotFound(String),
+    /// The cursor did not come from this server.
+    InvalidCursor(String),
+    /// The provider does not accept writes to this URI.
+    ReadOnly(String),
+    /// The caller lacks the scopes or roles the provider requires.
+    Forbidden(String, ToolAccess),
+    /// The resource is larger than the provider reads, in bytes.
+    TooLarge(String, u64),
+    /// The backing store failed.
+    Failed(String),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::NotFound(uri) => write!(f, "Resource not found: {}", uri),
+            ResourceError::InvalidCursor(cursor) => write!(f, "Invalid cursor: {}", cursor),
+            ResourceError::ReadOnly(uri) => write!(f, "Resource is read-only: {}", uri),
+            ResourceError::Forbidden(uri, access) => write!(f, "Forbidden: resource '{}' requires{}", uri, access),
+            ResourceError::TooLarge(uri, limit) => write!(f, "Resource is larger than {} bytes: {}", limit, uri),
+            ResourceError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A source of resources, such as a KV namespace. Each provider owns the
+/// URIs for which `handles` is true.
+#[async_trait(?Send)]
+pub trait ResourceProvider {
+    fn handles(&self, uri: &str) -> bool;
+
+    /// A page of resources, continuing from `cursor`.
+    async fn list(&self, cursor: Option<&str>) -> Result<ResourcePage, ResourceError>;
+
+    async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ResourceError>;
+
+    /// A page of the resources whose URIs start with `uri_prefix`, for tools
+    /// that browse part of a provider. The default filters `list`, so pages
+    /// may come back short or empty; stores that list by prefix override it.
+    async fn list_prefix(&self, uri_prefix: &str, cursor: Option<&str>) -> Result<ResourcePage, ResourceError> {
+        let page = self.list(cursor).await?;
+        Ok(ResourcePage {
+            resources: page.resources.into_iter().filter(|resource| resource.uri.starts_with(uri_prefix)).collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        Vec::new()
+    }
+
+    /// Up to `limit` values starting with `value` for the variable
+    /// `argument` of one of this provider's `templates`, for
+    /// `completion/complete`. The default suggests none.
+    async fn complete(
+        &self,
+        _template: &str,
+        _argument: &str,
+        _value: &str,
+        _limit: usize,
+    ) -> Result<Vec<String>, ResourceError> {
+        Ok(Vec::new())
+    }
+
+    /// Creates or replaces the resource at `uri`. Providers are read-only
+    /// unless they override this and `delete`.
+    async fn write(&self, uri: &str, _value: &[u8], _mime_type: Option<&str>) -> Result<(), ResourceError> {
+        Err(ResourceError::ReadOnly(uri.to_string()))
+    }
+
+    async fn delete(&self, uri: &str) -> Result<(), ResourceError> {
+        Err(ResourceError::ReadOnly(uri.to_string()))
+    }
+}
+
+/// Resource providers, in registration order; used for `resources/*`.
+#[derive(Default)]
+pub struct ResourceRegistry {
+    providers: Vec<(Box<dyn ResourceProvider>, ToolAccess)>,
+}
+
+impl fmt::Debug for ResourceRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResourceRegistry")
+            .field("providers", &self.providers.len())
+            .finish()
+    }
+}
+
+impl ResourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_provider(&mut self, provider: impl ResourceProvider + 'static) {
+        self.register_provider_with_access(provider, ToolAccess::default());
+    }
+
+    /// Registers a provider whose resources only callers with the given
+    /// scopes or roles can list, read and subscribe to.
+    pub fn register_provider_with_access(&mut self, provider: impl ResourceProvider + 'static, access: ToolAccess) {
+        self.providers.push((Box::new(provider), access));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Pages through each provider the caller may use in turn. Cursors are
+    /// `<provider index>:<provider cursor>`.
+    pub async fn list(&self, cursor: Option<&str>, principal: Option<&Principal>) -> Result<ResourcePage, ResourceError> {
+        let (index, inner) = match cursor {
+            None => (0, None),
+            Some(cursor) => {
+                let invalid = || ResourceError::InvalidCursor(cursor.to_string());
+                let (index, inner) = cursor.split_once(':').ok_or_else(invalid)?;
+                let index: usize = index.parse().map_err(|_| invalid())?;
+                if index >= self.providers.len() {
+                    return Err(invalid());
+                }
+                (index, Some(inner).filter(|inner| !inner.is_empty()))
+            },
+        };
+        let allowed = |next: &usize| self.providers[*next].1.allows(principal);
+        let Some(next) = (index..self.providers.len()).find(allowed) else {
+            return Ok(ResourcePage::default());
+        };
+        // The cursor of a provider the caller may not use means nothing here
+        let (index, inner) = (next, inner.filter(|_| next == index));
+
+        let page = self.providers[index].0.list(inner).await?;
+        let next_cursor = match page.next_cursor {
+            Some(next) => Some(format!("{}:{}", index, next)),
+            None => (index + 1..self.providers.len()).find(allowed).map(|next| format!("{}:", next)),
+        };
+        Ok(ResourcePage {
+            resources: page.resources,
+            next_cursor,
+        })
+    }
+
+    pub async fn read(&self, uri: &str, principal: Option<&Principal>) -> Result<Vec<ResourceContents>, ResourceError> {
+        self.provider(uri, principal)?.read(uri).await
+    }
+
+    /// Pages through the resources under `uri_prefix`, which must itself be
+    /// a URI some provider handles. Cursors come from that provider.
+    pub async fn list_prefix(&self, uri_prefix: &str, cursor: Option<&str>, principal: Option<&Principal>) -> Result<ResourcePage, ResourceError> {
+        self.provider(uri_prefix, principal)?.list_prefix(uri_prefix, cursor).await
+    }
+
+    /// Writes through the owning provider. Callers notify subscribers.
+    pub async fn write(&self, uri: &str, value: &[u8], mime_type: Option<&str>, principal: Option<&Principal>) -> Result<(), ResourceError> {
+        self.provider(uri, principal)?.write(uri, value, mime_type).await
+    }
+
+    pub async fn delete(&self, uri: &str, principal: Option<&Principal>) -> Result<(), ResourceError> {
+        self.provider(uri, principal)?.delete(uri).await
+    }
+
+    /// The templates of the providers the caller may use.
+    pub fn templates(&self, principal: Option<&Principal>) -> Vec<ResourceTemplate> {
+        self.providers
+            .iter()
+            .filter(|(_, access)| access.allows(principal))
+            .flat_map(|(provider, _)| provider.templates())
+            .collect()
+    }
+
+    /// Values for a template variable from the provider that lists
+    /// `template`, or none if no provider does. Callers check
+    /// [`allows_template`](Self::allows_template) first.
+    pub async fn complete(&self, template: &str, argument: &str, value: &str, limit: usize) -> Result<Vec<String>, ResourceError> {
+        match self.template_provider(template) {
+            Some((provider, _)) => provider.complete(template, argument, value, limit).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Whether the caller may use the provider that lists `template`. A
+    /// template no provider lists, such as one with a hand-registered
+    /// completer, is allowed.
+    pub fn allows_template(&self, template: &str, principal: Option<&Principal>) -> bool {
+        self.template_provider(template)
+            .is_none_or(|(_, access)| access.allows(principal))
+    }
+
+    fn template_provider(&self, template: &str) -> Option<&(Box<dyn ResourceProvider>, ToolAccess)> {
+        self.providers
+            .iter()
+            .find(|(provider, _)| provider.templates().iter().any(|t| t.uri_template == template))
+    }
+
+    /// Whether some provider owns `uri`; only those can be subscribed to.
+    pub fn handles(&self, uri: &str) -> bool {
+        self.providers.iter().any(|(provider, _)| provider.handles(uri))
+    }
+
+    /// Checks that the caller may use the provider of `uri`, as
+    /// [`read`](Self::read), [`write`](Self::write) and the others do. Tools
+    /// that go through them need both their own access and the provider's.
+    pub fn check_access(&self, uri: &str, principal: Option<&Principal>) -> Result<(), ResourceError> {
+        self.provider(uri, principal).map(|_| ())
+    }
+
+    fn provider(&self, uri: &str, principal: Option<&Principal>) -> Result<&dyn ResourceProvider, ResourceError> {
+        let (provider, access) = self
+            .providers
+            .iter()
+            .find(|(provider, _)| provider.handles(uri))
+            .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+        match access.allows(principal) {
+            true => Ok(provider.as_ref()),
+            false => Err(ResourceError::Forbidden(uri.to_string(), access.clone())),
+        }
+    }
+}
+
+/// Registers the providers enabled in `config`: a KV namespace, an R2
+/// bucket or, given the configured database, its schema. Each has access of
+/// its own, `MCP_KV_RESOURCE_ACCESS`, `MCP_R2_RESOURCE_ACCESS` and
+/// `MCP_D1_ACCESS`, apart from that of the tools.
+pub fn register_default_providers(
+    registry: &mut ResourceRegistry,
+    env: &Env,
+    config: &ServerConfig,
+    database: Option<&Rc<SqlDatabase>>,
+) -> worker::Result<()> {
+    if let Some(kv) = &config.kv_resources {
+        let store = kv::WorkersKv::new(env.kv(&kv.binding)?);
+        let provider = kv::KvResourceProvider::new(&kv.namespace, &kv.prefix, store).writable(kv.writable);
+        registry.register_provider_with_access(provider, kv.access.clone());
+    }
+    if let Some(r2) = &config.r2_resources {
+        let store = r2::WorkersR2::new(env.bucket(&r2.binding)?);
+        let provider = r2::R2ResourceProvider::new(&r2.bucket, &r2.prefixes, store)
+            .writable(r2.writable)
+            .max_read_bytes(r2.max_read_bytes);
+        registry.register_provider_with_access(provider, r2.access.clone());
+    }
+    if let Some(database) = database {
+        let access = config.d1.as_ref().map(|d1| d1.access.clone()).unwrap_or_default();
+        registry.register_provider_with_access(d1::SchemaProvider::new(database.clone()), access);
+    }
+    // Register other providers here, e.g. registry.register_provider(MyProvider::new())
+    Ok(())
+}
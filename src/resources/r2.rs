@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use worker::*;
+
+use crate::tools::ToolAccess;
+
+use super::{Resource, ResourceContents, ResourceError, ResourcePage, ResourceProvider, ResourceTemplate};
+
+/// Objects per `resources/list` page; R2 allows up to 1000.
+pub const PAGE_SIZE: u32 = 100;
+
+/// The largest object read in full, well under the Worker's 128 MB memory
+/// limit once base64-encoded.
+pub const DEFAULT_MAX_READ_BYTES: u64 = 8 << 20;
+
+/// Which R2 bucket to serve as resources, from `MCP_R2_RESOURCES` and
+/// friends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct R2ResourceConfig {
+    /// The R2 binding in `wrangler.toml`.
+    pub binding: String,
+    /// The URI authority, as in `r2://<bucket>/<key>`.
+    pub bucket: String,
+    /// Only keys starting with one of these are exposed; empty for all.
+    pub prefixes: Vec<String>,
+    /// Whether the destructive `r2_put` and `r2_delete` tools are registered.
+    pub writable: bool,
+    /// Larger objects are refused rather than read.
+    pub max_read_bytes: u64,
+    /// Required to see, read, subscribe to and change the objects.
+    pub access: ToolAccess,
+}
+
+impl R2ResourceConfig {
+    /// Serves the whole bucket read-only, named after the binding.
+    pub fn new(binding: &str) -> Self {
+        Self {
+            binding: binding.to_string(),
+            bucket: binding.to_ascii_lowercase().replace('_', "-"),
+            prefixes: Vec::new(),
+            writable: false,
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+            access: ToolAccess::default(),
+        }
+    }
+}
+
+/// An object as listed, without its body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectInfo>,
+    /// Where the next page starts; `None` on the last page.
+    pub cursor: Option<String>,
+}
+
+/// The R2 operations the provider needs, so it can run against a bucket
+/// or, locally and in tests, an in-memory map.
+#[async_trait(?Send)]
+pub trait R2Backend {
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<ObjectPage>;
+    /// The object and its body, or no body, without reading it, when the
+    /// object is larger than `max_bytes`.
+    async fn get(&self, key: &str, max_bytes: u64) -> Result<Option<(ObjectInfo, Option<Vec<u8>>)>>;
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: Option<&str>) -> Result<()>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+pub struct WorkersR2 {
+    bucket: Bucket,
+}
+
+impl WorkersR2 {
+    pub fn new(bucket: Bucket) -> Self {
+        Self { bucket }
+    }
+}
+
+fn object_info(object: &Object) -> ObjectInfo {
+    ObjectInfo {
+        key: object.key(),
+        size: object.size(),
+        content_type: object.http_metadata().content_type,
+    }
+}
+
+#[async_trait(?Send)]
+impl R2Backend for WorkersR2 {
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<ObjectPage> {
+        let mut list = self
+            .bucket
+            .list()
+            .prefix(prefix)
+            .limit(limit)
+            .include(vec![Include::HttpMetadata]);
+        if let Some(cursor) = cursor {
+            list = list.cursor(cursor);
+        }
+        let objects = list.execute().await?;
+        Ok(ObjectPage {
+            objects: objects.objects().iter().map(object_info).collect(),
+            cursor: objects.cursor().filter(|_| objects.truncated()),
+        })
+    }
+
+    async fn get(&self, key: &str, max_bytes: u64) -> Result<Option<(ObjectInfo, Option<Vec<u8>>)>> {
+        let Some(object) = self.bucket.get(key).execute().await? else {
+            return Ok(None);
+        };
+        let info = object_info(&object);
+        if info.size > max_bytes {
+            return Ok(Some((info, None)));
+        }
+        let body = match object.body() {
+            Some(body) => body.bytes().await?,
+            None => Vec::new(),
+        };
+        Ok(Some((info, Some(body))))
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: Option<&str>) -> Result<()> {
+        let metadata = HttpMetadata {
+            content_type: content_type.map(str::to_string),
+            ..HttpMetadata::default()
+        };
+        self.bucket.put(key, body).http_metadata(metadata).execute().await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.bucket.delete(key).await
+    }
+}
+
+/// A stored object's content type and body.
+type StoredObject = (Option<String>, Vec<u8>);
+
+/// A stand-in for an R2 bucket, for local runs and tests. Clones share the
+/// same objects.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryR2 {
+    objects: Rc<RefCell<BTreeMap<String, StoredObject>>>,
+}
+
+impl MemoryR2 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait(?Send)]
+impl R2Backend for MemoryR2 {
+    /// Keys in order; the cursor is the last key of the previous page.
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<ObjectPage> {
+        let objects = self.objects.borrow();
+        let mut page: Vec<ObjectInfo> = objects
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix) && cursor.is_none_or(|after| key.as_str() > after))
+            .take(limit as usize + 1)
+            .map(|(key, (content_type, body))| ObjectInfo {
+                key: key.clone(),
+                size: body.len() as u64,
+                content_type: content_type.clone(),
+            })
+            .collect();
+        let more = page.len() > limit as usize;
+        page.truncate(limit as usize);
+        Ok(ObjectPage {
+            cursor: more.then(|| page.last().map(|object| object.key.clone())).flatten(),
+            objects: page,
+        })
+    }
+
+    async fn get(&self, key: &str, max_bytes: u64) -> Result<Option<(ObjectInfo, Option<Vec<u8>>)>> {
+        Ok(self.objects.borrow().get(key).map(|(content_type, body)| {
+            let info = ObjectInfo {
+                key: key.to_string(),
+                size: body.len() as u64,
+                content_type: content_type.clone(),
+            };
+            let body = (info.size <= max_bytes).then(|| body.clone());
+            (info, body)
+        }))
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: Option<&str>) -> Result<()> {
+        self.objects
+            .borrow_mut()
+            .insert(key.to_string(), (content_type.map(str::to_string), body));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+/// Exposes the objects under the configured prefixes as
+/// `r2://<bucket>/<key>`. Objects keep their full key in the URI, and their
+/// `Content-Type` is passed on to clients. Read-only unless made `writable`.
+pub struct R2ResourceProvider {
+    bucket: String,
+    prefixes: Vec<String>,
+    backend: Box<dyn R2Backend>,
+    writable: bool,
+    max_read_bytes: u64,
+}
+
+impl R2ResourceProvider {
+    /// With no `prefixes`, the whole bucket is exposed.
+    pub fn new(bucket: &str, prefixes: &[String], backend: impl R2Backend + 'static) -> Self {
+        let prefixes = match prefixes {
+            [] => vec![String::new()],
+            prefixes => prefixes.to_vec(),
+        };
+        Self {
+            bucket: bucket.to_string(),
+            prefixes,
+            backend: Box::new(backend),
+            writable: false,
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+        }
+    }
+
+    pub fn writable(mut self, writable: bool) -> Self {
+        self.writable = writable;
+        self
+    }
+
+    /// Refuses to read objects larger than `max_read_bytes`.
+    pub fn max_read_bytes(mut self, max_read_bytes: u64) -> Self {
+        self.max_read_bytes = max_read_bytes;
+        self
+    }
+
+    pub fn uri(&self, key: &str) -> String {
+        format!("r2://{}/{}", self.bucket, key)
+    }
+
+    /// The key part of `uri`, which may be empty for the bucket itself.
+    fn key<'a>(&self, uri: &'a str) -> Option<&'a str> {
+        uri.strip_prefix("r2://")?.strip_prefix(&self.bucket)?.strip_prefix('/')
+    }
+
+    fn is_exposed(&self, key: &str) -> bool {
+        self.prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    /// The key of the object at `uri`, if it is exposed.
+    fn object_key(&self, uri: &str) -> std::result::Result<String, ResourceError> {
+        match self.key(uri) {
+            Some(key) if !key.is_empty() && self.is_exposed(key) => Ok(key.to_string()),
+            _ => Err(ResourceError::NotFound(uri.to_string())),
+        }
+    }
+
+    fn writable_key(&self, uri: &str) -> std::result::Result<String, ResourceError> {
+        let key = self.object_key(uri)?;
+        match self.writable {
+            true => Ok(key),
+            false => Err(ResourceError::ReadOnly(uri.to_string())),
+        }
+    }
+
+    async fn list_objects(&self, prefix: &str, cursor: Option<&str>) -> std::result::Result<ObjectPage, ResourceError> {
+        self.backend
+            .list(prefix, cursor, PAGE_SIZE)
+            .await
+            .map_err(failed)
+    }
+
+    fn resources(&self, objects: Vec<ObjectInfo>) -> Vec<Resource> {
+        objects
+            .into_iter()
+            .map(|object| Resource {
+                mime_type: object.content_type,
+                size: Some(object.size),
+                ..Resource::new(&self.uri(&object.key), &object.key)
+            })
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl ResourceProvider for R2ResourceProvider {
+    /// Objects under the prefixes, and the bucket root for browsing.
+    fn handles(&self, uri: &str) -> bool {
+        self.key(uri).is_some_and(|key| key.is_empty() || self.is_exposed(key))
+    }
+
+    /// Pages through each prefix in turn. Cursors are
+    /// `<prefix index>:<R2 cursor>`.
+    async fn list(&self, cursor: Option<&str>) -> std::result::Result<ResourcePage, ResourceError> {
+        let (index, inner) = match cursor {
+            None => (0, None),
+            Some(cursor) => {
+                let invalid = || ResourceError::InvalidCursor(cursor.to_string());
+                let (index, inner) = cursor.split_once(':').ok_or_else(invalid)?;
+                let index: usize = index.parse().map_err(|_| invalid())?;
+                if index >= self.prefixes.len() {
+                    return Err(invalid());
+                }
+                (index, Some(inner).filter(|inner| !inner.is_empty()))
+            },
+        };
+
+        let page = self.list_objects(&self.prefixes[index], inner).await?;
+        let next_cursor = match page.cursor {
+            Some(next) => Some(format!("{}:{}", index, next)),
+            None if index + 1 < self.prefixes.len() => Some(format!("{}:", index + 1)),
+            None => None,
+        };
+        Ok(ResourcePage {
+            resources: self.resources(page.objects),
+            next_cursor,
+        })
+    }
+
+    async fn list_prefix(&self, uri_prefix: &str, cursor: Option<&str>) -> std::result::Result<ResourcePage, ResourceError> {
+        match self.key(uri_prefix) {
+            Some("") => self.list(cursor).await,
+            Some(prefix) if self.is_exposed(prefix) => {
+                let page = self.list_objects(prefix, cursor).await?;
+                Ok(ResourcePage {
+                    resources: self.resources(page.objects),
+                    next_cursor: page.cursor,
+                })
+            },
+            _ => Err(ResourceError::NotFound(uri_prefix.to_string())),
+        }
+    }
+
+    async fn read(&self, uri: &str) -> std::result::Result<Vec<ResourceContents>, ResourceError> {
+        let key = self.object_key(uri)?;
+        let (info, body) = self
+            .backend
+            .get(&key, self.max_read_bytes)
+            .await
+            .map_err(failed)?
+            .ok_or_else(|| ResourceError::NotFound(uri.to_string()))?;
+        let body = body.ok_or_else(|| ResourceError::TooLarge(uri.to_string(), self.max_read_bytes))?;
+        Ok(vec![ResourceContents::from_bytes(uri, info.content_type.as_deref(), body)])
+    }
+
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        vec![ResourceTemplate {
+            uri_template: format!("r2://{}/{}", self.bucket, "{+key}"),
+            name: format!("{} objects", self.bucket),
+            description: None,
+            mime_type: None,
+        }]
+    }
+
+    /// The configured prefixes that extend `value`, then the exposed keys
+    /// starting with it.
+    async fn complete(
+        &self,
+        _template: &str,
+        argument: &str,
+        value: &str,
+        limit: usize,
+    ) -> std::result::Result<Vec<String>, ResourceError> {
+        if argument != "key" {
+            return Ok(Vec::new());
+        }
+        let mut values: Vec<String> = self
+            .prefixes
+            .iter()
+            .filter(|prefix| prefix.len() > value.len() && prefix.starts_with(value))
+            .cloned()
+            .collect();
+        if self.is_exposed(value) && values.len() < limit {
+            let limit = (limit - values.len()).min(1000) as u32;
+            let page = self.backend.list(value, None, limit).await.map_err(failed)?;
+            values.extend(page.objects.into_iter().map(|object| object.key));
+        }
+        values.truncate(limit);
+        Ok(values)
+    }
+
+    async fn write(&self, uri: &str, value: &[u8], mime_type: Option<&str>) -> std::result::Result<(), ResourceError> {
+        let key = self.writable_key(uri)?;
+        self.backend
+            .put(&key, value.to_vec(), mime_type)
+            .await
+            .map_err(failed)
+    }
+
+    async fn delete(&self, uri: &str) -> std::result::Result<(), ResourceError> {
+        let key = self.writable_key(uri)?;
+        self.backend
+            .delete(&key)
+            .await
+            .map_err(failed)
+    }
+}
+
+fn failed(e: Error) -> ResourceError {
+    ResourceError::Failed(e.to_string())
+}
@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::notifications;
+
+/// Longest URI a session may subscribe to.
+pub const MAX_URI_LEN: usize = 2048;
+/// Subscriptions one session may hold in an isolate.
+pub const MAX_PER_SESSION: usize = 100;
+/// Subscriptions an isolate holds across all sessions.
+pub const MAX_TOTAL: usize = 10_000;
+
+thread_local! {
+    static SUBSCRIPTIONS: RefCell<HashMap<String, BTreeSet<String>>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscribeError {
+    UriTooLong,
+    /// The session already holds [`MAX_PER_SESSION`] subscriptions.
+    TooManyForSession,
+    /// The isolate already holds [`MAX_TOTAL`] subscriptions.
+    TooMany,
+}
+
+impl fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscribeError::UriTooLong => write!(f, "URI is longer than {} bytes", MAX_URI_LEN),
+            SubscribeError::TooManyForSession => {
+                write!(f, "Session already has {} subscriptions; unsubscribe from some first", MAX_PER_SESSION)
+            },
+            SubscribeError::TooMany => write!(f, "Too many subscriptions on this server; try again later"),
+        }
+    }
+}
+
+/// Subscribes a session to updates of `uri`, within the limits above.
+/// Subscribing again to the same URI is always allowed.
+pub fn subscribe(session_id: &str, uri: &str) -> Result<(), SubscribeError> {
+    if uri.len() > MAX_URI_LEN {
+        return Err(SubscribeError::UriTooLong);
+    }
+    SUBSCRIPTIONS.with(|subscriptions| {
+        let mut subscriptions = subscriptions.borrow_mut();
+        if subscriptions.get(uri).is_some_and(|sessions| sessions.contains(session_id)) {
+            return Ok(());
+        }
+        let (mut total, mut held) = (0, 0);
+        for sessions in subscriptions.values() {
+            total += sessions.len();
+            held += usize::from(sessions.contains(session_id));
+        }
+        if held >= MAX_PER_SESSION {
+            return Err(SubscribeError::TooManyForSession);
+        }
+        if total >= MAX_TOTAL {
+            return Err(SubscribeError::TooMany);
+        }
+        subscriptions
+            .entry(uri.to_string())
+            .or_default()
+            .insert(session_id.to_string());
+        Ok(())
+    })
+}
+
+pub fn unsubscribe(session_id: &str, uri: &str) {
+    SUBSCRIPTIONS.with(|subscriptions| {
+        let mut subscriptions = subscriptions.borrow_mut();
+        if let Some(sessions) = subscriptions.get_mut(uri) {
+            sessions.remove(session_id);
+            if sessions.is_empty() {
+                subscriptions.remove(uri);
+            }
+        }
+    });
+}
+
+/// Drops all of a session's subscriptions, e.g. when its stream closes.
+pub fn unsubscribe_all(session_id: &str) {
+    SUBSCRIPTIONS.with(|subscriptions| {
+        let mut subscriptions = subscriptions.borrow_mut();
+        subscriptions.values_mut().for_each(|sessions| {
+            sessions.remove(session_id);
+        });
+        subscriptions.retain(|_, sessions| !sessions.is_empty());
+    });
+}
+
+/// Sessions subscribed to `uri`, in a stable order.
+pub fn subscribers(uri: &str) -> Vec<String> {
+    SUBSCRIPTIONS.with(|subscriptions| {
+        subscriptions
+            .borrow()
+            .get(uri)
+            .map(|sessions| sessions.iter().cloned().collect())
+            .unwrap_or_default()
+    })
+}
+
+/// Tells every subscribed session that `uri` changed, over the session's
+/// `GET` stream. Providers call this after writing a resource. Returns the
+/// number of sessions reached; subscribers without an open stream in this
+/// isolate miss the update.
+pub fn notify_updated(uri: &str) -> usize {
+    subscribers(uri)
+        .iter()
+        .filter(|session_id| {
+            notifications::send_to_session(
+                session_id,
+                "notifications/resources/updated",
+                serde_json::json!({ "uri": uri }),
+            )
+        })
+        .count()
+}
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How long to wait for the client to answer `roots/list`.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most roots a client may list; a longer list is an invalid result.
+pub const MAX_ROOTS: usize = 100;
+/// Sessions whose roots an isolate caches; past this, roots are fetched
+/// again on each use.
+pub const MAX_CACHED_SESSIONS: usize = 1_000;
+
+/// A directory or file the user has shared with the server, as a
+/// `file://` URI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Root {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Root {
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.to_string(),
+            name: None,
+        }
+    }
+
+    /// Whether `uri` is this root or lies beneath it.
+    pub fn contains(&self, uri: &str) -> bool {
+        let root = self.uri.trim_end_matches('/');
+        uri == root || uri.strip_prefix(root).is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Whether any of `roots` contains `uri`. Tools that touch files should
+/// refuse paths outside the shared roots.
+pub fn is_within(roots: &[Root], uri: &str) -> bool {
+    roots.iter().any(|root| root.contains(uri))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ListRootsResult {
+    pub roots: Vec<Root>,
+}
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Vec<Root>>> = RefCell::new(HashMap::new());
+}
+
+/// The roots last listed for a session in this isolate.
+pub fn cached(session_id: &str) -> Option<Vec<Root>> {
+    CACHE.with(|cache| cache.borrow().get(session_id).cloned())
+}
+
+/// Caches a session's roots, unless the cache already holds
+/// [`MAX_CACHED_SESSIONS`] others.
+pub fn store(session_id: &str, roots: Vec<Root>) {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.contains_key(session_id) || cache.len() < MAX_CACHED_SESSIONS {
+            cache.insert(session_id.to_string(), roots);
+        }
+    });
+}
+
+/// Drops a session's roots, on `notifications/roots/list_changed` or when
+/// its stream goes dead, so the next lookup asks the client again.
+pub fn invalidate(session_id: &str) {
+    CACHE.with(|cache| cache.borrow_mut().remove(session_id));
+}
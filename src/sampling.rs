@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How long a tool waits for the client, and its user, to produce a
+/// completion.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingMessage {
+    pub role: Role,
+    /// Text, image or audio content, e.g. `{"type": "text", "text": "..."}`.
+    pub content: serde_json::Value,
+}
+
+impl SamplingMessage {
+    pub fn text(role: Role, text: &str) -> Self {
+        Self {
+            role,
+            content: serde_json::json!({ "type": "text", "text": text }),
+        }
+    }
+}
+
+/// Parameters of `sampling/createMessage`. The client picks the model and
+/// may show the request to its user before running it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequest {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// `none`, `thisServer` or `allServers`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl CreateMessageRequest {
+    /// A single user message.
+    pub fn new(prompt: &str, max_tokens: u32) -> Self {
+        Self {
+            messages: vec![SamplingMessage::text(Role::User, prompt)],
+            max_tokens,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: serde_json::Value,
+    /// The model the client actually used.
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+impl CreateMessageResult {
+    /// The text of a text reply.
+    pub fn text(&self) -> Option<&str> {
+        (self.content.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .then(|| self.content.get("text").and_then(|t| t.as_str()))
+            .flatten()
+    }
+}
@@ -0,0 +1,143 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use worker::Env;
+
+use crate::auth::{constant_time_eq, env_string};
+use crate::protocol::ProtocolVersion;
+
+/// What the client declared in `initialize`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<RootsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elicitation: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootsCapability {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_changed: Option<bool>,
+}
+
+/// A session as carried in `Mcp-Session-Id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub capabilities: ClientCapabilities,
+    pub protocol_version: ProtocolVersion,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    /// Makes every id unique, even for identical capabilities.
+    nonce: String,
+    #[serde(rename = "caps")]
+    capabilities: ClientCapabilities,
+    /// Missing from ids issued before versions were negotiated.
+    #[serde(rename = "ver", default)]
+    protocol_version: Option<String>,
+}
+
+/// Issues and checks session ids. Ids are carried rather than stored, so any
+/// isolate can read them and recover the client's capabilities and protocol
+/// version. Signed ids that fail the check are unknown and get `404`.
+pub struct SessionSigner {
+    /// `None` issues unsigned ids; their random nonce still makes them
+    /// unguessable, but clients can edit the capabilities they carry.
+    key: Option<Vec<u8>>,
+}
+
+impl SessionSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self { key: Some(secret.to_vec()) }
+    }
+
+    pub fn unsigned() -> Self {
+        Self { key: None }
+    }
+
+    /// Signs with the `MCP_SESSION_SECRET` secret, or issues unsigned ids
+    /// without it so sessions still work on every isolate.
+    pub fn from_env(env: &Env) -> Self {
+        match env_string(env, "MCP_SESSION_SECRET") {
+            Some(secret) => Self::new(secret.as_bytes()),
+            None => Self::unsigned(),
+        }
+    }
+
+    pub fn issue(&self, capabilities: ClientCapabilities, protocol_version: ProtocolVersion) -> Session {
+        let claims = Claims {
+            nonce: URL_SAFE_NO_PAD.encode(random_bytes::<16>()),
+            capabilities,
+            protocol_version: Some(protocol_version.to_string()),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+        let id = match &self.key {
+            Some(key) => format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(hmac_sha256(key, payload.as_bytes()))),
+            None => payload,
+        };
+        Session {
+            id,
+            capabilities: claims.capabilities,
+            protocol_version,
+        }
+    }
+
+    /// The session behind `id`, or `None` if this server did not issue it.
+    pub fn verify(&self, id: &str) -> Option<Session> {
+        let payload = match &self.key {
+            Some(key) => {
+                let (payload, signature) = id.split_once('.')?;
+                let signature: [u8; 32] = URL_SAFE_NO_PAD.decode(signature).ok()?.try_into().ok()?;
+                if !constant_time_eq(&signature, &hmac_sha256(key, payload.as_bytes())) {
+                    return None;
+                }
+                payload
+            },
+            None => id,
+        };
+        let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        Some(Session {
+            id: id.to_string(),
+            capabilities: claims.capabilities,
+            protocol_version: claims
+                .protocol_version
+                .as_deref()
+                .and_then(ProtocolVersion::parse)
+                .unwrap_or_else(ProtocolVersion::assumed),
+        })
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).expect("random source available");
+    bytes
+}
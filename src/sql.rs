@@ -0,0 +1,461 @@
+use std::fmt;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use worker::wasm_bindgen::{JsCast, JsValue};
+use worker::*;
+
+use crate::config::ServerConfig;
+use crate::tools::ToolAccess;
+
+/// Rows returned when `MCP_D1_MAX_ROWS` is not set.
+pub const DEFAULT_MAX_ROWS: usize = 100;
+
+/// Which D1 database to expose, from `MCP_D1_DATABASE` and friends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct D1Config {
+    /// The D1 binding in `wrangler.toml`.
+    pub binding: String,
+    /// The URI authority of the schema resources, as in `d1://<name>/schema`.
+    pub name: String,
+    /// Only single `SELECT` statements are allowed when set.
+    pub read_only: bool,
+    /// The most rows `sql_query` returns.
+    pub max_rows: usize,
+    /// Required to see and read the schema resources; `sql_query` has the
+    /// access of a tool.
+    pub access: ToolAccess,
+}
+
+impl D1Config {
+    /// Exposes the database read-only, named after the binding.
+    pub fn new(binding: &str) -> Self {
+        Self {
+            binding: binding.to_string(),
+            name: binding.to_ascii_lowercase().replace('_', "-"),
+            read_only: true,
+            max_rows: DEFAULT_MAX_ROWS,
+            access: ToolAccess::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlError {
+    /// The statement cannot be parsed or is not allowed.
+    Rejected(String),
+    /// The parameters do not match the statement's placeholders.
+    InvalidParams(String),
+    /// The database failed.
+    Failed(String),
+}
+
+impl fmt::Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlError::Rejected(e) => write!(f, "Statement rejected: {}", e),
+            SqlError::InvalidParams(e) => write!(f, "Invalid params: {}", e),
+            SqlError::Failed(e) => write!(f, "Query failed: {}", e),
+        }
+    }
+}
+
+/// Whether a statement only reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    Read,
+    Write,
+}
+
+/// A single statement, checked by `parse_statement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// The statement without surrounding whitespace or its trailing `;`.
+    pub sql: String,
+    pub kind: StatementKind,
+    /// How many parameters the statement binds.
+    pub params: usize,
+}
+
+/// Keywords that make a statement a write wherever they appear.
+const WRITE_KEYWORDS: &[&str] = &[
+    "INSERT", "UPDATE", "DELETE", "REPLACE", "UPSERT", "CREATE", "DROP", "ALTER", "ATTACH", "DETACH", "PRAGMA",
+    "VACUUM", "REINDEX", "ANALYZE", "BEGIN", "COMMIT", "ROLLBACK", "SAVEPOINT", "RELEASE",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Param(Option<usize>),
+    /// With its position in characters.
+    Semicolon(usize),
+    OpenParen,
+    Other,
+}
+
+/// Splits SQL into the tokens `parse_statement` cares about, skipping
+/// comments, string literals and quoted identifiers.
+fn tokenize(sql: &str) -> std::result::Result<Vec<Token>, SqlError> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            },
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let end = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == '*' && chars[j + 1] == '/');
+                i = end.ok_or_else(|| SqlError::Rejected("unterminated comment".to_string()))? + 2;
+            },
+            '\'' | '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(SqlError::Rejected(format!("unterminated {}", if c == '\'' { "string" } else { "identifier" }))),
+                        // A doubled quote is an escaped quote
+                        Some(&next) if next == close && close != ']' && chars.get(i + 1) == Some(&close) => i += 2,
+                        Some(&next) if next == close => break,
+                        Some(_) => i += 1,
+                    }
+                }
+                i += 1;
+                tokens.push(Token::Other);
+            },
+            '?' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                let index = match number.is_empty() {
+                    true => None,
+                    false => match number.parse() {
+                        Ok(index) if index > 0 => Some(index),
+                        _ => return Err(SqlError::Rejected(format!("bad parameter ?{}", number))),
+                    },
+                };
+                tokens.push(Token::Param(index));
+            },
+            ':' | '@' | '$' if chars.get(i + 1).is_some_and(|next| next.is_alphabetic() || *next == '_') => {
+                return Err(SqlError::Rejected("named parameters are not supported; use ? or ?NNN".to_string()));
+            },
+            ';' => {
+                tokens.push(Token::Semicolon(i));
+                i += 1;
+            },
+            '(' => {
+                i += 1;
+                tokens.push(Token::OpenParen);
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect::<String>().to_ascii_uppercase()));
+            },
+            _ => {
+                i += 1;
+                tokens.push(Token::Other);
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+/// Checks that `sql` is exactly one statement and works out whether it
+/// writes. Anything other than `SELECT`, `VALUES` or a `WITH` query that
+/// only selects counts as a write. The check is lexical and errs on the side
+/// of calling a statement a write.
+pub fn parse_statement(sql: &str) -> std::result::Result<Statement, SqlError> {
+    let tokens = tokenize(sql)?;
+    let end = tokens.iter().position(|token| matches!(token, Token::Semicolon(_))).unwrap_or(tokens.len());
+    if tokens[end..].iter().any(|token| !matches!(token, Token::Semicolon(_))) {
+        return Err(SqlError::Rejected("only one statement may be run at a time".to_string()));
+    }
+    let semicolon = tokens.get(end);
+    let tokens = &tokens[..end];
+    if tokens.is_empty() {
+        return Err(SqlError::Rejected("empty statement".to_string()));
+    }
+
+    let writes = tokens.iter().enumerate().any(|(i, token)| match token {
+        // `replace(...)` is also a string function
+        Token::Word(word) if word == "REPLACE" => tokens.get(i + 1) != Some(&Token::OpenParen),
+        Token::Word(word) => WRITE_KEYWORDS.contains(&word.as_str()),
+        _ => false,
+    });
+    let kind = match &tokens[0] {
+        Token::Word(first) if !writes && matches!(first.as_str(), "SELECT" | "VALUES" | "WITH") => StatementKind::Read,
+        _ => StatementKind::Write,
+    };
+
+    // SQLite numbers a bare `?` one past the highest number used so far
+    let mut params = 0;
+    for token in tokens {
+        if let Token::Param(index) = token {
+            params = index.map_or(params + 1, |index| params.max(index));
+        }
+    }
+
+    // Whatever follows the first semicolon is more semicolons or comments
+    let sql: String = match semicolon {
+        Some(Token::Semicolon(position)) => sql.chars().take(*position).collect(),
+        _ => sql.to_string(),
+    };
+    Ok(Statement {
+        sql: sql.trim().to_string(),
+        kind,
+        params,
+    })
+}
+
+/// Rows from a query, in column order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Whether rows past `max_rows` were dropped.
+    pub truncated: bool,
+}
+
+impl QueryResult {
+    /// Keeps the first `max_rows` rows.
+    pub fn truncate(mut self, max_rows: usize) -> Self {
+        if self.rows.len() > max_rows {
+            self.rows.truncate(max_rows);
+            self.truncated = true;
+        }
+        self
+    }
+
+    /// The rows as a Markdown table, for clients that only show text.
+    pub fn to_markdown(&self) -> String {
+        if self.columns.is_empty() {
+            return "No rows".to_string();
+        }
+        let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+        let mut lines = vec![
+            row(self.columns.iter().map(|column| markdown_cell(&serde_json::json!(column))).collect()),
+            row(self.columns.iter().map(|_| "---".to_string()).collect()),
+        ];
+        lines.extend(self.rows.iter().map(|values| row(values.iter().map(markdown_cell).collect())));
+        if self.truncated {
+            lines.push(String::new());
+            lines.push(format!("Only the first {} rows are shown.", self.rows.len()));
+        }
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "columns": self.columns,
+            "rows": self.rows,
+            "rowCount": self.rows.len(),
+            "truncated": self.truncated,
+        })
+    }
+}
+
+fn markdown_cell(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// The database operations the SQL tool and schema resources need, so they
+/// can run against D1 or, in tests, SQLite. Parameters are JSON scalars
+/// bound to `?` placeholders in order.
+#[async_trait(?Send)]
+pub trait SqlBackend {
+    /// Runs a statement that returns rows, keeping at most `max_rows`.
+    async fn query(&self, sql: &str, params: &[serde_json::Value], max_rows: usize) -> Result<QueryResult>;
+
+    /// Runs a statement for its effect, returning the number of rows changed.
+    async fn execute(&self, sql: &str, params: &[serde_json::Value]) -> Result<u64>;
+}
+
+pub struct D1 {
+    db: D1Database,
+}
+
+impl D1 {
+    pub fn new(db: D1Database) -> Self {
+        Self { db }
+    }
+
+    fn prepare(&self, sql: &str, params: &[serde_json::Value]) -> Result<D1PreparedStatement> {
+        let params = params
+            .iter()
+            .map(|param| js_sys::JSON::parse(&param.to_string()))
+            .collect::<std::result::Result<Vec<JsValue>, JsValue>>()?;
+        self.db.prepare(sql).bind(&params)
+    }
+}
+
+#[async_trait(?Send)]
+impl SqlBackend for D1 {
+    /// D1 returns every row, so `SqlDatabase::run` limits reads in SQL and
+    /// only the extra row that shows truncation is dropped here.
+    async fn query(&self, sql: &str, params: &[serde_json::Value], max_rows: usize) -> Result<QueryResult> {
+        let statement = self.prepare(sql, params)?;
+        // `raw({ columnNames: true })` returns the column names as the first
+        // row, keeping their order
+        let raw: js_sys::Function = js_sys::Reflect::get(statement.inner(), &"raw".into())?.dyn_into()?;
+        let options = js_sys::Object::new();
+        js_sys::Reflect::set(&options, &"columnNames".into(), &true.into())?;
+        let promise: js_sys::Promise = raw.call1(statement.inner(), &options)?.dyn_into()?;
+        let rows = wasm_bindgen_futures::JsFuture::from(promise).await?;
+        let rows: String = js_sys::JSON::stringify(&rows)?.into();
+        let mut rows: Vec<Vec<serde_json::Value>> = serde_json::from_str(&rows)?;
+
+        if rows.is_empty() {
+            return Ok(QueryResult::default());
+        }
+        let columns = rows
+            .remove(0)
+            .into_iter()
+            .map(|column| column.as_str().unwrap_or_default().to_string())
+            .collect();
+        Ok(QueryResult {
+            columns,
+            rows,
+            truncated: false,
+        }
+        .truncate(max_rows))
+    }
+
+    async fn execute(&self, sql: &str, params: &[serde_json::Value]) -> Result<u64> {
+        let result = self.prepare(sql, params)?.run().await?;
+        Ok(result.meta()?.and_then(|meta| meta.changes).unwrap_or(0) as u64)
+    }
+}
+
+/// A database as agents see it: statements are checked before they run,
+/// and reads are capped.
+pub struct SqlDatabase {
+    name: String,
+    backend: Box<dyn SqlBackend>,
+    read_only: bool,
+    max_rows: usize,
+}
+
+impl fmt::Debug for SqlDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqlDatabase")
+            .field("name", &self.name)
+            .field("read_only", &self.read_only)
+            .field("max_rows", &self.max_rows)
+            .finish()
+    }
+}
+
+/// What running a statement produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlOutcome {
+    Rows(QueryResult),
+    /// A write, with the number of rows it changed.
+    Changed(u64),
+}
+
+impl SqlDatabase {
+    /// Read-only and capped at `DEFAULT_MAX_ROWS` until configured otherwise.
+    pub fn new(name: &str, backend: impl SqlBackend + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            backend: Box::new(backend),
+            read_only: true,
+            max_rows: DEFAULT_MAX_ROWS,
+        }
+    }
+
+    pub fn from_config(config: &D1Config, db: D1Database) -> Self {
+        Self::new(&config.name, D1::new(db))
+            .read_only(config.read_only)
+            .max_rows(config.max_rows)
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Checks and runs one statement. `max_rows` may lower the configured
+    /// cap but not raise it. Reads run inside `SELECT * FROM (...)`, so
+    /// SQLite renames duplicate columns, the second `a` becoming `a:1`.
+    pub async fn run(&self, sql: &str, params: &[serde_json::Value], max_rows: Option<usize>) -> std::result::Result<SqlOutcome, SqlError> {
+        if max_rows == Some(0) {
+            return Err(SqlError::InvalidParams("maxRows must be at least 1".to_string()));
+        }
+        let statement = parse_statement(sql)?;
+        if self.read_only && statement.kind == StatementKind::Write {
+            return Err(SqlError::Rejected("only SELECT statements are allowed".to_string()));
+        }
+        if params.len() != statement.params {
+            return Err(SqlError::InvalidParams(format!(
+                "the statement takes {} parameters but {} were given",
+                statement.params,
+                params.len()
+            )));
+        }
+        if let Some(param) = params.iter().find(|param| param.is_array() || param.is_object()) {
+            return Err(SqlError::InvalidParams(format!("{} is not a string, number, boolean or null", param)));
+        }
+
+        let failed = |e: Error| SqlError::Failed(e.to_string());
+        match statement.kind {
+            StatementKind::Read => {
+                let max_rows = max_rows.map_or(self.max_rows, |max_rows| max_rows.min(self.max_rows));
+                // The database stops after the cap, plus one row to tell
+                // whether any were left out. The newline ends a trailing
+                // `--` comment.
+                let limited = format!("SELECT * FROM ({}\n) LIMIT ?{}", statement.sql, statement.params + 1);
+                let mut params = params.to_vec();
+                params.push(serde_json::json!(max_rows.saturating_add(1)));
+                let result = self.backend.query(&limited, &params, max_rows).await.map_err(failed)?;
+                Ok(SqlOutcome::Rows(result))
+            },
+            StatementKind::Write => {
+                let changes = self.backend.execute(&statement.sql, params).await.map_err(failed)?;
+                Ok(SqlOutcome::Changed(changes))
+            },
+        }
+    }
+
+    /// Runs a query of our own, such as schema introspection, without the
+    /// checks or cap applied to agents' statements.
+    pub async fn query_unchecked(&self, sql: &str, params: &[serde_json::Value]) -> Result<QueryResult> {
+        self.backend.query(sql, params, usize::MAX).await
+    }
+}
+
+/// The database configured with `MCP_D1_DATABASE`, if any.
+pub fn database_from_env(env: &Env, config: &ServerConfig) -> Result<Option<Rc<SqlDatabase>>> {
+    let Some(d1) = &config.d1 else {
+        return Ok(None);
+    };
+    Ok(Some(Rc::new(SqlDatabase::from_config(d1, env.d1(&d1.binding)?))))
+}
@@ -1,17 +1,114 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, RoundingMode, Signed, Zero};
 use serde::{Deserialize, Serialize};
-use crate::{ContentItem, ToolsCallResult};
+use serde_json::json;
+use crate::ToolsCallResult;
+use super::{parse_arguments, text_result, ToolContext};
+
+/// Digits kept after the point when dividing in decimal precision without a
+/// `scale`.
+pub const DEFAULT_DIVISION_SCALE: i64 = 20;
+
+/// The largest `scale`, and the largest exponent an operand may have, so a
+/// request cannot ask for millions of digits.
+pub const MAX_SCALE: u32 = 1000;
+
+/// Operands longer than this are rejected.
+pub const MAX_OPERAND_LENGTH: usize = 1000;
 
 #[derive(Debug, Deserialize)]
 pub struct CalculateRequest {
     operation: Operation,
-    a: f64,
-    b: f64,
+    a: Operand,
+    b: Operand,
+    #[serde(default)]
+    precision: Precision,
+    scale: Option<u32>,
+    rounding: Option<Rounding>,
 }
 
+/// A JSON number or a string of digits. Strings keep digits that a JSON
+/// number would lose, such as `"12345678901234567890.01"`.
 #[derive(Debug, Deserialize)]
-pub struct AddRequest {
-    a: f64,
-    b: f64,
+#[serde(untagged)]
+pub enum Operand {
+    Number(serde_json::Number),
+    Text(String),
+}
+
+impl Operand {
+    fn text(&self) -> String {
+        match self {
+            Operand::Number(number) => number.to_string(),
+            Operand::Text(text) => text.trim().to_string(),
+        }
+    }
+
+    /// Rejects `NaN` and infinities, which `f64` parses from text.
+    fn to_float(&self, name: &str) -> Result<f64, CalculateError> {
+        match self {
+            Operand::Number(number) => number.as_f64(),
+            Operand::Text(text) => text.trim().parse().ok(),
+        }
+        .filter(|value: &f64| value.is_finite())
+        .ok_or_else(|| CalculateError::Invalid(format!("{} is not a finite number", name)))
+    }
+
+    fn to_decimal(&self, name: &str) -> Result<BigDecimal, CalculateError> {
+        let text = self.text();
+        if text.len() > MAX_OPERAND_LENGTH {
+            return Err(CalculateError::Invalid(format!("{} is longer than {} characters", name, MAX_OPERAND_LENGTH)));
+        }
+        let value = BigDecimal::from_str(&text).map_err(|_| CalculateError::Invalid(format!("{} is not a decimal number", name)))?;
+        match value.as_bigint_and_exponent().1.unsigned_abs() > u64::from(MAX_SCALE) {
+            true => Err(CalculateError::Invalid(format!("{} has an exponent beyond {}", name, MAX_SCALE))),
+            false => Ok(value),
+        }
+    }
+}
+
+/// How `calculate` does its arithmetic: `float` is 64-bit floating point,
+/// `decimal` is exact decimal and `integer` is whole numbers of any size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    #[serde(alias = "f64")]
+    Float,
+    Decimal,
+    Integer,
+}
+
+/// How results are rounded to `scale` digits, the same modes as Java's
+/// `RoundingMode`. Banker's rounding, `half_even`, is the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+    HalfUp,
+    HalfDown,
+    #[default]
+    HalfEven,
+}
+
+impl From<Rounding> for RoundingMode {
+    fn from(rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::Up => RoundingMode::Up,
+            Rounding::Down => RoundingMode::Down,
+            Rounding::Ceiling => RoundingMode::Ceiling,
+            Rounding::Floor => RoundingMode::Floor,
+            Rounding::HalfUp => RoundingMode::HalfUp,
+            Rounding::HalfDown => RoundingMode::HalfDown,
+            Rounding::HalfEven => RoundingMode::HalfEven,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,93 +120,115 @@ pub enum Operation {
     Divide,
 }
 
-pub fn handle_add(arguments: Option<serde_json::Value>) -> Result<ToolsCallResult, String> {
-    match arguments {
-        Some(args) => {
-            match serde_json::from_value::<AddRequest>(args) {
-                Ok(add_req) => {
-                    let sum = add_req.a + add_req.b;
-                    Ok(ToolsCallResult {
-                        content: vec![ContentItem {
-                            content_type: "text".to_string(),
-                            text: sum.to_string(),
-                        }],
-                        is_error: Some(false),
-                    })
-                },
-                Err(e) => {
-                    Ok(ToolsCallResult {
-                        content: vec![ContentItem {
-                            content_type: "text".to_string(),
-                            text: format!("Invalid arguments: {}", e),
-                        }],
-                        is_error: Some(true),
-                    })
-                }
-            }
+pub fn handle_calculate(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<CalculateRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    let result = match request.precision {
+        Precision::Float => calculate_float(&request).map(|value| value.to_string()),
+        Precision::Decimal | Precision::Integer => calculate_exact(&request).map(|value| value.to_plain_string()),
+    };
+    match result {
+        Ok(text) => {
+            let structured = json!({ "result": text, "precision": request.precision });
+            Ok(text_result(text, false).with_structured_content(structured))
         },
-        None => {
-            Ok(ToolsCallResult {
-                content: vec![ContentItem {
-                    content_type: "text".to_string(),
-                    text: "Missing arguments".to_string(),
-                }],
-                is_error: Some(true),
-            })
-        }
+        Err(CalculateError::DivisionByZero) => {
+            ctx.logger.warning("Division by zero requested");
+            Ok(text_result("Cannot divide by zero".to_string(), true))
+        },
+        Err(CalculateError::Invalid(message)) => Ok(text_result(message, true)),
     }
 }
 
-pub fn handle_calculate(arguments: Option<serde_json::Value>) -> Result<ToolsCallResult, String> {
-    match arguments {
-        Some(args) => {
-            match serde_json::from_value::<CalculateRequest>(args) {
-                Ok(calc_req) => {
-                    let result = match calc_req.operation {
-                        Operation::Add => calc_req.a + calc_req.b,
-                        Operation::Subtract => calc_req.a - calc_req.b,
-                        Operation::Multiply => calc_req.a * calc_req.b,
-                        Operation::Divide => {
-                            if calc_req.b == 0.0 {
-                                return Ok(ToolsCallResult {
-                                    content: vec![ContentItem {
-                                        content_type: "text".to_string(),
-                                        text: "Cannot divide by zero".to_string(),
-                                    }],
-                                    is_error: Some(true),
-                                });
-                            }
-                            calc_req.a / calc_req.b
-                        }
-                    };
-                    
-                    Ok(ToolsCallResult {
-                        content: vec![ContentItem {
-                            content_type: "text".to_string(),
-                            text: result.to_string(),
-                        }],
-                        is_error: Some(false),
-                    })
-                },
-                Err(e) => {
-                    Ok(ToolsCallResult {
-                        content: vec![ContentItem {
-                            content_type: "text".to_string(),
-                            text: format!("Invalid arguments: {}", e),
-                        }],
-                        is_error: Some(true),
-                    })
-                }
-            }
+enum CalculateError {
+    DivisionByZero,
+    Invalid(String),
+}
+
+/// `scale` and `rounding` only apply to exact precisions, so they are
+/// rejected here rather than ignored.
+fn calculate_float(request: &CalculateRequest) -> Result<f64, CalculateError> {
+    if request.scale.is_some() || request.rounding.is_some() {
+        return Err(CalculateError::Invalid("scale and rounding only apply to decimal or integer precision".to_string()));
+    }
+    let (a, b) = (request.a.to_float("a")?, request.b.to_float("b")?);
+    let result = match request.operation {
+        Operation::Add => a + b,
+        Operation::Subtract => a - b,
+        Operation::Multiply => a * b,
+        Operation::Divide if b == 0.0 => return Err(CalculateError::DivisionByZero),
+        Operation::Divide => a / b,
+    };
+    match result.is_finite() {
+        true => Ok(result),
+        false => Err(CalculateError::Invalid("result is out of range for float precision".to_string())),
+    }
+}
+
+/// Sums, differences and products are exact unless a `scale` is given;
+/// quotients are rounded to `scale` digits, or `DEFAULT_DIVISION_SCALE`.
+/// Integer precision rounds quotients to whole numbers.
+fn calculate_exact(request: &CalculateRequest) -> Result<BigDecimal, CalculateError> {
+    let integer = request.precision == Precision::Integer;
+    let scale = match request.scale {
+        Some(_) if integer => return Err(CalculateError::Invalid("scale only applies to decimal precision".to_string())),
+        Some(scale) if scale > MAX_SCALE => {
+            return Err(CalculateError::Invalid(format!("scale must be at most {}", MAX_SCALE)));
         },
-        None => {
-            Ok(ToolsCallResult {
-                content: vec![ContentItem {
-                    content_type: "text".to_string(),
-                    text: "Missing arguments".to_string(),
-                }],
-                is_error: Some(true),
-            })
+        scale => scale.map(i64::from),
+    };
+    let (a, b) = (request.a.to_decimal("a")?, request.b.to_decimal("b")?);
+    if integer {
+        for (name, value) in [("a", &a), ("b", &b)] {
+            if !value.is_integer() {
+                return Err(CalculateError::Invalid(format!("{} must be an integer in integer precision", name)));
+            }
         }
     }
-}
\ No newline at end of file
+    let rounding = RoundingMode::from(request.rounding.unwrap_or_default());
+    let result = match request.operation {
+        Operation::Add => &a + &b,
+        Operation::Subtract => &a - &b,
+        Operation::Multiply => &a * &b,
+        Operation::Divide if b.is_zero() => return Err(CalculateError::DivisionByZero),
+        Operation::Divide if integer => return Ok(divide(&a, &b, 0, rounding)),
+        Operation::Divide => return Ok(divide(&a, &b, scale.unwrap_or(DEFAULT_DIVISION_SCALE), rounding)),
+    };
+    Ok(match scale {
+        _ if integer => result.with_scale(0),
+        Some(scale) => result.with_scale_round(scale, rounding),
+        None => result,
+    })
+}
+
+/// `a / b` rounded to `scale` digits. The digits past `scale` are replaced
+/// by one that is below, at or above half, which rounds the same way in
+/// every mode as the exact quotient would.
+fn divide(a: &BigDecimal, b: &BigDecimal, scale: i64, rounding: RoundingMode) -> BigDecimal {
+    let (a_digits, a_scale) = a.as_bigint_and_exponent();
+    let (b_digits, b_scale) = b.as_bigint_and_exponent();
+    // a / b * 10^scale = a_digits * 10^shift / b_digits
+    let shift = scale + b_scale - a_scale;
+    let power = BigInt::from(10).pow(shift.unsigned_abs() as u32);
+    let (numerator, denominator) = match shift >= 0 {
+        true => (a_digits * power, b_digits),
+        false => (a_digits, b_digits * power),
+    };
+    let quotient = &numerator / &denominator;
+    let remainder = &numerator - &quotient * &denominator;
+    if remainder.is_zero() {
+        return BigDecimal::new(quotient, scale);
+    }
+    let digit = match (remainder.abs() * 2u32).cmp(&denominator.abs()) {
+        Ordering::Less => 1,
+        Ordering::Equal => 5,
+        Ordering::Greater => 9,
+    };
+    let digit = match a.is_negative() == b.is_negative() {
+        true => BigInt::from(digit),
+        false => -BigInt::from(digit),
+    };
+    BigDecimal::new(quotient * 10u32 + digit, scale + 1).with_scale_round(scale, rounding)
+}
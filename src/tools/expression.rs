@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use crate::{Tool, ToolAnnotations, ToolsCallResult};
+use crate::expression::{self, FUNCTIONS};
+use super::{parse_arguments, text_result, ToolContext, ToolRegistry};
+
+#[derive(Debug, Deserialize)]
+pub struct EvaluateRequest {
+    expression: String,
+    #[serde(default)]
+    variables: HashMap<String, f64>,
+}
+
+pub fn register_expression_tools(registry: &mut ToolRegistry) {
+    let functions: Vec<&str> = FUNCTIONS.iter().map(|(name, _)| *name).collect();
+    let description = format!(
+        "Evaluate an arithmetic expression with + - * / % ^, parentheses, the constants pi, e and tau, and the functions {}",
+        functions.join(", ")
+    );
+    registry.register_tool(
+        Tool::new(
+            "evaluate",
+            Some(&description),
+            json!({
+                "type": "object",
+                "properties": {
+                    "expression": { "type": "string", "description": "For example 2 * (x + 1) ^ 2 - sqrt(y)" },
+                    "variables": {
+                        "type": "object",
+                        "description": "Values of the names used in the expression",
+                        "additionalProperties": { "type": "number" }
+                    }
+                },
+                "required": ["expression"]
+            }),
+        )
+        .with_annotations(ToolAnnotations::read_only()),
+    );
+}
+
+/// Errors point at the offending part of the expression, so the model can
+/// correct it.
+pub fn handle_evaluate(arguments: Option<serde_json::Value>, _ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<EvaluateRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    match expression::evaluate(&request.expression, &request.variables) {
+        Ok(value) => Ok(text_result(value.to_string(), false).with_structured_content(json!({ "result": value }))),
+        Err(e) => Ok(text_result(format!("{}\n{}", e, e.pointer(&request.expression)), true)),
+    }
+}
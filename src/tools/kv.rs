@@ -0,0 +1,77 @@
+use serde::Deserialize;
+use serde_json::json;
+use crate::{Tool, ToolAnnotations, ToolsCallResult};
+use super::{check_scheme, finish_write, parse_arguments, ToolContext, ToolRegistry};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteRequest {
+    uri: String,
+    value: String,
+    #[serde(default)]
+    mime_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRequest {
+    uri: String,
+}
+
+/// Registers `kv_write` and `kv_delete`, which write through the `kv://`
+/// resource provider and refuse any other URI.
+pub fn register_kv_tools(registry: &mut ToolRegistry) {
+    registry.register_tool(Tool {
+        name: "kv_write".to_string(),
+        description: Some("Create or replace a kv:// resource".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string" },
+                "value": { "type": "string" },
+                "mimeType": { "type": "string" }
+            },
+            "required": ["uri", "value"]
+        }),
+        annotations: Some(ToolAnnotations::destructive(true)),
+    });
+
+    registry.register_tool(Tool {
+        name: "kv_delete".to_string(),
+        description: Some("Delete a kv:// resource".to_string()),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string" }
+            },
+            "required": ["uri"]
+        }),
+        annotations: Some(ToolAnnotations::destructive(true)),
+    });
+}
+
+pub async fn handle_kv_write(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<WriteRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "kv") {
+        return Ok(result);
+    }
+    let written = ctx
+        .resources
+        .write(&request.uri, request.value.as_bytes(), request.mime_type.as_deref(), ctx.principal.as_ref())
+        .await;
+    finish_write(&request.uri, written, format!("Wrote {} ({} bytes)", request.uri, request.value.len()))
+}
+
+pub async fn handle_kv_delete(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<DeleteRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "kv") {
+        return Ok(result);
+    }
+    let deleted = ctx.resources.delete(&request.uri, ctx.principal.as_ref()).await;
+    finish_write(&request.uri, deleted, format!("Deleted {}", request.uri))
+}
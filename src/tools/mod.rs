@@ -1,56 +1,460 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use crate::{ContentItem, Tool, ToolsCallResult};
+use crate::auth::Principal;
+use crate::cancellation::CancellationToken;
+use crate::correlator::{self, ClientRequestError};
+use crate::elicitation::{self, ElicitResult};
+use crate::logging::Logger;
+use crate::notifications::Outbox;
+use crate::openapi::OpenApi;
+use crate::plugin::Plugin;
+use crate::resources::{subscriptions, ResourceError, ResourceRegistry};
+use crate::roots::{self, ListRootsResult, Root};
+use crate::sampling::{self, CreateMessageRequest, CreateMessageResult};
+use crate::sql::SqlDatabase;
+use crate::protocol::{Feature, ProtocolVersion};
+use crate::session::ClientCapabilities;
+use crate::upstream::{self, Upstream, UpstreamError};
 
 pub mod example_calculator;
+pub mod expression;
+pub mod kv;
+pub mod r2;
+pub mod sql;
+
+/// Request-scoped context handed to every tool handler.
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    /// The authenticated caller, or `None` when auth is disabled.
+    pub principal: Option<Principal>,
+    /// Sends log messages to the client at or above its chosen level.
+    pub logger: Logger,
+    /// The `Mcp-Session-Id` the request arrived with.
+    pub session_id: Option<String>,
+    /// Notifications to send ahead of the response.
+    pub outbox: Outbox,
+    /// The `_meta.progressToken` of the `tools/call` request, if any.
+    pub progress_token: Option<serde_json::Value>,
+    /// Trips when the client cancels the request. Long-running handlers
+    /// should check it or race their work against `cancelled()`.
+    pub cancellation: CancellationToken,
+    /// What the client declared in `initialize`, carried by its session.
+    pub client_capabilities: ClientCapabilities,
+    /// The protocol version agreed in `initialize`.
+    pub protocol_version: ProtocolVersion,
+    /// Whether the response is an event stream, which server-to-client
+    /// requests need.
+    pub streaming: bool,
+    /// Whether server-to-client requests are allowed, because the deployment
+    /// routes every request to one isolate; see `ServerConfig::single_isolate`.
+    pub client_requests: bool,
+    /// The server's resource providers, for tools that read or write them.
+    pub resources: Rc<ResourceRegistry>,
+    /// The database behind `sql_query`, when one is configured.
+    pub database: Option<Rc<SqlDatabase>>,
+}
+
+impl ToolContext {
+    /// Sends `notifications/progress` if the client asked for progress.
+    /// `progress` must increase with each call; `total` may be omitted
+    /// when unknown.
+    pub fn report_progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let Some(token) = &self.progress_token else {
+            return;
+        };
+        let mut params = json!({ "progressToken": token, "progress": progress });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        self.outbox.notify("notifications/progress", params);
+    }
+
+    /// Sends a request to the client on the response stream and waits for
+    /// its reply. Callers check the matching client capability first.
+    pub async fn send_request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, ClientRequestError> {
+        if !self.client_requests {
+            return Err(ClientRequestError::Disabled);
+        }
+        if !self.streaming {
+            return Err(ClientRequestError::NoStream);
+        }
+        correlator::request(&self.outbox, self.session_id.as_deref(), method, params, timeout, &self.cancellation).await
+    }
+
+    /// Asks the client's LLM for a completion with `sampling/createMessage`.
+    pub async fn create_message(&self, request: &CreateMessageRequest) -> Result<CreateMessageResult, ClientRequestError> {
+        if self.client_capabilities.sampling.is_none() {
+            return Err(ClientRequestError::Unsupported("sampling"));
+        }
+        let params = serde_json::to_value(request).unwrap();
+        let result = self.send_request("sampling/createMessage", params, sampling::TIMEOUT).await?;
+        serde_json::from_value(result).map_err(|e| ClientRequestError::InvalidResult(e.to_string()))
+    }
+
+    /// Asks the user for input matching `schema` with `elicitation/create`.
+    /// Accepted content has been validated against the schema.
+    pub async fn elicit(&self, message: &str, schema: serde_json::Value) -> Result<ElicitResult, ClientRequestError> {
+        if self.client_capabilities.elicitation.is_none() || !self.protocol_version.supports(Feature::Elicitation) {
+            return Err(ClientRequestError::Unsupported("elicitation"));
+        }
+        elicitation::check_schema(&schema).map_err(ClientRequestError::InvalidRequest)?;
+        let params = serde_json::json!({ "message": message, "requestedSchema": schema });
+        let result = self.send_request("elicitation/create", params, elicitation::TIMEOUT).await?;
+        elicitation::parse_result(result, &schema).map_err(ClientRequestError::InvalidResult)
+    }
+
+    /// Asks the user a yes/no question; anything but an explicit yes is
+    /// `false`. For approval steps before refunds, deletions and the like.
+    pub async fn confirm(&self, message: &str) -> Result<bool, ClientRequestError> {
+        match self.elicit(message, elicitation::confirmation_schema()).await? {
+            ElicitResult::Accept(content) => Ok(content.get("confirm").and_then(|c| c.as_bool()) == Some(true)),
+            ElicitResult::Decline | ElicitResult::Cancel => Ok(false),
+        }
+    }
+
+    /// The roots the client has shared, from `roots/list`. They are cached
+    /// per session until the client sends `notifications/roots/list_changed`.
+    pub async fn roots(&self) -> Result<Vec<Root>, ClientRequestError> {
+        if self.client_capabilities.roots.is_none() {
+            return Err(ClientRequestError::Unsupported("roots"));
+        }
+        if let Some(roots) = self.session_id.as_deref().and_then(roots::cached) {
+            return Ok(roots);
+        }
+        let result = self.send_request("roots/list", serde_json::json!({}), roots::TIMEOUT).await?;
+        let ListRootsResult { roots } =
+            serde_json::from_value(result).map_err(|e| ClientRequestError::InvalidResult(e.to_string()))?;
+        if roots.len() > roots::MAX_ROOTS {
+            return Err(ClientRequestError::InvalidResult(format!("more than {} roots", roots::MAX_ROOTS)));
+        }
+        if let Some(session_id) = &self.session_id {
+            roots::store(session_id, roots.clone());
+        }
+        Ok(roots)
+    }
+}
+
+/// Scopes and roles a caller needs to see and call a tool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolAccess {
+    /// Every one of these scopes is required.
+    pub scopes: Vec<String>,
+    /// When non-empty, at least one of these roles is required.
+    pub roles: Vec<String>,
+}
+
+impl ToolAccess {
+    pub fn scopes(scopes: &[&str]) -> Self {
+        Self {
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            roles: Vec::new(),
+        }
+    }
+
+    pub fn roles(roles: &[&str]) -> Self {
+        Self {
+            scopes: Vec::new(),
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.scopes.is_empty() && self.roles.is_empty()
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
+    /// Restricted tools are never available to anonymous callers.
+    pub fn allows(&self, principal: Option<&Principal>) -> bool {
+        if self.is_public() {
+            return true;
+        }
+        let Some(principal) = principal else {
+            return false;
+        };
+        self.scopes.iter().all(|scope| principal.scopes.contains(scope))
+            && (self.roles.is_empty() || self.roles.iter().any(|role| principal.roles.contains(role)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolError {
+    NotFound(String),
+    /// The caller lacks the scopes or roles the tool requires.
+    Forbidden(String, ToolAccess),
+    /// The handler itself failed.
+    Failed(String),
+    /// An upstream server answered with a JSON-RPC error, passed on as is.
+    Remote { code: i32, message: String },
+    /// The client cancelled the request; no response is sent.
+    Cancelled,
+}
+
+/// What the access requires, e.g. ` scopes [a] and one of roles [b]`.
+impl fmt::Display for ToolAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.scopes.is_empty() {
+            write!(f, " scopes [{}]", self.scopes.join(", "))?;
+        }
+        if !self.roles.is_empty() {
+            if !self.scopes.is_empty() {
+                write!(f, " and")?;
+            }
+            write!(f, " one of roles [{}]", self.roles.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolError::NotFound(name) => write!(f, "Unknown tool: {}", name),
+            ToolError::Forbidden(name, access) => write!(f, "Forbidden: tool '{}' requires{}", name, access),
+            ToolError::Failed(e) => write!(f, "{}", e),
+            ToolError::Remote { message, .. } => write!(f, "{}", message),
+            ToolError::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ToolRegistry {
     tools: Vec<Tool>,
+    access: HashMap<String, ToolAccess>,
+    /// APIs whose operations are registered as tools.
+    #[serde(skip)]
+    apis: Vec<Rc<OpenApi>>,
+    /// WebAssembly modules whose tools are registered.
+    #[serde(skip)]
+    plugins: Vec<Rc<Plugin>>,
+    /// MCP servers whose tools are served as `<upstream>.<tool>`.
+    #[serde(skip)]
+    upstreams: Vec<Upstream>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
-        Self {
-            tools: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn register_tool(&mut self, tool: Tool) {
         self.tools.push(tool);
     }
 
+    /// Registers a tool that only callers with the given scopes or roles
+    /// can list and call.
+    pub fn register_tool_with_access(&mut self, tool: Tool, access: ToolAccess) {
+        self.access.insert(tool.name.clone(), access);
+        self.tools.push(tool);
+    }
+
+    /// Registers a tool for each of the API's operations. Fails if one is
+    /// named like a tool that is already registered.
+    pub fn register_openapi(&mut self, api: OpenApi) -> Result<(), String> {
+        let tools = api.tools();
+        if let Some(tool) = tools.iter().find(|tool| self.tools.iter().any(|other| other.name == tool.name)) {
+            return Err(format!("tool {} is already registered", tool.name));
+        }
+        self.tools.extend(tools);
+        self.apis.push(Rc::new(api));
+        Ok(())
+    }
+
+    /// Registers the plugin's tools. Fails if one is named like a tool that
+    /// is already registered.
+    pub fn register_plugin(&mut self, plugin: impl Into<Rc<Plugin>>) -> Result<(), String> {
+        let plugin = plugin.into();
+        if let Some(tool) = plugin.tools().iter().find(|tool| self.tools.iter().any(|other| other.name == tool.name)) {
+            return Err(format!("tool {} is already registered", tool.name));
+        }
+        self.tools.extend(plugin.tools().iter().cloned());
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    /// Serves the upstream's tools in its namespace, behind its configured
+    /// access. Fails if the name is taken or contains the separator.
+    pub fn register_upstream(&mut self, upstream: Upstream) -> Result<(), String> {
+        if upstream.name().is_empty() || upstream.name().contains(upstream::SEPARATOR) {
+            return Err(format!("invalid upstream name: {}", upstream.name()));
+        }
+        if self.upstreams.iter().any(|other| other.name() == upstream.name()) {
+            return Err(format!("upstream {} is already registered", upstream.name()));
+        }
+        if !upstream.access().is_public() {
+            self.access.insert(format!("{}{}*", upstream.name(), upstream::SEPARATOR), upstream.access().clone());
+        }
+        self.upstreams.push(upstream);
+        Ok(())
+    }
+
+    /// Requires `access` to list and call `name`, which may also be an
+    /// upstream tool, `<upstream>.<tool>`, or all of them, `<upstream>.*`.
+    pub fn restrict_tool(&mut self, name: &str, access: ToolAccess) {
+        self.access.insert(name.to_string(), access);
+    }
+
+    pub fn upstreams(&self) -> &[Upstream] {
+        &self.upstreams
+    }
+
+    /// Whether `name` is registered, or names an upstream tool,
+    /// `<upstream>.<tool>`, or all of them, `<upstream>.*`.
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name == name) || self.upstream_for(name).is_some()
+    }
+
+    /// Drops every tool not in `names`. Upstream tools are kept by
+    /// `<upstream>.<tool>`, or all of them by `<upstream>.*`; upstreams not
+    /// named at all are dropped. Fails on names that are not registered.
+    pub fn retain_tools(&mut self, names: &[String]) -> Result<(), String> {
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|name| !self.has_tool(name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("unknown tools: {}", unknown.join(", ")));
+        }
+        self.tools.retain(|tool| names.contains(&tool.name));
+        let upstreams: Vec<String> = self.upstreams.iter().map(|upstream| upstream.name().to_string()).collect();
+        self.access.retain(|name, _| {
+            names.contains(name) || name.split_once(upstream::SEPARATOR).is_some_and(|(namespace, _)| upstreams.iter().any(|u| u == namespace))
+        });
+        self.upstreams.retain_mut(|upstream| {
+            let prefix = format!("{}{}", upstream.name(), upstream::SEPARATOR);
+            let tools: Vec<&str> = names.iter().filter_map(|name| name.strip_prefix(&prefix)).collect();
+            if !tools.contains(&"*") {
+                upstream.enabled = Some(tools.iter().map(|tool| tool.to_string()).collect());
+            }
+            !tools.is_empty()
+        });
+        Ok(())
+    }
+
+    /// The upstream serving a namespaced name, and the tool's own name.
+    fn upstream_for<'a>(&self, name: &'a str) -> Option<(&Upstream, &'a str)> {
+        let (namespace, tool) = name.split_once(upstream::SEPARATOR)?;
+        let upstream = self.upstreams.iter().find(|upstream| upstream.name() == namespace)?;
+        Some((upstream, tool))
+    }
+
     pub fn get_tools(&self) -> &Vec<Tool> {
         &self.tools
     }
 
-    pub fn call_tool(&self, name: &str, arguments: Option<serde_json::Value>) -> Result<ToolsCallResult, String> {
-        match name {
-            "add" => example_calculator::handle_add(arguments),
-            "calculate" => example_calculator::handle_calculate(arguments),
-            _ => Err(format!("Unknown tool: {}", name)),
+    /// Upstream tools without access of their own fall back to that of
+    /// `<upstream>.*`.
+    pub fn access(&self, name: &str) -> ToolAccess {
+        self.access
+            .get(name)
+            .or_else(|| {
+                let (upstream, _) = self.upstream_for(name)?;
+                self.access.get(&format!("{}{}*", upstream.name(), upstream::SEPARATOR))
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Tools the caller is allowed to use; used for `tools/list`.
+    pub fn list_tools(&self, principal: Option<&Principal>) -> Vec<Tool> {
+        self.tools
+            .iter()
+            .filter(|tool| self.access(&tool.name).allows(principal))
+            .cloned()
+            .collect()
+    }
+
+    /// [`list_tools`](Self::list_tools) followed by the tools of every
+    /// upstream the caller may use, fetched concurrently. An upstream that
+    /// cannot be reached is logged and left out.
+    pub async fn list_all_tools(&self, ctx: &ToolContext) -> Vec<Tool> {
+        let principal = ctx.principal.as_ref();
+        let mut tools = self.list_tools(principal);
+        let lists = futures_util::future::join_all(self.upstreams.iter().map(Upstream::list_tools)).await;
+        for (upstream, list) in self.upstreams.iter().zip(lists) {
+            match list {
+                Ok(list) => tools.extend(list.into_iter().filter(|tool| self.access(&tool.name).allows(principal))),
+                Err(e) => ctx.logger.warning(format!("Upstream {}: {}", upstream.name(), e)),
+            }
         }
+        tools
+    }
+
+    /// Tools generated from OpenAPI operations and plugin tools go to their
+    /// owner, so one named like a disabled built-in never reaches it. Built-in
+    /// handlers may be synchronous or `async`; add `.await` to the match arm
+    /// for the latter.
+    pub async fn call_tool(&self, name: &str, arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, ToolError> {
+        let upstream = self.upstream_for(name).filter(|(upstream, tool)| upstream.serves(tool));
+        if upstream.is_none() && !self.tools.iter().any(|tool| tool.name == name) {
+            return Err(ToolError::NotFound(name.to_string()));
+        }
+        let access = self.access(name);
+        if !access.allows(ctx.principal.as_ref()) {
+            return Err(ToolError::Forbidden(name.to_string(), access));
+        }
+        if let Some((upstream, tool)) = upstream {
+            return call_upstream(upstream, tool, arguments, ctx).await;
+        }
+        if ctx.cancellation.is_cancelled() {
+            return Err(ToolError::Cancelled);
+        }
+
+        let api = self.apis.iter().find_map(|api| Some((api, api.operation(name)?)));
+        let plugin = self.plugins.iter().find(|plugin| plugin.has_tool(name));
+        let result = match (api, plugin) {
+            (Some((api, operation)), _) => api.call(operation, arguments).await,
+            (None, Some(plugin)) => plugin.call(name, arguments),
+            (None, None) => match name {
+                "calculate" => example_calculator::handle_calculate(arguments, ctx),
+                "evaluate" => expression::handle_evaluate(arguments, ctx),
+                "kv_write" => kv::handle_kv_write(arguments, ctx).await,
+                "kv_delete" => kv::handle_kv_delete(arguments, ctx).await,
+                "r2_list" => r2::handle_r2_list(arguments, ctx).await,
+                "r2_get" => r2::handle_r2_get(arguments, ctx).await,
+                "r2_put" => r2::handle_r2_put(arguments, ctx).await,
+                "r2_delete" => r2::handle_r2_delete(arguments, ctx).await,
+                "sql_query" => sql::handle_sql_query(arguments, ctx).await,
+                _ => return Err(ToolError::NotFound(name.to_string())),
+            },
+        };
+        if ctx.cancellation.is_cancelled() {
+            return Err(ToolError::Cancelled);
+        }
+        result.map_err(ToolError::Failed)
+    }
+}
+
+/// Upstream failures other than JSON-RPC errors become tool errors, so the
+/// model can see which server is having trouble.
+async fn call_upstream(upstream: &Upstream, tool: &str, arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, ToolError> {
+    match ctx.cancellation.run_until_cancelled(upstream.call_tool(tool, arguments, ctx)).await {
+        None => Err(ToolError::Cancelled),
+        Some(Ok(result)) => Ok(result),
+        Some(Err(UpstreamError::Rejected { code, message })) => Err(ToolError::Remote { code, message }),
+        Some(Err(e)) => Ok(text_result(format!("Upstream {}: {}", upstream.name(), e), true)),
     }
 }
 
 pub fn register_default_tools(registry: &mut ToolRegistry) {
     // Register example calculator tools
-    registry.register_tool(Tool {
-        name: "add".to_string(),
-        description: Some("Add two numbers".to_string()),
-        input_schema: json!({
-            "type": "object",
-            "properties": {
-                "a": { "type": "number" },
-                "b": { "type": "number" }
-            },
-            "required": ["a", "b"]
-        }),
-    });
-
     registry.register_tool(Tool {
         name: "calculate".to_string(),
-        description: Some("Perform arithmetic operations".to_string()),
+        description: Some("Perform arithmetic operations, in floating point or exactly".to_string()),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -58,10 +462,76 @@ pub fn register_default_tools(registry: &mut ToolRegistry) {
                     "type": "string", 
                     "enum": ["add", "subtract", "multiply", "divide"]
                 },
-                "a": { "type": "number" },
-                "b": { "type": "number" }
+                "a": { "type": ["number", "string"], "description": "Pass a string to keep every digit, e.g. \"0.1\"" },
+                "b": { "type": ["number", "string"] },
+                "precision": {
+                    "type": "string",
+                    "enum": ["float", "decimal", "integer"],
+                    "description": "float (the default) is 64-bit floating point, decimal is exact decimal and integer is whole numbers of any size"
+                },
+                "scale": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": example_calculator::MAX_SCALE,
+                    "description": format!("Digits after the point for decimal results, not allowed with float; quotients default to {}", example_calculator::DEFAULT_DIVISION_SCALE)
+                },
+                "rounding": {
+                    "type": "string",
+                    "enum": ["up", "down", "ceiling", "floor", "half_up", "half_down", "half_even"],
+                    "description": "How decimal and integer results are rounded, half_even by default; not allowed with float"
+                }
             },
             "required": ["operation", "a", "b"]
         }),
+        annotations: None,
     });
-}
\ No newline at end of file
+
+    expression::register_expression_tools(registry);
+}
+
+/// Parses a handler's arguments, or returns the error result to send back.
+pub(crate) fn parse_arguments<T: serde::de::DeserializeOwned>(arguments: Option<serde_json::Value>) -> Result<T, ToolsCallResult> {
+    let arguments = arguments.ok_or_else(|| text_result("Missing arguments".to_string(), true))?;
+    serde_json::from_value(arguments).map_err(|e| text_result(format!("Invalid arguments: {}", e), true))
+}
+
+/// Store tools only accept URIs of their own scheme, so that restricting
+/// one store's tools also keeps callers out of it through another's.
+pub(crate) fn check_scheme(uri: &str, scheme: &str) -> Result<(), ToolsCallResult> {
+    match uri.strip_prefix(scheme).is_some_and(|rest| rest.starts_with("://")) {
+        true => Ok(()),
+        false => Err(text_result(format!("Invalid arguments: uri must start with {}://", scheme), true)),
+    }
+}
+
+/// Ends a resource write, telling subscribers about a successful change.
+pub(crate) fn finish_write(uri: &str, outcome: Result<(), ResourceError>, message: String) -> Result<ToolsCallResult, String> {
+    match outcome {
+        Ok(()) => {
+            subscriptions::notify_updated(uri);
+            Ok(text_result(message, false))
+        },
+        Err(e) => resource_error_result(e),
+    }
+}
+
+/// Unknown URIs, bad cursors and read-only resources become tool errors the
+/// model can correct; store failures fail the call.
+pub(crate) fn resource_error_result(error: ResourceError) -> Result<ToolsCallResult, String> {
+    match error {
+        ResourceError::Failed(e) => Err(e),
+        e => Ok(text_result(e.to_string(), true)),
+    }
+}
+
+pub(crate) fn text_result(text: String, is_error: bool) -> ToolsCallResult {
+    ToolsCallResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text,
+            data: serde_json::Map::new(),
+        }],
+        is_error: Some(is_error),
+        structured_content: None,
+    }
+}
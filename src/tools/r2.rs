@@ -0,0 +1,187 @@
+use serde::Deserialize;
+use serde_json::json;
+use crate::resources::ResourceContents;
+use crate::{ContentItem, Tool, ToolAnnotations, ToolsCallResult};
+use super::{check_scheme, finish_write, parse_arguments, resource_error_result, text_result, ToolContext, ToolRegistry};
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    uri: String,
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UriRequest {
+    uri: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Text,
+    Base64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PutRequest {
+    uri: String,
+    content: String,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    mime_type: Option<String>,
+}
+
+/// Registers the `r2://` tools, which refuse any other URI. Those annotated as destructive, `r2_put` and
+/// `r2_delete`, are left out unless `writable`.
+pub fn register_r2_tools(registry: &mut ToolRegistry, writable: bool) {
+    let tools = [
+        Tool::new(
+            "r2_list",
+            Some("List r2:// objects under a URI prefix, such as r2://bucket/images/"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "uri": { "type": "string" },
+                    "cursor": { "type": "string" }
+                },
+                "required": ["uri"]
+            }),
+        )
+        .with_annotations(ToolAnnotations::read_only()),
+        Tool::new(
+            "r2_get",
+            Some("Fetch an r2:// object; binary objects come back as an embedded resource with a base64 blob"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "uri": { "type": "string" }
+                },
+                "required": ["uri"]
+            }),
+        )
+        .with_annotations(ToolAnnotations::read_only()),
+        Tool::new(
+            "r2_put",
+            Some("Create or replace an r2:// object"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "uri": { "type": "string" },
+                    "content": { "type": "string" },
+                    "encoding": { "type": "string", "enum": ["text", "base64"] },
+                    "mimeType": { "type": "string" }
+                },
+                "required": ["uri", "content"]
+            }),
+        )
+        .with_annotations(ToolAnnotations::destructive(true)),
+        Tool::new(
+            "r2_delete",
+            Some("Delete an r2:// object"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "uri": { "type": "string" }
+                },
+                "required": ["uri"]
+            }),
+        )
+        .with_annotations(ToolAnnotations::destructive(true)),
+    ];
+    for tool in tools {
+        if writable || !tool.is_destructive() {
+            registry.register_tool(tool);
+        }
+    }
+}
+
+pub async fn handle_r2_list(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<ListRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "r2") {
+        return Ok(result);
+    }
+    match ctx.resources.list_prefix(&request.uri, request.cursor.as_deref(), ctx.principal.as_ref()).await {
+        Ok(page) => Ok(text_result(serde_json::to_string(&page).unwrap(), false)),
+        Err(e) => resource_error_result(e),
+    }
+}
+
+/// Text objects come back as their text; anything else as an embedded
+/// resource with the body in `blob`. Objects over the provider's read limit
+/// are a tool error.
+pub async fn handle_r2_get(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<UriRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "r2") {
+        return Ok(result);
+    }
+    match ctx.resources.read(&request.uri, ctx.principal.as_ref()).await {
+        Ok(contents) => Ok(ToolsCallResult {
+            content: contents.into_iter().map(content_item).collect(),
+            is_error: Some(false),
+            structured_content: None,
+        }),
+        Err(e) => resource_error_result(e),
+    }
+}
+
+fn content_item(contents: ResourceContents) -> ContentItem {
+    match contents.text {
+        Some(text) => ContentItem {
+            content_type: "text".to_string(),
+            text,
+            data: serde_json::Map::new(),
+        },
+        None => {
+            let mut data = serde_json::Map::new();
+            data.insert("resource".to_string(), serde_json::to_value(contents).unwrap());
+            ContentItem {
+                content_type: "resource".to_string(),
+                text: String::new(),
+                data,
+            }
+        },
+    }
+}
+
+pub async fn handle_r2_put(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    use base64::Engine;
+
+    let request = match parse_arguments::<PutRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "r2") {
+        return Ok(result);
+    }
+    let body = match request.encoding {
+        Encoding::Text => request.content.into_bytes(),
+        Encoding::Base64 => match base64::engine::general_purpose::STANDARD.decode(&request.content) {
+            Ok(body) => body,
+            Err(e) => return Ok(text_result(format!("Invalid arguments: content is not base64: {}", e), true)),
+        },
+    };
+    let written = ctx.resources.write(&request.uri, &body, request.mime_type.as_deref(), ctx.principal.as_ref()).await;
+    finish_write(&request.uri, written, format!("Wrote {} ({} bytes)", request.uri, body.len()))
+}
+
+pub async fn handle_r2_delete(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<UriRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    if let Err(result) = check_scheme(&request.uri, "r2") {
+        return Ok(result);
+    }
+    let deleted = ctx.resources.delete(&request.uri, ctx.principal.as_ref()).await;
+    finish_write(&request.uri, deleted, format!("Deleted {}", request.uri))
+}
@@ -0,0 +1,61 @@
+use serde::Deserialize;
+use serde_json::json;
+use crate::{Tool, ToolAnnotations, ToolsCallResult};
+use crate::sql::SqlOutcome;
+use super::{parse_arguments, text_result, ToolContext, ToolRegistry};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    sql: String,
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+    #[serde(default)]
+    max_rows: Option<usize>,
+}
+
+/// Registers `sql_query`, annotated as read-only unless the database allows
+/// writes.
+pub fn register_sql_tools(registry: &mut ToolRegistry, read_only: bool) {
+    let (description, annotations) = match read_only {
+        true => ("Run a read-only SQL SELECT against the database", ToolAnnotations::read_only()),
+        false => ("Run a SQL statement against the database", ToolAnnotations::destructive(false)),
+    };
+    registry.register_tool(
+        Tool::new(
+            "sql_query",
+            Some(description),
+            json!({
+                "type": "object",
+                "properties": {
+                    "sql": { "type": "string", "description": "One SQLite statement; use ? placeholders for values. Repeated column names come back as name:1 and so on, so alias them" },
+                    "params": { "type": "array", "description": "Values bound to the placeholders in order" },
+                    "maxRows": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["sql"]
+            }),
+        )
+        .with_annotations(annotations),
+    );
+}
+
+/// Rows come back as structured content, with a Markdown table for
+/// clients that only show text.
+pub async fn handle_sql_query(arguments: Option<serde_json::Value>, ctx: &ToolContext) -> Result<ToolsCallResult, String> {
+    let request = match parse_arguments::<QueryRequest>(arguments) {
+        Ok(request) => request,
+        Err(result) => return Ok(result),
+    };
+    let Some(database) = &ctx.database else {
+        return Err("No database is configured".to_string());
+    };
+    match database.run(&request.sql, &request.params, request.max_rows).await {
+        Ok(SqlOutcome::Rows(result)) => Ok(text_result(result.to_markdown(), false).with_structured_content(result.to_json())),
+        Ok(SqlOutcome::Changed(changes)) => {
+            Ok(text_result(format!("{} rows changed", changes), false).with_structured_content(json!({ "changes": changes })))
+        },
+        // Database errors are usually mistakes in the query the model can
+        // fix, so they are tool errors like rejected statements
+        Err(e) => Ok(text_result(e.to_string(), true)),
+    }
+}
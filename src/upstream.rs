@@ -0,0 +1,569 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::correlator::{first, sleep};
+use crate::http::{BodyStream, HttpClient, HttpRequest, HttpResponse};
+use crate::logging::LogLevel;
+use crate::protocol::LATEST_PROTOCOL_VERSION;
+use crate::tools::{ToolAccess, ToolContext};
+use crate::{Tool, ToolsCallResult};
+
+/// How long an upstream server may go quiet unless configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long one call to an upstream server may take in all, however much
+/// it streams, unless configured.
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(120);
+
+/// The most of a response, or of one event in a stream, that is buffered
+/// unless configured.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+
+/// How long an upstream that failed is left alone before it is tried again.
+pub const RETRY_AFTER_SECS: u64 = 30;
+
+/// How long `GET /health` reuses an isolate's last check, so that hitting
+/// it cannot flood the upstreams.
+pub const HEALTH_TTL_SECS: u64 = 30;
+
+/// Separates the upstream's name from its tool's, as in `github.search`.
+pub const SEPARATOR: char = '.';
+
+/// An MCP server reached over Streamable HTTP, from `MCP_UPSTREAMS`.
+#[derive(Clone, PartialEq)]
+pub struct UpstreamConfig {
+    /// The namespace of its tools.
+    pub name: String,
+    pub url: String,
+    /// Applies to each wait, for headers or the next piece of a body.
+    pub timeout: Duration,
+    /// Applies to a whole call, including opening a session.
+    pub deadline: Duration,
+    pub max_response_bytes: usize,
+    /// Sent with every request, such as `Authorization`.
+    pub headers: Vec<(String, String)>,
+    /// Required to list and call any of its tools.
+    pub access: ToolAccess,
+}
+
+impl UpstreamConfig {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            deadline: DEFAULT_DEADLINE,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            headers: Vec::new(),
+            access: ToolAccess::default(),
+        }
+    }
+}
+
+/// Header values are usually credentials, so only their names are shown.
+impl fmt::Debug for UpstreamConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("UpstreamConfig")
+            .field("name", &self.name)
+            .field("url", &self.url)
+            .field("timeout", &self.timeout)
+            .field("deadline", &self.deadline)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("headers", &headers)
+            .field("access", &self.access)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpstreamError {
+    /// No reply within the upstream's timeout or deadline.
+    Timeout,
+    /// A response or event was over the upstream's size limit.
+    TooLarge(usize),
+    /// A recent request failed, so the upstream is not being tried.
+    Unavailable(String),
+    /// The request could not be sent.
+    Transport(String),
+    /// The upstream answered with an HTTP error.
+    Status(u16, String),
+    /// The upstream answered with a JSON-RPC error, passed on as is.
+    Rejected { code: i32, message: String },
+    InvalidResponse(String),
+}
+
+impl UpstreamError {
+    /// Failures that say the upstream is down rather than that the request
+    /// was wrong.
+    fn is_outage(&self) -> bool {
+        match self {
+            UpstreamError::Timeout | UpstreamError::Transport(_) => true,
+            UpstreamError::Status(status, _) => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpstreamError::Timeout => write!(f, "timed out"),
+            UpstreamError::TooLarge(limit) => write!(f, "response larger than {} bytes", limit),
+            UpstreamError::Unavailable(e) => write!(f, "unavailable after an earlier failure: {}", e),
+            UpstreamError::Transport(e) => write!(f, "unreachable: {}", e),
+            UpstreamError::Status(status, body) => write!(f, "HTTP {}: {}", status, body),
+            UpstreamError::Rejected { code, message } => write!(f, "error {}: {}", code, message),
+            UpstreamError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+/// What an isolate remembers about an upstream between requests.
+#[derive(Debug, Clone, Default)]
+struct State {
+    session_id: Option<String>,
+    protocol_version: Option<String>,
+    /// Set after an outage; requests before then fail fast.
+    down_until: u64,
+    last_error: Option<String>,
+}
+
+thread_local! {
+    static NEXT_ID: Cell<u64> = const { Cell::new(1) };
+    static STATES: RefCell<HashMap<String, State>> = RefCell::new(HashMap::new());
+    /// The last [`cached_health`] check and when it ran.
+    static HEALTH: RefCell<Option<(u64, Vec<UpstreamHealth>)>> = const { RefCell::new(None) };
+}
+
+/// Whether an upstream answered its last health check, for `GET /health`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpstreamHealth {
+    pub name: String,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An upstream MCP server whose tools are served as `<name>.<tool>`.
+pub struct Upstream {
+    config: UpstreamConfig,
+    client: Box<dyn HttpClient>,
+    /// When set, only these of its tools are served.
+    pub(crate) enabled: Option<Vec<String>>,
+}
+
+impl fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstream")
+            .field("config", &self.config)
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
+
+impl Upstream {
+    pub fn new(config: UpstreamConfig, client: impl HttpClient + 'static) -> Self {
+        Self {
+            config,
+            client: Box::new(client),
+            enabled: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn access(&self) -> &ToolAccess {
+        &self.config.access
+    }
+
+    /// Whether `tool`, without the namespace, is served.
+    pub fn serves(&self, tool: &str) -> bool {
+        self.enabled.as_ref().is_none_or(|enabled| enabled.iter().any(|name| name == tool))
+    }
+
+    /// The upstream's tools, renamed into its namespace.
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, UpstreamError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params, None).await?;
+            let page: Vec<Tool> = serde_json::from_value(result.get("tools").cloned().unwrap_or_default())
+                .map_err(|e| UpstreamError::InvalidResponse(e.to_string()))?;
+            tools.extend(page.into_iter().filter(|tool| self.serves(&tool.name)).map(|mut tool| {
+                tool.name = format!("{}{}{}", self.name(), SEPARATOR, tool.name);
+                tool
+            }));
+            match result.get("nextCursor").and_then(Value::as_str) {
+                Some(next) if cursor.as_deref() != Some(next) => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    /// Calls `tool`, named without the namespace. The caller's progress
+    /// token is passed on so the upstream's progress reaches the client.
+    pub async fn call_tool(&self, tool: &str, arguments: Option<Value>, ctx: &ToolContext) -> Result<ToolsCallResult, UpstreamError> {
+        let mut params = json!({ "name": tool, "arguments": arguments.unwrap_or_else(|| json!({})) });
+        if let Some(token) = &ctx.progress_token {
+            params["_meta"] = json!({ "progressToken": token });
+        }
+        let result = self.request("tools/call", params, Some(ctx)).await?;
+        serde_json::from_value(result).map_err(|e| UpstreamError::InvalidResponse(e.to_string()))
+    }
+
+    /// Sends `ping`, even while the upstream is marked down, and records
+    /// the outcome.
+    pub async fn check_health(&self) -> UpstreamHealth {
+        let outcome = self.exchange("ping", json!({}), None).await;
+        self.record(&outcome);
+        UpstreamHealth {
+            name: self.name().to_string(),
+            healthy: outcome.is_ok(),
+            error: outcome.err().map(|e| e.to_string()),
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value, ctx: Option<&ToolContext>) -> Result<Value, UpstreamError> {
+        let state = self.state();
+        if state.down_until > now_secs() {
+            return Err(UpstreamError::Unavailable(state.last_error.unwrap_or_default()));
+        }
+        let outcome = self.exchange(method, params, ctx).await;
+        self.record(&outcome);
+        outcome
+    }
+
+    /// Gives up on `step` after the upstream's timeout. Each step is one
+    /// wait, for headers or the next piece of a body; the deadline in
+    /// [`exchange`](Self::exchange) bounds a call that keeps streaming.
+    async fn timed<T>(&self, step: impl Future<Output = Result<T, UpstreamError>>) -> Result<T, UpstreamError> {
+        within(self.config.timeout, step).await
+    }
+
+    fn record(&self, outcome: &Result<Value, UpstreamError>) {
+        let mut state = self.state();
+        match outcome {
+            Err(e) if e.is_outage() => {
+                state.down_until = now_secs() + RETRY_AFTER_SECS;
+                state.last_error = Some(e.to_string());
+                // A restarted upstream will not know the old session
+                state.session_id = None;
+                state.protocol_version = None;
+            },
+            _ => {
+                state.down_until = 0;
+                state.last_error = None;
+            },
+        }
+        self.set_state(state);
+    }
+
+    /// [`send`](Self::send), given up on after the upstream's deadline.
+    async fn exchange(&self, method: &str, params: Value, ctx: Option<&ToolContext>) -> Result<Value, UpstreamError> {
+        within(self.config.deadline, self.send(method, params, ctx)).await
+    }
+
+    /// Sends one request, first opening a session if there is none. A
+    /// session the upstream has forgotten is replaced once.
+    async fn send(&self, method: &str, params: Value, ctx: Option<&ToolContext>) -> Result<Value, UpstreamError> {
+        let mut retried = false;
+        loop {
+            let mut state = self.state();
+            if state.protocol_version.is_none() {
+                state = self.initialize().await?;
+            }
+            let id = format!("gateway-{}", NEXT_ID.with(|next| next.replace(next.get() + 1)));
+            let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+            let (response, body) = self.post(&state, &message).await?;
+            if response.status == 404 && state.session_id.is_some() && !retried {
+                self.set_state(State::default());
+                retried = true;
+                continue;
+            }
+            return self.reply(&state, &id, response, body, ctx).await;
+        }
+    }
+
+    async fn initialize(&self) -> Result<State, UpstreamError> {
+        let id = format!("gateway-{}", NEXT_ID.with(|next| next.replace(next.get() + 1)));
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": LATEST_PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }
+            }
+        });
+        let (response, body) = self.post(&State::default(), &message).await?;
+        let mut state = State {
+            session_id: response.header("Mcp-Session-Id").map(str::to_string),
+            protocol_version: None,
+            ..self.state()
+        };
+        let result = self.reply(&state, &id, response, body, None).await?;
+        state.protocol_version = Some(result.get("protocolVersion").and_then(Value::as_str).unwrap_or(LATEST_PROTOCOL_VERSION).to_string());
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        let (response, body) = self.post(&state, &initialized).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(status_error(&self.read_to_end(response, body).await?));
+        }
+        self.set_state(state.clone());
+        Ok(state)
+    }
+
+    /// Posts `message` and returns once the upstream's headers are back,
+    /// leaving the body to be read.
+    async fn post(&self, state: &State, message: &Value) -> Result<(HttpResponse, BodyStream), UpstreamError> {
+        let mut headers = self.config.headers.clone();
+        headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        headers.push(("Accept".to_string(), "application/json, text/event-stream".to_string()));
+        if let Some(session_id) = &state.session_id {
+            headers.push(("Mcp-Session-Id".to_string(), session_id.clone()));
+        }
+        if let Some(version) = &state.protocol_version {
+            headers.push(("MCP-Protocol-Version".to_string(), version.clone()));
+        }
+        let request = HttpRequest {
+            method: "POST".to_string(),
+            url: self.config.url.clone(),
+            headers,
+            body: Some(message.to_string()),
+        };
+        self.timed(async { self.client.open(request).await.map_err(|e| UpstreamError::Transport(e.to_string())) })
+            .await
+    }
+
+    /// The next piece of `body`, or `None` at its end.
+    async fn read(&self, body: &mut BodyStream) -> Result<Option<Vec<u8>>, UpstreamError> {
+        self.timed(async { body.next().await.transpose().map_err(|e| UpstreamError::Transport(e.to_string())) })
+            .await
+    }
+
+    async fn read_to_end(&self, mut response: HttpResponse, mut body: BodyStream) -> Result<HttpResponse, UpstreamError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.read(&mut body).await? {
+            if bytes.len() + chunk.len() > self.config.max_response_bytes {
+                return Err(UpstreamError::TooLarge(self.config.max_response_bytes));
+            }
+            bytes.extend(chunk);
+        }
+        response.body = String::from_utf8_lossy(&bytes).into_owned();
+        Ok(response)
+    }
+
+    /// Finds the response to `id`. An event stream is read as it arrives,
+    /// so the progress and log notifications before the response reach the
+    /// caller while the upstream is still working.
+    async fn reply(
+        &self,
+        state: &State,
+        id: &str,
+        response: HttpResponse,
+        mut body: BodyStream,
+        ctx: Option<&ToolContext>,
+    ) -> Result<Value, UpstreamError> {
+        if !(200..300).contains(&response.status) {
+            return Err(status_error(&self.read_to_end(response, body).await?));
+        }
+        if response.media_type() != Some("text/event-stream") {
+            let response = self.read_to_end(response, body).await?;
+            if !response.body.trim().is_empty() {
+                for message in parse(&response.body)? {
+                    if let Some(outcome) = self.handle(state, id, message, ctx).await {
+                        return outcome;
+                    }
+                }
+            }
+            return Err(UpstreamError::InvalidResponse(format!("no response to {}", id)));
+        }
+        let mut events = Events::new(self.config.max_response_bytes);
+        while let Some(chunk) = self.read(&mut body).await? {
+            for data in events.push(&chunk)? {
+                for message in parse(&data)? {
+                    if let Some(outcome) = self.handle(state, id, message, ctx).await {
+                        return outcome;
+                    }
+                }
+            }
+        }
+        Err(UpstreamError::InvalidResponse(format!("no response to {}", id)))
+    }
+
+    /// The outcome if `message` is the response to `id`. Progress and log
+    /// notifications are passed on to the caller. Requests from the
+    /// upstream, such as `sampling/createMessage` or `elicitation/create`,
+    /// are refused, as the gateway does not pass them on to its client;
+    /// only `ping` is answered.
+    async fn handle(&self, state: &State, id: &str, message: Value, ctx: Option<&ToolContext>) -> Option<Result<Value, UpstreamError>> {
+        let method = message.get("method").and_then(Value::as_str);
+        match (message.get("id"), method) {
+            (Some(request_id), Some(method)) => {
+                let answer = match method {
+                    "ping" => json!({ "jsonrpc": "2.0", "id": request_id, "result": {} }),
+                    _ => json!({
+                        "jsonrpc": "2.0",
+                        "id": request_id,
+                        "error": { "code": -32601, "message": format!("{} is not passed on by the gateway", method) }
+                    }),
+                };
+                self.post(state, &answer).await.err().map(Err)
+            },
+            (Some(response_id), None) if response_id.as_str() == Some(id) => Some(match message.get("error") {
+                Some(error) => Err(UpstreamError::Rejected {
+                    code: error.get("code").and_then(Value::as_i64).and_then(|code| i32::try_from(code).ok()).unwrap_or(-32603),
+                    message: error.get("message").and_then(Value::as_str).unwrap_or_default().to_string(),
+                }),
+                None => Ok(message.get("result").cloned().unwrap_or_default()),
+            }),
+            (None, Some(method)) => {
+                let (Some(ctx), Some(params)) = (ctx, message.get("params")) else {
+                    return None;
+                };
+                match method {
+                    "notifications/progress" => {
+                        let progress = params.get("progress").and_then(Value::as_f64).unwrap_or_default();
+                        let total = params.get("total").and_then(Value::as_f64);
+                        ctx.report_progress(progress, total, params.get("message").and_then(Value::as_str));
+                    },
+                    "notifications/message" => {
+                        let level = serde_json::from_value(params.get("level").cloned().unwrap_or_default()).unwrap_or(LogLevel::Info);
+                        ctx.logger.log(level, params.get("data").cloned().unwrap_or_default());
+                    },
+                    _ => {},
+                }
+                None
+            },
+            _ => None,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{} {}", self.config.name, self.config.url)
+    }
+
+    fn state(&self) -> State {
+        STATES.with(|states| states.borrow().get(&self.key()).cloned().unwrap_or_default())
+    }
+
+    fn set_state(&self, state: State) {
+        STATES.with(|states| states.borrow_mut().insert(self.key(), state));
+    }
+}
+
+/// Splits an event stream into the data of each event, however its bytes
+/// happen to be chunked.
+struct Events {
+    buffer: Vec<u8>,
+    /// The most an unfinished event may hold.
+    limit: usize,
+}
+
+impl Events {
+    fn new(limit: usize) -> Self {
+        Self { buffer: Vec::new(), limit }
+    }
+
+    /// The data of every event completed by `chunk`, or an error once the
+    /// unfinished one is over the limit.
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<String>, UpstreamError> {
+        self.buffer.extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+            if end > self.limit {
+                return Err(UpstreamError::TooLarge(self.limit));
+            }
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let data = String::from_utf8_lossy(&event)
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !data.is_empty() {
+                events.push(data);
+            }
+        }
+        if self.buffer.len() > self.limit {
+            return Err(UpstreamError::TooLarge(self.limit));
+        }
+        Ok(events)
+    }
+}
+
+/// `step`, or [`UpstreamError::Timeout`] if it takes longer than `duration`.
+async fn within<T>(duration: Duration, step: impl Future<Output = Result<T, UpstreamError>>) -> Result<T, UpstreamError> {
+    first(step, async {
+        sleep(duration).await;
+        Err(UpstreamError::Timeout)
+    })
+    .await
+}
+
+/// The JSON-RPC messages in one body or event, which may be a batch.
+fn parse(body: &str) -> Result<Vec<Value>, UpstreamError> {
+    match serde_json::from_str(body).map_err(|e| UpstreamError::InvalidResponse(e.to_string()))? {
+        Value::Array(batch) => Ok(batch),
+        message => Ok(vec![message]),
+    }
+}
+
+fn status_error(response: &HttpResponse) -> UpstreamError {
+    let body: String = response.body.chars().take(200).collect();
+    UpstreamError::Status(response.status, body)
+}
+
+/// Checks every upstream at once.
+pub async fn check_health(upstreams: &[Upstream]) -> Vec<UpstreamHealth> {
+    futures_util::future::join_all(upstreams.iter().map(Upstream::check_health)).await
+}
+
+/// [`check_health`], or this isolate's result for the same upstreams if it
+/// is less than [`HEALTH_TTL_SECS`] old.
+pub async fn cached_health(upstreams: &[Upstream]) -> Vec<UpstreamHealth> {
+    let now = now_secs();
+    let cached = HEALTH.with(|health| {
+        health
+            .borrow()
+            .as_ref()
+            .filter(|(checked_at, health)| {
+                now < checked_at + HEALTH_TTL_SECS && health.iter().map(|h| h.name.as_str()).eq(upstreams.iter().map(Upstream::name))
+            })
+            .map(|(_, health)| health.clone())
+    });
+    if let Some(health) = cached {
+        return health;
+    }
+    let health = check_health(upstreams).await;
+    HEALTH.with(|cached| *cached.borrow_mut() = Some((now, health.clone())));
+    health
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_secs() -> u64 {
+    crate::auth::now_secs()
+}
+
+/// Outside Workers, e.g. in tests, the system clock stands in for `Date`.
+#[cfg(not(target_arch = "wasm32"))]
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolRegistry};
+
+    fn calculate(arguments: Value) -> Value {
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        let result = block_on(tools.call_tool("calculate", Some(arguments), &ToolContext::default())).unwrap();
+        serde_json::to_value(result).unwrap()
+    }
+
+    /// The text of a successful result, checking the structured copy agrees.
+    fn text(arguments: Value) -> String {
+        let result = calculate(arguments);
+        assert_eq!(result["isError"], json!(false), "{}", result);
+        let text = result["content"][0]["text"].as_str().unwrap().to_string();
+        assert_eq!(result["structuredContent"]["result"], json!(text));
+        text
+    }
+
+    fn error(arguments: Value) -> String {
+        let result = calculate(arguments);
+        assert_eq!(result["isError"], json!(true), "{}", result);
+        result["content"][0]["text"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_float_is_the_default() {
+        assert_eq!(text(json!({ "operation": "add", "a": 0.1, "b": 0.2 })), "0.30000000000000004");
+        assert_eq!(text(json!({ "operation": "divide", "a": 7, "b": "2", "precision": "f64" })), "3.5");
+        let result = calculate(json!({ "operation": "multiply", "a": 2, "b": 3 }));
+        assert_eq!(result["structuredContent"], json!({ "result": "6", "precision": "float" }));
+    }
+
+    #[test]
+    fn test_decimal_is_exact() {
+        let decimal = |operation: &str, a: Value, b: Value| text(json!({ "operation": operation, "a": a, "b": b, "precision": "decimal" }));
+        assert_eq!(decimal("add", json!(0.1), json!(0.2)), "0.3");
+        assert_eq!(decimal("subtract", json!("100.00"), json!("0.01")), "99.99");
+        assert_eq!(decimal("multiply", json!("12345678901234567890.5"), json!("2")), "24691357802469135781.0");
+        assert_eq!(decimal("divide", json!(1), json!(4)), "0.25000000000000000000");
+        assert_eq!(decimal("divide", json!(2), json!(3)), "0.66666666666666666667");
+        assert_eq!(decimal("add", json!("1e-3"), json!(1)), "1.001");
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let round = |a: &str, rounding: &str| {
+            text(json!({ "operation": "multiply", "a": a, "b": 1, "precision": "decimal", "scale": 1, "rounding": rounding }))
+        };
+        let cases = [
+            ("up", ["1.3", "1.3", "-1.3"]),
+            ("down", ["1.2", "1.2", "-1.2"]),
+            ("ceiling", ["1.3", "1.3", "-1.2"]),
+            ("floor", ["1.2", "1.2", "-1.3"]),
+            ("half_up", ["1.3", "1.3", "-1.3"]),
+            ("half_down", ["1.2", "1.3", "-1.2"]),
+            ("half_even", ["1.2", "1.3", "-1.2"]),
+        ];
+        for (rounding, expected) in cases {
+            let actual = [round("1.25", rounding), round("1.251", rounding), round("-1.25", rounding)];
+            assert_eq!(actual, expected.map(String::from), "{}", rounding);
+        }
+
+        // Quotients round as the exact value would, not a truncated one
+        let divide = |a: i64, b: i64, rounding: &str| {
+            text(json!({ "operation": "divide", "a": a, "b": b, "precision": "decimal", "scale": 0, "rounding": rounding }))
+        };
+        assert_eq!(divide(5, 2, "half_even"), "2");
+        assert_eq!(divide(7, 2, "half_even"), "4");
+        assert_eq!(divide(-5, 2, "half_up"), "-3");
+        assert_eq!(divide(2, 3, "down"), "0");
+        assert_eq!(divide(-2, 3, "floor"), "-1");
+        assert_eq!(divide(1, 3, "half_down"), "0");
+    }
+
+    #[test]
+    fn test_integer_precision() {
+        let integer = |operation: &str, a: Value, b: Value| text(json!({ "operation": operation, "a": a, "b": b, "precision": "integer" }));
+        assert_eq!(
+            integer("multiply", json!("123456789012345678901234567890"), json!("987654321")),
+            "121932631124828532112482853211126352690"
+        );
+        assert_eq!(integer("add", json!("9007199254740993"), json!(0)), "9007199254740993");
+        assert_eq!(integer("divide", json!(7), json!(2)), "4");
+        assert_eq!(text(json!({ "operation": "divide", "a": 7, "b": 2, "precision": "integer", "rounding": "down" })), "3");
+
+        assert_eq!(error(json!({ "operation": "add", "a": 1.5, "b": 1, "precision": "integer" })), "a must be an integer in integer precision");
+        assert_eq!(error(json!({ "operation": "add", "a": 1, "b": 1, "precision": "integer", "scale": 2 })), "scale only applies to decimal precision");
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        for precision in ["float", "decimal", "integer"] {
+            assert_eq!(error(json!({ "operation": "divide", "a": 1, "b": "0", "precision": precision })), "Cannot divide by zero");
+        }
+        assert_eq!(error(json!({ "operation": "add", "a": "ten", "b": 1, "precision": "decimal" })), "a is not a decimal number");
+        assert_eq!(error(json!({ "operation": "add", "a": 1, "b": "ten" })), "b is not a finite number");
+        for value in ["NaN", "inf", "-infinity", "1e400"] {
+            assert_eq!(error(json!({ "operation": "add", "a": value, "b": 1 })), "a is not a finite number", "{}", value);
+        }
+        assert_eq!(error(json!({ "operation": "multiply", "a": 1e308, "b": 10 })), "result is out of range for float precision");
+        let float_only = "scale and rounding only apply to decimal or integer precision";
+        assert_eq!(error(json!({ "operation": "divide", "a": 1, "b": 3, "scale": 2 })), float_only);
+        assert_eq!(error(json!({ "operation": "divide", "a": 1, "b": 3, "precision": "float", "rounding": "up" })), float_only);
+        assert_eq!(error(json!({ "operation": "add", "a": "1e5000", "b": 1, "precision": "decimal" })), "a has an exponent beyond 1000");
+        assert_eq!(error(json!({ "operation": "add", "a": 1, "b": 1, "precision": "decimal", "scale": 5000 })), "scale must be at most 1000");
+        assert!(error(json!({ "operation": "add", "a": 1, "b": 1, "precision": "exact" })).starts_with("Invalid arguments"));
+    }
+}
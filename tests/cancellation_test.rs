@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::future::{self, join};
+    use serde_json::json;
+    use {{crate_name}}::cancellation::{self, CancellationToken};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolError, ToolRegistry};
+
+    #[test]
+    fn test_token_cancels_once() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel(Some("User requested".to_string()));
+        clone.cancel(Some("Again".to_string()));
+        assert!(token.is_cancelled());
+        assert_eq!(token.reason().as_deref(), Some("User requested"));
+        block_on(token.cancelled());
+    }
+
+    #[test]
+    fn test_run_until_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(block_on(token.run_until_cancelled(async { 42 })), Some(42));
+
+        let canceller = async {
+            token.cancel(None);
+        };
+        let (result, ()) = block_on(join(token.run_until_cancelled(future::pending::<()>()), canceller));
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_in_flight_requests_are_scoped_to_session() {
+        let in_flight = cancellation::track(Some("session-a"), &json!(1));
+        assert!(!cancellation::cancel(Some("session-b"), &json!(1), None));
+        assert!(!cancellation::cancel(Some("session-a"), &json!("1"), None));
+        assert!(!in_flight.token().is_cancelled());
+
+        assert!(cancellation::cancel(Some("session-a"), &json!(1), Some("timeout".to_string())));
+        assert_eq!(in_flight.token().reason().as_deref(), Some("timeout"));
+
+        drop(in_flight);
+        assert!(!cancellation::cancel(Some("session-a"), &json!(1), None));
+
+        // Without a session, one client could cancel another's request
+        let anonymous = cancellation::track(None, &json!(1));
+        assert!(!cancellation::cancel(None, &json!(1), None));
+        assert!(!anonymous.token().is_cancelled());
+    }
+
+    #[test]
+    fn test_cancelled_tool_call() {
+        let mut registry = ToolRegistry::new();
+        register_default_tools(&mut registry);
+        let ctx = ToolContext::default();
+        ctx.cancellation.cancel(None);
+
+        let result = block_on(registry.call_tool("evaluate", Some(json!({ "expression": "1 + 2" })), &ctx));
+        assert_eq!(result.unwrap_err(), ToolError::Cancelled);
+    }
+}
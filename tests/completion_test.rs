@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::auth::Principal;
+    use {{crate_name}}::completion::{self, Completer, Completion, CompletionRef, CompletionRegistry, StaticCompleter, MAX_VALUES};
+    use {{crate_name}}::resources::kv::{KvBackend, KvResourceProvider, MemoryKv};
+    use {{crate_name}}::resources::r2::{MemoryR2, R2Backend, R2ResourceProvider};
+    use {{crate_name}}::resources::ResourceRegistry;
+    use {{crate_name}}::tools::{register_default_tools, ToolAccess, ToolRegistry};
+
+    fn defaults() -> CompletionRegistry {
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        let mut registry = CompletionRegistry::new();
+        completion::register_default_completers(&mut registry, &tools);
+        registry
+    }
+
+    #[test]
+    fn test_calculator_operation() {
+        let registry = defaults();
+        let calculate = CompletionRef::Tool { name: "calculate".to_string() };
+        let no_context = HashMap::new();
+
+        let completion = registry.complete(&calculate, "operation", "d", &no_context);
+        assert_eq!(completion.values, vec!["divide", "add"]);
+        assert_eq!(completion.total, Some(2));
+        assert!(!completion.has_more);
+
+        assert_eq!(registry.complete(&calculate, "operation", "", &no_context).values.len(), 4);
+        assert_eq!(registry.complete(&calculate, "a", "1", &no_context), Completion::default());
+    }
+
+    #[test]
+    fn test_static_completer_ranking() {
+        let completer = StaticCompleter::new(&["Subtract", "add", "ADDRESS", "padding"]);
+        assert_eq!(completer.complete("ad", &HashMap::new()), vec!["add", "ADDRESS", "padding"]);
+        assert!(completer.complete("zzz", &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_context_and_truncation() {
+        let mut registry = CompletionRegistry::new();
+        let template = CompletionRef::Resource { uri: "repo://{owner}/{repo}".to_string() };
+        registry.register(template.clone(), "repo", |value: &str, arguments: &HashMap<String, String>| {
+            let owner = arguments.get("owner").cloned().unwrap_or_default();
+            (0..150).map(|i| format!("{}/{}{}", owner, value, i)).collect::<Vec<_>>()
+        });
+
+        let arguments = HashMap::from([("owner".to_string(), "acme".to_string())]);
+        let completion = registry.complete(&template, "repo", "api", &arguments);
+        assert_eq!(completion.values.len(), MAX_VALUES);
+        assert_eq!(completion.values[0], "acme/api0");
+        assert_eq!(completion.total, Some(150));
+        assert!(completion.has_more);
+    }
+
+    #[test]
+    fn test_reference_wire_format() {
+        let prompt: CompletionRef = serde_json::from_value(json!({ "type": "ref/prompt", "name": "review" })).unwrap();
+        assert_eq!(prompt, CompletionRef::Prompt { name: "review".to_string() });
+        assert!(serde_json::from_value::<CompletionRef>(json!({ "type": "ref/unknown" })).is_err());
+
+        let completion = Completion::from_ranked(vec!["a".to_string()]);
+        assert_eq!(
+            serde_json::to_value(completion).unwrap(),
+            json!({ "values": ["a"], "total": 1, "hasMore": false })
+        );
+    }
+
+    #[test]
+    fn test_resource_templates_complete_from_their_provider() {
+        let kv = MemoryKv::new();
+        let r2 = MemoryR2::new();
+        block_on(async {
+            kv.put("docs/readme", b"", None).await.unwrap();
+            kv.put("docs/roadmap", b"", None).await.unwrap();
+            kv.put("docs/guide", b"", None).await.unwrap();
+            r2.put("images/logo.png", vec![], None).await.unwrap();
+            r2.put("images/icon.png", vec![], None).await.unwrap();
+            r2.put("private/keys.txt", vec![], None).await.unwrap();
+        });
+        let mut resources = ResourceRegistry::new();
+        resources.register_provider(KvResourceProvider::new("docs", "docs/", kv));
+        let prefixes = vec!["docs/".to_string(), "images/".to_string()];
+        resources.register_provider_with_access(R2ResourceProvider::new("files", &prefixes, r2), ToolAccess::scopes(&["files:read"]));
+
+        let complete = |template: &str, argument: &str, value: &str| {
+            block_on(resources.complete(template, argument, value, 10)).unwrap()
+        };
+        assert_eq!(complete("kv://docs/{key}", "key", "r"), vec!["readme", "roadmap"]);
+        assert!(complete("kv://docs/{key}", "namespace", "").is_empty());
+        assert_eq!(complete("r2://files/{+key}", "key", ""), vec!["docs/", "images/"]);
+        assert_eq!(complete("r2://files/{+key}", "key", "i"), vec!["images/"]);
+        assert_eq!(complete("r2://files/{+key}", "key", "images/"), vec!["images/icon.png", "images/logo.png"]);
+        assert!(complete("r2://files/{+key}", "key", "private/").is_empty());
+        assert!(complete("repo://{owner}", "owner", "").is_empty());
+
+        // Restricted providers only complete for callers allowed to use them
+        let reader = Principal {
+            subject: "reader".to_string(),
+            scopes: vec!["files:read".to_string()],
+            roles: Vec::new(),
+            claims: json!({}),
+        };
+        assert!(!resources.allows_template("r2://files/{+key}", None));
+        assert!(resources.allows_template("r2://files/{+key}", Some(&reader)));
+        assert!(resources.allows_template("kv://docs/{key}", None));
+        assert!(resources.allows_template("repo://{owner}", None));
+
+        let completion = Completion::from_first((0..=MAX_VALUES).map(|i| i.to_string()).collect());
+        assert_eq!((completion.values.len(), completion.total, completion.has_more), (MAX_VALUES, None, true));
+    }
+}
@@ -0,0 +1,318 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use {{crate_name}}::config::{ConfigError, Icon, ServerConfig};
+    use {{crate_name}}::cors::OriginPattern;
+    use {{crate_name}}::plugin::{PluginLimits, PluginSource};
+    use {{crate_name}}::rate_limit::{KeyStrategy, RateLimit};
+    use {{crate_name}}::tools::ToolAccess;
+    use {{crate_name}}::upstream::UpstreamConfig;
+
+    fn load(vars: &[(&str, &str)]) -> Result<ServerConfig, ConfigError> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        ServerConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_defaults_from_package() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.name, env!("CARGO_PKG_NAME"));
+        assert_eq!(config.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(config.instructions, None);
+        assert!(!config.rate_limits.is_enabled());
+        assert_eq!(config.cors.allowed_origins, Vec::<OriginPattern>::new());
+        assert_eq!(config.cors.allowed_hosts, Vec::<String>::new());
+        assert!(!config.single_isolate);
+        assert!(load(&[("MCP_SINGLE_ISOLATE", "true")]).unwrap().single_isolate);
+        assert_eq!(load(&[("MCP_SINGLE_ISOLATE", "always")]).unwrap_err().var, "MCP_SINGLE_ISOLATE");
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = load(&[
+            ("MCP_SERVER_NAME", "Weather Server"),
+            ("MCP_SERVER_VERSION", "2.3.0"),
+            ("MCP_INSTRUCTIONS", "Call forecast before alerts."),
+            ("MCP_ENABLED_TOOLS", "evaluate"),
+            ("MCP_RATE_LIMIT", "100/min"),
+            ("MCP_RATE_LIMIT_KEY", "ip"),
+            ("MCP_CORS_ORIGINS", "https://app.example.com"),
+            ("MCP_CORS_ALLOWED_HOSTS", "mcp.example.com"),
+            ("MCP_CORS_ALLOW_CREDENTIALS", "true"),
+            ("MCP_CORS_MAX_AGE", "600"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.name, "Weather Server");
+        assert_eq!(config.version, "2.3.0");
+        assert_eq!(config.instructions.as_deref(), Some("Call forecast before alerts."));
+        assert_eq!(config.rate_limits.global, Some(RateLimit::new(100, 60)));
+        assert_eq!(config.rate_limits.key, KeyStrategy::Ip);
+        assert_eq!(config.cors.allowed_hosts, vec!["mcp.example.com"]);
+        assert!(config.cors.allow_credentials);
+        assert_eq!(config.cors.max_age_secs, 600);
+
+        let tools = config.tool_registry().unwrap();
+        let names: Vec<&str> = tools.get_tools().iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["evaluate"]);
+    }
+
+    #[test]
+    fn test_server_metadata() {
+        let config = load(&[
+            ("MCP_SERVER_TITLE", "Weather"),
+            ("MCP_WEBSITE_URL", "https://weather.example.com"),
+            ("MCP_ICONS", "https://weather.example.com/icon.png, https://weather.example.com/icon.svg"),
+        ])
+        .unwrap();
+        assert_eq!(config.title.as_deref(), Some("Weather"));
+        assert_eq!(config.website_url.as_deref(), Some("https://weather.example.com"));
+        assert_eq!(config.icons[1], Icon::new("https://weather.example.com/icon.svg"));
+
+        let config = load(&[(
+            "MCP_ICONS",
+            r#"[{ "src": "data:image/png;base64,iVBORw0KGgo=", "mimeType": "image/png", "sizes": ["48x48"] }]"#,
+        )])
+        .unwrap();
+        assert_eq!(config.icons[0].mime_type.as_deref(), Some("image/png"));
+        assert_eq!(config.icons[0].sizes, vec!["48x48"]);
+
+        assert_eq!(load(&[("MCP_WEBSITE_URL", "weather.example.com")]).unwrap_err().var, "MCP_WEBSITE_URL");
+        assert_eq!(load(&[("MCP_ICONS", "http://weather.example.com/icon.png")]).unwrap_err().var, "MCP_ICONS");
+        assert_eq!(load(&[("MCP_ICONS", "[{}]")]).unwrap_err().var, "MCP_ICONS");
+    }
+
+    #[test]
+    fn test_invalid_values_name_the_variable() {
+        let error = load(&[("MCP_RATE_LIMIT", "lots")]).unwrap_err();
+        assert_eq!(error.var, "MCP_RATE_LIMIT");
+        assert!(error.to_string().starts_with("Invalid MCP_RATE_LIMIT: "));
+
+        assert_eq!(load(&[("MCP_CORS_MAX_AGE", "1 day")]).unwrap_err().var, "MCP_CORS_MAX_AGE");
+        assert_eq!(load(&[("MCP_CORS_ALLOW_CREDENTIALS", "maybe")]).unwrap_err().var, "MCP_CORS_ALLOW_CREDENTIALS");
+        let wildcard_credentials = load(&[("MCP_CORS_ORIGINS", "*"), ("MCP_CORS_ALLOW_CREDENTIALS", "true")]);
+        assert_eq!(wildcard_credentials.unwrap_err().var, "MCP_CORS_ALLOW_CREDENTIALS");
+        assert_eq!(load(&[("MCP_RATE_LIMIT_KEY", "user")]).unwrap_err().var, "MCP_RATE_LIMIT_KEY");
+        // Without a secret any id verifies, so each made-up one is a new client
+        assert_eq!(load(&[("MCP_RATE_LIMIT_KEY", "session")]).unwrap_err().var, "MCP_RATE_LIMIT_KEY");
+        let config = load(&[("MCP_RATE_LIMIT_KEY", "session"), ("MCP_SESSION_SECRET", "s3cret")]).unwrap();
+        assert_eq!(config.rate_limits.key, KeyStrategy::Session);
+        assert_eq!(load(&[("MCP_CORS_ORIGINS", " , ")]).unwrap_err().var, "MCP_CORS_ORIGINS");
+        assert_eq!(load(&[("MCP_CORS_ALLOWED_HOSTS", ",")]).unwrap_err().var, "MCP_CORS_ALLOWED_HOSTS");
+        assert_eq!(load(&[("MCP_PING_INTERVAL", "30s")]).unwrap_err().var, "MCP_PING_INTERVAL");
+        assert_eq!(load(&[("MCP_PING_MAX_MISSED", "0")]).unwrap_err().var, "MCP_PING_MAX_MISSED");
+        assert_eq!(load(&[("MCP_KV_RESOURCE_PREFIX", "docs/")]).unwrap_err().var, "MCP_KV_RESOURCE_PREFIX");
+        assert_eq!(
+            load(&[("MCP_KV_RESOURCES", "DOCS"), ("MCP_KV_RESOURCE_NAMESPACE", "my docs")]).unwrap_err().var,
+            "MCP_KV_RESOURCE_NAMESPACE"
+        );
+        assert_eq!(load(&[("MCP_R2_RESOURCE_WRITABLE", "true")]).unwrap_err().var, "MCP_R2_RESOURCE_WRITABLE");
+    }
+
+    #[test]
+    fn test_kv_resources() {
+        let config = load(&[("MCP_KV_RESOURCES", "DOCS_KV")]).unwrap();
+        let kv = config.kv_resources.as_ref().unwrap();
+        assert_eq!((kv.binding.as_str(), kv.namespace.as_str(), kv.writable), ("DOCS_KV", "docs-kv", false));
+        assert!(config.tool_registry().unwrap().get_tools().iter().all(|tool| tool.name() != "kv_write"));
+
+        let config = load(&[
+            ("MCP_KV_RESOURCES", "DOCS_KV"),
+            ("MCP_KV_RESOURCE_NAMESPACE", "docs"),
+            ("MCP_KV_RESOURCE_PREFIX", "public/"),
+            ("MCP_KV_RESOURCE_WRITABLE", "true"),
+        ])
+        .unwrap();
+        let kv = config.kv_resources.as_ref().unwrap();
+        assert_eq!((kv.namespace.as_str(), kv.prefix.as_str(), kv.writable), ("docs", "public/", true));
+        let tools = config.tool_registry().unwrap();
+        assert!(tools.get_tools().iter().any(|tool| tool.name() == "kv_write"));
+        assert!(tools.get_tools().iter().any(|tool| tool.name() == "kv_delete"));
+        assert!(kv.access.is_public());
+
+        // Resource access is its own, whichever tools are enabled
+        let access = ToolAccess::scopes(&["docs:read"]);
+        for enabled in ["evaluate", "evaluate, kv_write"] {
+            let config = load(&[
+                ("MCP_KV_RESOURCES", "DOCS_KV"),
+                ("MCP_KV_RESOURCE_WRITABLE", "true"),
+                ("MCP_KV_RESOURCE_ACCESS", r#"{"scopes": ["docs:read"]}"#),
+                ("MCP_ENABLED_TOOLS", enabled),
+            ])
+            .unwrap();
+            assert_eq!(config.kv_resources.as_ref().unwrap().access, access, "{}", enabled);
+        }
+        let bad = load(&[("MCP_KV_RESOURCES", "DOCS_KV"), ("MCP_KV_RESOURCE_ACCESS", r#"{"scope": "docs:read"}"#)]);
+        assert_eq!(bad.unwrap_err().var, "MCP_KV_RESOURCE_ACCESS");
+        assert_eq!(load(&[("MCP_KV_RESOURCE_ACCESS", "{}")]).unwrap_err().var, "MCP_KV_RESOURCE_ACCESS");
+    }
+
+    #[test]
+    fn test_unknown_enabled_tool() {
+        let config = load(&[("MCP_ENABLED_TOOLS", "evaluate, forecast")]).unwrap();
+        assert_eq!(
+            config.tool_registry().unwrap_err().to_string(),
+            "Invalid MCP_ENABLED_TOOLS: unknown tools: forecast"
+        );
+    }
+
+    #[test]
+    fn test_r2_resources() {
+        let config = load(&[("MCP_R2_RESOURCES", "FILES"), ("MCP_R2_RESOURCE_PREFIXES", "docs/, images/")]).unwrap();
+        let r2 = config.r2_resources.as_ref().unwrap();
+        assert_eq!((r2.bucket.as_str(), r2.writable), ("files", false));
+        assert_eq!(r2.prefixes, vec!["docs/", "images/"]);
+        let tools = config.tool_registry().unwrap();
+        assert!(tools.get_tools().iter().any(|tool| tool.name() == "r2_get"));
+        assert!(tools.get_tools().iter().all(|tool| tool.name() != "r2_put"));
+
+        let config = load(&[("MCP_R2_RESOURCES", "FILES"), ("MCP_R2_RESOURCE_WRITABLE", "yes")]).unwrap();
+        assert!(config.tool_registry().unwrap().get_tools().iter().any(|tool| tool.name() == "r2_delete"));
+
+        let config = load(&[("MCP_R2_RESOURCES", "FILES"), ("MCP_R2_MAX_READ_BYTES", "1048576")]).unwrap();
+        assert_eq!(config.r2_resources.unwrap().max_read_bytes, 1 << 20);
+        assert_eq!(load(&[("MCP_R2_RESOURCES", "FILES"), ("MCP_R2_MAX_READ_BYTES", "0")]).unwrap_err().var, "MCP_R2_MAX_READ_BYTES");
+
+        let config = load(&[
+            ("MCP_R2_RESOURCES", "FILES"),
+            ("MCP_R2_RESOURCE_ACCESS", r#"{"roles": ["staff"]}"#),
+            ("MCP_ENABLED_TOOLS", "evaluate"),
+        ])
+        .unwrap();
+        assert_eq!(config.r2_resources.unwrap().access, ToolAccess::roles(&["staff"]));
+        assert_eq!(load(&[("MCP_R2_RESOURCE_ACCESS", "{}")]).unwrap_err().var, "MCP_R2_RESOURCE_ACCESS");
+    }
+
+    #[test]
+    fn test_d1() {
+        let config = load(&[("MCP_D1_DATABASE", "APP_DB")]).unwrap();
+        let d1 = config.d1.as_ref().unwrap();
+        assert_eq!((d1.name.as_str(), d1.read_only, d1.max_rows), ("app-db", true, 100));
+        let tools = config.tool_registry().unwrap();
+        let tool = tools.get_tools().iter().find(|tool| tool.name() == "sql_query").unwrap();
+        assert!(!tool.is_destructive());
+
+        let config = load(&[
+            ("MCP_D1_DATABASE", "APP_DB"),
+            ("MCP_D1_NAME", "app"),
+            ("MCP_D1_READ_ONLY", "false"),
+            ("MCP_D1_MAX_ROWS", "20"),
+        ])
+        .unwrap();
+        let d1 = config.d1.as_ref().unwrap();
+        assert_eq!((d1.name.as_str(), d1.read_only, d1.max_rows), ("app", false, 20));
+
+        assert_eq!(load(&[("MCP_D1_DATABASE", "APP_DB"), ("MCP_D1_MAX_ROWS", "0")]).unwrap_err().var, "MCP_D1_MAX_ROWS");
+        assert_eq!(load(&[("MCP_D1_READ_ONLY", "false")]).unwrap_err().var, "MCP_D1_READ_ONLY");
+
+        let config = load(&[
+            ("MCP_D1_DATABASE", "APP_DB"),
+            ("MCP_D1_ACCESS", r#"{"scopes": ["db:schema"]}"#),
+            ("MCP_ENABLED_TOOLS", "evaluate"),
+        ])
+        .unwrap();
+        assert_eq!(config.d1.unwrap().access, ToolAccess::scopes(&["db:schema"]));
+        assert_eq!(load(&[("MCP_D1_DATABASE", "APP_DB"), ("MCP_D1_ACCESS", "[]")]).unwrap_err().var, "MCP_D1_ACCESS");
+    }
+
+    #[test]
+    fn test_openapi() {
+        let config = load(&[
+            ("MCP_OPENAPI_BASE_URL", "https://staging.example.com/v2"),
+            ("MCP_OPENAPI_BEARER_TOKEN", "abc123"),
+            ("MCP_OPENAPI_HEADERS", r#"{"X-Api-Key": "key"}"#),
+        ])
+        .unwrap();
+        assert_eq!(config.openapi.base_url.as_deref(), Some("https://staging.example.com/v2"));
+        assert_eq!(
+            config.openapi.headers,
+            vec![
+                ("Authorization".to_string(), "Bearer abc123".to_string()),
+                ("X-Api-Key".to_string(), "key".to_string()),
+            ]
+        );
+        // Header values are secrets
+        assert!(!format!("{:?}", config).contains("abc123"));
+
+        assert_eq!(load(&[("MCP_OPENAPI_BASE_URL", "staging.example.com")]).unwrap_err().var, "MCP_OPENAPI_BASE_URL");
+        assert_eq!(load(&[("MCP_OPENAPI_HEADERS", r#"{"X-Retries": 3}"#)]).unwrap_err().var, "MCP_OPENAPI_HEADERS");
+    }
+
+    #[test]
+    fn test_upstreams() {
+        let config = load(&[
+            ("MCP_UPSTREAMS", "github=https://github-mcp.example.com/mcp, docs=http://localhost:8788/mcp"),
+            ("MCP_UPSTREAM_TIMEOUT", "10"),
+            ("MCP_UPSTREAM_DEADLINE", "60"),
+        ])
+        .unwrap();
+        let names: Vec<(&str, Duration, Duration)> = config.upstreams.iter().map(|u| (u.name.as_str(), u.timeout, u.deadline)).collect();
+        let (ten, sixty) = (Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(names, vec![("github", ten, sixty), ("docs", ten, sixty)]);
+        let tools = config.tool_registry().unwrap();
+        assert_eq!(tools.upstreams().len(), 2);
+
+        let config = load(&[(
+            "MCP_UPSTREAMS",
+            r#"[{"name": "github", "url": "https://github-mcp.example.com/mcp", "timeoutSecs": 5, "deadlineSecs": 20, "headers": {"Authorization": "Bearer ghp_secret"}}]"#,
+        )])
+        .unwrap();
+        assert_eq!(
+            config.upstreams,
+            vec![UpstreamConfig {
+                timeout: Duration::from_secs(5),
+                deadline: Duration::from_secs(20),
+                headers: vec![("Authorization".to_string(), "Bearer ghp_secret".to_string())],
+                ..UpstreamConfig::new("github", "https://github-mcp.example.com/mcp")
+            }]
+        );
+        assert!(!format!("{:?}", config).contains("ghp_secret"));
+
+        let config = load(&[(
+            "MCP_UPSTREAMS",
+            r#"[{"name": "billing", "url": "https://billing.example.com/mcp", "scopes": ["billing:read"]}]"#,
+        )])
+        .unwrap();
+        assert_eq!(config.upstreams[0].access, ToolAccess::scopes(&["billing:read"]));
+        assert_eq!(config.tool_registry().unwrap().access("billing.refund"), ToolAccess::scopes(&["billing:read"]));
+
+        // Upstream tools can be enabled one by one or all at once
+        let config = load(&[("MCP_UPSTREAMS", "docs=https://docs.example.com/mcp"), ("MCP_ENABLED_TOOLS", "evaluate, docs.*")]).unwrap();
+        assert_eq!(config.tool_registry().unwrap().upstreams().len(), 1);
+
+        for bad in ["docs", "a.b=https://x.example.com", "docs=ftp://x.example.com", "a=https://a.example.com,a=https://b.example.com", "[]"] {
+            assert_eq!(load(&[("MCP_UPSTREAMS", bad)]).unwrap_err().var, "MCP_UPSTREAMS", "{}", bad);
+        }
+        let timeout = load(&[("MCP_UPSTREAMS", "docs=https://docs.example.com/mcp"), ("MCP_UPSTREAM_TIMEOUT", "0")]);
+        assert_eq!(timeout.unwrap_err().var, "MCP_UPSTREAM_TIMEOUT");
+        assert_eq!(load(&[("MCP_UPSTREAM_TIMEOUT", "5")]).unwrap_err().var, "MCP_UPSTREAM_TIMEOUT");
+        let deadline = load(&[("MCP_UPSTREAMS", "docs=https://docs.example.com/mcp"), ("MCP_UPSTREAM_DEADLINE", "soon")]);
+        assert_eq!(deadline.unwrap_err().var, "MCP_UPSTREAM_DEADLINE");
+        assert_eq!(load(&[("MCP_UPSTREAM_DEADLINE", "60")]).unwrap_err().var, "MCP_UPSTREAM_DEADLINE");
+    }
+
+    #[test]
+    fn test_plugins() {
+        let config = load(&[
+            ("MCP_PLUGINS", "r2://PLUGINS/text.wasm, kv://CACHE/dates"),
+            ("MCP_PLUGIN_FUEL", "1000"),
+            ("MCP_PLUGIN_MEMORY_MB", "4"),
+        ])
+        .unwrap();
+        assert_eq!(config.plugins.sources.len(), 2);
+        assert_eq!(config.plugins.sources[1], PluginSource::Kv { binding: "CACHE".to_string(), key: "dates".to_string() });
+        assert_eq!(config.plugins.limits, PluginLimits { fuel: 1000, memory_bytes: 4 << 20 });
+        // Plugins are registered once loaded; until then the other tools are
+        // served, and enabled tools the plugins may have are not an error
+        assert_eq!(config.tool_registry().unwrap().get_tools().len(), 2);
+        let config = load(&[("MCP_PLUGINS", "r2://PLUGINS/text.wasm"), ("MCP_ENABLED_TOOLS", "evaluate, shout")]).unwrap();
+        let names: Vec<String> = config.tool_registry().unwrap().get_tools().iter().map(|tool| tool.name().to_string()).collect();
+        assert_eq!(names, vec!["evaluate"]);
+
+        assert_eq!(load(&[("MCP_PLUGINS", "https://example.com/text.wasm")]).unwrap_err().var, "MCP_PLUGINS");
+        assert_eq!(load(&[("MCP_PLUGINS", "r2://PLUGINS/a"), ("MCP_PLUGIN_FUEL", "0")]).unwrap_err().var, "MCP_PLUGIN_FUEL");
+        assert_eq!(load(&[("MCP_PLUGIN_MEMORY_MB", "4")]).unwrap_err().var, "MCP_PLUGIN_MEMORY_MB");
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use {{crate_name}}::cors::{CorsPolicy, OriginPattern};
+
+    fn header<'a>(headers: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+        headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        assert!(OriginPattern::parse("*").matches("https://anything.example"));
+        assert!(OriginPattern::parse("https://app.example.com/").matches("https://APP.example.com"));
+        assert!(!OriginPattern::parse("https://app.example.com").matches("https://app.example.com.evil.net"));
+
+        let subdomains = OriginPattern::parse("https://*.example.com");
+        assert!(subdomains.matches("https://a.b.example.com"));
+        assert!(!subdomains.matches("https://example.com"));
+        assert!(!subdomains.matches("http://a.example.com"));
+        assert!(!subdomains.matches("https://evil.net/.example.com"));
+
+        let any_port = OriginPattern::parse("http://localhost:*");
+        assert!(any_port.matches("http://localhost:6274"));
+        assert!(!any_port.matches("http://localhost.evil.net:80"));
+    }
+
+    const HOST: &str = "mcp.example.com";
+
+    #[test]
+    fn test_default_policy_rejects_every_origin() {
+        let policy = CorsPolicy::default();
+
+        assert!(policy.allows_origin(None, HOST));
+        assert!(!policy.allows_origin(Some("https://mcp.example.com"), HOST));
+        assert!(!policy.allows_origin(Some("http://localhost:8787"), "localhost:8787"));
+
+        let headers = policy.response_headers(Some("https://mcp.example.com"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), None);
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_allowed_hosts_admit_same_host_pages() {
+        let policy = CorsPolicy::default().with_hosts("MCP.example.com, localhost:8787");
+
+        assert!(policy.allows_origin(None, HOST));
+        assert!(policy.allows_origin(Some("https://mcp.example.com"), HOST));
+        assert!(policy.allows_origin(Some("http://localhost:8787"), "localhost:8787"));
+        // Pages on other hosts are rejected
+        assert!(!policy.allows_origin(Some("http://attacker.test:8787"), "localhost:8787"));
+        assert!(!policy.allows_origin(Some("http://localhost:9999"), "localhost:8787"));
+        assert!(!policy.allows_origin(Some("null"), HOST));
+
+        let headers = policy.response_headers(Some("https://mcp.example.com"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("https://mcp.example.com"));
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+        assert_eq!(
+            header(&headers, "Access-Control-Expose-Headers"),
+            Some("Mcp-Session-Id, WWW-Authenticate")
+        );
+        assert_eq!(header(&headers, "Access-Control-Allow-Credentials"), None);
+    }
+
+    #[test]
+    fn test_rebinding_origin_and_host_are_rejected() {
+        // After rebinding, the attacker's page and its requests both name the
+        // attacker's host, so the origin matches the Host header
+        let rebound = Some("http://attacker.test:8787");
+        let rebound_host = "attacker.test:8787";
+
+        assert!(!CorsPolicy::default().allows_origin(rebound, rebound_host));
+        assert!(!CorsPolicy::default().with_hosts("localhost:8787").allows_origin(rebound, rebound_host));
+    }
+
+    #[test]
+    fn test_wildcard_policy() {
+        let policy = CorsPolicy::default().with_origins("*");
+        let headers = policy.response_headers(Some("https://client.example"), HOST);
+
+        assert!(policy.allows_origin(Some("https://client.example"), HOST));
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("*"));
+
+        // Credentials are never sent with the wildcard
+        let credentials = CorsPolicy {
+            allow_credentials: true,
+            ..policy
+        };
+        let headers = credentials.response_headers(Some("https://client.example"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(header(&headers, "Access-Control-Allow-Credentials"), None);
+    }
+
+    #[test]
+    fn test_allowlist_rejects_rebinding_origin() {
+        let policy = CorsPolicy::default().with_origins("http://localhost:8787, https://*.example.com");
+
+        assert!(policy.allows_origin(None, HOST));
+        assert!(policy.allows_origin(Some("http://localhost:8787"), HOST));
+        assert!(policy.allows_origin(Some("https://inspector.example.com"), HOST));
+        assert!(!policy.allows_origin(Some("http://attacker.test:8787"), "attacker.test:8787"));
+
+        let headers = policy.response_headers(Some("http://attacker.test:8787"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), None);
+    }
+
+    #[test]
+    fn test_credentials_echo_listed_origin() {
+        let policy = CorsPolicy {
+            allow_credentials: true,
+            ..CorsPolicy::default().with_origins("https://client.example")
+        };
+
+        let headers = policy.response_headers(Some("https://client.example"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("https://client.example"));
+        assert_eq!(header(&headers, "Access-Control-Allow-Credentials"), Some("true"));
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_redirect_keeps_its_headers() {
+        // The authorize POST answers with a redirect, whose headers cannot be
+        // changed in place, so they are merged into a new set
+        let policy = CorsPolicy::default().with_hosts(HOST);
+        let redirect = vec![
+            ("location".to_string(), "https://app.example/cb?code=abc".to_string()),
+            ("vary".to_string(), "Accept".to_string()),
+        ];
+        let headers = policy.merge_headers(redirect, Some("https://mcp.example.com"), HOST);
+        let value = |name: &str| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str());
+
+        assert_eq!(value("Location"), Some("https://app.example/cb?code=abc"));
+        assert_eq!(value("Access-Control-Allow-Origin"), Some("https://mcp.example.com"));
+        assert_eq!(value("Vary"), Some("Origin"));
+        assert_eq!(headers.iter().filter(|(n, _)| n.eq_ignore_ascii_case("vary")).count(), 1);
+    }
+
+    #[test]
+    fn test_preflight_headers() {
+        let policy = CorsPolicy::default().with_origins("https://client.example");
+
+        let headers = policy.preflight_headers(Some("https://client.example"), HOST);
+        assert_eq!(header(&headers, "Access-Control-Allow-Methods"), Some("GET, POST, DELETE, OPTIONS"));
+        assert!(header(&headers, "Access-Control-Allow-Headers").unwrap().contains("Mcp-Session-Id"));
+        assert_eq!(header(&headers, "Access-Control-Max-Age"), Some("86400"));
+
+        let rejected = policy.preflight_headers(Some("https://other.example"), HOST);
+        assert_eq!(header(&rejected, "Access-Control-Allow-Methods"), None);
+    }
+}
@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::task::Poll;
+
+    use futures::executor::block_on;
+    use futures::future::join;
+    use serde_json::json;
+    use {{crate_name}}::correlator::{self, ClientRequestError};
+    use {{crate_name}}::elicitation::{self, ElicitResult};
+    use {{crate_name}}::protocol::ProtocolVersion;
+    use {{crate_name}}::tools::ToolContext;
+
+    fn context() -> ToolContext {
+        let mut ctx = ToolContext {
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        ctx.client_capabilities.elicitation = Some(json!({}));
+        ctx
+    }
+
+    fn refund_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "amount": { "type": "number", "minimum": 0, "maximum": 500 },
+                "reason": { "type": "string", "enum": ["damaged", "late", "other"] },
+                "notify": { "type": "boolean" }
+            },
+            "required": ["amount", "reason"]
+        })
+    }
+
+    /// Answers the next elicitation with `result`.
+    async fn user(ctx: &ToolContext, result: serde_json::Value) -> serde_json::Value {
+        let request = poll_fn(|cx| match ctx.outbox.take().pop() {
+            Some(message) => Poll::Ready(message),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            },
+        })
+        .await;
+        correlator::resolve(ctx.session_id.as_deref(), &request["id"], &json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }));
+        request
+    }
+
+    #[test]
+    fn test_accepted_content() {
+        let ctx = context();
+        let answer = json!({ "action": "accept", "content": { "amount": 42.5, "reason": "late" } });
+        let (result, request) = block_on(join(ctx.elicit("Refund order #1?", refund_schema()), user(&ctx, answer)));
+
+        assert_eq!(request["method"], "elicitation/create");
+        assert_eq!(request["params"]["message"], "Refund order #1?");
+        assert_eq!(request["params"]["requestedSchema"], refund_schema());
+        let ElicitResult::Accept(content) = result.unwrap() else {
+            panic!("expected accept");
+        };
+        assert_eq!(content["amount"], 42.5);
+    }
+
+    #[test]
+    fn test_decline_and_confirm() {
+        let ctx = context();
+        let (result, _) = block_on(join(ctx.elicit("Refund?", refund_schema()), user(&ctx, json!({ "action": "decline" }))));
+        assert_eq!(result.unwrap(), ElicitResult::Decline);
+
+        let yes = json!({ "action": "accept", "content": { "confirm": true } });
+        let (confirmed, request) = block_on(join(ctx.confirm("Delete 3 files?"), user(&ctx, yes)));
+        assert!(confirmed.unwrap());
+        assert_eq!(request["params"]["requestedSchema"], elicitation::confirmation_schema());
+
+        let (confirmed, _) = block_on(join(ctx.confirm("Delete 3 files?"), user(&ctx, json!({ "action": "cancel" }))));
+        assert!(!confirmed.unwrap());
+    }
+
+    #[test]
+    fn test_invalid_content_is_rejected() {
+        let schema = refund_schema();
+        let cases = [
+            json!({ "action": "accept", "content": { "amount": 42 } }),
+            json!({ "action": "accept", "content": { "amount": 900, "reason": "late" } }),
+            json!({ "action": "accept", "content": { "amount": 1, "reason": "bored" } }),
+            json!({ "action": "accept", "content": { "amount": 1, "reason": "late", "extra": 1 } }),
+            json!({ "action": "accept", "content": { "amount": "1", "reason": "late" } }),
+            json!({ "action": "maybe" }),
+        ];
+        for case in cases {
+            assert!(elicitation::parse_result(case.clone(), &schema).is_err(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_schema_and_capability_checks() {
+        let ctx = context();
+        let nested = json!({ "type": "object", "properties": { "address": { "type": "object" } } });
+        assert!(matches!(block_on(ctx.elicit("Where?", nested)), Err(ClientRequestError::InvalidRequest(_))));
+
+        let without = ToolContext {
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        assert_eq!(
+            block_on(without.confirm("Sure?")).unwrap_err(),
+            ClientRequestError::Unsupported("elicitation")
+        );
+
+        // Versions before elicitation existed cannot use it, whatever they declare
+        let older = ToolContext {
+            protocol_version: ProtocolVersion::parse("2025-03-26").unwrap(),
+            ..context()
+        };
+        assert_eq!(
+            block_on(older.confirm("Sure?")).unwrap_err(),
+            ClientRequestError::Unsupported("elicitation")
+        );
+    }
+}
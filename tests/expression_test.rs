@@ -0,0 +1,200 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use {{crate_name}}::expression::{evaluate, parse, BinaryOp, Expression, UnaryOp, CONSTANTS, FUNCTIONS, MAX_DEPTH, MAX_LENGTH};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolRegistry};
+
+    fn eval(source: &str) -> f64 {
+        evaluate(source, &HashMap::new()).unwrap_or_else(|e| panic!("{}: {}", source, e))
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let e = evaluate(source, &HashMap::from([("x".to_string(), 2.0)])).unwrap_err();
+        (e.column, e.message)
+    }
+
+    /// xorshift64, so failures reproduce from the seed in the message.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("2 ** 10"), 1024.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("7 % 4 * 2"), 6.0);
+        assert_eq!(eval("--3"), 3.0);
+        assert_eq!(eval("6 ÷ 3 × 2 − 1"), 3.0);
+        assert_eq!(eval("1.5e3 + .5"), 1500.5);
+    }
+
+    #[test]
+    fn test_functions_constants_and_variables() {
+        assert_eq!(eval("sqrt(16) + ln(e)"), 5.0);
+        assert_eq!(eval("max(1, 7, 3) - min(4, 2)"), 5.0);
+        assert_eq!(eval("hypot(3, 4)"), 5.0);
+        assert_eq!(eval("round(sin(pi / 2) * 100)"), 100.0);
+        assert_eq!(eval("tau / pi"), 2.0);
+
+        let variables = HashMap::from([("x".to_string(), 3.0), ("pi".to_string(), 3.0)]);
+        assert_eq!(evaluate("2 * (x + 1) ^ 2", &variables).unwrap(), 32.0);
+        // Variables shadow constants
+        assert_eq!(evaluate("pi", &variables).unwrap(), 3.0);
+
+        let expression = parse("x * y + pi + x").unwrap();
+        assert_eq!(expression.variables(), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_errors_point_at_the_problem() {
+        assert_eq!(error(""), (1, "Expression is empty".to_string()));
+        assert_eq!(error("1 + "), (5, "Expected a number, name or '(' but the expression ended".to_string()));
+        assert_eq!(error("2 * $"), (5, "Unexpected character '$'".to_string()));
+        assert_eq!(error("(1 + 2"), (7, "Expected ')' but the expression ended".to_string()));
+        assert_eq!(error("1 + 2)"), (6, "Unmatched ')'".to_string()));
+        assert_eq!(error("1 2"), (3, "Expected an operator but found '2'".to_string()));
+        assert_eq!(error("x + y"), (5, "Unknown variable y".to_string()));
+        assert_eq!(error("1 / (x - 2)"), (3, "Division by zero".to_string()));
+        assert_eq!(error("5 % 0"), (3, "Division by zero".to_string()));
+        assert_eq!(error("1 + sqrt(-1)"), (5, "sqrt is undefined for these values".to_string()));
+        assert_eq!(error("10 ^ 400"), (4, "^ overflowed".to_string()));
+        assert_eq!(error("foo(1)"), (1, "Unknown function foo".to_string()));
+        assert_eq!(error("pow(2)"), (1, "pow takes 2 arguments but got 1".to_string()));
+        assert_eq!(error("1.2.3"), (1, "Invalid number 1.2.3".to_string()));
+
+        let e = parse("2 * $").unwrap_err();
+        assert_eq!(e.to_string(), "Unexpected character '$' at column 5");
+        assert_eq!(e.pointer("2 * $"), "2 * $\n    ^");
+    }
+
+    #[test]
+    fn test_limits() {
+        let long = "1+".repeat(MAX_LENGTH / 2) + "1";
+        assert!(parse(&long).unwrap_err().message.starts_with("Expression is longer than"));
+
+        let deep = "(".repeat(MAX_DEPTH + 1) + "1" + &")".repeat(MAX_DEPTH + 1);
+        assert!(parse(&deep).unwrap_err().message.contains("nested"), "{:?}", parse(&deep));
+        let fine = "(".repeat(MAX_DEPTH / 2) + "1" + &")".repeat(MAX_DEPTH / 2);
+        assert_eq!(eval(&fine), 1.0);
+    }
+
+    #[test]
+    fn test_evaluate_tool() {
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        let call = |arguments: Value| {
+            let result = block_on(tools.call_tool("evaluate", Some(arguments), &ToolContext::default())).unwrap();
+            serde_json::to_value(result).unwrap()
+        };
+
+        let result = call(json!({ "expression": "2 * (x + 1) ^ 2", "variables": { "x": 2 } }));
+        assert_eq!(result["content"][0]["text"], json!("18"));
+        assert_eq!(result["structuredContent"], json!({ "result": 18.0 }));
+
+        let result = call(json!({ "expression": "1 +* 2" }));
+        assert_eq!(result["isError"], json!(true));
+        assert_eq!(
+            result["content"][0]["text"],
+            json!("Expected a number, name or '(' but found '*' at column 4\n1 +* 2\n   ^")
+        );
+    }
+
+    /// Arbitrary input is rejected with an error, never a panic, and the
+    /// error points inside the input or just past its end.
+    #[test]
+    fn fuzz_random_input_never_panics() {
+        let alphabet: Vec<char> = "0123456789.eE+-*/%^(),  xyzpiqrtmaxn_$×−ü".chars().collect();
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20_000 {
+            let length = rng.below(24);
+            let source: String = (0..length).map(|_| alphabet[rng.below(alphabet.len())]).collect();
+            let variables = HashMap::from([("x".to_string(), rng.below(5) as f64)]);
+            if let Err(e) = evaluate(&source, &variables) {
+                assert!(e.column >= 1 && e.column <= source.chars().count() + 1, "{:?}: {:?}", source, e);
+                e.pointer(&source);
+            }
+        }
+
+        for seed in 1..2_000u64 {
+            let mut rng = Rng(seed);
+            let bytes: Vec<u8> = (0..rng.below(32)).map(|_| rng.next() as u8).collect();
+            let _ = parse(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    fn random_expression(rng: &mut Rng, depth: usize) -> Expression {
+        let leaf = depth == 0 || rng.below(4) == 0;
+        if leaf {
+            return match rng.below(3) {
+                0 => Expression::Number(rng.below(20) as f64 / 4.0),
+                1 => {
+                    let name = ["x", "y", CONSTANTS[rng.below(CONSTANTS.len())].0][rng.below(3)];
+                    Expression::Name { name: name.to_string(), position: 0 }
+                },
+                _ => Expression::Number(rng.below(1000) as f64),
+            };
+        }
+        match rng.below(4) {
+            0 => Expression::Unary {
+                op: if rng.below(2) == 0 { UnaryOp::Negate } else { UnaryOp::Plus },
+                operand: Box::new(random_expression(rng, depth - 1)),
+            },
+            1 => {
+                let (function, arity) = FUNCTIONS[rng.below(FUNCTIONS.len())];
+                let count = arity.unwrap_or_else(|| 1 + rng.below(3));
+                Expression::Call {
+                    function: function.to_string(),
+                    arguments: (0..count).map(|_| random_expression(rng, depth - 1)).collect(),
+                    position: 0,
+                }
+            },
+            _ => {
+                let ops = [BinaryOp::Add, BinaryOp::Subtract, BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Remainder, BinaryOp::Power];
+                Expression::Binary {
+                    op: ops[rng.below(ops.len())],
+                    left: Box::new(random_expression(rng, depth - 1)),
+                    right: Box::new(random_expression(rng, depth - 1)),
+                    position: 0,
+                }
+            },
+        }
+    }
+
+    /// Printing a random tree and parsing it back gives an expression that
+    /// evaluates the same way, so the parser builds the tree it was given.
+    #[test]
+    fn fuzz_round_trips_random_expressions() {
+        let variables = HashMap::from([("x".to_string(), 1.5), ("y".to_string(), -2.0)]);
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5_000 {
+            let expression = random_expression(&mut rng, 5);
+            let source = expression.to_string();
+            let parsed = parse(&source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+            assert_eq!(parsed.to_string(), source);
+            match (expression.eval(&variables), parsed.eval(&variables)) {
+                (Ok(expected), Ok(actual)) => assert_eq!(expected.to_bits(), actual.to_bits(), "{}", source),
+                (Err(expected), Err(actual)) => assert_eq!(expected.message, actual.message, "{}", source),
+                (expected, actual) => panic!("{}: {:?} != {:?}", source, expected, actual),
+            }
+        }
+    }
+}
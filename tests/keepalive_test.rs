@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use {{crate_name}}::cancellation;
+    use {{crate_name}}::keepalive::{self, Heartbeat, PingConfig};
+    use {{crate_name}}::logging::{self, LogLevel};
+
+    fn config(max_missed: u32) -> PingConfig {
+        PingConfig {
+            interval_secs: 30,
+            max_missed,
+        }
+    }
+
+    #[test]
+    fn test_pings_are_requests() {
+        let mut heartbeat = Heartbeat::new(config(3), None);
+        let ping = heartbeat.next_ping().unwrap();
+        assert_eq!(ping["jsonrpc"], "2.0");
+        assert_eq!(ping["method"], "ping");
+        assert_ne!(heartbeat.next_ping().unwrap()["id"], ping["id"]);
+        assert!(!PingConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn test_pongs_keep_the_stream_alive() {
+        let mut heartbeat = Heartbeat::new(config(2), Some("session-live".to_string()));
+        for _ in 0..5 {
+            let ping = heartbeat.next_ping().expect("stream should stay open");
+            // Another session cannot answer for this one
+            assert!(!keepalive::record_pong(Some("session-other"), &ping["id"]));
+            assert!(!keepalive::record_pong(None, &ping["id"]));
+            assert!(keepalive::record_pong(Some("session-live"), &ping["id"]));
+            assert!(!keepalive::record_pong(Some("session-live"), &ping["id"]));
+        }
+        assert_eq!(heartbeat.missed(), 0);
+        assert!(!keepalive::record_pong(Some("session-live"), &json!(1)));
+    }
+
+    #[test]
+    fn test_missed_pings_release_the_session_state() {
+        logging::set_session_level(Some("session-dead"), LogLevel::Debug);
+        let in_flight = cancellation::track(Some("session-dead"), &json!(9));
+
+        let mut heartbeat = Heartbeat::new(config(2), Some("session-dead".to_string()));
+        let first = heartbeat.next_ping().unwrap();
+        heartbeat.next_ping().unwrap();
+        assert_eq!(heartbeat.missed(), 2);
+        assert!(heartbeat.next_ping().is_none());
+
+        assert_eq!(logging::session_level(Some("session-dead")), LogLevel::Info);
+        assert!(in_flight.token().is_cancelled());
+        assert_eq!(in_flight.token().reason().as_deref(), Some("Session stream closed"));
+
+        drop(heartbeat);
+        assert!(!keepalive::record_pong(Some("session-dead"), &first["id"]));
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::rc::Rc;
+
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::notifications::SessionStream;
+    use {{crate_name}}::resources::kv::{KvBackend, KvResourceProvider, MemoryKv, PAGE_SIZE};
+    use {{crate_name}}::resources::r2::{MemoryR2, R2Backend, R2ResourceProvider};
+    use {{crate_name}}::resources::{subscriptions, ResourceError, ResourceProvider, ResourceRegistry};
+    use {{crate_name}}::tools::{kv, ToolContext, ToolRegistry};
+
+    fn store() -> MemoryKv {
+        let kv = MemoryKv::new();
+        block_on(async {
+            kv.put("docs/readme", b"# Hello", Some(json!({ "mimeType": "text/markdown" }))).await.unwrap();
+            kv.put("docs/logo", &[0x89, 0x50, 0x4e, 0x47], Some(json!({ "contentType": "image/png" }))).await.unwrap();
+            kv.put("docs/plain", b"no metadata", None).await.unwrap();
+            kv.put("private/secret", b"hidden", None).await.unwrap();
+        });
+        kv
+    }
+
+    #[test]
+    fn test_lists_keys_under_prefix() {
+        let provider = KvResourceProvider::new("docs", "docs/", store());
+        let page = block_on(provider.list(None)).unwrap();
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(
+            serde_json::to_value(&page.resources).unwrap(),
+            json!([
+                { "uri": "kv://docs/logo", "name": "logo", "mimeType": "image/png" },
+                { "uri": "kv://docs/plain", "name": "plain" },
+                { "uri": "kv://docs/readme", "name": "readme", "mimeType": "text/markdown" },
+            ])
+        );
+        assert_eq!(provider.templates()[0].uri_template, "kv://docs/{key}");
+        assert!(provider.handles("kv://docs/anything"));
+        assert!(!provider.handles("kv://other/readme"));
+        assert!(!provider.handles("kv://docs/"));
+    }
+
+    #[test]
+    fn test_pages_through_large_namespaces() {
+        let kv = MemoryKv::new();
+        let count = PAGE_SIZE as usize * 2 + 5;
+        block_on(async {
+            for i in 0..count {
+                kv.put(&format!("key-{:04}", i), b"x", None).await.unwrap();
+            }
+        });
+        let mut registry = ResourceRegistry::new();
+        registry.register_provider(KvResourceProvider::new("cache", "", kv));
+
+        let mut cursor = None;
+        let mut pages = 0;
+        let mut uris = Vec::new();
+        loop {
+            let page = block_on(registry.list(cursor.as_deref(), None)).unwrap();
+            pages += 1;
+            uris.extend(page.resources.into_iter().map(|resource| resource.uri));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(uris.len(), count);
+        assert_eq!(uris[0], "kv://cache/key-0000");
+        assert_eq!(uris[count - 1], format!("kv://cache/key-{:04}", count - 1));
+    }
+
+    #[test]
+    fn test_reads_text_and_binary_values() {
+        let provider = KvResourceProvider::new("docs", "docs/", store());
+        assert_eq!(
+            serde_json::to_value(block_on(provider.read("kv://docs/readme")).unwrap()).unwrap(),
+            json!([{ "uri": "kv://docs/readme", "mimeType": "text/markdown", "text": "# Hello" }])
+        );
+        assert_eq!(
+            serde_json::to_value(block_on(provider.read("kv://docs/logo")).unwrap()).unwrap(),
+            json!([{ "uri": "kv://docs/logo", "mimeType": "image/png", "blob": "iVBORw==" }])
+        );
+        assert_eq!(
+            serde_json::to_value(block_on(provider.read("kv://docs/plain")).unwrap()).unwrap(),
+            json!([{ "uri": "kv://docs/plain", "text": "no metadata" }])
+        );
+        // Keys outside the prefix are not reachable
+        assert_eq!(
+            block_on(provider.read("kv://docs/../private/secret")).unwrap_err(),
+            ResourceError::NotFound("kv://docs/../private/secret".to_string())
+        );
+        assert_eq!(
+            block_on(provider.read("kv://docs/missing")).unwrap_err(),
+            ResourceError::NotFound("kv://docs/missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_only_by_default() {
+        let provider = KvResourceProvider::new("docs", "docs/", store());
+        assert_eq!(
+            block_on(provider.write("kv://docs/readme", b"changed", None)).unwrap_err(),
+            ResourceError::ReadOnly("kv://docs/readme".to_string())
+        );
+        assert_eq!(
+            block_on(provider.delete("kv://docs/readme")).unwrap_err(),
+            ResourceError::ReadOnly("kv://docs/readme".to_string())
+        );
+    }
+
+    #[test]
+    fn test_write_tools_go_through_the_provider() {
+        let kv = store();
+        let mut resources = ResourceRegistry::new();
+        resources.register_provider(KvResourceProvider::new("docs", "docs/", kv.clone()).writable(true));
+        let r2 = MemoryR2::new();
+        resources.register_provider(R2ResourceProvider::new("files", &[], r2.clone()).writable(true));
+        let mut tools = ToolRegistry::new();
+        kv::register_kv_tools(&mut tools);
+        let ctx = ToolContext {
+            resources: Rc::new(resources),
+            ..Default::default()
+        };
+
+        let stream = SessionStream::open("kv-session");
+        subscriptions::subscribe("kv-session", "kv://docs/notes").unwrap();
+
+        let arguments = json!({ "uri": "kv://docs/notes", "value": "{}", "mimeType": "application/json" });
+        let result = block_on(tools.call_tool("kv_write", Some(arguments), &ctx)).unwrap();
+        assert_eq!(serde_json::to_value(&result).unwrap()["isError"], json!(false));
+        let (value, metadata) = block_on(kv.get("docs/notes")).unwrap().unwrap();
+        assert_eq!((value.as_slice(), metadata), (&b"{}"[..], Some(json!({ "mimeType": "application/json" }))));
+        let messages = block_on(poll_fn(|cx| stream.poll_messages(cx)));
+        assert_eq!(messages[0]["params"], json!({ "uri": "kv://docs/notes" }));
+
+        let result = block_on(tools.call_tool("kv_delete", Some(json!({ "uri": "kv://docs/notes" })), &ctx)).unwrap();
+        assert_eq!(serde_json::to_value(&result).unwrap()["isError"], json!(false));
+        assert_eq!(block_on(kv.get("docs/notes")).unwrap(), None);
+
+        let arguments = json!({ "uri": "kv://elsewhere/notes", "value": "x" });
+        let result = serde_json::to_value(block_on(tools.call_tool("kv_write", Some(arguments), &ctx)).unwrap()).unwrap();
+        assert_eq!(result["isError"], json!(true));
+        assert_eq!(result["content"][0]["text"], json!("Resource not found: kv://elsewhere/notes"));
+
+        // Other stores stay behind their own tools
+        let arguments = json!({ "uri": "r2://files/notes", "value": "x" });
+        let result = serde_json::to_value(block_on(tools.call_tool("kv_write", Some(arguments), &ctx)).unwrap()).unwrap();
+        assert_eq!(result["content"][0]["text"], json!("Invalid arguments: uri must start with kv://"));
+        assert_eq!(block_on(r2.get("notes", u64::MAX)).unwrap(), None);
+
+        subscriptions::unsubscribe_all("kv-session");
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::logging::{self, LogLevel, Logger};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolRegistry};
+
+    #[test]
+    fn test_levels_follow_rfc_5424_order() {
+        assert!(LogLevel::Debug < LogLevel::Info);
+        assert!(LogLevel::Warning < LogLevel::Error);
+        assert!(LogLevel::Alert < LogLevel::Emergency);
+        assert_eq!(LogLevel::default(), LogLevel::Info);
+        assert_eq!(serde_json::from_value::<LogLevel>(json!("critical")).unwrap(), LogLevel::Critical);
+        assert!(serde_json::from_value::<LogLevel>(json!("verbose")).is_err());
+        assert_eq!(LogLevel::Notice.to_string(), "notice");
+    }
+
+    #[test]
+    fn test_messages_are_filtered_by_level() {
+        let logger = Logger::new(LogLevel::Warning);
+        logger.info("hidden");
+        logger.error(json!({ "code": 7 }));
+
+        assert_eq!(
+            logger.take_messages(),
+            vec![json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": "error", "data": { "code": 7 } },
+            })]
+        );
+        assert!(logger.take_messages().is_empty());
+
+        logger.set_level(LogLevel::Debug);
+        logger.debug("shown");
+        assert_eq!(logger.take_messages().len(), 1);
+    }
+
+    #[test]
+    fn test_named_loggers_share_state() {
+        let logger = Logger::new(LogLevel::Info);
+        let tool_logger = logger.named("calculate");
+        tool_logger.notice("started");
+
+        let messages = logger.take_messages();
+        assert_eq!(messages[0]["params"]["logger"], "calculate");
+        assert_eq!(messages[0]["params"]["data"], "started");
+
+        logger.set_level(LogLevel::Error);
+        assert_eq!(tool_logger.level(), LogLevel::Error);
+    }
+
+    #[test]
+    fn test_session_levels() {
+        logging::set_session_level(Some("session-a"), LogLevel::Debug);
+        logging::set_session_level(None, LogLevel::Emergency);
+
+        assert_eq!(logging::session_level(Some("session-a")), LogLevel::Debug);
+        assert_eq!(logging::session_level(Some("session-b")), LogLevel::Info);
+        assert_eq!(logging::session_level(None), LogLevel::Info);
+    }
+
+    #[test]
+    fn test_session_levels_are_limited() {
+        for i in 0..logging::MAX_SESSION_LEVELS {
+            logging::set_session_level(Some(&format!("session-{}", i)), LogLevel::Debug);
+        }
+        // Setting a known session again keeps the others
+        logging::set_session_level(Some("session-0"), LogLevel::Error);
+        assert_eq!(logging::session_level(Some("session-1")), LogLevel::Debug);
+
+        // A new one pushes out the oldest
+        logging::set_session_level(Some("session-new"), LogLevel::Warning);
+        assert_eq!(logging::session_level(Some("session-new")), LogLevel::Warning);
+        assert_eq!(logging::session_level(Some("session-1")), LogLevel::Info);
+        assert_eq!(logging::session_level(Some("session-0")), LogLevel::Error);
+    }
+
+    #[test]
+    fn test_tools_log_through_context() {
+        let mut registry = ToolRegistry::new();
+        register_default_tools(&mut registry);
+        let ctx = ToolContext {
+            logger: Logger::new(LogLevel::Info),
+            ..ToolContext::default()
+        };
+
+        let arguments = json!({ "operation": "divide", "a": 1, "b": 0 });
+        block_on(registry.call_tool("calculate", Some(arguments), &ctx)).unwrap();
+
+        let messages = ctx.logger.take_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"]["level"], "warning");
+    }
+}
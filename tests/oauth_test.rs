@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    use serde_json::json;
+    use {{crate_name}}::auth::{ApiKeyAuthenticator, Authenticator};
+    use {{crate_name}}::oauth::{
+        authorization_server_metadata, basic_credentials, check_grant_type, granted_scope, protected_resource_metadata,
+        refreshed_scope, verify_pkce, ClientRegistrationRequest, RegisteredClient, TokenSigner,
+    };
+
+    const ORIGIN: &str = "https://mcp.example.com";
+
+    fn registration(value: serde_json::Value) -> ClientRegistrationRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_protected_resource_metadata() {
+        let servers = vec![ORIGIN.to_string()];
+        let scopes = vec!["tools:read".to_string()];
+        let metadata = protected_resource_metadata(ORIGIN, "Example Server", &servers, &scopes);
+
+        assert_eq!(metadata["resource"], "https://mcp.example.com/mcp");
+        assert_eq!(metadata["authorization_servers"], json!([ORIGIN]));
+        assert_eq!(metadata["bearer_methods_supported"], json!(["header"]));
+        assert_eq!(metadata["resource_name"], "Example Server");
+        assert_eq!(metadata["scopes_supported"], json!(["tools:read"]));
+    }
+
+    #[test]
+    fn test_authorization_server_metadata() {
+        let metadata = authorization_server_metadata(ORIGIN, &[]);
+
+        assert_eq!(metadata["issuer"], ORIGIN);
+        assert_eq!(metadata["token_endpoint"], "https://mcp.example.com/token");
+        assert_eq!(metadata["registration_endpoint"], "https://mcp.example.com/register");
+        assert_eq!(metadata["code_challenge_methods_supported"], json!(["S256"]));
+        assert!(metadata.get("scopes_supported").is_none());
+    }
+
+    #[test]
+    fn test_pkce_s256() {
+        // Example from RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert!(verify_pkce(verifier, challenge));
+        assert!(!verify_pkce("short", challenge));
+        assert!(!verify_pkce(&"a".repeat(43), challenge));
+    }
+
+    #[test]
+    fn test_registration_defaults_and_validation() {
+        let request = registration(json!({ "redirect_uris": ["http://localhost:3000/callback"] }))
+            .validate()
+            .unwrap();
+        assert_eq!(request.token_endpoint_auth_method.as_deref(), Some("client_secret_basic"));
+        assert_eq!(request.grant_types, Some(vec!["authorization_code".to_string()]));
+
+        let public = registration(json!({
+            "redirect_uris": ["https://app.example/cb", "com.example.app:/cb"],
+            "token_endpoint_auth_method": "none",
+            "grant_types": ["authorization_code", "refresh_token"],
+        }));
+        assert!(public.validate().is_ok());
+
+        for invalid in [
+            json!({ "redirect_uris": [] }),
+            json!({ "redirect_uris": ["http://evil.example/cb"] }),
+            json!({ "redirect_uris": ["https://a/cb"], "grant_types": ["password"] }),
+            json!({ "redirect_uris": ["https://a/cb"], "token_endpoint_auth_method": "private_key_jwt" }),
+        ] {
+            assert!(registration(invalid.clone()).validate().is_err(), "accepted {}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_granted_scope_is_limited_to_what_the_key_holds() {
+        let keys = ApiKeyAuthenticator::from_spec("reader:secret:read, admin:root:read admin");
+        let reader = keys.authenticate("secret", 0).unwrap();
+
+        let denied = granted_scope(Some("read admin"), &reader.scopes, None).unwrap_err();
+        assert_eq!((denied.error, denied.description.as_str()), ("invalid_scope", "Scope admin is not available to this account"));
+        assert_eq!(granted_scope(Some("read"), &reader.scopes, None).unwrap().as_deref(), Some("read"));
+        assert_eq!(granted_scope(None, &reader.scopes, None).unwrap().as_deref(), Some("read"));
+        assert_eq!(granted_scope(None, &[], None).unwrap(), None);
+
+        // The client's registered scope narrows it further
+        let admin = keys.authenticate("root", 0).unwrap();
+        assert_eq!(granted_scope(None, &admin.scopes, Some("read")).unwrap().as_deref(), Some("read"));
+        assert!(granted_scope(Some("admin"), &admin.scopes, Some("read")).is_err());
+    }
+
+    #[test]
+    fn test_refresh_rechecks_what_the_key_holds() {
+        let grant = Some("read write");
+        let keys = ApiKeyAuthenticator::from_spec("alice:secret:read");
+        let alice = keys.principal("api-key:alice").unwrap();
+
+        // Write was taken from the key after the grant
+        assert_eq!(refreshed_scope(None, grant, &alice.scopes, None).unwrap().as_deref(), Some("read"));
+        assert!(refreshed_scope(Some("write"), grant, &alice.scopes, None).is_err());
+        // Scopes the key gained are not added to the grant
+        let more = vec!["read".to_string(), "admin".to_string()];
+        assert_eq!(refreshed_scope(None, grant, &more, None).unwrap().as_deref(), Some("read"));
+        assert!(keys.principal("api-key:bob").is_none());
+        assert!(keys.principal("user-42").is_none());
+    }
+
+    #[test]
+    fn test_grant_types_must_be_registered() {
+        let request = registration(json!({ "redirect_uris": ["https://app.example/cb"] })).validate().unwrap();
+        let client = RegisteredClient {
+            client_id: "client".to_string(),
+            client_name: None,
+            redirect_uris: request.redirect_uris,
+            token_endpoint_auth_method: "none".to_string(),
+            grant_types: request.grant_types.unwrap(),
+            scope: None,
+            client_id_issued_at: 0,
+            client_secret_hash: None,
+        };
+
+        assert!(check_grant_type(&client, "authorization_code").is_ok());
+        assert_eq!(check_grant_type(&client, "refresh_token").unwrap_err().error, "unauthorized_client");
+        assert_eq!(check_grant_type(&client, "password").unwrap_err().error, "unsupported_grant_type");
+    }
+
+    #[test]
+    fn test_basic_credentials_are_form_decoded() {
+        let header = format!("Basic {}", STANDARD.encode("my%20client:s%3Acret+1"));
+        assert_eq!(basic_credentials(&header), Some(("my client".to_string(), "s:cret 1".to_string())));
+        assert_eq!(basic_credentials("Bearer abc"), None);
+        assert_eq!(basic_credentials(&format!("Basic {}", STANDARD.encode("no-colon"))), None);
+    }
+
+    #[test]
+    fn test_issued_tokens_verify_against_published_jwks() {
+        let signer = TokenSigner::from_base64(&URL_SAFE_NO_PAD.encode([9u8; 32])).unwrap();
+        let token = signer.sign(&json!({
+            "iss": ORIGIN,
+            "aud": "https://mcp.example.com/mcp",
+            "sub": "api-key:alice",
+            "exp": 2_000_000_000u64,
+            "scope": "tools:call",
+        }));
+
+        let principal = signer.authenticator(ORIGIN).authenticate(&token, 1_900_000_000).unwrap();
+        assert_eq!(principal.subject, "api-key:alice");
+        assert_eq!(principal.scopes, vec!["tools:call"]);
+
+        // Tokens minted for another deployment are rejected
+        assert!(signer.authenticator("https://other.example").authenticate(&token, 1_900_000_000).is_err());
+        assert!(TokenSigner::from_base64("not a key").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use {{crate_name}}::http::{HttpClient, HttpRequest, HttpResponse};
+    use {{crate_name}}::openapi::{parse_spec, Location, OpenApi, SpecError};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolRegistry};
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Pets", "version": "1.0" },
+            "servers": [
+                { "url": "/relative" },
+                { "url": "https://api.example.com/{version}/", "variables": { "version": { "default": "v1" } } }
+            ],
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "operationId": "listPets",
+                        "summary": "List pets",
+                        "parameters": [
+                            { "$ref": "#/components/parameters/Limit" },
+                            { "name": "tags", "in": "query", "schema": { "type": "array", "items": { "type": "string" } } }
+                        ]
+                    },
+                    "post": {
+                        "operationId": "createPet",
+                        "requestBody": {
+                            "required": true,
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Pet" } } }
+                        }
+                    }
+                },
+                "/pets/{petId}": {
+                    "parameters": [{ "name": "petId", "in": "path", "required": true, "schema": { "type": "string" } }],
+                    "get": { "operationId": "getPet", "description": "Fetch one pet" },
+                    "delete": {
+                        "operationId": "deletePet",
+                        "parameters": [{ "name": "X-Reason", "in": "header", "schema": { "type": "string" } }]
+                    }
+                },
+                "/pets/{petId}/rename": {
+                    "post": {
+                        "operationId": "renamePet",
+                        "parameters": [{ "name": "petId", "in": "path", "required": true, "schema": { "type": "integer" } }],
+                        "requestBody": {
+                            "content": {
+                                "application/x-www-form-urlencoded": { "schema": { "type": "object", "properties": { "name": { "type": "string" } } } }
+                            }
+                        }
+                    }
+                },
+                "/health": { "get": {} },
+                "/session": {
+                    "get": { "operationId": "getSession", "parameters": [{ "name": "sid", "in": "cookie", "schema": { "type": "string" } }] }
+                },
+                "/photos": {
+                    "put": { "operationId": "uploadPhoto", "requestBody": { "content": { "multipart/form-data": {} } } }
+                }
+            },
+            "components": {
+                "parameters": {
+                    "Limit": { "name": "limit", "in": "query", "description": "Most pets to return", "schema": { "type": "integer" } }
+                },
+                "schemas": {
+                    "Pet": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": {
+                            "name": { "type": "string" },
+                            "parent": { "$ref": "#/components/schemas/Pet" }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let api = parse_spec(&spec()).unwrap();
+        // Paths are read in sorted order
+        assert_eq!(api.base_url.as_deref(), Some("https://api.example.com/v1"));
+        let names: Vec<&str> = api.operations.iter().map(|operation| operation.name.as_str()).collect();
+        assert_eq!(names, vec!["get_health", "listPets", "createPet", "getPet", "deletePet", "renamePet"]);
+        assert_eq!(
+            api.skipped,
+            vec![
+                "PUT /photos: unsupported request body types: multipart/form-data",
+                "GET /session: cookie parameters are not supported",
+            ]
+        );
+
+        let list = &api.operations[1];
+        assert_eq!((list.method.as_str(), list.description.as_deref()), ("GET", Some("List pets")));
+        assert_eq!(
+            list.input_schema(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "description": "Most pets to return" },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": []
+            })
+        );
+        // The self-reference accepts any value
+        assert_eq!(
+            api.operations[2].input_schema()["properties"]["body"],
+            json!({
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "parent": {}
+                }
+            })
+        );
+        assert_eq!(api.operations[2].input_schema()["required"], json!(["body"]));
+
+        let delete = &api.operations[4];
+        let locations: Vec<(&str, Location)> = delete.parameters.iter().map(|p| (p.name.as_str(), p.location)).collect();
+        assert_eq!(locations, vec![("petId", Location::Path), ("X-Reason", Location::Header)]);
+        let rename = &api.operations[5];
+        assert_eq!(rename.parameters[0].schema, json!({ "type": "integer" }));
+        assert_eq!(rename.body.as_ref().unwrap().content_type, "application/x-www-form-urlencoded");
+    }
+
+    #[test]
+    fn test_rejects_bad_documents() {
+        assert!(matches!(parse_spec(&json!({ "swagger": "2.0" })), Err(SpecError(_))));
+
+        let duplicate = json!({
+            "openapi": "3.1.0",
+            "paths": {
+                "/a": { "get": { "operationId": "fetch" } },
+                "/b": { "get": { "operationId": "fetch" } }
+            }
+        });
+        assert_eq!(
+            parse_spec(&duplicate).unwrap_err(),
+            SpecError("GET /b: tool name fetch is used twice".to_string())
+        );
+
+        let undeclared = json!({ "openapi": "3.1.0", "paths": { "/pets/{id}": { "get": {} } } });
+        let api = parse_spec(&undeclared).unwrap();
+        assert!(api.operations.is_empty());
+        assert_eq!(api.skipped, vec!["GET /pets/{id}: path parameter id is not declared"]);
+    }
+
+    /// A blocking client standing in for `Fetch`.
+    struct Ureq;
+
+    #[async_trait(?Send)]
+    impl HttpClient for Ureq {
+        async fn send(&self, request: HttpRequest) -> worker::Result<HttpResponse> {
+            let mut call = ureq::request(&request.method, &request.url);
+            for (name, value) in &request.headers {
+                call = call.set(name, value);
+            }
+            let response = match &request.body {
+                Some(body) => call.send_string(body),
+                None => call.call(),
+            };
+            match response {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(HttpResponse {
+                    status: response.status(),
+                    headers: response
+                        .headers_names()
+                        .into_iter()
+                        .filter_map(|name| Some((name.clone(), response.header(&name)?.to_string())))
+                        .collect(),
+                    body: response.into_string().unwrap(),
+                }),
+                Err(e) => Err(worker::Error::RustError(e.to_string())),
+            }
+        }
+    }
+
+    /// Echoes each request back as JSON; paths containing `missing` are 404s.
+    fn serve() -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                let headers: Vec<(String, String)> = request
+                    .headers()
+                    .iter()
+                    .map(|header| (header.field.as_str().as_str().to_ascii_lowercase(), header.value.to_string()))
+                    .collect();
+                let header = |name: &str| headers.iter().find(|(field, _)| field == name).map(|(_, value)| value.clone());
+                let url = request.url().to_string();
+                let (status, echo) = match url.contains("missing") {
+                    true => (404, json!({ "error": "no such pet" })),
+                    false => (200, json!({
+                        "method": request.method().to_string(),
+                        "url": url,
+                        "authorization": header("authorization"),
+                        "contentType": header("content-type"),
+                        "reason": header("x-reason"),
+                        "body": body,
+                    })),
+                };
+                let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+                let response = tiny_http::Response::from_string(echo.to_string()).with_status_code(status).with_header(content_type);
+                request.respond(response).unwrap();
+            }
+        });
+        format!("http://{}/v1", address)
+    }
+
+    fn registry() -> ToolRegistry {
+        let api = OpenApi::from_json(&spec().to_string(), Ureq)
+            .unwrap()
+            .base_url(&serve())
+            .header("Authorization", "Bearer secret");
+        let mut tools = ToolRegistry::new();
+        tools.register_openapi(api).unwrap();
+        tools
+    }
+
+    fn call(tools: &ToolRegistry, name: &str, arguments: Value) -> Value {
+        let result = block_on(tools.call_tool(name, Some(arguments), &ToolContext::default())).unwrap();
+        serde_json::to_value(result).unwrap()
+    }
+
+    #[test]
+    fn test_registers_annotated_tools() {
+        let tools = registry();
+        let listed = serde_json::to_value(tools.list_tools(None)).unwrap();
+        assert_eq!(listed[1]["name"], json!("listPets"));
+        assert_eq!(listed[1]["description"], json!("List pets"));
+        assert_eq!(listed[1]["annotations"], json!({ "readOnlyHint": true, "openWorldHint": true }));
+        assert_eq!(
+            listed[4]["annotations"],
+            json!({ "readOnlyHint": false, "destructiveHint": true, "idempotentHint": true, "openWorldHint": true })
+        );
+        assert_eq!(listed[5]["annotations"]["idempotentHint"], json!(false));
+    }
+
+    #[test]
+    fn test_calls_operations() {
+        let tools = registry();
+
+        let result = call(&tools, "listPets", json!({ "limit": 5, "tags": ["dog", "small & fluffy"] }));
+        assert_eq!(result["isError"], json!(false));
+        let echo = &result["structuredContent"];
+        assert_eq!(echo["method"], json!("GET"));
+        assert_eq!(echo["url"], json!("/v1/pets?limit=5&tags=dog&tags=small+%26+fluffy"));
+        assert_eq!(echo["authorization"], json!("Bearer secret"));
+        assert_eq!(serde_json::from_str::<Value>(result["content"][0]["text"].as_str().unwrap()).unwrap(), *echo);
+
+        let echo = call(&tools, "getPet", json!({ "petId": "a b/c" }))["structuredContent"].clone();
+        assert_eq!(echo["url"], json!("/v1/pets/a%20b%2Fc"));
+
+        let echo = call(&tools, "createPet", json!({ "body": { "name": "Rex" } }))["structuredContent"].clone();
+        assert_eq!((&echo["method"], &echo["contentType"]), (&json!("POST"), &json!("application/json")));
+        assert_eq!(serde_json::from_str::<Value>(echo["body"].as_str().unwrap()).unwrap(), json!({ "name": "Rex" }));
+
+        let echo = call(&tools, "renamePet", json!({ "petId": 7, "body": { "name": "Max Power" } }))["structuredContent"].clone();
+        assert_eq!(echo["url"], json!("/v1/pets/7/rename"));
+        assert_eq!(echo["body"], json!("name=Max+Power"));
+
+        let echo = call(&tools, "deletePet", json!({ "petId": "7", "X-Reason": "adopted" }))["structuredContent"].clone();
+        assert_eq!((&echo["method"], &echo["reason"]), (&json!("DELETE"), &json!("adopted")));
+    }
+
+    #[test]
+    fn test_configured_headers_win_over_parameters() {
+        let api = OpenApi::from_json(&spec().to_string(), Ureq)
+            .unwrap()
+            .base_url(&serve())
+            .header("x-reason", "audit");
+        assert!(api.operation("deletePet").unwrap().input_schema()["properties"].get("X-Reason").is_none());
+        let mut tools = ToolRegistry::new();
+        tools.register_openapi(api).unwrap();
+
+        let echo = call(&tools, "deletePet", json!({ "petId": "7", "X-Reason": "adopted" }))["structuredContent"].clone();
+        assert_eq!(echo["reason"], json!("audit"));
+    }
+
+    #[test]
+    fn test_errors_are_tool_errors() {
+        let tools = registry();
+
+        let result = call(&tools, "getPet", json!({ "petId": "missing" }));
+        assert_eq!(result["isError"], json!(true));
+        assert_eq!(result["content"][0]["text"], json!("HTTP 404: {\"error\":\"no such pet\"}"));
+        assert_eq!(result.get("structuredContent"), None);
+
+        let result = call(&tools, "getPet", json!({}));
+        assert_eq!(result["content"][0]["text"], json!("Missing required argument: petId"));
+        let result = call(&tools, "createPet", json!({}));
+        assert_eq!(result["content"][0]["text"], json!("Missing required argument: body"));
+        let result = call(&tools, "renamePet", json!({ "petId": 7, "body": "Max" }));
+        assert_eq!(result["isError"], json!(true));
+
+        // Dot segments would leave the operation's path
+        for pet_id in ["..", ".", ""] {
+            let result = call(&tools, "getPet", json!({ "petId": pet_id }));
+            assert_eq!(result["content"][0]["text"], json!("Invalid arguments: petId must name a path segment"));
+        }
+        let echo = call(&tools, "getPet", json!({ "petId": "..a" }))["structuredContent"].clone();
+        assert_eq!(echo["url"], json!("/v1/pets/..a"));
+
+        let unreachable = OpenApi::from_json(&spec().to_string(), Ureq).unwrap().base_url("http://127.0.0.1:9");
+        let mut tools = ToolRegistry::new();
+        tools.register_openapi(unreachable).unwrap();
+        assert!(block_on(tools.call_tool("getPet", Some(json!({ "petId": "1" })), &ToolContext::default())).is_err());
+    }
+
+    #[test]
+    fn test_rejects_clashing_names() {
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        let clash = json!({ "openapi": "3.0.0", "paths": { "/calculate": { "post": { "operationId": "calculate" } } } });
+        let api = OpenApi::from_json(&clash.to_string(), Ureq).unwrap();
+        assert_eq!(tools.register_openapi(api).unwrap_err(), "tool calculate is already registered");
+    }
+}
@@ -0,0 +1,220 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use {{crate_name}}::plugin::{Plugin, PluginError, PluginLimits, PluginSource, MAX_MODULE_BYTES};
+    use {{crate_name}}::tools::{register_default_tools, ToolContext, ToolRegistry};
+
+    fn quote(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    /// A plugin written against `wit/plugin.wit` by hand: `hello` succeeds,
+    /// `echo` fails with its arguments, `spin` never returns, `grow` asks
+    /// for a lot of memory and `overflow` returns a result at the end of
+    /// the address space.
+    fn module(tools: &Value) -> Vec<u8> {
+        wat::parse_str(module_text(tools)).unwrap()
+    }
+
+    fn module_text(tools: &Value) -> String {
+        let tools = tools.to_string();
+        let hello = json!({ "content": [{ "type": "text", "text": "Hello from WebAssembly" }] }).to_string();
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 32768))
+                (data (i32.const 1024) "{tools}")
+                (data (i32.const 8192) "{hello}")
+                (data (i32.const 16384) "boom")
+
+                (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get 3)))
+                    (local.get $ptr))
+
+                (func $ok (param $tag i32) (param $ptr i32) (param $len i32) (result i32)
+                    (i32.store8 (i32.const 16) (local.get $tag))
+                    (i32.store (i32.const 20) (local.get $ptr))
+                    (i32.store (i32.const 24) (local.get $len))
+                    (i32.const 16))
+
+                (func (export "tools") (result i32)
+                    (i32.store (i32.const 0) (i32.const 1024))
+                    (i32.store (i32.const 4) (i32.const {tools_len}))
+                    (i32.const 0))
+
+                (func (export "invoke") (param $name i32) (param $name_len i32) (param $args i32) (param $args_len i32) (result i32)
+                    (local $first i32)
+                    (local.set $first (i32.load8_u (local.get $name)))
+                    (if (i32.eq (local.get $first) (i32.const 104))
+                        (then (return (call $ok (i32.const 0) (i32.const 8192) (i32.const {hello_len})))))
+                    (if (i32.eq (local.get $first) (i32.const 101))
+                        (then (return (call $ok (i32.const 1) (local.get $args) (local.get $args_len)))))
+                    (if (i32.eq (local.get $first) (i32.const 115))
+                        (then (loop $forever (br $forever))))
+                    (if (i32.eq (local.get $first) (i32.const 103))
+                        (then (drop (memory.grow (i32.const 1024)))))
+                    (if (i32.eq (local.get $first) (i32.const 111))
+                        (then (return (i32.const 2147483647))))
+                    (call $ok (i32.const 2) (i32.const 16384) (i32.const 4))))"#,
+            tools = quote(&tools),
+            tools_len = tools.len(),
+            hello = quote(&hello),
+            hello_len = hello.len(),
+        )
+    }
+
+    /// The same plugin as a component, as `wasm-tools component new` wraps
+    /// a module built with `wit-bindgen`.
+    fn component(tools: &Value) -> Vec<u8> {
+        let module = module_text(tools).replacen("(module", "(core module $plugin", 1);
+        let options = r#"(memory (core memory $instance "memory")) (realloc (core func $instance "cabi_realloc"))"#;
+        let wat = format!(
+            r#"(component
+                {module}
+                (core instance $instance (instantiate $plugin))
+                (type $invoke (func (param "name" string) (param "arguments" string) (result (result string (error string)))))
+                (func (export "tools") (result string) (canon lift (core func $instance "tools") {options}))
+                (func (export "invoke") (type $invoke) (canon lift (core func $instance "invoke") {options})))"#
+        );
+        wat::parse_str(wat).unwrap()
+    }
+
+    fn demo_tools() -> Value {
+        let tool = |name: &str| json!({ "name": name, "description": format!("The {} tool", name), "inputSchema": { "type": "object" } });
+        json!([tool("hello"), tool("echo"), tool("spin"), tool("grow"), tool("overflow"), tool("bad")])
+    }
+
+    fn limits() -> PluginLimits {
+        PluginLimits {
+            fuel: 1_000_000,
+            memory_bytes: 1 << 20,
+        }
+    }
+
+    fn call(tools: &ToolRegistry, name: &str, arguments: Value) -> Value {
+        let result = block_on(tools.call_tool(name, Some(arguments), &ToolContext::default())).unwrap();
+        serde_json::to_value(result).unwrap()
+    }
+
+    #[test]
+    fn test_registers_plugin_tools() {
+        let plugin = Plugin::new("demo", &module(&demo_tools()), limits()).unwrap();
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        tools.register_plugin(plugin).unwrap();
+
+        let listed = serde_json::to_value(tools.list_tools(None)).unwrap();
+        assert_eq!(listed[2], json!({ "name": "hello", "description": "The hello tool", "inputSchema": { "type": "object" } }));
+
+        // The plugin's result is passed on as it is
+        let result = call(&tools, "hello", json!({}));
+        assert_eq!(result, json!({ "content": [{ "type": "text", "text": "Hello from WebAssembly" }], "isError": null }));
+
+        // Arguments reach the plugin, and its errors come back as tool errors
+        let result = call(&tools, "echo", json!({ "text": "hi" }));
+        assert_eq!((&result["isError"], &result["content"][0]["text"]), (&json!(true), &json!("{\"text\":\"hi\"}")));
+    }
+
+    #[test]
+    fn test_plugin_tools_go_to_the_plugin() {
+        // A plugin may take the name of a built-in tool that is not registered
+        let evaluate = module(&json!([{ "name": "evaluate", "inputSchema": { "type": "object" } }]));
+        let mut tools = ToolRegistry::new();
+        tools.register_plugin(Plugin::new("demo", &evaluate, limits()).unwrap()).unwrap();
+
+        let result = call(&tools, "evaluate", json!({ "expression": "1 + 1" }));
+        assert_eq!(result["content"][0]["text"], json!("{\"expression\":\"1 + 1\"}"));
+    }
+
+    #[test]
+    fn test_limits_are_enforced() {
+        let mut tools = ToolRegistry::new();
+        tools.register_plugin(Plugin::new("demo", &module(&demo_tools()), limits()).unwrap()).unwrap();
+
+        let result = call(&tools, "spin", json!({}));
+        assert_eq!((&result["isError"], &result["content"][0]["text"]), (&json!(true), &json!("Plugin demo ran out of fuel")));
+        let result = call(&tools, "grow", json!({}));
+        assert!(result["content"][0]["text"].as_str().unwrap().starts_with("Plugin demo trapped"), "{}", result);
+        let result = call(&tools, "bad", json!({}));
+        assert_eq!(result["content"][0]["text"], json!("Plugin demo broke the calling convention: result discriminant 2"));
+        let result = call(&tools, "overflow", json!({}));
+        assert!(result["content"][0]["text"].as_str().unwrap().starts_with("Plugin demo broke the calling convention"), "{}", result);
+
+        // Each call gets a fresh instance with its own fuel
+        assert_eq!(call(&tools, "hello", json!({}))["content"][0]["text"], json!("Hello from WebAssembly"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_plugins() {
+        assert!(matches!(Plugin::new("junk", b"not wasm", limits()), Err(PluginError::Invalid(_))));
+
+        let importing = wat::parse_str(r#"(module (import "env" "fetch" (func)) (memory (export "memory") 1))"#).unwrap();
+        assert_eq!(
+            Plugin::new("importing", &importing, limits()).unwrap_err(),
+            PluginError::Invalid("imports env.fetch; plugins cannot import anything".to_string())
+        );
+
+        let huge = vec![0; MAX_MODULE_BYTES + 1];
+        assert!(matches!(Plugin::new("huge", &huge, limits()), Err(PluginError::Invalid(_))));
+
+        // Tables count against the store's limits like memory does
+        let table = wat::parse_str(r#"(module (table 100000 funcref) (memory (export "memory") 1))"#).unwrap();
+        assert!(matches!(Plugin::new("table", &table, limits()), Err(PluginError::Trap(_))));
+
+        let malformed = module(&json!({ "name": "hello" }));
+        assert!(matches!(Plugin::new("malformed", &malformed, limits()), Err(PluginError::Invalid(_))));
+
+        let clash = module(&json!([{ "name": "calculate", "inputSchema": { "type": "object" } }]));
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        assert_eq!(
+            tools.register_plugin(Plugin::new("clash", &clash, limits()).unwrap()).unwrap_err(),
+            "tool calculate is already registered"
+        );
+    }
+
+    #[test]
+    fn test_loads_components() {
+        let plugin = Plugin::new("demo", &component(&demo_tools()), limits()).unwrap();
+        assert_eq!(plugin.tools().len(), 6);
+        let mut tools = ToolRegistry::new();
+        tools.register_plugin(plugin).unwrap();
+
+        let result = call(&tools, "hello", json!({}));
+        assert_eq!(result["content"][0]["text"], json!("Hello from WebAssembly"));
+        let result = call(&tools, "spin", json!({}));
+        assert_eq!(result["content"][0]["text"], json!("Plugin demo ran out of fuel"));
+
+        // Components that need a component host are rejected
+        let empty = b"\0asm\x0d\x00\x01\x00";
+        assert_eq!(
+            Plugin::new("empty", empty, limits()).unwrap_err(),
+            PluginError::Invalid("does not export the function tools".to_string())
+        );
+        let wasi = wat::parse_str(r#"(component (import "wasi:cli/environment@0.2.0" (instance)))"#).unwrap();
+        assert_eq!(
+            Plugin::new("wasi", &wasi, limits()).unwrap_err(),
+            PluginError::Invalid("imports wasi:cli/environment@0.2.0; plugins cannot import anything".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        assert_eq!(
+            PluginSource::parse("r2://PLUGINS/tools/text.wasm").unwrap(),
+            PluginSource::R2 { binding: "PLUGINS".to_string(), key: "tools/text.wasm".to_string() }
+        );
+        let kv = PluginSource::parse("kv://CACHE/text").unwrap();
+        assert_eq!(kv.to_string(), "kv://CACHE/text");
+        assert_eq!(
+            PluginSource::parse("assets://ASSETS/plugins/text.wasm").unwrap(),
+            PluginSource::Assets { binding: "ASSETS".to_string(), path: "plugins/text.wasm".to_string() }
+        );
+        for bad in ["https://example.com/text.wasm", "r2://PLUGINS", "kv:///text"] {
+            assert!(PluginSource::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
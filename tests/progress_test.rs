@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use {{crate_name}}::logging::{LogLevel, Logger};
+    use {{crate_name}}::notifications::Outbox;
+    use {{crate_name}}::tools::ToolContext;
+
+    fn context(progress_token: Option<serde_json::Value>) -> ToolContext {
+        let outbox = Outbox::new();
+        ToolContext {
+            logger: Logger::with_outbox(LogLevel::Info, outbox.clone()),
+            outbox,
+            progress_token,
+            ..ToolContext::default()
+        }
+    }
+
+    #[test]
+    fn test_progress_notifications() {
+        let ctx = context(Some(json!("job-1")));
+        ctx.report_progress(1.0, Some(4.0), Some("Fetched page 1"));
+        ctx.report_progress(2.0, None, None);
+
+        assert_eq!(
+            ctx.outbox.take(),
+            vec![
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": { "progressToken": "job-1", "progress": 1.0, "total": 4.0, "message": "Fetched page 1" },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": { "progressToken": "job-1", "progress": 2.0 },
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_token_means_no_progress() {
+        let ctx = context(None);
+        ctx.report_progress(1.0, Some(2.0), None);
+        assert!(ctx.outbox.take().is_empty());
+    }
+
+    #[test]
+    fn test_numeric_token_and_ordering_with_logs() {
+        let ctx = context(Some(json!(7)));
+        ctx.logger.info("starting");
+        ctx.report_progress(0.5, Some(1.0), None);
+        ctx.logger.info("done");
+
+        let methods: Vec<serde_json::Value> = ctx.outbox.take().into_iter().map(|m| m["method"].clone()).collect();
+        assert_eq!(
+            methods,
+            vec![json!("notifications/message"), json!("notifications/progress"), json!("notifications/message")]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use {{crate_name}}::protocol::{Feature, ProtocolVersion, LATEST_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+
+    #[test]
+    fn test_negotiation() {
+        for version in SUPPORTED_PROTOCOL_VERSIONS {
+            assert_eq!(ProtocolVersion::negotiate(Some(version)).as_str(), *version);
+        }
+        assert_eq!(ProtocolVersion::negotiate(Some("2099-01-01")).as_str(), LATEST_PROTOCOL_VERSION);
+        assert_eq!(ProtocolVersion::negotiate(None).as_str(), LATEST_PROTOCOL_VERSION);
+        assert!(ProtocolVersion::parse("2024-10-07").is_none());
+        assert_eq!(ProtocolVersion::assumed().as_str(), "2025-03-26");
+    }
+
+    #[test]
+    fn test_features_follow_the_version() {
+        let version = |v: &str| ProtocolVersion::parse(v).unwrap();
+
+        let oldest = version("2024-11-05");
+        assert!(!oldest.supports(Feature::Completions));
+        assert!(!oldest.supports(Feature::StructuredContent));
+
+        let march = version("2025-03-26");
+        assert!(march.supports(Feature::Completions));
+        assert!(!march.supports(Feature::Elicitation));
+        assert!(!march.supports(Feature::Title));
+
+        let june = version("2025-06-18");
+        assert!(june.supports(Feature::Elicitation));
+        assert!(june.supports(Feature::StructuredContent));
+        assert!(june.supports(Feature::Title));
+        assert!(!june.supports(Feature::Icons));
+
+        assert!(ProtocolVersion::default().supports(Feature::Icons));
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::resources::kv::{KvBackend, KvResourceProvider, MemoryKv};
+    use {{crate_name}}::resources::r2::{MemoryR2, R2Backend, R2ResourceProvider, PAGE_SIZE};
+    use {{crate_name}}::resources::{ResourceError, ResourceProvider, ResourceRegistry};
+    use {{crate_name}}::auth::Principal;
+    use {{crate_name}}::tools::{r2, ToolAccess, ToolContext, ToolRegistry};
+
+    fn store() -> MemoryR2 {
+        let r2 = MemoryR2::new();
+        block_on(async {
+            r2.put("docs/guide.md", b"# Guide".to_vec(), Some("text/markdown")).await.unwrap();
+            r2.put("docs/data.json", b"{\"a\":1}".to_vec(), Some("application/json; charset=utf-8")).await.unwrap();
+            r2.put("images/logo.png", vec![0x89, 0x50, 0x4e, 0x47], Some("image/png")).await.unwrap();
+            r2.put("private/keys.txt", b"secret".to_vec(), Some("text/plain")).await.unwrap();
+        });
+        r2
+    }
+
+    fn prefixes() -> Vec<String> {
+        vec!["docs/".to_string(), "images/".to_string()]
+    }
+
+    fn list_all(provider: &R2ResourceProvider) -> Vec<String> {
+        let mut cursor = None;
+        let mut uris = Vec::new();
+        loop {
+            let page = block_on(provider.list(cursor.as_deref())).unwrap();
+            uris.extend(page.resources.into_iter().map(|resource| resource.uri));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return uris,
+            }
+        }
+    }
+
+    #[test]
+    fn test_lists_objects_under_prefixes() {
+        let provider = R2ResourceProvider::new("files", &prefixes(), store());
+        assert_eq!(
+            list_all(&provider),
+            vec!["r2://files/docs/data.json", "r2://files/docs/guide.md", "r2://files/images/logo.png"]
+        );
+        let page = block_on(provider.list(None)).unwrap();
+        assert_eq!(
+            serde_json::to_value(&page.resources[1]).unwrap(),
+            json!({ "uri": "r2://files/docs/guide.md", "name": "docs/guide.md", "mimeType": "text/markdown", "size": 7 })
+        );
+
+        assert!(provider.handles("r2://files/docs/anything"));
+        assert!(provider.handles("r2://files/"));
+        assert!(!provider.handles("r2://files/private/keys.txt"));
+        assert!(!provider.handles("r2://other/docs/guide.md"));
+
+        let whole_bucket = R2ResourceProvider::new("files", &[], store());
+        assert_eq!(list_all(&whole_bucket).len(), 4);
+        assert!(matches!(block_on(provider.list(Some("7:"))), Err(ResourceError::InvalidCursor(_))));
+    }
+
+    #[test]
+    fn test_pages_through_large_prefixes() {
+        let r2 = MemoryR2::new();
+        let count = PAGE_SIZE as usize + 1;
+        block_on(async {
+            for i in 0..count {
+                r2.put(&format!("logs/{:04}.txt", i), b"x".to_vec(), None).await.unwrap();
+            }
+        });
+        let provider = R2ResourceProvider::new("files", &["logs/".to_string()], r2);
+        let first = block_on(provider.list(None)).unwrap();
+        assert_eq!(first.resources.len(), PAGE_SIZE as usize);
+        let second = block_on(provider.list(first.next_cursor.as_deref())).unwrap();
+        assert_eq!(second.resources.len(), 1);
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(list_all(&provider).len(), count);
+    }
+
+    #[test]
+    fn test_reads_text_and_binary_objects() {
+        let provider = R2ResourceProvider::new("files", &prefixes(), store());
+        assert_eq!(
+            serde_json::to_value(block_on(provider.read("r2://files/docs/data.json")).unwrap()).unwrap(),
+            json!([{ "uri": "r2://files/docs/data.json", "mimeType": "application/json; charset=utf-8", "text": "{\"a\":1}" }])
+        );
+        assert_eq!(
+            serde_json::to_value(block_on(provider.read("r2://files/images/logo.png")).unwrap()).unwrap(),
+            json!([{ "uri": "r2://files/images/logo.png", "mimeType": "image/png", "blob": "iVBORw==" }])
+        );
+        assert_eq!(
+            block_on(provider.read("r2://files/private/keys.txt")).unwrap_err(),
+            ResourceError::NotFound("r2://files/private/keys.txt".to_string())
+        );
+        assert_eq!(
+            block_on(provider.read("r2://files/docs/missing.md")).unwrap_err(),
+            ResourceError::NotFound("r2://files/docs/missing.md".to_string())
+        );
+    }
+
+    #[test]
+    fn test_destructive_tools_need_writes() {
+        let mut tools = ToolRegistry::new();
+        r2::register_r2_tools(&mut tools, false);
+        let names: Vec<&str> = tools.get_tools().iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["r2_list", "r2_get"]);
+        assert!(tools.get_tools().iter().all(|tool| !tool.is_destructive()));
+
+        let mut tools = ToolRegistry::new();
+        r2::register_r2_tools(&mut tools, true);
+        let listed = serde_json::to_value(tools.list_tools(None)).unwrap();
+        assert_eq!(listed[0]["annotations"], json!({ "readOnlyHint": true }));
+        assert_eq!(
+            listed[2]["annotations"],
+            json!({ "readOnlyHint": false, "destructiveHint": true, "idempotentHint": true })
+        );
+        assert_eq!(listed[3]["name"], json!("r2_delete"));
+    }
+
+    fn call(tools: &ToolRegistry, ctx: &ToolContext, name: &str, arguments: serde_json::Value) -> serde_json::Value {
+        serde_json::to_value(block_on(tools.call_tool(name, Some(arguments), ctx)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_tools_browse_and_change_objects() {
+        let r2 = store();
+        let mut resources = ResourceRegistry::new();
+        resources.register_provider(R2ResourceProvider::new("files", &prefixes(), r2.clone()).writable(true));
+        let kv = MemoryKv::new();
+        block_on(kv.put("secret", b"hidden", None)).unwrap();
+        resources.register_provider(KvResourceProvider::new("cache", "", kv.clone()).writable(true));
+        let mut tools = ToolRegistry::new();
+        r2::register_r2_tools(&mut tools, true);
+        let ctx = ToolContext {
+            resources: Rc::new(resources),
+            ..Default::default()
+        };
+
+        let listed = call(&tools, &ctx, "r2_list", json!({ "uri": "r2://files/docs/" }));
+        let page: serde_json::Value = serde_json::from_str(listed["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(page["resources"].as_array().unwrap().len(), 2);
+        let listed = call(&tools, &ctx, "r2_list", json!({ "uri": "r2://files/private/" }));
+        assert_eq!(listed["isError"], json!(true));
+
+        let fetched = call(&tools, &ctx, "r2_get", json!({ "uri": "r2://files/docs/guide.md" }));
+        assert_eq!(fetched["content"][0]["text"], json!("# Guide"));
+        let fetched = call(&tools, &ctx, "r2_get", json!({ "uri": "r2://files/images/logo.png" }));
+        assert_eq!(
+            fetched["content"][0],
+            json!({
+                "type": "resource",
+                "resource": { "uri": "r2://files/images/logo.png", "mimeType": "image/png", "blob": "iVBORw==" }
+            })
+        );
+
+        let arguments = json!({ "uri": "r2://files/images/dot.gif", "content": "R0lGODlh", "encoding": "base64", "mimeType": "image/gif" });
+        assert_eq!(call(&tools, &ctx, "r2_put", arguments)["isError"], json!(false));
+        let (info, body) = block_on(r2.get("images/dot.gif", u64::MAX)).unwrap().unwrap();
+        assert_eq!((info.content_type.as_deref(), body.unwrap().as_slice()), (Some("image/gif"), &b"GIF89a"[..]));
+
+        let arguments = json!({ "uri": "r2://files/images/dot.gif", "content": "not base64!", "encoding": "base64" });
+        assert_eq!(call(&tools, &ctx, "r2_put", arguments)["isError"], json!(true));
+        let arguments = json!({ "uri": "r2://files/private/keys.txt", "content": "overwritten" });
+        assert_eq!(call(&tools, &ctx, "r2_put", arguments)["isError"], json!(true));
+
+        assert_eq!(call(&tools, &ctx, "r2_delete", json!({ "uri": "r2://files/images/dot.gif" }))["isError"], json!(false));
+        assert_eq!(block_on(r2.get("images/dot.gif", u64::MAX)).unwrap(), None);
+
+        // Other stores stay behind their own tools
+        let refused = json!("Invalid arguments: uri must start with r2://");
+        assert_eq!(call(&tools, &ctx, "r2_list", json!({ "uri": "kv://cache/" }))["content"][0]["text"], refused);
+        assert_eq!(call(&tools, &ctx, "r2_get", json!({ "uri": "kv://cache/secret" }))["content"][0]["text"], refused);
+        let arguments = json!({ "uri": "kv://cache/secret", "content": "overwritten" });
+        assert_eq!(call(&tools, &ctx, "r2_put", arguments)["content"][0]["text"], refused);
+        assert_eq!(call(&tools, &ctx, "r2_delete", json!({ "uri": "kv://cache/secret" }))["content"][0]["text"], refused);
+        assert_eq!(block_on(kv.get("secret")).unwrap().unwrap().0, b"hidden");
+    }
+
+    #[test]
+    fn test_refuses_objects_over_the_read_limit() {
+        let provider = R2ResourceProvider::new("files", &prefixes(), store()).max_read_bytes(4);
+        assert!(block_on(provider.read("r2://files/images/logo.png")).is_ok());
+        assert_eq!(
+            block_on(provider.read("r2://files/docs/guide.md")).unwrap_err(),
+            ResourceError::TooLarge("r2://files/docs/guide.md".to_string(), 4)
+        );
+
+        let mut resources = ResourceRegistry::new();
+        resources.register_provider(R2ResourceProvider::new("files", &prefixes(), store()).max_read_bytes(4));
+        let mut tools = ToolRegistry::new();
+        r2::register_r2_tools(&mut tools, false);
+        let ctx = ToolContext {
+            resources: Rc::new(resources),
+            ..Default::default()
+        };
+        let fetched = call(&tools, &ctx, "r2_get", json!({ "uri": "r2://files/docs/guide.md" }));
+        assert_eq!(fetched["isError"], json!(true));
+        assert_eq!(fetched["content"][0]["text"], json!("Resource is larger than 4 bytes: r2://files/docs/guide.md"));
+        let fetched = call(&tools, &ctx, "r2_get", json!({ "uri": "r2://files/images/logo.png" }));
+        assert_eq!(fetched["isError"], json!(false));
+    }
+
+    #[test]
+    fn test_tools_need_the_provider_access() {
+        let r2 = store();
+        let mut resources = ResourceRegistry::new();
+        let provider = R2ResourceProvider::new("files", &prefixes(), r2.clone()).writable(true);
+        resources.register_provider_with_access(provider, ToolAccess::scopes(&["files:write"]));
+        let mut tools = ToolRegistry::new();
+        r2::register_r2_tools(&mut tools, true);
+        let anonymous = ToolContext {
+            resources: Rc::new(resources),
+            ..Default::default()
+        };
+
+        let forbidden = json!("Forbidden: resource 'r2://files/docs/guide.md' requires scopes [files:write]");
+        assert_eq!(call(&tools, &anonymous, "r2_get", json!({ "uri": "r2://files/docs/guide.md" }))["content"][0]["text"], forbidden);
+        assert_eq!(call(&tools, &anonymous, "r2_delete", json!({ "uri": "r2://files/docs/guide.md" }))["content"][0]["text"], forbidden);
+        let arguments = json!({ "uri": "r2://files/docs/guide.md", "content": "overwritten" });
+        assert_eq!(call(&tools, &anonymous, "r2_put", arguments.clone())["content"][0]["text"], forbidden);
+        assert_eq!(block_on(r2.get("docs/guide.md", u64::MAX)).unwrap().unwrap().1.unwrap(), b"# Guide");
+
+        let writer = ToolContext {
+            principal: Some(Principal {
+                subject: "writer".to_string(),
+                scopes: vec!["files:write".to_string()],
+                roles: Vec::new(),
+                claims: json!({}),
+            }),
+            ..anonymous
+        };
+        assert_eq!(call(&tools, &writer, "r2_put", arguments)["isError"], json!(false));
+    }
+
+    #[test]
+    fn test_read_only_provider_rejects_writes() {
+        let provider = R2ResourceProvider::new("files", &prefixes(), store());
+        assert_eq!(
+            block_on(provider.write("r2://files/docs/guide.md", b"changed", None)).unwrap_err(),
+            ResourceError::ReadOnly("r2://files/docs/guide.md".to_string())
+        );
+    }
+}
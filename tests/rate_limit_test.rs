@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::auth::Principal;
+    use {{crate_name}}::rate_limit::{
+        Decision, KeyStrategy, MemoryRateLimitStore, RateLimit, RateLimitConfig, RateLimited, RateLimiter,
+    };
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(RateLimit::parse("60/min").unwrap(), RateLimit::new(60, 60));
+        assert_eq!(RateLimit::parse(" 5 / s ").unwrap(), RateLimit::new(5, 1));
+        assert_eq!(RateLimit::parse("1000/day").unwrap(), RateLimit::new(1000, 86400));
+        assert!(RateLimit::parse("60").is_err());
+        assert!(RateLimit::parse("0/min").is_err());
+        assert!(RateLimit::parse("60/fortnight").is_err());
+        assert_eq!(RateLimit::new(10, 3600).to_string(), "10/h");
+
+        let config = RateLimitConfig::default()
+            .with_tool_limits("add=10/s, calculate=2/min")
+            .unwrap();
+        assert_eq!(config.tools["calculate"], RateLimit::new(2, 60));
+        assert!(config.is_enabled());
+        assert!(RateLimitConfig::default().with_tool_limits("add").is_err());
+        assert!(RateLimitConfig::default().with_tool_limits("add=0/s").is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let limit = RateLimit::new(2, 60);
+        let (bucket, first) = limit.take(None, 0);
+        assert_eq!(first, Decision::Allowed { remaining: 1 });
+        let (bucket, _) = limit.take(Some(bucket), 0);
+        let (bucket, limited) = limit.take(Some(bucket), 0);
+        // One token refills every 30 seconds
+        assert_eq!(limited, Decision::Limited { retry_after_ms: 30_000 });
+
+        let (_, later) = limit.take(Some(bucket), 30_000);
+        assert_eq!(later, Decision::Allowed { remaining: 0 });
+    }
+
+    #[test]
+    fn test_global_and_tool_limits() {
+        let config = RateLimitConfig {
+            global: Some(RateLimit::new(3, 60)),
+            ..RateLimitConfig::default()
+        }
+        .with_tool_limits("calculate=1/min")
+        .unwrap();
+        let limiter = RateLimiter::new(config, MemoryRateLimitStore::new());
+
+        block_on(async {
+            assert_eq!(limiter.check_tool("ip:1", "calculate", 0).await.unwrap(), Ok(()));
+            let limited = limiter.check_tool("ip:1", "calculate", 1000).await.unwrap().unwrap_err();
+            assert_eq!(limited.tool.as_deref(), Some("calculate"));
+            assert_eq!(limited.retry_after_secs(), 59);
+
+            // Another client has its own buckets
+            assert_eq!(limiter.check_tool("ip:2", "calculate", 1000).await.unwrap(), Ok(()));
+
+            // The refused call took nothing from the global bucket, and calls
+            // to other tools still count towards it
+            assert_eq!(limiter.check_tool("ip:1", "add", 1000).await.unwrap(), Ok(()));
+            assert_eq!(limiter.check_tool("ip:1", "add", 1000).await.unwrap(), Ok(()));
+            let limited = limiter.check_tool("ip:1", "add", 1000).await.unwrap().unwrap_err();
+            assert_eq!(limited.tool, None);
+        });
+    }
+
+    #[test]
+    fn test_idle_buckets_are_evicted() {
+        let config = RateLimitConfig {
+            global: Some(RateLimit::new(2, 60)),
+            ..RateLimitConfig::default()
+        };
+        let store = MemoryRateLimitStore::new();
+        let limiter = RateLimiter::new(config, store.clone());
+
+        block_on(async {
+            assert_eq!(limiter.check_tool("ip:1", "calculate", 0).await.unwrap(), Ok(()));
+            assert_eq!(limiter.check_tool("ip:2", "calculate", 50_000).await.unwrap(), Ok(()));
+            assert_eq!(store.len(), 2);
+
+            // ip:1 refilled after 30 seconds, ip:2 has not yet
+            assert_eq!(limiter.check_tool("ip:3", "calculate", 60_000).await.unwrap(), Ok(()));
+            assert_eq!(store.len(), 2);
+            assert_eq!(limiter.check_tool("ip:3", "calculate", 120_000).await.unwrap(), Ok(()));
+            assert_eq!(store.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_retry_hints() {
+        let limited = RateLimited {
+            tool: Some("add".to_string()),
+            limit: RateLimit::new(10, 60),
+            retry_after_ms: 1500,
+        };
+        assert_eq!(limited.to_string(), "Rate limit of 10/min exceeded for tool 'add'; retry after 2s");
+        assert_eq!(
+            limited.data(),
+            json!({ "tool": "add", "limit": "10/min", "retryAfter": 2, "retryAfterMs": 1500 })
+        );
+    }
+
+    #[test]
+    fn test_client_keys() {
+        let principal = Principal {
+            subject: "user-1".to_string(),
+            scopes: Vec::new(),
+            roles: Vec::new(),
+            claims: json!({}),
+        };
+
+        assert_eq!(
+            KeyStrategy::Principal.client_key(Some(&principal), Some("203.0.113.7"), None),
+            "principal:user-1"
+        );
+        assert_eq!(KeyStrategy::Principal.client_key(None, Some("203.0.113.7"), None), "ip:203.0.113.7");
+        assert_eq!(KeyStrategy::Ip.client_key(Some(&principal), Some("203.0.113.7"), None), "ip:203.0.113.7");
+        assert_eq!(KeyStrategy::Session.client_key(None, Some("203.0.113.7"), Some("abc")), "session:abc");
+        assert_eq!(KeyStrategy::Session.client_key(None, None, None), "anonymous");
+        assert_eq!("session".parse::<KeyStrategy>(), Ok(KeyStrategy::Session));
+        assert!("user".parse::<KeyStrategy>().is_err());
+    }
+}
@@ -0,0 +1,222 @@
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::auth::Principal;
+    use {{crate_name}}::notifications::SessionStream;
+    use {{crate_name}}::resources::subscriptions::{self, SubscribeError};
+    use {{crate_name}}::resources::{
+        Resource, ResourceContents, ResourceError, ResourcePage, ResourceProvider, ResourceRegistry, ResourceTemplate,
+    };
+    use {{crate_name}}::tools::ToolAccess;
+
+    /// Serves `notes://<name>`, two per page.
+    struct Notes(Vec<&'static str>);
+
+    #[async_trait(?Send)]
+    impl ResourceProvider for Notes {
+        fn handles(&self, uri: &str) -> bool {
+            uri.starts_with("notes://")
+        }
+
+        async fn list(&self, cursor: Option<&str>) -> Result<ResourcePage, ResourceError> {
+            let start: usize = cursor.map_or(Ok(0), str::parse).map_err(|_| ResourceError::InvalidCursor("bad".into()))?;
+            let resources = self.0[start..]
+                .iter()
+                .take(2)
+                .map(|name| Resource::new(&format!("notes://{}", name), name))
+                .collect();
+            let next_cursor = (start + 2 < self.0.len()).then(|| (start + 2).to_string());
+            Ok(ResourcePage { resources, next_cursor })
+        }
+
+        async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ResourceError> {
+            let name = uri.trim_start_matches("notes://");
+            match self.0.contains(&name) {
+                true => Ok(vec![ResourceContents::text(uri, Some("text/plain"), format!("Note {}", name))]),
+                false => Err(ResourceError::NotFound(uri.to_string())),
+            }
+        }
+
+        fn templates(&self) -> Vec<ResourceTemplate> {
+            vec![ResourceTemplate {
+                uri_template: "notes://{name}".to_string(),
+                name: "Notes".to_string(),
+                description: None,
+                mime_type: Some("text/plain".to_string()),
+            }]
+        }
+    }
+
+    fn registry() -> ResourceRegistry {
+        let mut registry = ResourceRegistry::new();
+        registry.register_provider(Notes(vec!["a", "b", "c"]));
+        registry.register_provider(Notes(vec!["d"]));
+        registry
+    }
+
+    #[test]
+    fn test_pagination_spans_providers() {
+        let registry = registry();
+        let mut cursor = None;
+        let mut uris = Vec::new();
+        loop {
+            let page = block_on(registry.list(cursor.as_deref(), None)).unwrap();
+            uris.extend(page.resources.into_iter().map(|resource| resource.uri));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(uris, vec!["notes://a", "notes://b", "notes://c", "notes://d"]);
+
+        assert!(matches!(block_on(registry.list(Some("9:"), None)), Err(ResourceError::InvalidCursor(_))));
+        assert!(matches!(block_on(registry.list(Some("nonsense"), None)), Err(ResourceError::InvalidCursor(_))));
+        assert_eq!(block_on(ResourceRegistry::new().list(None, None)).unwrap(), ResourcePage::default());
+    }
+
+    #[test]
+    fn test_read_and_templates() {
+        let registry = registry();
+        let contents = block_on(registry.read("notes://b", None)).unwrap();
+        assert_eq!(
+            serde_json::to_value(&contents).unwrap(),
+            json!([{ "uri": "notes://b", "mimeType": "text/plain", "text": "Note b" }])
+        );
+        assert_eq!(
+            block_on(registry.read("other://x", None)).unwrap_err(),
+            ResourceError::NotFound("other://x".to_string())
+        );
+        assert_eq!(registry.templates(None).len(), 2);
+        assert_eq!(ResourceContents::blob("notes://x", None, b"hi").blob.as_deref(), Some("aGk="));
+    }
+
+    #[test]
+    fn test_restricted_providers() {
+        /// Serves `secret://<name>`.
+        struct Secrets;
+
+        #[async_trait(?Send)]
+        impl ResourceProvider for Secrets {
+            fn handles(&self, uri: &str) -> bool {
+                uri.starts_with("secret://")
+            }
+
+            async fn list(&self, _cursor: Option<&str>) -> Result<ResourcePage, ResourceError> {
+                Ok(ResourcePage {
+                    resources: vec![Resource::new("secret://plans", "plans")],
+                    next_cursor: None,
+                })
+            }
+
+            async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ResourceError> {
+                Ok(vec![ResourceContents::text(uri, None, "hidden".to_string())])
+            }
+        }
+
+        let mut registry = ResourceRegistry::new();
+        registry.register_provider(Notes(vec!["a"]));
+        registry.register_provider_with_access(Secrets, ToolAccess::scopes(&["secrets:read"]));
+        registry.register_provider(Notes(vec!["b"]));
+        let reader = Principal {
+            subject: "reader".to_string(),
+            scopes: vec!["secrets:read".to_string()],
+            roles: Vec::new(),
+            claims: json!({}),
+        };
+
+        let list_all = |principal: Option<&Principal>| {
+            let mut cursor = None;
+            let mut uris = Vec::new();
+            loop {
+                let page = block_on(registry.list(cursor.as_deref(), principal)).unwrap();
+                uris.extend(page.resources.into_iter().map(|resource| resource.uri));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => return uris,
+                }
+            }
+        };
+        assert_eq!(list_all(None), vec!["notes://a", "notes://b"]);
+        assert_eq!(list_all(Some(&reader)), vec!["notes://a", "secret://plans", "notes://b"]);
+        // A cursor pointing at the restricted provider moves past it
+        let page = block_on(registry.list(Some("1:"), None)).unwrap();
+        assert_eq!(page.resources[0].uri, "notes://b");
+
+        // Reads and writes need the provider's access too
+        assert_eq!(
+            block_on(registry.read("secret://plans", None)).unwrap_err(),
+            ResourceError::Forbidden("secret://plans".to_string(), ToolAccess::scopes(&["secrets:read"]))
+        );
+        assert!(block_on(registry.read("secret://plans", Some(&reader))).is_ok());
+        assert!(matches!(block_on(registry.delete("secret://plans", None)), Err(ResourceError::Forbidden(..))));
+
+        assert_eq!(registry.check_access("notes://a", None), Ok(()));
+        assert_eq!(registry.check_access("secret://plans", Some(&reader)), Ok(()));
+        let forbidden = registry.check_access("secret://plans", None).unwrap_err();
+        assert_eq!(forbidden.to_string(), "Forbidden: resource 'secret://plans' requires scopes [secrets:read]");
+        assert_eq!(
+            registry.check_access("other://x", None).unwrap_err(),
+            ResourceError::NotFound("other://x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_updates_reach_subscribed_streams() {
+        let stream = SessionStream::open("session-1");
+        subscriptions::subscribe("session-1", "notes://a").unwrap();
+        subscriptions::subscribe("session-2", "notes://a").unwrap();
+        subscriptions::subscribe("session-1", "notes://b").unwrap();
+        assert_eq!(subscriptions::subscribers("notes://a"), vec!["session-1", "session-2"]);
+
+        // session-2 has no open stream in this isolate
+        assert_eq!(subscriptions::notify_updated("notes://a"), 1);
+        let messages = block_on(poll_fn(|cx| stream.poll_messages(cx)));
+        assert_eq!(
+            messages,
+            vec![json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/resources/updated",
+                "params": { "uri": "notes://a" },
+            })]
+        );
+
+        subscriptions::unsubscribe("session-1", "notes://a");
+        assert_eq!(subscriptions::notify_updated("notes://a"), 0);
+        assert_eq!(subscriptions::notify_updated("notes://b"), 1);
+
+        subscriptions::unsubscribe_all("session-1");
+        assert!(subscriptions::subscribers("notes://b").is_empty());
+
+        // Closing the stream drops its subscriptions; a replaced one does not
+        subscriptions::subscribe("session-1", "notes://c").unwrap();
+        let replacement = SessionStream::open("session-1");
+        drop(stream);
+        assert_eq!(subscriptions::subscribers("notes://c"), vec!["session-1"]);
+        drop(replacement);
+        assert!(subscriptions::subscribers("notes://c").is_empty());
+        assert!(SessionStream::open("session-3").take().is_empty());
+    }
+
+    #[test]
+    fn test_subscriptions_are_limited() {
+        let long = format!("notes://{}", "x".repeat(subscriptions::MAX_URI_LEN));
+        assert_eq!(subscriptions::subscribe("session-limits", &long), Err(SubscribeError::UriTooLong));
+
+        for i in 0..subscriptions::MAX_PER_SESSION {
+            subscriptions::subscribe("session-limits", &format!("notes://{}", i)).unwrap();
+        }
+        assert_eq!(
+            subscriptions::subscribe("session-limits", "notes://one-more"),
+            Err(SubscribeError::TooManyForSession)
+        );
+        // Repeating a subscription is not a new one
+        assert_eq!(subscriptions::subscribe("session-limits", "notes://0"), Ok(()));
+        subscriptions::unsubscribe("session-limits", "notes://0");
+        assert_eq!(subscriptions::subscribe("session-limits", "notes://one-more"), Ok(()));
+        subscriptions::unsubscribe_all("session-limits");
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::task::Poll;
+
+    use futures::executor::block_on;
+    use futures::future::join;
+    use serde_json::json;
+    use {{crate_name}}::correlator::{self, ClientRequestError};
+    use {{crate_name}}::roots::{self, Root};
+    use {{crate_name}}::session::RootsCapability;
+    use {{crate_name}}::tools::ToolContext;
+
+    fn context(session_id: &str) -> ToolContext {
+        let mut ctx = ToolContext {
+            session_id: Some(session_id.to_string()),
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        ctx.client_capabilities.roots = Some(RootsCapability { list_changed: Some(true) });
+        ctx
+    }
+
+    /// Answers the next `roots/list` with `uris`.
+    async fn client(ctx: &ToolContext, uris: &[&str]) {
+        let request = poll_fn(|cx| match ctx.outbox.take().pop() {
+            Some(message) => Poll::Ready(message),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            },
+        })
+        .await;
+        assert_eq!(request["method"], "roots/list");
+        let roots: Vec<_> = uris.iter().map(|uri| json!({ "uri": uri, "name": "Project" })).collect();
+        correlator::resolve(ctx.session_id.as_deref(), &request["id"], &json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "roots": roots } }));
+    }
+
+    #[test]
+    fn test_roots_are_cached_until_changed() {
+        let ctx = context("session-roots");
+        let (listed, ()) = block_on(join(ctx.roots(), client(&ctx, &["file:///home/user/project"])));
+        assert_eq!(listed.unwrap()[0].uri, "file:///home/user/project");
+
+        // Served from the cache; the client is not asked again
+        assert_eq!(block_on(ctx.roots()).unwrap().len(), 1);
+        assert!(ctx.outbox.take().is_empty());
+
+        roots::invalidate("session-roots");
+        let (listed, ()) = block_on(join(ctx.roots(), client(&ctx, &["file:///a", "file:///b"])));
+        assert_eq!(listed.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_roots_are_limited() {
+        let ctx = context("session-many-roots");
+        let uris: Vec<String> = (0..=roots::MAX_ROOTS).map(|i| format!("file:///r{}", i)).collect();
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+        let (listed, ()) = block_on(join(ctx.roots(), client(&ctx, &uris)));
+        assert_eq!(listed.unwrap_err(), ClientRequestError::InvalidResult(format!("more than {} roots", roots::MAX_ROOTS)));
+        assert_eq!(roots::cached("session-many-roots"), None);
+
+        // A full cache leaves new sessions out but keeps updating known ones
+        for i in 0..roots::MAX_CACHED_SESSIONS {
+            roots::store(&format!("session-{}", i), Vec::new());
+        }
+        roots::store("session-late", vec![Root::new("file:///a")]);
+        assert_eq!(roots::cached("session-late"), None);
+        roots::store("session-0", vec![Root::new("file:///a")]);
+        assert_eq!(roots::cached("session-0").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_requires_roots_capability() {
+        let ctx = ToolContext {
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        assert_eq!(block_on(ctx.roots()).unwrap_err(), ClientRequestError::Unsupported("roots"));
+    }
+
+    #[test]
+    fn test_scoping() {
+        let shared = vec![Root::new("file:///home/user/project/")];
+        assert!(roots::is_within(&shared, "file:///home/user/project"));
+        assert!(roots::is_within(&shared, "file:///home/user/project/src/main.rs"));
+        assert!(!roots::is_within(&shared, "file:///home/user/project-secrets/key"));
+        assert!(!roots::is_within(&shared, "file:///etc/passwd"));
+        assert!(!roots::is_within(&[], "file:///home/user/project"));
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::task::Poll;
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+    use futures::future::join;
+    use serde_json::json;
+    use {{crate_name}}::correlator::{self, ClientRequestError};
+    use {{crate_name}}::notifications::Outbox;
+    use {{crate_name}}::sampling::{CreateMessageRequest, Role};
+    use {{crate_name}}::tools::ToolContext;
+
+    fn context() -> ToolContext {
+        let mut ctx = ToolContext {
+            session_id: Some("session-a".to_string()),
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        ctx.client_capabilities.sampling = Some(json!({}));
+        ctx
+    }
+
+    /// Waits for the server's next request, like a client reading the
+    /// response stream.
+    async fn next_request(outbox: &Outbox) -> serde_json::Value {
+        poll_fn(|cx| match outbox.take().pop() {
+            Some(message) => Poll::Ready(message),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            },
+        })
+        .await
+    }
+
+    #[test]
+    fn test_create_message_round_trip() {
+        let ctx = context();
+        let request = CreateMessageRequest {
+            system_prompt: Some("Be brief".to_string()),
+            ..CreateMessageRequest::new("Summarise the forecast", 200)
+        };
+
+        let client = async {
+            let request = next_request(&ctx.outbox).await;
+            assert_eq!(request["method"], "sampling/createMessage");
+            assert_eq!(request["params"]["maxTokens"], 200);
+            assert_eq!(request["params"]["systemPrompt"], "Be brief");
+            assert_eq!(request["params"]["messages"][0]["content"]["text"], "Summarise the forecast");
+            let reply = json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": {
+                    "role": "assistant",
+                    "content": { "type": "text", "text": "Sunny." },
+                    "model": "some-model",
+                    "stopReason": "endTurn",
+                },
+            });
+            // Only the session the request went to can answer it
+            assert!(!correlator::resolve(Some("session-b"), &request["id"], &reply));
+            assert!(!correlator::resolve(None, &request["id"], &reply));
+            assert!(correlator::resolve(Some("session-a"), &request["id"], &reply));
+        };
+        let (result, ()) = block_on(join(ctx.create_message(&request), client));
+
+        let result = result.unwrap();
+        assert_eq!(result.role, Role::Assistant);
+        assert_eq!(result.text(), Some("Sunny."));
+        assert_eq!(result.model, "some-model");
+    }
+
+    #[test]
+    fn test_client_errors_are_returned() {
+        let ctx = context();
+        let client = async {
+            let request = next_request(&ctx.outbox).await;
+            let reply = json!({ "jsonrpc": "2.0", "id": request["id"], "error": { "code": -1, "message": "User rejected" } });
+            correlator::resolve(Some("session-a"), &request["id"], &reply);
+        };
+        let (result, ()) = block_on(join(ctx.create_message(&CreateMessageRequest::new("hi", 10)), client));
+        assert_eq!(
+            result.unwrap_err(),
+            ClientRequestError::Rejected { code: -1, message: "User rejected".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_capability_and_stream_checks() {
+        let request = CreateMessageRequest::new("hi", 10);
+        let without_sampling = ToolContext {
+            streaming: true,
+            client_requests: true,
+            ..ToolContext::default()
+        };
+        assert_eq!(
+            block_on(without_sampling.create_message(&request)).unwrap_err(),
+            ClientRequestError::Unsupported("sampling")
+        );
+
+        let json_response = ToolContext {
+            streaming: false,
+            ..context()
+        };
+        assert_eq!(block_on(json_response.create_message(&request)).unwrap_err(), ClientRequestError::NoStream);
+
+        // Replies may reach another isolate unless the deployment says not
+        let multi_isolate = ToolContext {
+            client_requests: false,
+            ..context()
+        };
+        assert_eq!(block_on(multi_isolate.create_message(&request)).unwrap_err(), ClientRequestError::Disabled);
+        assert!(multi_isolate.outbox.take().is_empty());
+    }
+
+    #[test]
+    fn test_timeout_and_cancellation() {
+        let ctx = context();
+        let result = block_on(ctx.send_request("sampling/createMessage", json!({}), Duration::from_millis(10)));
+        assert_eq!(result.unwrap_err(), ClientRequestError::Timeout);
+
+        let id = ctx.outbox.take()[0]["id"].clone();
+        assert!(!correlator::resolve(Some("session-a"), &id, &json!({ "result": {} })));
+
+        ctx.cancellation.cancel(None);
+        let result = block_on(ctx.send_request("sampling/createMessage", json!({}), Duration::from_secs(60)));
+        assert_eq!(result.unwrap_err(), ClientRequestError::Cancelled);
+    }
+}
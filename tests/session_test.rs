@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::json;
+    use {{crate_name}}::protocol::ProtocolVersion;
+    use {{crate_name}}::session::{ClientCapabilities, RootsCapability, SessionSigner};
+
+    fn capabilities() -> ClientCapabilities {
+        serde_json::from_value(json!({ "sampling": {}, "roots": { "listChanged": true } })).unwrap()
+    }
+
+    #[test]
+    fn test_issued_sessions_verify() {
+        let signer = SessionSigner::new(b"secret");
+        let session = signer.issue(capabilities(), ProtocolVersion::default());
+
+        assert!(session.id.bytes().all(|b| (0x21..=0x7e).contains(&b)));
+        let verified = signer.verify(&session.id).unwrap();
+        assert_eq!(verified, session);
+        assert_eq!(verified.capabilities.sampling, Some(json!({})));
+        assert_eq!(verified.capabilities.roots, Some(RootsCapability { list_changed: Some(true) }));
+        assert_eq!(verified.protocol_version, ProtocolVersion::default());
+        assert_ne!(signer.issue(capabilities(), ProtocolVersion::default()).id, session.id);
+    }
+
+    #[test]
+    fn test_unknown_sessions_are_rejected() {
+        let signer = SessionSigner::new(b"secret");
+        let session = signer.issue(ClientCapabilities::default(), ProtocolVersion::default());
+
+        assert!(SessionSigner::new(b"other").verify(&session.id).is_none());
+        assert!(signer.verify("conformance-unknown-session").is_none());
+        assert!(signer.verify("").is_none());
+
+        // Claiming a capability the client never declared breaks the signature
+        let (_, signature) = session.id.split_once('.').unwrap();
+        let forged = signer.issue(capabilities(), ProtocolVersion::default()).id;
+        let (payload, _) = forged.split_once('.').unwrap();
+        assert!(signer.verify(&format!("{}.{}", payload, signature)).is_none());
+    }
+
+    #[test]
+    fn test_unsigned_sessions_work_without_a_secret() {
+        let signer = SessionSigner::unsigned();
+        let session = signer.issue(capabilities(), ProtocolVersion::default());
+
+        // Another isolate reads the same id
+        assert_eq!(SessionSigner::unsigned().verify(&session.id), Some(session.clone()));
+        assert_ne!(signer.issue(capabilities(), ProtocolVersion::default()).id, session.id);
+        assert!(signer.verify("conformance-unknown-session").is_none());
+        assert!(SessionSigner::new(b"secret").verify(&session.id).is_none());
+    }
+
+    #[test]
+    fn test_sessions_carry_the_agreed_version() {
+        let signer = SessionSigner::unsigned();
+        let version = ProtocolVersion::parse("2024-11-05").unwrap();
+        let session = signer.issue(capabilities(), version);
+        assert_eq!(signer.verify(&session.id).unwrap().protocol_version, version);
+
+        // Ids from before negotiation get the spec's assumed version
+        let old = URL_SAFE_NO_PAD.encode(json!({ "nonce": "n", "caps": {} }).to_string());
+        assert_eq!(signer.verify(&old).unwrap().protocol_version, ProtocolVersion::assumed());
+    }
+}
@@ -0,0 +1,307 @@
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use rusqlite::types::{Value as SqliteValue, ValueRef};
+    use rusqlite::Connection;
+    use serde_json::{json, Value};
+    use {{crate_name}}::resources::d1::SchemaProvider;
+    use {{crate_name}}::resources::{ResourceError, ResourceProvider};
+    use {{crate_name}}::sql::{parse_statement, QueryResult, SqlBackend, SqlDatabase, SqlError, SqlOutcome, StatementKind};
+    use {{crate_name}}::tools::{sql, ToolContext, ToolRegistry};
+
+    /// SQLite standing in for D1, which speaks the same dialect.
+    struct Sqlite {
+        connection: Connection,
+        /// Every query as it reached the database.
+        queries: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Sqlite {
+        fn new() -> Self {
+            let connection = Connection::open_in_memory().unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT, score REAL DEFAULT 0);
+                     CREATE VIEW top_users AS SELECT name FROM users WHERE score > 5;
+                     INSERT INTO users (name, email, score) VALUES ('ada', 'ada@example.com', 9.5), ('bob', NULL, 3), ('cy|d', 'c@example.com', 7);",
+                )
+                .unwrap();
+            Self {
+                connection,
+                queries: Rc::default(),
+            }
+        }
+    }
+
+    fn bind(params: &[Value]) -> Vec<SqliteValue> {
+        params
+            .iter()
+            .map(|param| match param {
+                Value::Null => SqliteValue::Null,
+                Value::Bool(b) => SqliteValue::Integer(*b as i64),
+                Value::Number(n) => match n.as_i64() {
+                    Some(i) => SqliteValue::Integer(i),
+                    None => SqliteValue::Real(n.as_f64().unwrap()),
+                },
+                Value::String(s) => SqliteValue::Text(s.clone()),
+                other => panic!("unexpected param {}", other),
+            })
+            .collect()
+    }
+
+    fn failed(e: rusqlite::Error) -> worker::Error {
+        worker::Error::RustError(e.to_string())
+    }
+
+    #[async_trait(?Send)]
+    impl SqlBackend for Sqlite {
+        async fn query(&self, sql: &str, params: &[Value], max_rows: usize) -> worker::Result<QueryResult> {
+            self.queries.borrow_mut().push(sql.to_string());
+            let mut statement = self.connection.prepare(sql).map_err(failed)?;
+            let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+            let mut rows = statement.query(rusqlite::params_from_iter(bind(params))).map_err(failed)?;
+            let mut result = QueryResult {
+                columns,
+                ..Default::default()
+            };
+            while let Some(row) = rows.next().map_err(failed)? {
+                let values = (0..result.columns.len())
+                    .map(|i| match row.get_ref(i).unwrap() {
+                        ValueRef::Null => Value::Null,
+                        ValueRef::Integer(i) => json!(i),
+                        ValueRef::Real(f) => json!(f),
+                        ValueRef::Text(text) => json!(String::from_utf8_lossy(text)),
+                        ValueRef::Blob(_) => panic!("unexpected blob"),
+                    })
+                    .collect();
+                result.rows.push(values);
+            }
+            Ok(result.truncate(max_rows))
+        }
+
+        async fn execute(&self, sql: &str, params: &[Value]) -> worker::Result<u64> {
+            let changes = self.connection.execute(sql, rusqlite::params_from_iter(bind(params))).map_err(failed)?;
+            Ok(changes as u64)
+        }
+    }
+
+    fn rows(outcome: SqlOutcome) -> QueryResult {
+        match outcome {
+            SqlOutcome::Rows(result) => result,
+            other => panic!("expected rows, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_statement() {
+        let statement = parse_statement("  SELECT * FROM users WHERE id = ?;  ").unwrap();
+        assert_eq!(statement.sql, "SELECT * FROM users WHERE id = ?");
+        assert_eq!(parse_statement("SELECT 1; -- done").unwrap().sql, "SELECT 1");
+        assert_eq!((statement.kind, statement.params), (StatementKind::Read, 1));
+
+        let reads = [
+            "select 1",
+            "VALUES (1), (2)",
+            "WITH t AS (SELECT 1) SELECT * FROM t",
+            "SELECT replace(name, 'a', 'b') FROM users",
+            "SELECT 'DELETE FROM users' AS text",
+            "SELECT \"update\" FROM t -- DROP TABLE t",
+            "SELECT 1; ",
+        ];
+        for sql in reads {
+            assert_eq!(parse_statement(sql).unwrap().kind, StatementKind::Read, "{}", sql);
+        }
+        let writes = [
+            "INSERT INTO users (name) VALUES ('x')",
+            "WITH t AS (SELECT 1) DELETE FROM users",
+            "REPLACE INTO users (id, name) VALUES (1, 'x')",
+            "PRAGMA table_info(users)",
+            "SELECT * FROM users /* ok */ UNION SELECT 1; ",
+        ];
+        for sql in &writes[..4] {
+            assert_eq!(parse_statement(sql).unwrap().kind, StatementKind::Write, "{}", sql);
+        }
+        assert_eq!(parse_statement(writes[4]).unwrap().kind, StatementKind::Read);
+
+        assert_eq!(parse_statement("SELECT ?2, ?, ?1").unwrap().params, 3);
+        let rejected = ["", " ; ", "SELECT 1; DROP TABLE users", "SELECT 'oops", "SELECT /* oops", "SELECT :name", "SELECT ?0"];
+        for sql in rejected {
+            assert!(matches!(parse_statement(sql), Err(SqlError::Rejected(_))), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_read_only_rejects_writes() {
+        let database = SqlDatabase::new("app", Sqlite::new());
+        assert!(database.is_read_only());
+        for sql in ["DELETE FROM users", "SELECT 1; DELETE FROM users", "WITH t AS (SELECT 1) UPDATE users SET name = 'x'"] {
+            assert!(matches!(block_on(database.run(sql, &[], None)), Err(SqlError::Rejected(_))), "{}", sql);
+        }
+        let count = rows(block_on(database.run("SELECT count(*) AS n FROM users", &[], None)).unwrap());
+        assert_eq!(count.rows, vec![vec![json!(3)]]);
+
+        let database = SqlDatabase::new("app", Sqlite::new()).read_only(false);
+        let outcome = block_on(database.run("UPDATE users SET score = score + 1 WHERE score > ?", &[json!(5)], None));
+        assert_eq!(outcome.unwrap(), SqlOutcome::Changed(2));
+    }
+
+    #[test]
+    fn test_binds_params() {
+        let database = SqlDatabase::new("app", Sqlite::new());
+        let sql = "SELECT name FROM users WHERE name = ? OR email = ?";
+        let result = rows(block_on(database.run(sql, &[json!("ada' OR '1'='1"), json!("c@example.com")], None)).unwrap());
+        assert_eq!(result.rows, vec![vec![json!("cy|d")]]);
+        let result = rows(block_on(database.run("SELECT ?1 + ?1", &[json!(2)], None)).unwrap());
+        assert_eq!(result.rows, vec![vec![json!(4)]]);
+
+        assert_eq!(
+            block_on(database.run(sql, &[json!("ada")], None)).unwrap_err(),
+            SqlError::InvalidParams("the statement takes 2 parameters but 1 were given".to_string())
+        );
+        assert!(matches!(
+            block_on(database.run("SELECT ?", &[json!([1, 2])], None)),
+            Err(SqlError::InvalidParams(_))
+        ));
+        assert!(matches!(block_on(database.run("SELECT * FROM missing", &[], None)), Err(SqlError::Failed(_))));
+    }
+
+    #[test]
+    fn test_caps_rows() {
+        let database = SqlDatabase::new("app", Sqlite::new()).max_rows(2);
+        let sql = "SELECT name, email, score FROM users ORDER BY id";
+        let result = rows(block_on(database.run(sql, &[], None)).unwrap());
+        assert_eq!((result.rows.len(), result.truncated), (2, true));
+        // maxRows can lower the cap but not raise it
+        let result = rows(block_on(database.run(sql, &[], Some(1))).unwrap());
+        assert_eq!(result.rows.len(), 1);
+        let result = rows(block_on(database.run(sql, &[], Some(50))).unwrap());
+        assert_eq!(result.rows.len(), 2);
+        let result = rows(block_on(database.run("SELECT name FROM users LIMIT 2", &[], Some(2))).unwrap());
+        assert_eq!((result.rows.len(), result.truncated), (2, false));
+        assert_eq!(
+            block_on(database.run(sql, &[], Some(0))).unwrap_err(),
+            SqlError::InvalidParams("maxRows must be at least 1".to_string())
+        );
+
+        // The wrapping subquery renames repeated columns
+        let result = rows(block_on(database.run("SELECT name, name FROM users WHERE id = 1", &[], None)).unwrap());
+        assert_eq!(result.columns, vec!["name", "name:1"]);
+
+        // The cap is part of the query, so the database stops early
+        let backend = Sqlite::new();
+        let queries = backend.queries.clone();
+        let capped = SqlDatabase::new("app", backend).max_rows(1);
+        let query = "SELECT name FROM users WHERE id > ?2 OR name = ?1 ORDER BY id; -- oldest first";
+        let result = rows(block_on(capped.run(query, &[json!("cy|d"), json!(1)], None)).unwrap());
+        assert_eq!((result.rows, result.truncated), (vec![vec![json!("bob")]], true));
+        assert_eq!(
+            queries.borrow().last().unwrap(),
+            "SELECT * FROM (SELECT name FROM users WHERE id > ?2 OR name = ?1 ORDER BY id\n) LIMIT ?3"
+        );
+
+        let result = rows(block_on(database.run(sql, &[], None)).unwrap());
+        assert_eq!(
+            result.to_markdown(),
+            "| name | email | score |\n| --- | --- | --- |\n| ada | ada@example.com | 9.5 |\n| bob | NULL | 3.0 |\n\nOnly the first 2 rows are shown."
+        );
+        let result = rows(block_on(database.run("SELECT name FROM users WHERE id = 3", &[], None)).unwrap());
+        assert_eq!(result.to_markdown(), "| name |\n| --- |\n| cy\\|d |");
+    }
+
+    #[test]
+    fn test_schema_resources() {
+        let provider = SchemaProvider::new(Rc::new(SqlDatabase::new("app", Sqlite::new())));
+        let page = block_on(provider.list(None)).unwrap();
+        let uris: Vec<&str> = page.resources.iter().map(|resource| resource.uri.as_str()).collect();
+        assert_eq!(uris, vec!["d1://app/schema", "d1://app/tables/top_users", "d1://app/tables/users"]);
+        assert!(provider.handles("d1://app/tables/anything"));
+        assert!(!provider.handles("d1://app/tables/"));
+        assert!(!provider.handles("d1://other/schema"));
+
+        let contents = block_on(provider.read("d1://app/tables/users")).unwrap();
+        let table: Value = serde_json::from_str(contents[0].text.as_deref().unwrap()).unwrap();
+        assert_eq!(table["type"], json!("table"));
+        assert_eq!(
+            table["columns"],
+            json!([
+                { "name": "id", "type": "INTEGER", "notNull": false, "primaryKey": true, "default": null },
+                { "name": "name", "type": "TEXT", "notNull": true, "primaryKey": false, "default": null },
+                { "name": "email", "type": "TEXT", "notNull": false, "primaryKey": false, "default": null },
+                { "name": "score", "type": "REAL", "notNull": false, "primaryKey": false, "default": "0" },
+            ])
+        );
+        let contents = block_on(provider.read("d1://app/schema")).unwrap();
+        let schema: Value = serde_json::from_str(contents[0].text.as_deref().unwrap()).unwrap();
+        assert_eq!(schema.as_array().unwrap().len(), 2);
+        assert_eq!(
+            block_on(provider.read("d1://app/tables/missing")).unwrap_err(),
+            ResourceError::NotFound("d1://app/tables/missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_schema_uris_encode_table_names() {
+        let sqlite = Sqlite::new();
+        sqlite.connection.execute_batch("CREATE TABLE \"order items/2024%\" (id INTEGER);").unwrap();
+        let provider = SchemaProvider::new(Rc::new(SqlDatabase::new("app", sqlite)));
+
+        let page = block_on(provider.list(None)).unwrap();
+        let listed = page.resources.iter().find(|resource| resource.name == "order items/2024%").unwrap();
+        assert_eq!(listed.uri, "d1://app/tables/order%20items%2F2024%25");
+        assert!(provider.handles(&listed.uri));
+        assert!(!provider.handles("d1://app/tables/bad%zz"));
+
+        let contents = block_on(provider.read(&listed.uri)).unwrap();
+        let table: Value = serde_json::from_str(contents[0].text.as_deref().unwrap()).unwrap();
+        assert_eq!(table["name"], json!("order items/2024%"));
+    }
+
+    fn call(tools: &ToolRegistry, ctx: &ToolContext, arguments: Value) -> Value {
+        serde_json::to_value(block_on(tools.call_tool("sql_query", Some(arguments), ctx)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sql_query_tool() {
+        let mut tools = ToolRegistry::new();
+        sql::register_sql_tools(&mut tools, true);
+        let listed = serde_json::to_value(tools.list_tools(None)).unwrap();
+        assert_eq!(listed[0]["annotations"], json!({ "readOnlyHint": true }));
+
+        let ctx = ToolContext {
+            database: Some(Rc::new(SqlDatabase::new("app", Sqlite::new()))),
+            ..Default::default()
+        };
+        let result = call(&tools, &ctx, json!({ "sql": "SELECT id, name FROM users WHERE score > ?", "params": [5], "maxRows": 1 }));
+        assert_eq!(result["isError"], json!(false));
+        assert_eq!(result["content"][0]["text"], json!("| id | name |\n| --- | --- |\n| 1 | ada |\n\nOnly the first 1 rows are shown."));
+        assert_eq!(
+            result["structuredContent"],
+            json!({ "columns": ["id", "name"], "rows": [[1, "ada"]], "rowCount": 1, "truncated": true })
+        );
+
+        let result = call(&tools, &ctx, json!({ "sql": "DROP TABLE users" }));
+        assert_eq!(result["isError"], json!(true));
+        assert_eq!(result["content"][0]["text"], json!("Statement rejected: only SELECT statements are allowed"));
+
+        let ctx = ToolContext::default();
+        assert!(block_on(tools.call_tool("sql_query", Some(json!({ "sql": "SELECT 1" })), &ctx)).is_err());
+    }
+
+    #[test]
+    fn test_sql_query_tool_writes() {
+        let mut tools = ToolRegistry::new();
+        sql::register_sql_tools(&mut tools, false);
+        assert!(tools.get_tools()[0].is_destructive());
+        let ctx = ToolContext {
+            database: Some(Rc::new(SqlDatabase::new("app", Sqlite::new()).read_only(false))),
+            ..Default::default()
+        };
+        let result = call(&tools, &ctx, json!({ "sql": "DELETE FROM users WHERE email IS NULL" }));
+        assert_eq!(result["content"][0]["text"], json!("1 rows changed"));
+        assert_eq!(result["structuredContent"], json!({ "changes": 1 }));
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::json;
+    use {{crate_name}}::auth::{ApiKeyAuthenticator, Authenticator, Principal};
+    use {{crate_name}}::tools::{ToolAccess, ToolContext, ToolError, ToolRegistry};
+    use {{crate_name}}::Tool;
+
+    fn principal(scopes: &[&str], roles: &[&str]) -> Principal {
+        Principal {
+            subject: "user-1".to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            claims: json!({}),
+        }
+    }
+
+    fn registry() -> ToolRegistry {
+        let schema = json!({ "type": "object" });
+        let mut registry = ToolRegistry::new();
+        registry.register_tool(Tool::new("evaluate", Some("Evaluate an expression"), schema.clone()));
+        registry.register_tool_with_access(
+            Tool::new("calculate", Some("Calculator"), schema),
+            ToolAccess::scopes(&["math:write"]),
+        );
+        registry
+    }
+
+    fn context(principal: Option<Principal>) -> ToolContext {
+        ToolContext {
+            principal,
+            ..ToolContext::default()
+        }
+    }
+
+    #[test]
+    fn test_list_filters_by_scope() {
+        let registry = registry();
+        let names = |principal: Option<&Principal>| -> Vec<String> {
+            registry.list_tools(principal).iter().map(|tool| tool.name().to_string()).collect()
+        };
+
+        assert_eq!(names(None), vec!["evaluate"]);
+        assert_eq!(names(Some(&principal(&["math:read"], &[]))), vec!["evaluate"]);
+        assert_eq!(names(Some(&principal(&["math:write"], &[]))), vec!["evaluate", "calculate"]);
+    }
+
+    #[test]
+    fn test_call_requires_scope() {
+        let registry = registry();
+        let arguments = Some(json!({ "operation": "multiply", "a": 2, "b": 3 }));
+
+        let denied = block_on(registry.call_tool("calculate", arguments.clone(), &context(Some(principal(&[], &[])))));
+        assert_eq!(
+            denied.unwrap_err(),
+            ToolError::Forbidden("calculate".to_string(), ToolAccess::scopes(&["math:write"]))
+        );
+
+        let anonymous = block_on(registry.call_tool("calculate", arguments.clone(), &context(None)));
+        assert!(matches!(anonymous, Err(ToolError::Forbidden(..))));
+
+        let allowed = block_on(registry.call_tool("calculate", arguments, &context(Some(principal(&["math:write"], &[])))));
+        assert!(allowed.is_ok());
+    }
+
+    #[test]
+    fn test_public_and_unknown_tools() {
+        let registry = registry();
+        assert!(block_on(registry.call_tool("evaluate", Some(json!({ "expression": "1 + 2" })), &context(None))).is_ok());
+        assert_eq!(
+            block_on(registry.call_tool("missing", None, &context(None))).unwrap_err(),
+            ToolError::NotFound("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_role_requirements() {
+        let access = ToolAccess {
+            scopes: vec!["reports".to_string()],
+            roles: vec!["admin".to_string(), "analyst".to_string()],
+        };
+
+        assert!(access.allows(Some(&principal(&["reports"], &["analyst"]))));
+        assert!(!access.allows(Some(&principal(&["reports"], &["viewer"]))));
+        assert!(!access.allows(Some(&principal(&[], &["admin"]))));
+        assert!(ToolAccess::default().allows(None));
+        assert_eq!(
+            ToolError::Forbidden("report".to_string(), access).to_string(),
+            "Forbidden: tool 'report' requires scopes [reports] and one of roles [admin, analyst]"
+        );
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        let auth = ApiKeyAuthenticator::from_spec("ci:secret-1:math:write tools:read, ops:secret-2");
+
+        let ci = auth.authenticate("secret-1", 0).unwrap();
+        assert_eq!(ci.subject, "api-key:ci");
+        assert_eq!(ci.scopes, vec!["math:write", "tools:read"]);
+
+        let ops = auth.authenticate("secret-2", 0).unwrap();
+        assert!(ops.scopes.is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use futures::channel::oneshot;
+    use futures::executor::block_on;
+    use futures::stream;
+    use serde_json::{json, Value};
+    use {{crate_name}}::auth::Principal;
+    use {{crate_name}}::http::{BodyStream, HttpClient, HttpRequest, HttpResponse};
+    use {{crate_name}}::logging::{LogLevel, Logger};
+    use {{crate_name}}::notifications::Outbox;
+    use {{crate_name}}::tools::{register_default_tools, ToolAccess, ToolContext, ToolError, ToolRegistry};
+    use {{crate_name}}::upstream::{self, Upstream, UpstreamConfig, UpstreamError};
+
+    /// A client standing in for `Fetch`. Requests run on their own thread
+    /// so that timeouts can fire while one is in flight.
+    struct Ureq;
+
+    #[async_trait(?Send)]
+    impl HttpClient for Ureq {
+        async fn send(&self, request: HttpRequest) -> worker::Result<HttpResponse> {
+            let (sender, receiver) = oneshot::channel();
+            thread::spawn(move || {
+                let mut call = ureq::request(&request.method, &request.url);
+                for (name, value) in &request.headers {
+                    call = call.set(name, value);
+                }
+                let response = match &request.body {
+                    Some(body) => call.send_string(body),
+                    None => call.call(),
+                };
+                let response = match response {
+                    Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(HttpResponse {
+                        status: response.status(),
+                        headers: response
+                            .headers_names()
+                            .into_iter()
+                            .filter_map(|name| Some((name.clone(), response.header(&name)?.to_string())))
+                            .collect(),
+                        body: response.into_string().unwrap(),
+                    }),
+                    Err(e) => Err(e.to_string()),
+                };
+                let _ = sender.send(response);
+            });
+            receiver.await.unwrap().map_err(worker::Error::RustError)
+        }
+
+        /// Hands the body over a few bytes at a time, splitting events and
+        /// characters across pieces the way a network might.
+        async fn open(&self, request: HttpRequest) -> worker::Result<(HttpResponse, BodyStream)> {
+            let mut response = self.send(request).await?;
+            let pieces: Vec<worker::Result<Vec<u8>>> = response.body.as_bytes().chunks(5).map(|piece| Ok(piece.to_vec())).collect();
+            response.body.clear();
+            Ok((response, Box::pin(stream::iter(pieces))))
+        }
+    }
+
+    /// What the mock server saw, and the sessions it still knows.
+    #[derive(Default)]
+    struct Mock {
+        /// `(method, Mcp-Session-Id, MCP-Protocol-Version)` of each request.
+        requests: Vec<(String, Option<String>, Option<String>)>,
+        sessions: Vec<String>,
+        opened: usize,
+        /// Responses the gateway sent back to the server's own requests.
+        answers: Vec<Value>,
+    }
+
+    fn reply(id: &Value, result: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "result": result })
+    }
+
+    /// A Streamable HTTP MCP server with tools `echo`, `slow`, `fail` and
+    /// `progress`, listed over two pages.
+    fn serve() -> (String, Arc<Mutex<Mock>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let mock = Arc::new(Mutex::new(Mock::default()));
+        let state = mock.clone();
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let state = state.clone();
+                thread::spawn(move || {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    let headers: Vec<tiny_http::Header> = request.headers().to_vec();
+                    let header = |name: &str| headers.iter().find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name)).map(|h| h.value.to_string());
+                    let (session, version) = (header("Mcp-Session-Id"), header("MCP-Protocol-Version"));
+                    let message: Value = serde_json::from_str(&body).unwrap();
+                    let method = message["method"].as_str().unwrap_or("response").to_string();
+                    let id = message["id"].clone();
+                    let params = message["params"].clone();
+
+                    let mut mock = state.lock().unwrap();
+                    mock.requests.push((method.clone(), session.clone(), version));
+                    let known = session.as_ref().is_some_and(|session| mock.sessions.contains(session));
+                    let (status, content_type, body, new_session) = match method.as_str() {
+                        "initialize" => {
+                            mock.opened += 1;
+                            let session = format!("session-{}", mock.opened);
+                            mock.sessions.push(session.clone());
+                            let result = json!({
+                                "protocolVersion": "2025-03-26",
+                                "capabilities": { "tools": {} },
+                                "serverInfo": { "name": "mock", "version": "1.0" }
+                            });
+                            (200, "application/json", reply(&id, result).to_string(), Some(session))
+                        },
+                        _ if !known => (404, "text/plain", "Session not found".to_string(), None),
+                        "notifications/initialized" => (202, "text/plain", String::new(), None),
+                        "response" => {
+                            mock.answers.push(message.clone());
+                            (202, "text/plain", String::new(), None)
+                        },
+                        "ping" => (200, "application/json", reply(&id, json!({})).to_string(), None),
+                        "tools/list" => {
+                            let tool = |name: &str| json!({ "name": name, "inputSchema": { "type": "object" } });
+                            let result = match params["cursor"].as_str() {
+                                None => json!({ "tools": [tool("echo"), tool("slow")], "nextCursor": "page-2" }),
+                                Some(_) => json!({ "tools": [tool("fail"), tool("progress")] }),
+                            };
+                            (200, "application/json", reply(&id, result).to_string(), None)
+                        },
+                        "tools/call" => {
+                            drop(mock);
+                            let arguments = params["arguments"].clone();
+                            match params["name"].as_str().unwrap() {
+                                "echo" => {
+                                    let result = json!({
+                                        "content": [{ "type": "text", "text": arguments.to_string() }, { "type": "image", "data": "AAAA", "mimeType": "image/png" }],
+                                        "structuredContent": arguments
+                                    });
+                                    (200, "application/json", reply(&id, result).to_string(), None)
+                                },
+                                "slow" => {
+                                    thread::sleep(Duration::from_millis(1500));
+                                    let result = json!({ "content": [{ "type": "text", "text": "late" }] });
+                                    (200, "application/json", reply(&id, result).to_string(), None)
+                                },
+                                "fail" => {
+                                    let error = json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32602, "message": "bad input" } });
+                                    (200, "application/json", error.to_string(), None)
+                                },
+                                _ => {
+                                    let token = params["_meta"]["progressToken"].clone();
+                                    let events = [
+                                        json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": { "progressToken": token, "progress": 1, "total": 2 } }),
+                                        json!({ "jsonrpc": "2.0", "method": "notifications/message", "params": { "level": "warning", "data": "halfway ✓" } }),
+                                        json!({ "jsonrpc": "2.0", "id": "ask-1", "method": "sampling/createMessage", "params": { "messages": [], "maxTokens": 10 } }),
+                                        json!({ "jsonrpc": "2.0", "id": "ping-1", "method": "ping" }),
+                                        reply(&id, json!({ "content": [{ "type": "text", "text": "done" }] })),
+                                    ];
+                                    let stream: String = events.iter().map(|event| format!("event: message\ndata: {}\n\n", event)).collect();
+                                    (200, "text/event-stream", stream, None)
+                                },
+                            }
+                        },
+                        _ => (200, "application/json", json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": "Method not found" } }).to_string(), None),
+                    };
+
+                    let mut response = tiny_http::Response::from_string(body)
+                        .with_status_code(status)
+                        .with_header(tiny_http::Header::from_bytes("Content-Type", content_type).unwrap());
+                    if let Some(session) = new_session {
+                        response = response.with_header(tiny_http::Header::from_bytes("Mcp-Session-Id", session).unwrap());
+                    }
+                    let _ = request.respond(response);
+                });
+            }
+        });
+        (format!("http://{}/mcp", address), mock)
+    }
+
+    fn upstream(url: &str) -> Upstream {
+        let config = UpstreamConfig {
+            timeout: Duration::from_millis(500),
+            headers: vec![("Authorization".to_string(), "Bearer secret".to_string())],
+            ..UpstreamConfig::new("mock", url)
+        };
+        Upstream::new(config, Ureq)
+    }
+
+    fn registry(url: &str) -> ToolRegistry {
+        let mut tools = ToolRegistry::new();
+        register_default_tools(&mut tools);
+        tools.register_upstream(upstream(url)).unwrap();
+        tools
+    }
+
+    fn context(progress_token: Option<Value>) -> ToolContext {
+        let outbox = Outbox::new();
+        ToolContext {
+            logger: Logger::with_outbox(LogLevel::Info, outbox.clone()),
+            outbox,
+            progress_token,
+            ..ToolContext::default()
+        }
+    }
+
+    fn names(tools: &ToolRegistry) -> Vec<String> {
+        let listed = serde_json::to_value(block_on(tools.list_all_tools(&context(None)))).unwrap();
+        listed.as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_lists_namespaced_tools() {
+        let (url, mock) = serve();
+        let tools = registry(&url);
+        assert_eq!(names(&tools), vec!["calculate", "evaluate", "mock.echo", "mock.slow", "mock.fail", "mock.progress"]);
+
+        let requests = mock.lock().unwrap().requests.clone();
+        let methods: Vec<&str> = requests.iter().map(|(method, _, _)| method.as_str()).collect();
+        assert_eq!(methods, vec!["initialize", "notifications/initialized", "tools/list", "tools/list"]);
+        // The session and negotiated version are sent after initialize
+        assert_eq!(requests[0].1, None);
+        assert_eq!(requests[2].1.as_deref(), Some("session-1"));
+        assert_eq!(requests[2].2.as_deref(), Some("2025-03-26"));
+    }
+
+    #[test]
+    fn test_forwards_calls() {
+        let (url, _) = serve();
+        let tools = registry(&url);
+
+        let result = block_on(tools.call_tool("mock.echo", Some(json!({ "word": "hi" })), &context(None))).unwrap();
+        let result = serde_json::to_value(result).unwrap();
+        assert_eq!(result["structuredContent"], json!({ "word": "hi" }));
+        // Content types this server does not produce pass through untouched
+        assert_eq!(result["content"][1], json!({ "type": "image", "data": "AAAA", "mimeType": "image/png" }));
+
+        let err = block_on(tools.call_tool("mock.fail", None, &context(None))).unwrap_err();
+        assert_eq!(err, ToolError::Remote { code: -32602, message: "bad input".to_string() });
+        assert!(matches!(block_on(tools.call_tool("other.echo", None, &context(None))), Err(ToolError::NotFound(_))));
+        // Local tools still work
+        assert!(block_on(tools.call_tool("evaluate", Some(json!({ "expression": "1 + 2" })), &context(None))).is_ok());
+    }
+
+    #[test]
+    fn test_access_applies_to_upstream_tools() {
+        let (url, _) = serve();
+        let mut tools = ToolRegistry::new();
+        let config = UpstreamConfig {
+            access: ToolAccess::scopes(&["mock:use"]),
+            ..UpstreamConfig::new("mock", &url)
+        };
+        tools.register_upstream(Upstream::new(config, Ureq)).unwrap();
+        tools.restrict_tool("mock.fail", ToolAccess::roles(&["admin"]));
+
+        let as_caller = |scopes: &[&str], roles: &[&str]| ToolContext {
+            principal: Some(Principal {
+                subject: "user-1".to_string(),
+                scopes: scopes.iter().map(|s| s.to_string()).collect(),
+                roles: roles.iter().map(|r| r.to_string()).collect(),
+                claims: json!({}),
+            }),
+            ..context(None)
+        };
+        let listed = |ctx: &ToolContext| -> Vec<String> {
+            block_on(tools.list_all_tools(ctx)).iter().map(|tool| tool.name().to_string()).collect()
+        };
+
+        assert!(listed(&context(None)).is_empty());
+        assert!(matches!(block_on(tools.call_tool("mock.echo", None, &context(None))), Err(ToolError::Forbidden(..))));
+
+        let user = as_caller(&["mock:use"], &[]);
+        assert_eq!(listed(&user), vec!["mock.echo", "mock.slow", "mock.progress"]);
+        assert!(block_on(tools.call_tool("mock.echo", None, &user)).is_ok());
+        let err = block_on(tools.call_tool("mock.fail", None, &user)).unwrap_err();
+        assert_eq!(err, ToolError::Forbidden("mock.fail".to_string(), ToolAccess::roles(&["admin"])));
+
+        let admin = as_caller(&[], &["admin"]);
+        assert_eq!(listed(&admin), vec!["mock.fail"]);
+    }
+
+    #[test]
+    fn test_passes_progress_through() {
+        let (url, mock) = serve();
+        let tools = registry(&url);
+        let ctx = context(Some(json!("job-9")));
+
+        let result = block_on(tools.call_tool("mock.progress", None, &ctx)).unwrap();
+        assert_eq!(serde_json::to_value(result).unwrap()["content"][0]["text"], json!("done"));
+        let messages = ctx.outbox.take();
+        assert_eq!(messages[0]["params"], json!({ "progressToken": "job-9", "progress": 1.0, "total": 2.0 }));
+        assert_eq!((&messages[1]["params"]["level"], &messages[1]["params"]["data"]), (&json!("warning"), &json!("halfway ✓")));
+
+        // The server's own requests are answered rather than left hanging
+        let answers = mock.lock().unwrap().answers.clone();
+        assert_eq!(answers.len(), 2);
+        assert_eq!((&answers[0]["id"], &answers[0]["error"]["code"]), (&json!("ask-1"), &json!(-32601)));
+        assert_eq!((&answers[1]["id"], &answers[1]["result"]), (&json!("ping-1"), &json!({})));
+    }
+
+    #[test]
+    fn test_timeouts_mark_the_upstream_down() {
+        let (url, mock) = serve();
+        let tools = registry(&url);
+
+        let result = block_on(tools.call_tool("mock.slow", None, &context(None))).unwrap();
+        let result = serde_json::to_value(result).unwrap();
+        assert_eq!(result["isError"], json!(true));
+        assert_eq!(result["content"][0]["text"], json!("Upstream mock: timed out"));
+
+        // Later calls fail fast without reaching the server
+        let before = mock.lock().unwrap().requests.len();
+        let result = serde_json::to_value(block_on(tools.call_tool("mock.echo", None, &context(None))).unwrap()).unwrap();
+        assert_eq!(result["content"][0]["text"], json!("Upstream mock: unavailable after an earlier failure: timed out"));
+        assert_eq!(mock.lock().unwrap().requests.len(), before);
+        // Listing leaves the upstream out
+        assert_eq!(names(&tools), vec!["calculate", "evaluate"]);
+
+        // A health check still tries, and clears the failure
+        let health = block_on(upstream::check_health(tools.upstreams()));
+        assert!(health[0].healthy, "{:?}", health);
+        assert!(block_on(tools.call_tool("mock.echo", None, &context(None))).is_ok());
+    }
+
+    #[test]
+    fn test_deadline_bounds_the_whole_call() {
+        let (url, _mock) = serve();
+        let config = UpstreamConfig {
+            timeout: Duration::from_secs(5),
+            deadline: Duration::from_millis(500),
+            ..UpstreamConfig::new("mock", &url)
+        };
+        let mut tools = ToolRegistry::new();
+        tools.register_upstream(Upstream::new(config, Ureq)).unwrap();
+
+        let result = serde_json::to_value(block_on(tools.call_tool("mock.slow", None, &context(None))).unwrap()).unwrap();
+        assert_eq!(result["content"][0]["text"], json!("Upstream mock: timed out"));
+    }
+
+    #[test]
+    fn test_oversized_responses_are_refused() {
+        let (url, _mock) = serve();
+        // Enough for the handshake, not for the echo or a progress event
+        let config = UpstreamConfig {
+            max_response_bytes: 200,
+            ..UpstreamConfig::new("mock", &url)
+        };
+        let mut tools = ToolRegistry::new();
+        tools.register_upstream(Upstream::new(config, Ureq)).unwrap();
+
+        let arguments = json!({ "text": "x".repeat(300) });
+        let result = serde_json::to_value(block_on(tools.call_tool("mock.echo", Some(arguments), &context(None))).unwrap()).unwrap();
+        assert_eq!(result["content"][0]["text"], json!("Upstream mock: response larger than 200 bytes"));
+    }
+
+    #[test]
+    fn test_health_and_session_expiry() {
+        let (url, mock) = serve();
+        let tools = registry(&url);
+        let health = block_on(upstream::check_health(tools.upstreams()));
+        assert_eq!(serde_json::to_value(&health).unwrap(), json!([{ "name": "mock", "healthy": true }]));
+
+        // GET /health reuses a recent check rather than pinging again
+        let cached = block_on(upstream::cached_health(tools.upstreams()));
+        let before = mock.lock().unwrap().requests.len();
+        assert_eq!(block_on(upstream::cached_health(tools.upstreams())), cached);
+        assert_eq!(mock.lock().unwrap().requests.len(), before);
+
+        // The upstream forgets the session; a new one is opened
+        mock.lock().unwrap().sessions.clear();
+        assert!(block_on(tools.call_tool("mock.echo", None, &context(None))).is_ok());
+        assert_eq!(mock.lock().unwrap().opened, 2);
+
+        let down = Upstream::new(UpstreamConfig::new("down", "http://127.0.0.1:9/mcp"), Ureq);
+        let health = block_on(upstream::check_health(std::slice::from_ref(&down)));
+        assert!(!health[0].healthy);
+        assert!(health[0].error.as_deref().unwrap().starts_with("unreachable"));
+        assert!(matches!(block_on(down.list_tools()), Err(UpstreamError::Unavailable(_))));
+    }
+
+    #[test]
+    fn test_registration_and_filtering() {
+        let (url, _) = serve();
+        let mut tools = registry(&url);
+        assert_eq!(tools.register_upstream(upstream(&url)).unwrap_err(), "upstream mock is already registered");
+        let dotted = Upstream::new(UpstreamConfig::new("a.b", &url), Ureq);
+        assert_eq!(tools.register_upstream(dotted).unwrap_err(), "invalid upstream name: a.b");
+
+        assert_eq!(tools.retain_tools(&["other.echo".to_string()]).unwrap_err(), "unknown tools: other.echo");
+        tools.retain_tools(&["evaluate".to_string(), "mock.echo".to_string(), "mock.fail".to_string()]).unwrap();
+        assert_eq!(names(&tools), vec!["evaluate", "mock.echo", "mock.fail"]);
+        assert!(matches!(block_on(tools.call_tool("mock.slow", None, &context(None))), Err(ToolError::NotFound(_))));
+
+        let mut tools = registry(&url);
+        tools.retain_tools(&["mock.*".to_string()]).unwrap();
+        assert_eq!(names(&tools).len(), 4);
+        let mut tools = registry(&url);
+        tools.retain_tools(&["evaluate".to_string()]).unwrap();
+        assert!(tools.upstreams().is_empty());
+    }
+}